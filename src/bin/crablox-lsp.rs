@@ -0,0 +1,3 @@
+fn main() {
+    crablox::run_language_server();
+}
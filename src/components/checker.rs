@@ -0,0 +1,1217 @@
+use crate::components as lox;
+use lox::instructions::{statement as lox_statement, expression as lox_expression, node as lox_node, pattern as lox_pattern};
+use lox::instructions::callable::{Callable, ArityRange};
+use lox_statement::{Statement, Signature};
+use lox_expression::Expression;
+use lox_node::*;
+use lox_pattern::*;
+
+use core::fmt;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+// The static types the checker reasons about. Any is the type of everything left unannotated,
+// and is compatible with every other type in both directions.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum Type {
+    Any,
+    Nil,
+    Boolean,
+    Number,
+    Integer, // Integers are also Numbers
+    String,
+    List,
+    Map,
+    AnyFunction, // Written 'Function' in annotations; any callable value
+    Function(Vec<Type>, ArityRange, Box<Type>), // Parameter types, accepted argument counts, return type
+    Class(Name), // The class value itself
+    Instance(Name), // An instance of the named class or of a class using the named trait
+    Generator,
+    Enum(Name), // The enum value itself
+    Variant(Name), // A value of the named enum
+}
+use Type::*;
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Any => write!(f, "Any"),
+            Nil => write!(f, "Nil"),
+            Boolean => write!(f, "Boolean"),
+            Number => write!(f, "Number"),
+            Integer => write!(f, "Integer"),
+            String => write!(f, "String"),
+            List => write!(f, "List"),
+            Map => write!(f, "Map"),
+            AnyFunction | Function(..) => write!(f, "Function"),
+            Class(name) => write!(f, "class {}", name),
+            Instance(name) => write!(f, "{}", name),
+            Generator => write!(f, "Generator"),
+            Enum(name) => write!(f, "enum {}", name),
+            Variant(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+type Name = std::string::String; // String is taken by Type::String in this module
+
+#[derive(Debug)]
+#[derive(Clone)]
+struct Binding {
+    ty: Type,
+    annotated: bool, // Annotated bindings keep their type; others fall back to Any once reassigned
+}
+
+#[derive(Debug)]
+#[derive(Default)]
+struct ClassInfo {
+    superclass: Option<Name>,
+    traits: Vec<Name>,
+    methods: HashMap<Name, Type>, // Includes methods mixed in from traits, and accessors as Any
+    fields: HashMap<Name, Type>, // Set on this in init; Any unless set from an annotated variable
+    statics: HashMap<Name, Type>,
+}
+
+// Checks a program for type errors before it runs. Annotations are optional: unannotated
+// variables and parameters are treated as Any, so only code that could never succeed given
+// the declared types is reported. Declarations persist across calls to check(), so the same
+// checker can follow a prompt session.
+pub struct TypeChecker {
+    scopes: Vec<HashMap<Name, Binding>>,
+    classes: HashMap<Name, ClassInfo>,
+    traits: HashMap<Name, HashMap<Name, Type>>,
+    enums: HashMap<Name, Vec<(Name, usize)>>, // Variant names with their payload sizes
+    assigned_properties: HashSet<Name>, // Every property name the program assigns, on anything
+    reflective: bool, // Whether the program can set properties by name at runtime
+    returns: Vec<(Name, Option<Type>)>, // Enclosing functions with their declared return types
+    this_type: Option<Type>,
+    current_class: Option<Name>,
+    in_init: bool,
+    line: usize, // Of the statement being checked, or the function whose signature is
+    errors: Vec<Name>,
+}
+
+impl TypeChecker {
+    pub fn new() -> TypeChecker {
+        let mut globals = HashMap::new();
+        for (name, native) in Callable::native_fn_list() {
+            let arity = native.arity();
+            let ty = Function(vec![Any; arity.max.unwrap_or(arity.min)], arity, Box::new(Any));
+            // Reassigning a native is left for the runtime to reject with a clearer message.
+            globals.insert(name, Binding{ ty, annotated: false });
+        }
+        TypeChecker {
+            scopes: vec![globals],
+            classes: HashMap::new(),
+            traits: HashMap::new(),
+            enums: HashMap::new(),
+            assigned_properties: HashSet::new(),
+            reflective: false,
+            returns: Vec::new(),
+            this_type: None,
+            current_class: None,
+            in_init: false,
+            line: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn check(&mut self, program: &[Statement]) -> Result<(), Vec<Name>> {
+        self.errors = Vec::new();
+        // Lox objects take whatever properties are assigned to them, so a property is only known
+        // to be missing if nothing anywhere could have set it.
+        let mut assigned = Assigned::default();
+        for s in program {
+            collect_assigned_stmt(s, &mut assigned);
+        }
+        self.assigned_properties.extend(assigned.properties);
+        self.reflective |= assigned.reflectively;
+
+        for s in program {
+            self.check_stmt(s);
+        }
+        if self.errors.is_empty() { Ok(()) }
+        else { Err(std::mem::take(&mut self.errors)) }
+    }
+
+    fn add_error(&mut self, line: usize, message: &str) {
+        self.errors.push(lox::error::new_error_string(line, &format!("Type error: {}", message)));
+    }
+
+    fn define(&mut self, name: &str, ty: Type, annotated: bool) {
+        let last = self.scopes.len()-1;
+        self.scopes[last].insert(Name::from(name), Binding{ ty, annotated });
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut Binding> {
+        self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name))
+    }
+
+    fn resolve(&mut self, annotation: &str) -> Type {
+        match annotation {
+            "Any" => Any,
+            "Nil" => Nil,
+            "Boolean" => Boolean,
+            "Number" => Number,
+            "Integer" => Integer,
+            "String" => String,
+            "List" => List,
+            "Map" => Map,
+            "Function" => AnyFunction,
+            "Generator" => Generator,
+            name if self.classes.contains_key(name) || self.traits.contains_key(name) => Instance(Name::from(name)),
+            name if self.enums.contains_key(name) => Variant(Name::from(name)),
+            name => {
+                self.add_error(self.line, &format!("Unknown type {}.", name));
+                Any
+            },
+        }
+    }
+
+    fn resolve_option(&mut self, annotation: &Option<Name>) -> Option<Type> {
+        annotation.as_ref().map(|a| self.resolve(a))
+    }
+
+    fn function_type(&mut self, params: &[Name], signature: &Signature, returns: Type) -> Type {
+        let param_types = params.iter()
+            .enumerate()
+            .map(|(i, p)| match signature.param_types.get(p) {
+                Some(annotation) => self.resolve(annotation),
+                None if signature.rest && i == params.len()-1 => List,
+                None => Any,
+            })
+            .collect();
+        Function(param_types, ArityRange::of(params, signature), Box::new(returns))
+    }
+
+    // Whether a value of type 'from' may be stored where 'to' is expected.
+    fn assignable(&self, from: &Type, to: &Type) -> bool {
+        match (from, to) {
+            (Any, _) | (_, Any) => true,
+            (Integer, Number) => true,
+            (Instance(a), Instance(b)) => self.inherits_from(a, b),
+            (Function(..) | Class(_) | AnyFunction, AnyFunction) => true,
+            (AnyFunction, Function(..)) => true,
+            (Function(_, a, _), Function(_, b, _)) => a == b,
+            (a, b) => a == b,
+        }
+    }
+
+    fn inherits_from(&self, class: &str, target: &str) -> bool {
+        let mut current = Some(class);
+        while let Some(name) = current {
+            if name == target { return true; }
+            let Some(info) = self.classes.get(name) else { return false };
+            if info.traits.iter().any(|t| t == target) { return true; }
+            current = info.superclass.as_deref();
+        }
+        false
+    }
+
+    fn find_member(&self, class: &str, pick: fn(&ClassInfo) -> &HashMap<Name, Type>, name: &str) -> Option<Type> {
+        let mut current = Some(class);
+        while let Some(class_name) = current {
+            let info = self.classes.get(class_name)?;
+            if let Some(ty) = pick(info).get(name) { return Some(ty.clone()); }
+            current = info.superclass.as_deref();
+        }
+        None
+    }
+
+    fn find_method(&self, class: &str, name: &str) -> Option<Type> {
+        self.find_member(class, |info| &info.methods, name)
+    }
+
+    fn find_field(&self, class: &str, name: &str) -> Option<Type> {
+        self.find_member(class, |info| &info.fields, name)
+    }
+
+    fn find_static(&self, class: &str, name: &str) -> Option<Type> {
+        self.find_member(class, |info| &info.statics, name)
+    }
+
+    // Whether every class the named one inherits from was declared where the checker could see it,
+    // so that its members are all known.
+    fn is_known_class(&self, class: &str) -> bool {
+        let mut current = Some(class);
+        while let Some(name) = current {
+            let Some(info) = self.classes.get(name) else { return false };
+            current = info.superclass.as_deref();
+        }
+        true
+    }
+
+    fn check_block(&mut self, statements: &[Statement]) {
+        self.scopes.push(HashMap::new());
+        for s in statements {
+            self.check_stmt(s);
+        }
+        self.scopes.pop();
+    }
+
+    fn check_stmt(&mut self, s: &Statement) {
+        use Statement::*;
+        if let Some(line) = s.line() {
+            self.line = line;
+        }
+        match s {
+            Decl(id, expr, annotation, _) => {
+                let value = self.check_expr(expr);
+                match self.resolve_option(annotation) {
+                    Some(declared) => {
+                        // A declaration may start out nil and be filled in later.
+                        let starts_nil = **expr == Expression::LitExp(Literal::Nil);
+                        if !starts_nil && !self.assignable(&value, &declared) {
+                            self.add_error(self.line, &format!("Cannot initialize {} of type {} with {}.", id, declared, value));
+                        }
+                        self.define(id, declared, true);
+                    },
+                    None => {
+                        let inferred = if value == Nil { Any } else { value };
+                        self.define(id, inferred, false);
+                    },
+                }
+            },
+            Const(id, expr, annotation, _) => {
+                let value = self.check_expr(expr);
+                let ty = match self.resolve_option(annotation) {
+                    Some(declared) => {
+                        if !self.assignable(&value, &declared) {
+                            self.add_error(self.line, &format!("Cannot initialize {} of type {} with {}.", id, declared, value));
+                        }
+                        declared
+                    },
+                    None => value,
+                };
+                self.define(id, ty, true);
+            },
+            Expr(e, _) | Print(e, _) | Yield(e, _) => { self.check_expr(e); },
+            Block(v) | Test(_, v, _) => self.check_block(v),
+            Return(e, line) => {
+                let value = self.check_expr(e);
+                if let Some((name, Some(declared))) = self.returns.last().cloned() {
+                    if !self.assignable(&value, &declared) {
+                        self.add_error(*line, &format!("Function {} must return {} but returns {}.", name, declared, value));
+                    }
+                }
+            },
+            If(cond, then_branch, else_option, _) => {
+                self.check_expr(cond);
+                self.check_stmt(then_branch);
+                if let Some(else_branch) = else_option {
+                    self.check_stmt(else_branch);
+                }
+            },
+            While(cond, body, _) => {
+                self.forget_assigned(body);
+                self.check_expr(cond);
+                self.check_stmt(body);
+            },
+            For(init, cond, incr, body, line) => {
+                self.check_stmt(&Statement::desugar_for(init.clone(), cond.clone(), incr.clone(), (**body).clone(), *line));
+            },
+            ForIn(name, iterable, body, _) => {
+                self.check_expr(iterable);
+                self.forget_assigned(body);
+                self.scopes.push(HashMap::new());
+                self.define(name, Any, false);
+                self.check_stmt(body);
+                self.scopes.pop();
+            },
+            Fun(name, params, body, signature) => {
+                self.line = signature.line;
+                let returns = self.resolve_option(&signature.returns);
+                let ty = self.function_type(params, signature, returns.clone().unwrap_or(Any));
+                self.define(name, ty.clone(), false);
+                self.check_function(name, params, signature, body, ty, returns);
+            },
+            Generator(name, params, body, signature) => {
+                self.line = signature.line;
+                let ty = self.function_type(params, signature, Type::Generator);
+                self.define(name, ty.clone(), false);
+                self.check_function(name, params, signature, body, ty, None);
+            },
+            Class(name, super_name, trait_names, methods, statics, _) => self.check_class(name, super_name, trait_names, methods, statics),
+            Trait(name, methods) => {
+                let mut signatures = HashMap::new();
+                for method in methods {
+                    match method {
+                        Fun(method_name, params, _, signature) => {
+                            self.line = signature.line;
+                            let returns = self.resolve_option(&signature.returns).unwrap_or(Any);
+                            signatures.insert(method_name.clone(), self.function_type(params, signature, returns));
+                        },
+                        PropertyGet(prop, _) | PropertySet(prop, _, _) => { signatures.insert(prop.clone(), Any); },
+                        _ => (),
+                    }
+                }
+                self.traits.insert(name.clone(), signatures.clone());
+                self.define(name, Any, false);
+
+                // The class a trait ends up in is unknown here, so 'this' is left unchecked.
+                let outer = self.this_type.replace(Any);
+                self.check_members(methods, &signatures);
+                self.this_type = outer;
+            },
+            Enum(name, variants) => {
+                let variants = variants.iter().map(|(variant, fields)| (variant.clone(), fields.len())).collect();
+                self.enums.insert(name.clone(), variants);
+                self.define(name, Type::Enum(name.clone()), false);
+            },
+            PropertyGet(..) | PropertySet(..) => (), // Rejected at runtime outside a class
+            Comment(..) => (),
+            Match(subject, arms, default, _) => {
+                let subject_type = self.check_expr(subject);
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    for pattern in arm.patterns.iter() {
+                        self.bind_pattern(pattern, &subject_type);
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.check_expr(guard);
+                    }
+                    self.check_stmt(&arm.body);
+                    self.scopes.pop();
+                }
+                if let Some(stmt) = default {
+                    self.check_stmt(stmt);
+                }
+            },
+        }
+    }
+
+    fn check_function(
+        &mut self,
+        name: &str,
+        params: &[Name],
+        signature: &Signature,
+        body: &[Statement],
+        ty: Type,
+        returns: Option<Type>,
+    ) {
+        let Function(param_types, _, _) = ty else { return };
+        self.scopes.push(HashMap::new());
+        for (param, param_type) in params.iter().zip(param_types) {
+            // Defaults are evaluated after the parameters before them are bound.
+            if let Some(default) = signature.defaults.get(param) {
+                let value = self.check_expr(default);
+                if !self.assignable(&value, &param_type) {
+                    self.add_error(self.line, &format!("Default for parameter {} must be {} but is {}.", param, param_type, value));
+                }
+            }
+            let is_rest = signature.rest && params.last() == Some(param);
+            if is_rest && !self.assignable(&param_type, &List) {
+                self.add_error(self.line, &format!("Rest parameter {} collects a List, not {}.", param, param_type));
+            }
+            let annotated = param_type != Any;
+            self.define(param, param_type, annotated);
+        }
+        self.returns.push((Name::from(name), returns));
+        for s in body {
+            self.check_stmt(s);
+        }
+        self.returns.pop();
+        self.scopes.pop();
+    }
+
+    fn check_class(
+        &mut self,
+        name: &str,
+        super_name: &str,
+        trait_names: &[Name],
+        method_defs: &[Statement],
+        static_defs: &[Statement],
+    ) {
+        use Statement::*;
+
+        // Signatures are collected up front so methods can call each other in any order.
+        // The class is registered first so its signatures can name it.
+        self.classes.insert(Name::from(name), ClassInfo::default());
+        let mut info = ClassInfo {
+            superclass: if super_name.is_empty() { None } else { Some(Name::from(super_name)) },
+            traits: trait_names.to_vec(),
+            ..ClassInfo::default()
+        };
+        for method in method_defs {
+            match method {
+                Fun(method_name, params, _, signature) => {
+                    self.line = signature.line;
+                    let returns = if method_name == "init" { Instance(Name::from(name)) }
+                        else { self.resolve_option(&signature.returns).unwrap_or(Any) };
+                    let ty = self.function_type(params, signature, returns);
+                    info.methods.insert(method_name.clone(), ty);
+                },
+                PropertyGet(prop, _) | PropertySet(prop, _, _) => { info.methods.insert(prop.clone(), Any); },
+                _ => (),
+            }
+        }
+        for trait_name in trait_names {
+            for (method_name, ty) in self.traits.get(trait_name).cloned().unwrap_or_default() {
+                info.methods.entry(method_name).or_insert(ty);
+            }
+        }
+        for member in static_defs {
+            match member {
+                Fun(fn_name, params, _, signature) => {
+                    self.line = signature.line;
+                    let returns = self.resolve_option(&signature.returns).unwrap_or(Any);
+                    let ty = self.function_type(params, signature, returns);
+                    info.statics.insert(fn_name.clone(), ty);
+                },
+                Decl(field, expr, annotation, line) => {
+                    self.line = *line;
+                    let value = self.check_expr(expr);
+                    let ty = match self.resolve_option(annotation) {
+                        Some(declared) => {
+                            if !self.assignable(&value, &declared) {
+                                self.add_error(self.line, &format!("Cannot initialize {}.{} of type {} with {}.", name, field, declared, value));
+                            }
+                            declared
+                        },
+                        None => Any,
+                    };
+                    info.statics.insert(field.clone(), ty);
+                },
+                _ => (),
+            }
+        }
+        let signatures = info.methods.clone();
+        let static_signatures = info.statics.clone();
+        self.classes.insert(Name::from(name), info);
+        self.define(name, Type::Class(Name::from(name)), false);
+
+        let outer_this = self.this_type.replace(Instance(Name::from(name)));
+        let outer_class = self.current_class.replace(Name::from(name));
+
+        // init runs first, so it is checked first and decides the types of the fields it sets.
+        let (init, others): (Vec<_>, Vec<_>) = method_defs.iter()
+            .cloned()
+            .partition(|m| matches!(m, Fun(method_name, ..) if method_name == "init"));
+        self.in_init = true;
+        self.check_members(&init, &signatures);
+        self.in_init = false;
+        self.check_members(&others, &signatures);
+
+        self.this_type = Some(Type::Class(Name::from(name)));
+        self.check_members(static_defs, &static_signatures);
+
+        self.this_type = outer_this;
+        self.current_class = outer_class;
+    }
+
+    fn check_members(&mut self, members: &[Statement], signatures: &HashMap<Name, Type>) {
+        use Statement::*;
+        for member in members {
+            match member {
+                Fun(name, params, body, signature) => {
+                    self.line = signature.line;
+                    let ty = signatures.get(name).cloned().unwrap_or_else(|| fixed(vec![Any; params.len()], Any));
+                    let returns = match &ty {
+                        Function(_, _, returns) if name != "init" && **returns != Any => Some(*returns.clone()),
+                        _ => None,
+                    };
+                    self.check_function(name, params, signature, body, ty, returns);
+                },
+                PropertyGet(name, body) => {
+                    self.check_function(name, &[], &Signature::default(), body, fixed(vec![], Any), None);
+                },
+                PropertySet(name, param, body) => {
+                    self.check_function(name, std::slice::from_ref(param), &Signature::default(), body, fixed(vec![Any], Any), None);
+                },
+                _ => (),
+            }
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern, subject: &Type) {
+        match pattern {
+            Pattern::Binding(name) => self.define(name, subject.clone(), false),
+            Pattern::Instance(class, fields) => {
+                for field in fields {
+                    let ty = self.find_field(class, field).unwrap_or(Any);
+                    self.define(field, ty, false);
+                }
+            },
+            Pattern::Variant(_, _, names) => {
+                for name in names {
+                    self.define(name, Any, false);
+                }
+            },
+            Pattern::Literal(_) | Pattern::Wildcard => (),
+        }
+    }
+
+    // A loop body may run after its own assignments, so unannotated variables it assigns
+    // can't be trusted to keep their inferred types anywhere inside it.
+    fn forget_assigned(&mut self, body: &Statement) {
+        let mut assigned = Assigned::default();
+        collect_assigned_stmt(body, &mut assigned);
+        for name in assigned.variables {
+            if let Some(binding) = self.lookup_mut(&name) {
+                if !binding.annotated { binding.ty = Any; }
+            }
+        }
+    }
+
+    fn check_expr(&mut self, e: &Expression) -> Type {
+        use Expression::*;
+        match e {
+            LitExp(lit) => literal_type(lit),
+            Unary(op, operand) => {
+                let operand = self.check_expr(operand);
+                self.unary_type(*op, operand)
+            },
+            Binary{left, op, right} => {
+                let left = self.check_expr(left);
+                let right = self.check_expr(right);
+                self.binary_type(&left, *op, &right)
+            },
+            Logical{left, op: _, right} => {
+                let left = self.check_expr(left);
+                let right = self.check_expr(right);
+                if left == right { left } else { Any }
+            },
+            Identifier(id) => self.lookup(id).map(|b| b.ty.clone()).unwrap_or(Any),
+            Grouping(inner) => self.check_expr(inner),
+            Assignment(id, value, _) => {
+                let value = self.check_expr(value);
+                self.assign(id, value)
+            },
+            CompoundAssignment(id, op, value, _) => {
+                let current = self.lookup(id).map(|b| b.ty.clone()).unwrap_or(Any);
+                let operand = self.check_expr(value);
+                let result = self.binary_type(&current, *op, &operand);
+                self.assign(id, result)
+            },
+            Ternary(cond, then_branch, else_branch) => {
+                self.check_expr(cond);
+                let then_type = self.check_expr(then_branch);
+                let else_type = self.check_expr(else_branch);
+                match (then_type, else_type) {
+                    (a, b) if a == b => a,
+                    (Integer | Number, Integer | Number) => Number,
+                    _ => Any,
+                }
+            },
+            Call(callee, args, _, line) => {
+                let callee = self.check_expr(callee);
+                let spread_at = args.iter().position(|a| matches!(a, Spread(_) | Named(..)));
+                let args: Vec<Type> = args.iter().map(|a| self.check_expr(a)).collect();
+                self.call_type(callee, &args, spread_at, *line)
+            },
+            Spread(list) => {
+                let list = self.check_expr(list);
+                if !self.assignable(&list, &List) {
+                    self.add_error(self.line, &format!("Can only spread a list into arguments, not {}.", list));
+                }
+                Any
+            },
+            Named(_, value) => self.check_expr(value),
+            Getter(obj, name) => {
+                let obj = self.check_expr(obj);
+                self.property_type(&obj, name)
+            },
+            Setter(obj, name, value) => {
+                let obj = self.check_expr(obj);
+                let annotated = matches!(&**value, Identifier(id) if self.lookup(id).is_some_and(|b| b.annotated));
+                let value = self.check_expr(value);
+                self.set_field(&obj, name, value, annotated)
+            },
+            CompoundSetter(obj, name, op, value) => {
+                let obj = self.check_expr(obj);
+                let current = self.property_type(&obj, name);
+                let operand = self.check_expr(value);
+                let result = self.binary_type(&current, *op, &operand);
+                self.set_field(&obj, name, result, false)
+            },
+            Index(obj, index) => {
+                let obj = self.check_expr(obj);
+                self.check_expr(index);
+                if obj == String { String } else { Any }
+            },
+            IndexSetter(obj, index, value) => {
+                self.check_expr(obj);
+                self.check_expr(index);
+                self.check_expr(value)
+            },
+            CompoundIndexSetter(obj, index, _, value) => {
+                self.check_expr(obj);
+                self.check_expr(index);
+                self.check_expr(value);
+                Any
+            },
+            ListLiteral(items) => {
+                for item in items {
+                    self.check_expr(item);
+                }
+                List
+            },
+            MapLiteral(entries) => {
+                for (key, value) in entries {
+                    self.check_expr(key);
+                    self.check_expr(value);
+                }
+                Map
+            },
+            This(_) => self.this_type.clone().unwrap_or(Any),
+            Super(method) => {
+                let super_name = self.current_class.as_ref()
+                    .and_then(|c| self.classes.get(c))
+                    .and_then(|info| info.superclass.clone());
+                match super_name {
+                    Some(s) => self.find_method(&s, method).unwrap_or(Any),
+                    None => Any,
+                }
+            },
+        }
+    }
+
+    fn assign(&mut self, name: &str, value: Type) -> Type {
+        let mismatch = match self.lookup_mut(name) {
+            Some(binding) if binding.annotated => (!matches!(binding.ty, Any)).then(|| binding.ty.clone()),
+            Some(binding) => {
+                binding.ty = Any;
+                None
+            },
+            None => None,
+        };
+        if let Some(declared) = mismatch {
+            if !self.assignable(&value, &declared) {
+                self.add_error(self.line, &format!("Cannot assign {} to {}, which is declared {}.", value, name, declared));
+            }
+        }
+        value
+    }
+
+    fn property_type(&mut self, obj: &Type, name: &str) -> Type {
+        let found = match obj {
+            Instance(class) => self.find_field(class, name)
+                .or_else(|| self.find_method(class, name))
+                .or_else(|| self.traits.get(class).and_then(|methods| methods.get(name).cloned())),
+            Class(class) => self.find_static(class, name),
+            _ => return self.builtin_property_type(obj, name),
+        };
+        if let (None, Instance(class) | Class(class)) = (&found, obj) {
+            let could_be_set = self.reflective || self.assigned_properties.contains(name);
+            if self.is_known_class(class) && !could_be_set {
+                self.add_error(self.line, &format!("{} has no property {}.", obj, name));
+            }
+        }
+        found.unwrap_or(Any)
+    }
+
+    fn builtin_property_type(&self, obj: &Type, name: &str) -> Type {
+        match obj {
+            Type::Enum(enum_name) => {
+                let Some(variants) = self.enums.get(enum_name) else { return Any };
+                match variants.iter().find(|(variant, _)| variant == name) {
+                    Some((_, 0)) => Variant(enum_name.clone()),
+                    Some((_, fields)) => fixed(vec![Any; *fields], Variant(enum_name.clone())),
+                    None if name == "values" => fixed(vec![], List),
+                    None => Any,
+                }
+            },
+            Generator => match name {
+                "next" => fixed(vec![], Any),
+                "done" => Boolean,
+                _ => Any,
+            },
+            _ => Any,
+        }
+    }
+
+    // Annotated is whether the value comes straight from a variable with a declared type.
+    fn set_field(&mut self, obj: &Type, name: &str, value: Type, annotated: bool) -> Type {
+        let Instance(class) = obj else { return value };
+        match self.find_field(class, name) {
+            Some(declared) => {
+                if !self.assignable(&value, &declared) {
+                    self.add_error(self.line, &format!("Cannot assign {} to field {} of {}, which holds {}.", value, name, class, declared));
+                }
+            },
+            None => {
+                // Fields are declared by their first assignment in init. Like variables, they only
+                // keep a type that was written down: one set from an annotated parameter holds
+                // that parameter's type, and any other may later hold anything.
+                let setting_own_field = self.in_init && self.current_class.as_deref() == Some(class.as_str());
+                if setting_own_field {
+                    let ty = if annotated { value.clone() } else { Any };
+                    if let Some(info) = self.classes.get_mut(class) {
+                        info.fields.insert(Name::from(name), ty);
+                    }
+                }
+            },
+        }
+        value
+    }
+
+    // spread_at is the position of the first spread or named argument. Function types don't record
+    // parameter names, so past that point neither the count nor the positions are known.
+    fn call_type(&mut self, callee: Type, args: &[Type], spread_at: Option<usize>, line: usize) -> Type {
+        match callee {
+            Function(params, arity, returns) => {
+                self.check_arguments(&params, arity, args, spread_at, line);
+                *returns
+            },
+            Class(name) => {
+                let init = self.classes.get(&name).and_then(|info| info.methods.get("init").cloned());
+                let (params, arity) = match init {
+                    Some(Function(params, arity, _)) => (params, arity),
+                    _ => (vec![], ArityRange::exactly(0)),
+                };
+                self.check_arguments(&params, arity, args, spread_at, line);
+                Instance(name)
+            },
+            Any | AnyFunction => Any,
+            _ => {
+                self.add_error(line, "Can only call functions and classes.");
+                Any
+            },
+        }
+    }
+
+    fn check_arguments(&mut self, params: &[Type], arity: ArityRange, args: &[Type], spread_at: Option<usize>, line: usize) {
+        let known = match spread_at {
+            Some(i) => &args[..i],
+            None if !arity.accepts(args.len()) => {
+                self.add_error(line, &format!("Expected {} arguments but got {}.", arity, args.len()));
+                return;
+            },
+            None => args,
+        };
+        // A rest parameter's type describes the list it collects, not the arguments themselves.
+        let positional = if arity.max.is_none() { &params[..params.len()-1] } else { params };
+        for (i, (param, arg)) in positional.iter().zip(known).enumerate() {
+            if !self.assignable(arg, param) {
+                self.add_error(line, &format!("Expected argument {} to be {} but got {}.", i+1, param, arg));
+            }
+        }
+    }
+
+    fn unary_type(&mut self, op: UnaryOp, operand: Type) -> Type {
+        match op {
+            UnaryOp::Not => Boolean,
+            UnaryOp::Negative => match operand {
+                Number | Integer | Any => operand,
+                Instance(ref class) => match self.find_method(class, "__neg__") {
+                    Some(Function(_, _, returns)) => *returns,
+                    _ => Any,
+                },
+                other => {
+                    self.add_error(self.line, &format!("Operand of '-' must be a number, found {}.", other));
+                    Any
+                },
+            },
+        }
+    }
+
+    fn binary_type(&mut self, left: &Type, op: BinaryOp, right: &Type) -> Type {
+        use BinaryOp::*;
+
+        if let Instance(class) = left {
+            return match (op, self.find_method(class, op.special_method())) {
+                (Equal | NotEqual, _) => Boolean,
+                (_, Some(Function(params, _, returns))) => {
+                    if let Some(param) = params.first() {
+                        if !self.assignable(right, param) {
+                            self.add_error(self.line, &format!("Operand of '{}' on {} must be {} but got {}.", op.symbol(), class, param, right));
+                        }
+                    }
+                    *returns
+                },
+                (_, Some(_)) => Any,
+                (_, None) if self.classes.contains_key(class) => {
+                    self.add_error(self.line, &format!("{} does not overload '{}'.", class, op.symbol()));
+                    Any
+                },
+                (_, None) => Any,
+            };
+        }
+        if matches!(op, Equal | NotEqual) { return Boolean; }
+        // Anything untyped on the left might turn out to be an instance with an overload.
+        if *left == Any { return Any; }
+
+        let numeric = |t: &Type| matches!(t, Number | Integer | Any);
+        let valid = match op {
+            Add => (numeric(left) && numeric(right)) || (*left == String && matches!(right, String | Any)),
+            _ => numeric(left) && numeric(right),
+        };
+        if !valid {
+            let expected = match op {
+                Add => "two numbers or two strings",
+                BitAnd | BitOr | BitXor | ShiftLeft | ShiftRight => "integers",
+                _ => "numbers",
+            };
+            self.add_error(self.line, &format!("Operands of '{}' must be {}, found {} and {}.", op.symbol(), expected, left, right));
+            return Any;
+        }
+
+        match op {
+            Less | LessEqual | Greater | GreaterEqual => Boolean,
+            BitAnd | BitOr | BitXor | ShiftLeft | ShiftRight => Integer,
+            Divide => Number,
+            _ if *left == String => String,
+            _ if *right == Any => Any,
+            _ if *left == Integer && *right == Integer => Integer,
+            _ => Number,
+        }
+    }
+}
+
+// The type of a callable with no optional parameters.
+fn fixed(params: Vec<Type>, returns: Type) -> Type {
+    let arity = ArityRange::exactly(params.len());
+    Function(params, arity, Box::new(returns))
+}
+
+fn literal_type(lit: &Literal) -> Type {
+    match lit {
+        Literal::Number(_) => Number,
+        Literal::Integer(_) => Integer,
+        Literal::StringData(_) => String,
+        Literal::Boolean(_) => Boolean,
+        Literal::Nil => Nil,
+        Literal::List(_) => List,
+        Literal::Map(_) => Map,
+        Literal::GenLit(_) => Generator,
+        Literal::CallLit(_) | Literal::InstLit(_) | Literal::EnumLit(_) | Literal::VariantLit(_) => Any,
+    }
+}
+
+// What a statement assigns anywhere in it, including in nested functions and classes.
+#[derive(Default)]
+struct Assigned {
+    variables: Vec<Name>,
+    properties: Vec<Name>, // Of instances and classes alike
+    reflectively: bool, // Whether it uses setField, which can set properties of any name
+}
+
+fn collect_assigned_stmt(s: &Statement, assigned: &mut Assigned) {
+    use Statement::*;
+    match s {
+        Decl(_, e, _, _) | Const(_, e, _, _) | Expr(e, _) | Print(e, _) | Return(e, _) | Yield(e, _) => collect_assigned_expr(e, assigned),
+        Block(v) | Test(_, v, _) | Fun(_, _, v, _) | Generator(_, _, v, _) | Trait(_, v) | PropertyGet(_, v) | PropertySet(_, _, v) => {
+            v.iter().for_each(|s| collect_assigned_stmt(s, assigned));
+        },
+        If(cond, then_branch, else_option, _) => {
+            collect_assigned_expr(cond, assigned);
+            collect_assigned_stmt(then_branch, assigned);
+            if let Some(else_branch) = else_option { collect_assigned_stmt(else_branch, assigned); }
+        },
+        While(cond, body, _) | ForIn(_, cond, body, _) => {
+            collect_assigned_expr(cond, assigned);
+            collect_assigned_stmt(body, assigned);
+        },
+        For(init, cond, incr, body, _) => {
+            if let Some(init) = init { collect_assigned_stmt(init, assigned); }
+            collect_assigned_expr(cond, assigned);
+            if let Some(incr) = incr { collect_assigned_expr(incr, assigned); }
+            collect_assigned_stmt(body, assigned);
+        },
+        Class(_, _, _, methods, statics, _) => {
+            methods.iter().chain(statics.iter()).for_each(|s| collect_assigned_stmt(s, assigned));
+        },
+        Enum(..) | Comment(..) => (),
+        Match(subject, arms, default, _) => {
+            collect_assigned_expr(subject, assigned);
+            for arm in arms {
+                if let Some(guard) = &arm.guard { collect_assigned_expr(guard, assigned); }
+                collect_assigned_stmt(&arm.body, assigned);
+            }
+            if let Some(stmt) = default { collect_assigned_stmt(stmt, assigned); }
+        },
+    }
+}
+
+fn collect_assigned_expr(e: &Expression, assigned: &mut Assigned) {
+    use Expression::*;
+    match e {
+        Assignment(id, value, _) | CompoundAssignment(id, _, value, _) => {
+            assigned.variables.push(id.clone());
+            collect_assigned_expr(value, assigned);
+        },
+        Identifier(id) => assigned.reflectively |= id == Callable::SetField.get_name(),
+        LitExp(_) | This(_) | Super(_) => (),
+        Unary(_, inner) | Grouping(inner) | Getter(inner, _) | Spread(inner) | Named(_, inner) => collect_assigned_expr(inner, assigned),
+        Setter(obj, name, value) | CompoundSetter(obj, name, _, value) => {
+            assigned.properties.push(name.clone());
+            collect_assigned_expr(obj, assigned);
+            collect_assigned_expr(value, assigned);
+        },
+        Binary{left, op: _, right} | Logical{left, op: _, right} | Index(left, right) => {
+            collect_assigned_expr(left, assigned);
+            collect_assigned_expr(right, assigned);
+        },
+        Ternary(a, b, c) | IndexSetter(a, b, c) | CompoundIndexSetter(a, b, _, c) => {
+            collect_assigned_expr(a, assigned);
+            collect_assigned_expr(b, assigned);
+            collect_assigned_expr(c, assigned);
+        },
+        Call(callee, args, ..) => {
+            collect_assigned_expr(callee, assigned);
+            args.iter().for_each(|a| collect_assigned_expr(a, assigned));
+        },
+        ListLiteral(items) => items.iter().for_each(|i| collect_assigned_expr(i, assigned)),
+        MapLiteral(entries) => entries.iter().for_each(|(k, v)| {
+            collect_assigned_expr(k, assigned);
+            collect_assigned_expr(v, assigned);
+        }),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_string(s: &str) -> Result<(), Vec<Name>> {
+        let mut parser = lox::parser::LoxParser::new();
+        parser.load_string(s).expect("Error while scanning input string.");
+        let program = parser.parse().expect("Error while parsing input string.");
+        TypeChecker::new().check(&program)
+    }
+
+    fn test_errors_generic(cases: &[(&str, &str)]) {
+        for (source, message) in cases {
+            let errors = check_string(source).expect_err(source);
+            assert!(errors.iter().any(|e| e.contains(message)), "{:?} does not mention '{}'", errors, message);
+        }
+    }
+
+    mod annotations {
+        use super::*;
+
+        #[test]
+        fn test_check_valid_annotations() {
+            let sources = [
+                "var x: Number = 1; x = 2.5;",
+                "var s: String; s = \"later\";",
+                "const limit: Integer = 10;",
+                "fun add(a: Number, b: Number): Number { return a + b; } add(1, 2.5);",
+                "fun greet(name: String) { print \"Hi \" + name; } greet(\"Bob\");",
+                "fun apply(f: Function, x: Any) { return f(x); }",
+                "class Point { init(x: Number) { this.x = x; } copy(): Point { return Point(this.x); } }",
+            ];
+            for source in sources {
+                assert_eq!(Ok(()), check_string(source), "{}", source);
+            }
+        }
+
+        #[test]
+        fn test_check_declaration_mismatches() {
+            test_errors_generic(&[
+                ("var x: Number = \"one\";", "Cannot initialize x of type Number with String"),
+                ("const flag: Boolean = 1;", "Cannot initialize flag of type Boolean with Integer"),
+                ("var x: Integer = 1; x = 1.5;", "Cannot assign Number to x, which is declared Integer"),
+                ("var x: String = \"a\"; x += 1;", "Operands of '+' must be two numbers or two strings"),
+                ("var w: Widget;", "Unknown type Widget"),
+                ("fun f(w: Widget) {}", "Unknown type Widget"),
+            ]);
+        }
+
+        #[test]
+        fn test_check_function_signatures() {
+            test_errors_generic(&[
+                ("fun f(n: Number) {} f(\"1\");", "Expected argument 1 to be Number but got String"),
+                ("fun f(a, b) {} f(1);", "Expected 2 arguments but got 1"),
+                ("fun f(): String { return 1; }", "Function f must return String but returns Integer"),
+                ("fun f(): Number { return; }", "Function f must return Number but returns Nil"),
+                ("fun f(): Number { return 1; } var s: String = f();", "Cannot initialize s of type String with Number"),
+                ("var n = 1; n();", "Can only call functions and classes"),
+            ]);
+        }
+
+        #[test]
+        fn test_check_defaults_and_rest() {
+            let sources = [
+                "fun f(a: Number, b: Number = 2) {} f(1); f(1, 2);",
+                "fun f(...xs) { for (var x in xs) print x; } f(); f(1, 2, 3);",
+                "fun f(a, b, c) {} var xs = [1, 2]; f(0, ...xs);",
+            ];
+            for source in sources {
+                assert_eq!(Ok(()), check_string(source), "{}", source);
+            }
+            test_errors_generic(&[
+                ("fun f(a, b = 1) {} f();", "Expected 1 to 2 arguments but got 0"),
+                ("fun f(a, ...b) {} f();", "Expected at least 1 arguments but got 0"),
+                ("fun f(n: Number = \"one\") {}", "Default for parameter n must be Number but is String"),
+                ("fun f(...xs: Number) {}", "Rest parameter xs collects a List, not Number"),
+                ("fun f(...xs) {} f(...1);", "Can only spread a list into arguments, not Integer"),
+                ("fun f(a: String, ...b) {} f(1, 2);", "Expected argument 1 to be String but got Integer"),
+            ]);
+        }
+
+        #[test]
+        fn test_check_named_arguments() {
+            let sources = [
+                "fun f(a: Number, b: String = \"x\") {} f(1, b: \"y\"); f(b: \"y\", a: 2);",
+                "class P { init(x: Number, y: Number) {} } P(y: 1, x: 2);",
+            ];
+            for source in sources {
+                assert_eq!(Ok(()), check_string(source), "{}", source);
+            }
+            test_errors_generic(&[
+                ("fun f(a: Number, b) {} f(\"1\", b: 2);", "Expected argument 1 to be Number but got String"),
+                ("fun f(a) {} f(a: 1 - \"2\");", "Operands of '-' must be numbers"),
+            ]);
+        }
+
+        #[test]
+        fn test_check_reports_lines() {
+            let errors = check_string("fun f(n: Number) {}\n\nf(true);").expect_err("Mismatch went unreported");
+            assert_eq!(vec![Name::from("[Line 3] Error: Type error: Expected argument 1 to be Number but got Boolean.")], errors);
+
+            let errors = check_string(concat!(
+                "var ok = 1;\n",
+                "fun f(w: Widget) {}\n",
+                "class P {\n",
+                "    init(x: Number) { this.x = x; }\n",
+                "    class count: Integer = \"none\";\n",
+                "}\n",
+                "print \"a\" - 1;\n",
+                "P(1).x = \"left\";\n",
+            )).expect_err("Mismatches went unreported");
+            assert_eq!(vec![
+                Name::from("[Line 2] Error: Type error: Unknown type Widget."),
+                Name::from("[Line 5] Error: Type error: Cannot initialize P.count of type Integer with String."),
+                Name::from("[Line 7] Error: Type error: Operands of '-' must be numbers, found String and Integer."),
+                Name::from("[Line 8] Error: Type error: Cannot assign String to field x of P, which holds Number."),
+            ], errors);
+        }
+    }
+
+    mod inference {
+        use super::*;
+
+        #[test]
+        fn test_check_operators() {
+            test_errors_generic(&[
+                ("print \"a\" - 1;", "Operands of '-' must be numbers, found String and Integer"),
+                ("print 1 + \"a\";", "Operands of '+' must be two numbers or two strings"),
+                ("print true < 1;", "Operands of '<' must be numbers"),
+                ("print \"a\" & 1;", "Operands of '&' must be integers"),
+                ("print -\"a\";", "Operand of '-' must be a number, found String"),
+                ("var x = 1; print x + true;", "found Integer and Boolean"),
+                ("const name = \"crab\"; print name * 2;", "found String and Integer"),
+            ]);
+        }
+
+        #[test]
+        fn test_check_leaves_untyped_code_alone() {
+            let sources = [
+                "var x; print x + 1;",
+                "var x = 1; x = \"one\"; print x + \"!\";",
+                "fun f(a) { return a - 1; } print f(2);",
+                "var prev = nil; for (var x in [1, 2]) { if (prev != nil) print x - prev; prev = x; }",
+                "var total = 0; var i = 0; while (i < 3) { print total & 1; total = \"done\"; i = i + 1; }",
+                "var n = 1; print n == \"1\";",
+            ];
+            for source in sources {
+                assert_eq!(Ok(()), check_string(source), "{}", source);
+            }
+        }
+    }
+
+    mod classes {
+        use super::*;
+
+        const POINT: &str = "
+            class Point {
+                init(x: Number, y: Number) { this.x = x; this.y = y; this.label = nil; }
+                scale(k: Number): Point { return Point(this.x * k, this.y * k); }
+                __add__(other: Point): Point { return Point(this.x + other.x, this.y + other.y); }
+            }
+            var p = Point(1, 2);
+        ";
+
+        #[test]
+        fn test_check_valid_class_use() {
+            let source = format!("{} {}", POINT, "
+                var q: Point = p.scale(2);
+                print (p + q).x * 2;
+                p.x = 5;
+                p.label = \"origin\";
+                p.label = 3;
+                class Point3 < Point { init(x, y, z) { super.init(x, y); this.z = z; } }
+                var r: Point = Point3(1, 2, 3);
+            ");
+            assert_eq!(Ok(()), check_string(&source));
+        }
+
+        #[test]
+        fn test_check_unannotated_fields_hold_anything() {
+            let source = "class Box { init() { this.v = 1; } } var b = Box(); b.v = \"now a string\"; print b.v;";
+            assert_eq!(Ok(()), check_string(source));
+
+            let mut parser = lox::parser::LoxParser::new();
+            parser.load_string(source).expect("Error while scanning input string.");
+            let program = parser.parse().expect("Error while parsing input string.");
+            let output = lox::interpreter::LoxInterpreter::new().interpret(program);
+            assert_eq!(Ok(Name::from("now a string")), output);
+
+            let source = "class Pair { init(a, b) { this.a = a; this.b = b * 2; } } var p = Pair(1, 2); p.a = \"x\"; p.b = \"y\";";
+            assert_eq!(Ok(()), check_string(source));
+        }
+
+        #[test]
+        fn test_check_unknown_members() {
+            let cases = [
+                ("print p.z;", "Point has no property z."),
+                ("p.rotate(90);", "Point has no property rotate."),
+                ("p.y -= p.dy;", "Point has no property dy."),
+                ("class Grid { class size = 8; } print Grid.count;", "class Grid has no property count."),
+            ];
+            for (source, message) in cases {
+                let full = format!("{} {}", POINT, source);
+                test_errors_generic(&[(&full, message)]);
+            }
+        }
+
+        #[test]
+        fn test_check_members_that_may_exist() {
+            let sources = [
+                // Set elsewhere, or possibly set by name at runtime
+                "p.z = 3; print p.z;",
+                "fun tag(thing) { thing.tag = \"seen\"; } print p.tag;",
+                "setField(p, \"z\", 3); print p.z;",
+                // Inherited, mixed in, or computed
+                "class Point3 < Point { init(x, y, z) { super.init(x, y); } } print Point3(1, 2, 3).scale(2).x;",
+                "trait Named { describe() { return \"a point\"; } } class Spot with Named {} print Spot().describe();",
+                "class Circle { init(r: Number) { this.r = r; } area { return this.r * this.r * 3.14; } } print Circle(1).area;",
+                "class Base { class count = 0; } class Derived < Base {} print Derived.count;",
+                // Any instance of a trait, or of a class the checker didn't see declared
+                "trait Shape { area() { return 0; } } fun show(s: Shape) { print s.perimeter; }",
+            ];
+            for source in sources {
+                let full = format!("{} {}", POINT, source);
+                assert_eq!(Ok(()), check_string(&full), "{}", source);
+            }
+        }
+
+        #[test]
+        fn test_check_class_mismatches() {
+            let cases = [
+                ("Point(1);", "Expected 2 arguments but got 1"),
+                ("Point(1, \"2\");", "Expected argument 2 to be Number but got String"),
+                ("p.x = \"left\";", "Cannot assign String to field x of Point, which holds Number"),
+                ("p.y += \"up\";", "Operands of '+' must be two numbers or two strings"),
+                ("p.scale(\"twice\");", "Expected argument 1 to be Number but got String"),
+                ("var s: String = p.scale(2);", "Cannot initialize s of type String with Point"),
+                ("print p.scale(2).x - \"a\";", "found Number and String"),
+                ("print p + 1;", "Operand of '+' on Point must be Point but got Integer"),
+                ("print p * 2;", "Point does not overload '*'"),
+                ("class Other {} var o: Point = Other();", "Cannot initialize o of type Point with Other"),
+            ];
+            for (source, message) in cases {
+                let full = format!("{} {}", POINT, source);
+                test_errors_generic(&[(&full, message)]);
+            }
+        }
+    }
+}
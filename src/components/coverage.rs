@@ -0,0 +1,487 @@
+use crate::components as lox;
+use lox::interpreter::*;
+use lox::instructions::expression::Expression;
+use lox::instructions::statement::Statement;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Eq)]
+#[derive(PartialOrd)]
+#[derive(Ord)]
+#[derive(Clone)]
+#[derive(Copy)]
+pub enum BranchKind {
+    If,
+    Logical, // All the 'and' and 'or' operators in a statement count as one
+}
+
+impl BranchKind {
+    // The block number in lcov reports.
+    fn block(&self) -> usize {
+        match self {
+            BranchKind::If => 0,
+            BranchKind::Logical => 1,
+        }
+    }
+
+    fn from_block(block: usize) -> Option<BranchKind> {
+        match block {
+            0 => Some(BranchKind::If),
+            1 => Some(BranchKind::Logical),
+            _ => None,
+        }
+    }
+
+    // What each of the two ways means, for people.
+    fn describe(&self, hits: [usize; 2]) -> String {
+        match self {
+            BranchKind::If => format!("if: then {}, else {}", hits[0], hits[1]),
+            BranchKind::Logical => format!("and/or: evaluated right {}, skipped right {}", hits[0], hits[1]),
+        }
+    }
+}
+
+// What ran in one source file. Lines without a statement that can run aren't tracked, and nor are
+// branches in them.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Default)]
+pub struct FileCoverage {
+    pub lines: BTreeMap<usize, usize>, // How often each line ran
+    pub branches: BTreeMap<(usize, BranchKind), [usize; 2]>, // How often each way was taken, the first being then, or evaluated
+}
+
+impl FileCoverage {
+    fn lines_hit(&self) -> usize {
+        self.lines.values().filter(|hits| **hits > 0).count()
+    }
+
+    fn branches_hit(&self) -> usize {
+        self.branches.values().flatten().filter(|hits| **hits > 0).count()
+    }
+
+    fn summary(&self) -> String {
+        let percent = |hit: usize, found: usize| if found == 0 { 100.0 } else { hit as f64 * 100.0 / found as f64 };
+        let (lines, branches) = (self.lines.len(), self.branches.len() * 2);
+        format!(
+            "Lines: {} of {} ({:.1}%). Branches: {} of {} ({:.1}%).",
+            self.lines_hit(), lines, percent(self.lines_hit(), lines),
+            self.branches_hit(), branches, percent(self.branches_hit(), branches),
+        )
+    }
+}
+
+// Line and branch coverage of any number of source files, added up over every run.
+#[derive(Default)]
+pub struct Coverage {
+    files: BTreeMap<String, FileCoverage>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    // Registers what could run in a program, so that what doesn't shows up as missed.
+    pub fn track(&mut self, path: &str, program: &[Statement]) {
+        let file = self.files.entry(String::from(path)).or_default();
+        for s in program {
+            track_statement(file, s);
+        }
+    }
+
+    pub fn hit(&mut self, path: &str, line: usize) {
+        *self.files.entry(String::from(path)).or_default().lines.entry(line).or_default() += 1;
+    }
+
+    // Branches the program wasn't tracked as having, such as those in static field initializers,
+    // which don't run as statements, are ignored.
+    pub fn branch(&mut self, path: &str, line: usize, branch: Branch) {
+        let (kind, first) = match branch {
+            Branch::If(taken) => (BranchKind::If, taken),
+            Branch::Logical(evaluated) => (BranchKind::Logical, evaluated),
+        };
+        let hits = self.files.get_mut(path).and_then(|f| f.branches.get_mut(&(line, kind)));
+        if let Some(hits) = hits {
+            hits[if first { 0 } else { 1 }] += 1;
+        }
+    }
+
+    pub fn file(&self, path: &str) -> Option<&FileCoverage> {
+        self.files.get(path)
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &String> {
+        self.files.keys()
+    }
+
+    // One record per file. Branches on lines that never ran are marked as never reached.
+    pub fn lcov(&self) -> String {
+        let mut report = String::new();
+        for (path, file) in self.files.iter() {
+            report.push_str(&format!("TN:\nSF:{}\n", path));
+            for ((line, kind), hits) in file.branches.iter() {
+                let reached = file.lines.get(line).is_some_and(|hits| *hits > 0);
+                for (branch, taken) in hits.iter().enumerate() {
+                    let taken = if reached { taken.to_string() } else { String::from("-") };
+                    report.push_str(&format!("BRDA:{},{},{},{}\n", line, kind.block(), branch, taken));
+                }
+            }
+            report.push_str(&format!("BRF:{}\nBRH:{}\n", file.branches.len() * 2, file.branches_hit()));
+            for (line, hits) in file.lines.iter() {
+                report.push_str(&format!("DA:{},{}\n", line, hits));
+            }
+            report.push_str(&format!("LF:{}\nLH:{}\nend_of_record\n", file.lines.len(), file.lines_hit()));
+        }
+        report
+    }
+
+    // Adds in the counts from an lcov report, such as one written by an earlier run. Records the
+    // coverage doesn't produce itself are skipped.
+    pub fn merge_lcov(&mut self, report: &str) -> Result<(), String> {
+        let mut current = None;
+        for (n, line) in report.lines().enumerate() {
+            let malformed = || format!("Line {} of the lcov report is malformed.", n + 1);
+            let number = |field: Option<&str>| field.and_then(|f| f.trim().parse::<usize>().ok()).ok_or_else(malformed);
+            let (tag, value) = line.split_once(':').unwrap_or((line, ""));
+            match tag {
+                "SF" => current = Some(String::from(value)),
+                "end_of_record" => current = None,
+                "DA" | "BRDA" => {
+                    let Some(path) = &current else { return Err(malformed()); };
+                    let file = self.files.entry(path.clone()).or_default();
+                    let mut fields = value.split(',');
+                    let line = number(fields.next())?;
+                    if tag == "DA" {
+                        *file.lines.entry(line).or_default() += number(fields.next())?;
+                        continue;
+                    }
+                    let kind = BranchKind::from_block(number(fields.next())?).ok_or_else(malformed)?;
+                    let branch = number(fields.next())?;
+                    let hits = file.branches.entry((line, kind)).or_default();
+                    match (branch, fields.next()) {
+                        (0 | 1, Some("-")) => (),
+                        (0 | 1, taken) => hits[branch] += number(taken)?,
+                        _ => return Err(malformed()),
+                    }
+                },
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    // The source with how often each line ran down the side, gcov style: '-' where there's
+    // nothing to run, '#####' where it never did. Branches are listed under their lines.
+    pub fn annotate(&self, path: &str, source: &str) -> String {
+        let empty = FileCoverage::default();
+        let file = self.files.get(path).unwrap_or(&empty);
+        let mut view = format!("{}\n", path);
+        for (i, text) in source.lines().enumerate() {
+            let line = i + 1;
+            let count = match file.lines.get(&line) {
+                None => String::from("-"),
+                Some(0) => String::from("#####"),
+                Some(hits) => hits.to_string(),
+            };
+            view.push_str(&format!("{:>9}:{:>5}:{}\n", count, line, text));
+            for ((_, kind), hits) in file.branches.range((line, BranchKind::If)..=(line, BranchKind::Logical)) {
+                view.push_str(&format!("{:>16}branch {}\n", "", kind.describe(*hits)));
+            }
+        }
+        view.push_str(&file.summary());
+        view.push('\n');
+        view
+    }
+}
+
+fn track_statement(file: &mut FileCoverage, s: &Statement) {
+    use Statement::*;
+
+    let Some(line) = s.line() else {
+        match s {
+            Block(v) | Test(_, v, _) => {
+                for s in v { track_statement(file, s); }
+            },
+            Fun(_, _, body, _) | Generator(_, _, body, _) | PropertyGet(_, body) | PropertySet(_, _, body) => {
+                for s in body { track_statement(file, s); }
+            },
+            // Static fields are evaluated with the class, not run as statements.
+            Class(_, _, _, methods, statics, _) => {
+                for member in methods.iter().chain(statics.iter()).filter(|m| !matches!(**m, Decl(..))) {
+                    track_statement(file, member);
+                }
+            },
+            Trait(_, methods) => {
+                for s in methods { track_statement(file, s); }
+            },
+            _ => (),
+        }
+        return;
+    };
+
+    file.lines.entry(line).or_default();
+    let mut track_logical = |e: &Expression| {
+        if has_logical(e) {
+            file.branches.entry((line, BranchKind::Logical)).or_default();
+        }
+    };
+    let mut nested = Vec::new();
+    match s {
+        Decl(_, e, _, _) | Const(_, e, _, _) | Expr(e, _) | Print(e, _) | Return(e, _) | Yield(e, _) => track_logical(e),
+        If(cond, then_branch, else_option, _) => {
+            track_logical(cond);
+            nested.push(then_branch);
+            nested.extend(else_option);
+        },
+        While(cond, body, _) => {
+            track_logical(cond);
+            nested.push(body);
+        },
+        For(init, cond, incr, body, _) => {
+            track_logical(cond);
+            if let Some(incr) = incr { track_logical(incr); }
+            nested.extend(init);
+            nested.push(body);
+        },
+        ForIn(_, iterable, body, _) => {
+            track_logical(iterable);
+            nested.push(body);
+        },
+        Match(subject, arms, default, _) => {
+            track_logical(subject);
+            for arm in arms {
+                if let Some(guard) = &arm.guard { track_logical(guard); }
+                nested.push(&arm.body);
+            }
+            nested.extend(default);
+        },
+        _ => (),
+    }
+    if let If(..) = s {
+        file.branches.entry((line, BranchKind::If)).or_default();
+    }
+    for s in nested {
+        track_statement(file, s);
+    }
+}
+
+fn has_logical(e: &Expression) -> bool {
+    use Expression::*;
+
+    match e {
+        Logical{..} => true,
+        LitExp(_) | Identifier(_) | This(_) | Super(_) => false,
+        Unary(_, inner) | Grouping(inner) | Spread(inner) | Named(_, inner) | Getter(inner, _) |
+        Assignment(_, inner, _) | CompoundAssignment(_, _, inner, _) => has_logical(inner),
+        Binary{left, op: _, right} | Index(left, right) | Setter(left, _, right) | CompoundSetter(left, _, _, right) => {
+            has_logical(left) || has_logical(right)
+        },
+        Ternary(first, second, third) | IndexSetter(first, second, third) | CompoundIndexSetter(first, second, _, third) => {
+            has_logical(first) || has_logical(second) || has_logical(third)
+        },
+        Call(callee, args, ..) => has_logical(callee) || args.iter().any(has_logical),
+        ListLiteral(items) => items.iter().any(has_logical),
+        MapLiteral(entries) => entries.iter().any(|(k, v)| has_logical(k) || has_logical(v)),
+    }
+}
+
+// Feeds a shared coverage from a running program, under the path of its source.
+pub struct CoverageHook {
+    coverage: Rc<RefCell<Coverage>>,
+    path: String,
+}
+
+impl CoverageHook {
+    pub fn new(path: &str, coverage: Rc<RefCell<Coverage>>) -> CoverageHook {
+        CoverageHook{ coverage, path: String::from(path) }
+    }
+}
+
+impl ExecutionHook for CoverageHook {
+    fn before_statement(&mut self, _interpreter: &mut LoxInterpreter, line: usize) -> Result<(), String> {
+        self.coverage.borrow_mut().hit(&self.path, line);
+        Ok(())
+    }
+
+    fn branch(&mut self, _interpreter: &mut LoxInterpreter, line: usize, branch: Branch) {
+        self.coverage.borrow_mut().branch(&self.path, line, branch);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lox::parser::LoxParser;
+
+    const PATH: &str = "test.lox";
+
+    const SOURCE: &str = "\
+fun sign(n) {
+    if (n < 0) return -1;
+    if (n == 0 or n != n) {
+        return 0;
+    }
+    return 1;
+}
+print sign(5);
+print sign(0);";
+
+    fn parse(source: &str) -> Vec<Statement> {
+        let mut parser = LoxParser::new();
+        parser.load_string(source).expect("Error while scanning input string");
+        parser.parse().expect("Error while parsing input string")
+    }
+
+    // Runs the source once, adding to the coverage.
+    fn run(coverage: Coverage, source: &str) -> Coverage {
+        let program = parse(source);
+        let coverage = Rc::new(RefCell::new(coverage));
+        coverage.borrow_mut().track(PATH, &program);
+        let mut interpreter = LoxInterpreter::new();
+        interpreter.set_hook(Box::new(CoverageHook::new(PATH, Rc::clone(&coverage))));
+        interpreter.interpret(program).expect("Error while interpreting program");
+        drop(interpreter);
+        Rc::try_unwrap(coverage).ok().expect("The interpreter is done with the coverage").into_inner()
+    }
+
+    fn lines(coverage: &Coverage) -> Vec<(usize, usize)> {
+        coverage.file(PATH).expect("A tracked file").lines.iter().map(|(l, h)| (*l, *h)).collect()
+    }
+
+    fn branches(coverage: &Coverage) -> Vec<(usize, BranchKind, [usize; 2])> {
+        coverage.file(PATH).expect("A tracked file").branches.iter().map(|((l, k), h)| (*l, *k, *h)).collect()
+    }
+
+    mod recording {
+        use super::*;
+
+        #[test]
+        fn test_lines() {
+            let coverage = run(Coverage::new(), SOURCE);
+            assert_eq!(vec![(2, 2), (3, 2), (4, 1), (6, 1), (8, 1), (9, 1)], lines(&coverage));
+        }
+
+        #[test]
+        fn test_branches() {
+            let coverage = run(Coverage::new(), SOURCE);
+            assert_eq!(vec![
+                (2, BranchKind::If, [0, 2]),
+                (3, BranchKind::If, [1, 1]),
+                (3, BranchKind::Logical, [1, 1]),
+            ], branches(&coverage));
+        }
+
+        #[test]
+        fn test_missed_code() {
+            let coverage = run(Coverage::new(), "\
+var a = false;
+if (a) {
+    print 1;
+} else {
+    print 2;
+}
+fun never() {
+    return a and true;
+}
+while (a) print 3;");
+            assert_eq!(vec![(1, 1), (2, 1), (3, 0), (5, 1), (8, 0), (10, 1)], lines(&coverage));
+            assert_eq!(vec![
+                (2, BranchKind::If, [0, 1]),
+                (8, BranchKind::Logical, [0, 0]),
+            ], branches(&coverage));
+        }
+
+        #[test]
+        fn test_classes_and_generators() {
+            let coverage = run(Coverage::new(), "\
+class A {
+    class limit = 1 or 2;
+    get() {
+        return this.x;
+    }
+}
+fun* g() {
+    yield 1;
+    if (true) yield 2;
+}
+var gen = g();
+gen.next();");
+            assert_eq!(vec![(4, 0), (8, 1), (9, 0), (11, 1), (12, 1)], lines(&coverage));
+            assert_eq!(vec![(9, BranchKind::If, [0, 0])], branches(&coverage));
+        }
+
+        #[test]
+        fn test_runs_add_up() {
+            let coverage = run(run(Coverage::new(), SOURCE), SOURCE);
+            assert_eq!(vec![(2, 4), (3, 4), (4, 2), (6, 2), (8, 2), (9, 2)], lines(&coverage));
+        }
+    }
+
+    mod reports {
+        use super::*;
+
+        #[test]
+        fn test_lcov() {
+            let coverage = run(Coverage::new(), "var a = true;\nif (a) print 1;\nfun f() {\n    if (a) print 2;\n}");
+            assert_eq!("\
+TN:
+SF:test.lox
+BRDA:2,0,0,1
+BRDA:2,0,1,0
+BRDA:4,0,0,-
+BRDA:4,0,1,-
+BRF:4
+BRH:1
+DA:1,1
+DA:2,2
+DA:4,0
+LF:3
+LH:2
+end_of_record
+", coverage.lcov());
+        }
+
+        #[test]
+        fn test_merge_lcov() {
+            let first = run(Coverage::new(), SOURCE);
+            let mut merged = run(Coverage::new(), SOURCE);
+            merged.merge_lcov(&first.lcov()).expect("A valid report");
+            assert_eq!(run(first, SOURCE).lcov(), merged.lcov());
+
+            let mut coverage = Coverage::new();
+            assert_eq!(Err(String::from("Line 1 of the lcov report is malformed.")), coverage.merge_lcov("DA:1,1"));
+            assert_eq!(Err(String::from("Line 2 of the lcov report is malformed.")), coverage.merge_lcov("SF:a.lox\nDA:x,1"));
+        }
+
+        #[test]
+        fn test_annotate() {
+            let coverage = run(Coverage::new(), SOURCE);
+            let view = coverage.annotate(PATH, SOURCE);
+            let expected = "\
+test.lox
+        -:    1:fun sign(n) {
+        2:    2:    if (n < 0) return -1;
+                branch if: then 0, else 2
+        2:    3:    if (n == 0 or n != n) {
+                branch if: then 1, else 1
+                branch and/or: evaluated right 1, skipped right 1
+        1:    4:        return 0;
+        -:    5:    }
+        1:    6:    return 1;
+        -:    7:}
+        1:    8:print sign(5);
+        1:    9:print sign(0);
+Lines: 6 of 6 (100.0%). Branches: 5 of 6 (83.3%).
+";
+            assert_eq!(expected, view);
+            assert!(Coverage::new().annotate(PATH, "print 1;").contains("        -:    1:print 1;\n"));
+        }
+    }
+}
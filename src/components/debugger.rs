@@ -0,0 +1,355 @@
+use crate::components as lox;
+use lox::interpreter::*;
+use lox::instructions::node::Literal;
+
+use std::collections::BTreeSet;
+use std::io::BufRead;
+use std::io::Write;
+
+const HELP: &str = "\
+Commands:
+  break N, b N      Pause before line N
+  delete N, d N     Remove the breakpoint on line N
+  continue, c       Run until the next breakpoint
+  step, s           Run to the next statement, entering calls
+  next, n           Run to the next statement in this function or its callers
+  finish, f         Run until this function returns
+  backtrace, bt     Show the calls in progress
+  locals            Show the variables in scope, innermost first
+  globals           Show the global variables
+  print NAME, p     Show the value of a variable
+  list              Show the source around the current line
+  quit, q           Stop the program";
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Copy)]
+pub enum StepMode {
+    Continue, // Only pause at breakpoints
+    Into, // Pause at the next statement anywhere
+    Over(usize), // Pause at the next statement at or above this call depth
+    Out(usize), // Pause at the next statement above this call depth
+}
+
+// Decides when a debugged program should pause. Statements on the line that last ran, in the
+// same call, don't pause it again, so a line is a single step however many statements it holds.
+pub struct Stepper {
+    pub breakpoints: BTreeSet<usize>,
+    pub mode: StepMode,
+    previous: Option<(usize, usize)>, // Call depth and line of the last statement seen
+}
+
+impl Stepper {
+    // Starts out stepping, so the program pauses before its first statement.
+    pub fn new() -> Stepper {
+        Stepper{ breakpoints: BTreeSet::new(), mode: StepMode::Into, previous: None }
+    }
+
+    pub fn should_stop(&mut self, line: usize, depth: usize) -> bool {
+        if self.previous == Some((depth, line)) {
+            return false;
+        }
+        self.previous = Some((depth, line));
+        let stepped = match self.mode {
+            StepMode::Continue => false,
+            StepMode::Into => true,
+            StepMode::Over(from) => depth <= from,
+            StepMode::Out(from) => depth < from,
+        };
+        stepped || self.breakpoints.contains(&line)
+    }
+
+    // A loop going around again starts a new step, even on the line it was already on.
+    pub fn next_iteration(&mut self) {
+        self.previous = None;
+    }
+}
+
+// Variables to show, leaving out the native functions every program has.
+pub fn user_bindings(scope: Vec<(String, Literal)>) -> Vec<(String, Literal)> {
+    scope.into_iter()
+        .filter(|(_, value)| !matches!(value, Literal::CallLit(c) if c.is_native()))
+        .collect()
+}
+
+// Drops the bindings shadowed by an earlier one of the same name, for scopes listed innermost first.
+pub fn unshadowed(bindings: Vec<(String, Literal)>) -> Vec<(String, Literal)> {
+    let mut seen: Vec<String> = Vec::new();
+    let mut visible = Vec::new();
+    for (name, value) in bindings {
+        if seen.contains(&name) { continue; }
+        seen.push(name.clone());
+        visible.push((name, value));
+    }
+    visible
+}
+
+// A debugger for the terminal. It pauses before the program's first statement, then takes
+// commands until one resumes it. If the commands run out, the program runs to the end.
+pub struct LoxDebugger<R: BufRead, W: Write> {
+    source: Vec<String>,
+    commands: R,
+    output: W,
+    stepper: Stepper,
+}
+
+impl<R: BufRead, W: Write> LoxDebugger<R, W> {
+    pub fn new(source: &str, commands: R, output: W) -> LoxDebugger<R, W> {
+        LoxDebugger {
+            source: source.lines().map(String::from).collect(),
+            commands,
+            output,
+            stepper: Stepper::new(),
+        }
+    }
+
+    // Runs one command, returning whether it resumes the program.
+    fn command(&mut self, interpreter: &mut LoxInterpreter, line: usize, input: &str) -> Result<bool, String> {
+        let depth = interpreter.frames().len();
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let text = match words.as_slice() {
+            ["continue" | "c"] => return self.resume(StepMode::Continue),
+            ["step" | "s"] => return self.resume(StepMode::Into),
+            ["next" | "n"] => return self.resume(StepMode::Over(depth)),
+            ["finish" | "f"] => return self.resume(StepMode::Out(depth)),
+            ["quit" | "q"] => return Err(String::from("Debugging stopped.")),
+            ["break" | "b", n] => match n.parse::<usize>() {
+                Ok(n) if n > 0 && n <= self.source.len() => {
+                    self.stepper.breakpoints.insert(n);
+                    format!("Breakpoint set at line {}.", n)
+                },
+                _ => format!("There is no line {}.", n),
+            },
+            ["delete" | "d", n] => match n.parse::<usize>() {
+                Ok(n) if self.stepper.breakpoints.remove(&n) => format!("Breakpoint at line {} removed.", n),
+                _ => format!("No breakpoint at line {}.", n),
+            },
+            ["backtrace" | "bt"] => interpreter.frames().iter()
+                .rev()
+                .enumerate()
+                .map(|(i, frame)| format!("#{} {} at line {}", i, frame.function, frame.line))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            ["locals"] => {
+                let mut scopes = interpreter.scopes();
+                scopes.pop();
+                let lines = self.bindings(interpreter, unshadowed(scopes.into_iter().flatten().collect()))?;
+                if lines.is_empty() { String::from("No locals.") } else { lines.join("\n") }
+            },
+            ["globals"] => {
+                let globals = user_bindings(interpreter.globals());
+                let lines = self.bindings(interpreter, globals)?;
+                if lines.is_empty() { String::from("No globals.") } else { lines.join("\n") }
+            },
+            ["print" | "p", name] => match interpreter.lookup(name) {
+                Ok(value) => format!("{} = {}", name, interpreter.display(value)?),
+                Err(e) => e,
+            },
+            ["list"] => {
+                let first = line.saturating_sub(3).max(1);
+                let last = (line + 3).min(self.source.len());
+                (first..=last)
+                    .map(|n| format!("{}{:>4} {}", if n == line { "=>" } else { "  " }, n, self.source[n - 1]))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            },
+            ["help" | "h"] | [] => String::from(HELP),
+            _ => format!("Unknown command '{}'. Type 'help' for a list of commands.", input.trim()),
+        };
+        writeln!(self.output, "{}", text).map_err(|e| e.to_string())?;
+        Ok(false)
+    }
+
+    fn resume(&mut self, mode: StepMode) -> Result<bool, String> {
+        self.stepper.mode = mode;
+        Ok(true)
+    }
+
+    fn bindings(&self, interpreter: &mut LoxInterpreter, bindings: Vec<(String, Literal)>) -> Result<Vec<String>, String> {
+        bindings.into_iter()
+            .map(|(name, value)| Ok(format!("{} = {}", name, interpreter.display(value)?)))
+            .collect()
+    }
+}
+
+impl<R: BufRead, W: Write> ExecutionHook for LoxDebugger<R, W> {
+    fn before_statement(&mut self, interpreter: &mut LoxInterpreter, line: usize) -> Result<(), String> {
+        if !self.stepper.should_stop(line, interpreter.frames().len()) {
+            return Ok(());
+        }
+
+        let printed = interpreter.take_output();
+        let text = self.source.get(line - 1).map_or("", |l| l.trim());
+        write!(self.output, "{}", printed).map_err(|e| e.to_string())?;
+        writeln!(self.output, "Stopped at line {}: {}", line, text).map_err(|e| e.to_string())?;
+        loop {
+            write!(self.output, "(debug) ").map_err(|e| e.to_string())?;
+            self.output.flush().map_err(|e| e.to_string())?;
+
+            let mut input = String::new();
+            if self.commands.read_line(&mut input).map_err(|e| e.to_string())? == 0 {
+                self.stepper.breakpoints.clear();
+                self.stepper.mode = StepMode::Continue;
+                return Ok(());
+            }
+            if self.command(interpreter, line, &input)? {
+                return Ok(());
+            }
+        }
+    }
+
+    fn next_iteration(&mut self, _interpreter: &mut LoxInterpreter, _line: usize) {
+        self.stepper.next_iteration();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lox::parser::LoxParser;
+    use std::io;
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    // Output the test can still read after the debugger that owns it is done.
+    #[derive(Clone)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const SOURCE: &str = "\
+fun square(n) {
+    var result = n * n;
+    return result;
+}
+var total = 0;
+for (var i = 1; i < 3; i = i + 1) {
+    total = total + square(i);
+}
+print total;";
+
+    // Runs the source under the debugger with the given commands, returning the transcript and
+    // the program's result.
+    fn debug_session(source: &str, commands: &str) -> (String, Result<String, String>) {
+        let mut parser = LoxParser::new();
+        parser.load_string(source).expect("Error while scanning input string");
+        let program = parser.parse().expect("Error while parsing input string");
+
+        let buffer = SharedBuffer(Rc::new(RefCell::new(Vec::new())));
+        let debugger = LoxDebugger::new(source, io::Cursor::new(String::from(commands)), buffer.clone());
+        let mut interpreter = LoxInterpreter::new();
+        interpreter.set_hook(Box::new(debugger));
+        let result = interpreter.interpret(program);
+        let transcript = String::from_utf8(buffer.0.borrow().clone()).expect("Valid UTF-8");
+        (transcript, result)
+    }
+
+    // The lines the program paused at, in order. Prompts don't end their lines, so the pauses
+    // can't be found line by line.
+    fn stops(transcript: &str) -> Vec<usize> {
+        transcript.split("Stopped at line ")
+            .skip(1)
+            .map(|l| l.split(':').next().and_then(|n| n.parse().ok()).expect("A line number"))
+            .collect()
+    }
+
+    mod stepping {
+        use super::*;
+
+        #[test]
+        fn test_step_into_and_out() {
+            let (transcript, result) = debug_session(SOURCE, "n\nn\ns\ns\ns\ns\ns\nf\nn\n");
+            // Finishing skips the rest of line 7, which already ran, and stops at the loop header.
+            assert_eq!(vec![5, 6, 7, 2, 3, 6, 7, 2, 6, 9], stops(&transcript));
+            assert_eq!(Ok(String::from("5")), result);
+        }
+
+        #[test]
+        fn test_step_over_calls() {
+            let (transcript, _) = debug_session(SOURCE, "n\nn\nn\nn\nn\nn\nn\nn\n");
+            assert_eq!(vec![5, 6, 7, 6, 7, 6, 9], stops(&transcript));
+        }
+
+        #[test]
+        fn test_breakpoints() {
+            let (transcript, result) = debug_session(SOURCE, "b 3\nc\np result\nc\nd 3\nc\n");
+            assert_eq!(vec![5, 3, 3], stops(&transcript));
+            assert!(transcript.contains("Breakpoint set at line 3."), "{}", transcript);
+            assert!(transcript.contains("result = 1\n"), "{}", transcript);
+            assert!(transcript.contains("Breakpoint at line 3 removed."), "{}", transcript);
+            assert_eq!(Ok(String::from("5")), result);
+        }
+
+        #[test]
+        fn test_breakpoint_in_one_line_loop() {
+            let source = "var i = 0;\nwhile (i < 3) i = i + 1;\nfor (var j = 0; j < 2; j = j + 1) print j;";
+            let (transcript, result) = debug_session(source, "b 2\nb 3\nc\np i\nc\np i\nc\np i\nc\nc\nc\n");
+            assert_eq!(vec![1, 2, 2, 2, 3, 3], stops(&transcript));
+            assert!(transcript.contains("i = 0\n(debug) Stopped at line 2: while (i < 3) i = i + 1;\n(debug) i = 1\n"), "{}", transcript);
+            assert!(transcript.contains("i = 2\n(debug) Stopped at line 3"), "{}", transcript);
+            assert_eq!(Ok(String::from("1")), result);
+        }
+
+        #[test]
+        fn test_output_before_stops() {
+            let (transcript, result) = debug_session("print 1;\nprint 2;\nprint 3;", "n\nn\n");
+            assert_eq!("Stopped at line 1: print 1;\n(debug) 1\nStopped at line 2: print 2;\n(debug) 2\nStopped at line 3: print 3;\n(debug) ", transcript);
+            assert_eq!(Ok(String::from("3")), result);
+        }
+
+        #[test]
+        fn test_quit() {
+            let (_, result) = debug_session(SOURCE, "q\n");
+            assert_eq!(Err(String::from("Debugging stopped.")), result);
+        }
+    }
+
+    mod inspection {
+        use super::*;
+
+        #[test]
+        fn test_backtrace() {
+            let (transcript, _) = debug_session(SOURCE, "b 2\nc\nbt\nq\n");
+            assert!(transcript.contains("#0 square at line 2\n#1 script at line 7\n"), "{}", transcript);
+        }
+
+        #[test]
+        fn test_locals_and_globals() {
+            let (transcript, _) = debug_session(SOURCE, "b 3\nc\nlocals\nglobals\nq\n");
+            assert!(transcript.contains("(debug) result = 1\nn = 1\n(debug) "), "{}", transcript);
+            assert!(transcript.contains("(debug) square = <fn square>\ntotal = 0\n(debug) "), "{}", transcript);
+        }
+
+        #[test]
+        fn test_print_global_from_function() {
+            let (transcript, _) = debug_session(SOURCE, "b 3\nc\nc\np total\np n\nq\n");
+            assert!(transcript.contains("(debug) total = 1\n(debug) n = 2\n"), "{}", transcript);
+        }
+
+        #[test]
+        fn test_bad_commands() {
+            let (transcript, _) = debug_session(SOURCE, "b 99\nd 2\np missing\nfly\nq\n");
+            assert!(transcript.contains("There is no line 99."), "{}", transcript);
+            assert!(transcript.contains("No breakpoint at line 2."), "{}", transcript);
+            assert!(transcript.contains("Undefined variable 'missing'."), "{}", transcript);
+            assert!(transcript.contains("Unknown command 'fly'."), "{}", transcript);
+        }
+
+        #[test]
+        fn test_commands_run_out() {
+            let (transcript, result) = debug_session(SOURCE, "b 3\n");
+            assert_eq!(vec![5], stops(&transcript));
+            assert_eq!(Ok(String::from("5")), result);
+        }
+    }
+}
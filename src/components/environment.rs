@@ -5,11 +5,19 @@ use std::rc::Rc;
 use std::cell::RefCell;
 use std::collections::HashMap;
 
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+struct Binding {
+    value: Literal,
+    mutable: bool,
+}
+
 #[derive(Debug)]
 #[derive(PartialEq)]
 #[derive(Clone)]
 pub struct LoxEnvironment {
-    nodes: Vec<HashMap<String, Literal>>,
+    nodes: Vec<HashMap<String, Binding>>,
     in_closure: bool,
     cur_closure: Option<Rc<RefCell<LoxEnvironment>>>,
     self_mounts: usize,
@@ -26,7 +34,7 @@ impl LoxEnvironment  {
             None => {
                 let last = self.nodes.len()-1;
 
-                self.nodes[last].insert(String::from(name), value);
+                self.nodes[last].insert(String::from(name), Binding{ value, mutable: true });
             },
             Some(ref mut closure) => {
                 closure.borrow_mut().define(name, value);
@@ -34,17 +42,46 @@ impl LoxEnvironment  {
         };
     }
 
+    // Defines a binding on behalf of a declaration in the program. Unlike define(), this refuses
+    // to replace a constant declared in the same scope, or a core native.
+    pub fn declare(&mut self, name: &str, value: Literal, mutable: bool) -> Result<(), String> {
+        match &mut self.cur_closure {
+            None => {
+                let last = self.nodes.len()-1;
+                if let Some(existing) = self.nodes[last].get(name) {
+                    let replaceable = matches!(&existing.value, Literal::CallLit(c) if c.is_native() && !c.is_core_native());
+                    if !existing.mutable && !replaceable {
+                        return Err(LoxEnvironment::constant_error("redefine", name, &existing.value));
+                    }
+                }
+                self.nodes[last].insert(String::from(name), Binding{ value, mutable });
+                Ok(())
+            },
+            Some(ref mut closure) => closure.borrow_mut().declare(name, value, mutable),
+        }
+    }
+
+    fn constant_error(action: &str, name: &str, value: &Literal) -> String {
+        match value {
+            Literal::CallLit(c) if c.is_native() => format!("Cannot {} native function {}.", action, name),
+            _ => format!("Cannot {} constant {}.", action, name),
+        }
+    }
+
     pub fn assign(&mut self, name: &str, value: Literal) -> Result<Literal, String> {
         match &mut self.cur_closure {
             None => {
                 let iter = self.nodes.iter_mut().rev();
                 for node in iter {
-                    if node.contains_key(name) {
-                        node.insert(String::from(name), value.clone());
+                    if let Some(binding) = node.get_mut(name) {
+                        if !binding.mutable {
+                            return Err(LoxEnvironment::constant_error("assign to", name, &binding.value));
+                        }
+                        binding.value = value.clone();
                         return Ok(value);
                     }
                 }
-                Err(format!("Undefined variable {}.", name))
+                Err(format!("Undefined variable '{}'.", name))
             },
             Some(ref mut closure) => closure.borrow_mut().assign(name, value),
         }
@@ -53,11 +90,11 @@ impl LoxEnvironment  {
     fn get_internal(&mut self, name: &str) -> Result<&mut Literal, String> {
         let iter = self.nodes.iter_mut().rev();
         for node in iter {
-            if let Some(lit) = node.get_mut(name) {
-                return Ok(lit);
+            if let Some(binding) = node.get_mut(name) {
+                return Ok(&mut binding.value);
             }
         }
-        Err(format!("Undefined variable {}.", name))
+        Err(format!("Undefined variable '{}'.", name))
     }
 
     pub fn get(&mut self, name: &str) -> Result<Literal, String> {
@@ -67,6 +104,41 @@ impl LoxEnvironment  {
         }
     }
 
+    // The bindings of the environment code is running in, which may be a mounted closure,
+    // innermost scope first and sorted by name within each.
+    pub fn visible_scopes(&self) -> Vec<Vec<(String, Literal)>> {
+        if let Some(closure) = &self.cur_closure {
+            return closure.borrow().visible_scopes();
+        }
+        self.nodes.iter()
+            .rev()
+            .map(|node| {
+                let mut bindings: Vec<(String, Literal)> = node.iter()
+                    .map(|(name, binding)| (name.clone(), binding.value.clone()))
+                    .collect();
+                bindings.sort_by(|a, b| a.0.cmp(&b.0));
+                bindings
+            })
+            .collect()
+    }
+
+    // How many scopes are visible to running code, counting the globals.
+    pub fn depth(&self) -> usize {
+        match &self.cur_closure {
+            Some(closure) => closure.borrow().depth(),
+            None => self.nodes.len(),
+        }
+    }
+
+    // The outermost scope, whatever closure code is running in.
+    pub fn globals(&self) -> Vec<(String, Literal)> {
+        let mut bindings: Vec<(String, Literal)> = self.nodes[0].iter()
+            .map(|(name, binding)| (name.clone(), binding.value.clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
     pub fn lower_scope(&mut self){
         match &mut self.cur_closure {
             None => {
@@ -105,10 +177,18 @@ impl LoxEnvironment  {
 
     fn decouple_closures(&mut self) {
         for node in self.nodes.iter_mut() {
-            for (_, val) in node.iter_mut() {
-                match val {
+            for (_, binding) in node.iter_mut() {
+                match &mut binding.value {
                     Literal::CallLit(c) => c.decouple_closures(),
                     Literal::InstLit(i) => i.decouple_closures(),
+                    Literal::List(items) => {
+                        let copy = items.borrow().clone();
+                        *items = Rc::new(RefCell::new(copy));
+                    },
+                    Literal::Map(entries) => {
+                        let copy = entries.borrow().clone();
+                        *entries = Rc::new(RefCell::new(copy));
+                    },
                     _ => (),
                 }
             }
@@ -237,6 +317,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_env_constants() {
+        let mut env = LoxEnvironment::new();
+        env.declare("answer", Literal::Integer(42), false).expect("Declaration failed");
+
+        let err_out = env.assign("answer", Literal::Nil);
+        if let Err(e) = err_out {
+            assert!(e.contains("Cannot assign to constant answer"));
+        } else {
+            panic!("Unexpectedly recieved valid output.");
+        }
+
+        let err_out = env.declare("answer", Literal::Nil, true);
+        if let Err(e) = err_out {
+            assert!(e.contains("Cannot redefine constant answer"));
+        } else {
+            panic!("Unexpectedly recieved valid output.");
+        }
+
+        env.lower_scope();
+        env.declare("answer", Literal::Nil, true).expect("Shadowing declaration failed");
+        env.assign("answer", Literal::Boolean(true)).expect("Assignment failed");
+        env.raise_scope().expect("Scope raise failed");
+        assert_eq!(Ok(Literal::Integer(42)), env.get("answer"), "Constant changed through a shadowing binding");
+    }
+
     #[test]
     fn test_env_access_out_of_scope() {
         let mut env = LoxEnvironment::new();
@@ -268,4 +374,26 @@ mod tests {
             panic!("Unexpectedly recieved valid output.");
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_env_visible_scopes() {
+        let mut env = LoxEnvironment::new();
+        env.define("b", Literal::Integer(1));
+        env.define("a", Literal::Integer(2));
+        env.lower_scope();
+        env.define("a", Literal::Integer(3));
+
+        let closure = env.spawn_closure();
+        closure.borrow_mut().lower_scope();
+        closure.borrow_mut().define("c", Literal::Nil);
+        env.mount_closure(&Some(closure));
+
+        let expected = vec![
+            vec![(String::from("c"), Literal::Nil)],
+            vec![(String::from("a"), Literal::Integer(3))],
+            vec![(String::from("a"), Literal::Integer(2)), (String::from("b"), Literal::Integer(1))],
+        ];
+        assert_eq!(expected, env.visible_scopes());
+        assert_eq!(expected[2], env.globals());
+    }
+}
@@ -2,6 +2,10 @@ pub fn new_error_string(line: usize, message: &str) -> String {
     format!("[Line {}] Error: {}", line, message)
 }
 
+pub fn new_warning_string(line: usize, rule: &str, message: &str) -> String {
+    format!("[Line {}] Warning ({}): {}", line, rule, message)
+}
+
 
 #[cfg(test)]
 mod error_tests {
@@ -16,4 +20,13 @@ mod error_tests {
         );
     }
 
+    #[test]
+    fn generate_warning_test () {
+        let warning_string = lox::error::new_warning_string(7, "unused-crab", "Crab is never used.");
+        assert_eq!(
+            String::from("[Line 7] Warning (unused-crab): Crab is never used."),
+            warning_string
+        );
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,888 @@
+use crate::components as lox;
+use lox::instructions::{statement, expression, node, pattern};
+use lox::parser::LoxParser;
+use statement::Statement;
+use expression::Expression;
+use node::*;
+use pattern::*;
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+enum ListKind {
+    Statements,
+    Methods,
+    Statics, // Class members declared with a leading 'class'
+}
+
+#[derive(Clone)]
+#[derive(Copy)]
+enum Layout {
+    Flat,
+    Fit(usize, usize), // Depth and starting column of an expression that may be split over lines
+}
+
+// Prints programs as canonical Lox. Everything but comments and line breaks in long expressions
+// comes from the syntax tree, so the original spacing, blank lines and redundant parentheses
+// around statement-level expressions are all dropped.
+pub struct LoxFormatter {
+    indent_width: usize,
+    max_width: usize,
+}
+
+impl LoxFormatter {
+    pub fn new(indent_width: usize, max_width: usize) -> LoxFormatter {
+        LoxFormatter{indent_width, max_width}
+    }
+
+    // Scanning and parsing errors are passed back rather than formatted around. So are comments
+    // inside a statement, other than those after a parameter or argument, since the syntax tree
+    // has nowhere to keep them where they were.
+    pub fn format_source(&self, source: &str) -> Result<String, Vec<String>> {
+        let mut parser = LoxParser::new();
+        parser.keep_comments();
+        parser.load_string(source)?;
+        let program = parser.parse()?;
+        let interruptions = parser.interruptions();
+        if !interruptions.is_empty() {
+            return Err(interruptions.iter()
+                .map(|line| lox::error::new_error_string(*line, "Can't format around a comment inside a statement."))
+                .collect());
+        }
+        Ok(self.format(&program))
+    }
+
+    pub fn format(&self, program: &[Statement]) -> String {
+        let mut lines = Vec::new();
+        self.statement_list(program.iter().map(|s| (s, ListKind::Statements)), 0, &mut lines);
+        let mut output = lines.join("\n");
+        if !output.is_empty() { output.push('\n'); }
+        output
+    }
+
+    // Declarations are set apart from their neighbours by a blank line. Comments on lines of their
+    // own stay with the statement after them; trailing ones go back on the line they came from.
+    fn statement_list<'a>(&self, stmts: impl Iterator<Item = (&'a Statement, ListKind)>, depth: usize, lines: &mut Vec<String>) {
+        let mut last_was_decl = None;
+        let mut group_start = None;
+        for (s, kind) in stmts {
+            match s {
+                Statement::Comment(text, true, _) if !lines.is_empty() => {
+                    lines.last_mut().expect("Lines should be non-empty").push_str(&format!(" {}", comment(text)));
+                },
+                Statement::Comment(text, ..) => {
+                    group_start.get_or_insert(lines.len());
+                    lines.push(format!("{}{}", self.indent(depth), comment(text)));
+                },
+                _ => {
+                    let is_decl = is_declaration(s);
+                    if last_was_decl.is_some_and(|last| last || is_decl) {
+                        lines.insert(group_start.unwrap_or(lines.len()), String::new());
+                    }
+                    group_start = None;
+                    last_was_decl = Some(is_decl);
+                    match kind {
+                        ListKind::Statements => self.statement(s, depth, lines),
+                        ListKind::Methods => self.member(s, depth, "", lines),
+                        ListKind::Statics => self.member(s, depth, "class ", lines),
+                    }
+                },
+            }
+        }
+    }
+
+    fn statement(&self, s: &Statement, depth: usize, lines: &mut Vec<String>) {
+        use Statement::*;
+
+        match s {
+            Decl(name, init, annotation, _) => {
+                let text = self.declaration("var ", name, init, annotation, depth);
+                self.push_text(format!("{};", text), depth, lines);
+            },
+            Const(name, init, annotation, _) => {
+                let text = self.declaration("const ", name, init, annotation, depth);
+                self.push_text(format!("{};", text), depth, lines);
+            },
+            Expr(e, _) => {
+                let text = self.expr(e, depth, self.column(depth, ""));
+                self.push_text(format!("{};", text), depth, lines);
+            },
+            Print(e, _) => self.keyword_statement("print", e, depth, lines),
+            Return(e, _) => self.keyword_statement("return", e, depth, lines),
+            Yield(e, _) => self.keyword_statement("yield", e, depth, lines),
+            Block(v) => self.block(String::new(), v, depth, lines),
+            If(cond, then_branch, else_option, _) => {
+                let header = self.header("if", cond, depth);
+                self.nested(header, then_branch, depth, lines);
+                if let Some(else_branch) = else_option {
+                    // A closing brace takes the 'else' on its own line; anything else can't.
+                    let header = match lines.last() {
+                        Some(last) if matches!(**then_branch, Block(_)) => {
+                            let header = format!("{} else", last.trim_start());
+                            lines.pop();
+                            header
+                        },
+                        _ => String::from("else"),
+                    };
+                    self.nested(header, else_branch, depth, lines);
+                }
+            },
+            While(cond, body, _) => {
+                let header = self.header("while", cond, depth);
+                self.nested(header, body, depth, lines);
+            },
+            For(init, cond, incr, body, _) => {
+                let init = match init.as_deref() {
+                    Some(Decl(name, e, annotation, _)) => self.declaration("var ", name, e, annotation, depth),
+                    Some(Expr(e, _)) => self.expr(unwrap_grouping(e), depth, self.column(depth, "for (")),
+                    _ => String::new(),
+                };
+                let cond = self.expr(unwrap_grouping(cond), depth, self.column(depth, "for (; "));
+                let header = match incr {
+                    Some(incr) => {
+                        let incr = self.expr(unwrap_grouping(incr), depth, self.column(depth, "for (; ; "));
+                        format!("for ({}; {}; {})", init, cond, incr)
+                    },
+                    None => format!("for ({}; {};)", init, cond),
+                };
+                self.nested(header, body, depth, lines);
+            },
+            ForIn(name, iterable, body, _) => {
+                let prefix = format!("for (var {} in ", name);
+                let iterable = self.expr(unwrap_grouping(iterable), depth, self.column(depth, &prefix));
+                self.nested(format!("{}{})", prefix, iterable), body, depth, lines);
+            },
+            Fun(..) => self.function("fun ", s, depth, lines),
+            Generator(..) => self.function("fun* ", s, depth, lines),
+            Class(name, super_name, traits, methods, statics, order) => {
+                let mut header = format!("class {}", name);
+                if !super_name.is_empty() { header.push_str(&format!(" < {}", super_name)); }
+                if !traits.is_empty() { header.push_str(&format!(" with {}", traits.join(", "))); }
+
+                if methods.is_empty() && statics.is_empty() {
+                    lines.push(format!("{}{} {{}}", self.indent(depth), header));
+                    return;
+                }
+                // Members go back in the order they were written, with comments still beside them.
+                let (mut methods, mut statics) = (methods.iter(), statics.iter());
+                let mut members = Vec::new();
+                for is_static in order {
+                    let member = if *is_static { statics.next().map(|s| (s, ListKind::Statics)) }
+                        else { methods.next().map(|s| (s, ListKind::Methods)) };
+                    members.extend(member);
+                }
+                members.extend(statics.map(|s| (s, ListKind::Statics)));
+                members.extend(methods.map(|s| (s, ListKind::Methods)));
+                lines.push(format!("{}{} {{", self.indent(depth), header));
+                self.statement_list(members.into_iter(), depth + 1, lines);
+                lines.push(format!("{}}}", self.indent(depth)));
+            },
+            Trait(name, methods) => {
+                if methods.is_empty() {
+                    lines.push(format!("{}trait {} {{}}", self.indent(depth), name));
+                    return;
+                }
+                lines.push(format!("{}trait {} {{", self.indent(depth), name));
+                self.statement_list(methods.iter().map(|s| (s, ListKind::Methods)), depth + 1, lines);
+                lines.push(format!("{}}}", self.indent(depth)));
+            },
+            Enum(name, variants) => {
+                let variants: Vec<String> = variants.iter()
+                    .map(|(variant, fields)| {
+                        if fields.is_empty() { variant.clone() }
+                        else { format!("{}({})", variant, fields.join(", ")) }
+                    })
+                    .collect();
+                let flat = format!("enum {} {{ {} }}", name, variants.join(", "));
+                if variants.is_empty() {
+                    lines.push(format!("{}enum {} {{}}", self.indent(depth), name));
+                } else if self.column(depth, &flat) <= self.max_width {
+                    lines.push(format!("{}{}", self.indent(depth), flat));
+                } else {
+                    lines.push(format!("{}enum {} {{", self.indent(depth), name));
+                    for variant in variants {
+                        lines.push(format!("{}{},", self.indent(depth + 1), variant));
+                    }
+                    lines.push(format!("{}}}", self.indent(depth)));
+                }
+            },
+            PropertyGet(..) | PropertySet(..) => self.member(s, depth, "", lines),
+            Match(subject, arms, default, _) => {
+                let header = self.header("match", subject, depth);
+                if arms.is_empty() && default.is_none() {
+                    lines.push(format!("{}{} {{}}", self.indent(depth), header));
+                    return;
+                }
+                lines.push(format!("{}{} {{", self.indent(depth), header));
+                for arm in arms {
+                    let header = self.match_arm(arm, depth + 1);
+                    self.nested(header, &arm.body, depth + 1, lines);
+                }
+                if let Some(stmt) = default {
+                    self.nested(String::from("default =>"), stmt, depth + 1, lines);
+                }
+                lines.push(format!("{}}}", self.indent(depth)));
+            },
+            Test(name, body, _) => self.block(format!("test \"{}\"", name), body, depth, lines),
+            Comment(text, ..) => lines.push(format!("{}{}", self.indent(depth), comment(text))),
+        }
+    }
+
+    // Members of classes and traits; prefix is "class " for static members.
+    fn member(&self, s: &Statement, depth: usize, prefix: &str, lines: &mut Vec<String>) {
+        use Statement::*;
+
+        match s {
+            Fun(..) => self.function(prefix, s, depth, lines),
+            Decl(name, init, annotation, _) => {
+                let text = self.declaration(prefix, name, init, annotation, depth);
+                self.push_text(format!("{};", text), depth, lines);
+            },
+            PropertyGet(name, body) => self.block(name.clone(), body, depth, lines),
+            PropertySet(name, param, body) => self.block(format!("set {}({})", name, param), body, depth, lines),
+            other => self.statement(other, depth, lines),
+        }
+    }
+
+    fn declaration(&self, keyword: &str, name: &str, init: &Expression, annotation: &Option<String>, depth: usize) -> String {
+        let mut text = format!("{}{}", keyword, name);
+        if let Some(annotation) = annotation { text.push_str(&format!(": {}", annotation)); }
+        if !is_nil(init) {
+            text.push_str(" = ");
+            let value = self.expr(unwrap_grouping(init), depth, self.column(depth, &text));
+            text.push_str(&value);
+        }
+        text
+    }
+
+    fn keyword_statement(&self, keyword: &str, e: &Expression, depth: usize, lines: &mut Vec<String>) {
+        // 'return;' and 'yield;' parse as returning nil, but print has no such shorthand.
+        if is_nil(e) && keyword != "print" {
+            lines.push(format!("{}{};", self.indent(depth), keyword));
+            return;
+        }
+        let prefix = format!("{} ", keyword);
+        let text = self.expr(unwrap_grouping(e), depth, self.column(depth, &prefix));
+        self.push_text(format!("{}{};", prefix, text), depth, lines);
+    }
+
+    // 'keyword (condition)', for the statements that wrap their condition in parentheses.
+    fn header(&self, keyword: &str, cond: &Expression, depth: usize) -> String {
+        let prefix = format!("{} (", keyword);
+        let cond = self.expr(unwrap_grouping(cond), depth, self.column(depth, &prefix));
+        format!("{}{})", prefix, cond)
+    }
+
+    fn match_arm(&self, arm: &MatchArm, depth: usize) -> String {
+        let patterns: Vec<String> = arm.patterns.iter().map(pattern_text).collect();
+        let mut header = format!("case {}", patterns.join(", "));
+        if let Some(guard) = &arm.guard {
+            header.push_str(" if ");
+            let guard = self.expr(guard, depth, self.column(depth, &header));
+            header.push_str(&guard);
+        }
+        header.push_str(" =>");
+        header
+    }
+
+    // Puts a statement after a header such as 'while (x)'. Blocks open on the header's line, and
+    // so does any other statement, which keeps 'else if' chains flat.
+    fn nested(&self, header: String, body: &Statement, depth: usize, lines: &mut Vec<String>) {
+        if let Statement::Block(v) = body {
+            self.block(header, v, depth, lines);
+            return;
+        }
+        let mut body_lines = Vec::new();
+        self.statement(body, depth, &mut body_lines);
+        let mut body_lines = body_lines.into_iter();
+        let first = body_lines.next().unwrap_or_default();
+        lines.push(format!("{}{} {}", self.indent(depth), header, first.trim_start()));
+        lines.extend(body_lines);
+    }
+
+    fn block(&self, header: String, body: &[Statement], depth: usize, lines: &mut Vec<String>) {
+        let open = if header.is_empty() { String::from("{") } else { format!("{} {{", header) };
+        if body.is_empty() {
+            lines.push(format!("{}{}}}", self.indent(depth), open));
+            return;
+        }
+        lines.push(format!("{}{}", self.indent(depth), open));
+        self.statement_list(body.iter().map(|s| (s, ListKind::Statements)), depth + 1, lines);
+        lines.push(format!("{}}}", self.indent(depth)));
+    }
+
+    // Parameters go one per line if the header would otherwise be too long, or if there's a
+    // comment among them.
+    fn function(&self, keyword: &str, s: &Statement, depth: usize, lines: &mut Vec<String>) {
+        let (Statement::Fun(name, params, body, signature) | Statement::Generator(name, params, body, signature)) = s else {
+            return self.statement(s, depth, lines);
+        };
+        let param_text = |i: usize, param: &String, layout: Layout| {
+            let mut text = param.clone();
+            if signature.rest && i == params.len() - 1 { text.insert_str(0, "..."); }
+            if let Some(annotation) = signature.param_types.get(param) { text.push_str(&format!(": {}", annotation)); }
+            if let Some(default) = signature.defaults.get(param) {
+                text.push_str(" = ");
+                text.push_str(&self.part(default, layout, &text));
+            }
+            text
+        };
+        let flat: Vec<String> = params.iter().enumerate().map(|(i, param)| param_text(i, param, Layout::Flat)).collect();
+        let returns = match &signature.returns {
+            Some(annotation) => format!(": {}", annotation),
+            None => String::new(),
+        };
+
+        let header = format!("{}{}({}){}", keyword, name, flat.join(", "), returns);
+        let commented = !signature.comments.is_empty() || signature.defaults.values().any(|d| has_comments(d));
+        if params.is_empty() || (!commented && self.column(depth, &header) + 2 <= self.max_width) {
+            self.block(header, body, depth, lines);
+            return;
+        }
+        lines.push(format!("{}{}{}(", self.indent(depth), keyword, name));
+        let inner = self.indent(depth + 1);
+        for (i, param) in params.iter().enumerate() {
+            let text = param_text(i, param, Layout::Fit(depth + 1, inner.len()));
+            let comma = if i + 1 < params.len() { "," } else { "" };
+            let note = signature.comments.get(param).map_or(String::new(), |(text, _)| format!(" {}", comment(text)));
+            lines.push(format!("{}{}{}{}", inner, text, comma, note));
+        }
+        self.block(format!("){}", returns), body, depth, lines);
+    }
+
+    // An expression on one line, however long.
+    pub fn format_expression(&self, e: &Expression) -> String {
+        self.render(e, Layout::Flat)
+    }
+
+    // Renders an expression starting at the given column. It stays on one line if that fits;
+    // otherwise its argument lists and collections are split one item per line.
+    fn expr(&self, e: &Expression, depth: usize, column: usize) -> String {
+        let flat = self.render(e, Layout::Flat);
+        if !has_comments(e) && column + flat.chars().count() <= self.max_width { flat }
+        else { self.render(e, Layout::Fit(depth, column)) }
+    }
+
+    // Renders part of a larger expression, which continues the text rendered so far.
+    fn part(&self, e: &Expression, layout: Layout, so_far: &str) -> String {
+        match layout {
+            Layout::Flat => self.render(e, Layout::Flat),
+            Layout::Fit(depth, column) => self.expr(e, depth, column_after(column, so_far)),
+        }
+    }
+
+    fn render(&self, e: &Expression, layout: Layout) -> String {
+        use Expression::*;
+
+        let mut out = String::new();
+        match e {
+            LitExp(l) => out.push_str(&literal_text(l)),
+            Unary(op, inner) => {
+                out.push_str(match op { UnaryOp::Negative => "-", UnaryOp::Not => "!" });
+                out.push_str(&self.part(inner, layout, &out));
+            },
+            Binary{left, op, right} => self.infix(left, op.symbol(), right, layout, &mut out),
+            Logical{left, op, right} => {
+                let op = match op { LogicOp::And => "and", LogicOp::Or => "or" };
+                self.infix(left, op, right, layout, &mut out);
+            },
+            Identifier(name) => out.push_str(name),
+            Grouping(inner) => {
+                out.push('(');
+                out.push_str(&self.part(inner, layout, &out));
+                out.push(')');
+            },
+            Assignment(name, value, _) => {
+                out.push_str(&format!("{} = ", name));
+                out.push_str(&self.part(value, layout, &out));
+            },
+            CompoundAssignment(name, op, value, _) => {
+                out.push_str(&format!("{} {}= ", name, op.symbol()));
+                out.push_str(&self.part(value, layout, &out));
+            },
+            Ternary(cond, then_branch, else_branch) => {
+                out.push_str(&self.part(cond, layout, &out));
+                out.push_str(" ? ");
+                out.push_str(&self.part(then_branch, layout, &out));
+                out.push_str(" : ");
+                out.push_str(&self.part(else_branch, layout, &out));
+            },
+            Call(callee, args, comments, _) => {
+                out.push_str(&self.part(callee, layout, &out));
+                let items: Vec<&Expression> = args.iter().collect();
+                let nested = args.iter().any(has_comments);
+                let args = self.bracketed(("(", ")"), &items, (false, comments, nested), layout, &out, |e, layout, so_far| self.part(e, layout, so_far));
+                out.push_str(&args);
+            },
+            Getter(obj, name) => {
+                out.push_str(&self.part(obj, layout, &out));
+                out.push_str(&format!(".{}", name));
+            },
+            Setter(obj, name, value) => {
+                out.push_str(&self.part(obj, layout, &out));
+                out.push_str(&format!(".{} = ", name));
+                out.push_str(&self.part(value, layout, &out));
+            },
+            CompoundSetter(obj, name, op, value) => {
+                out.push_str(&self.part(obj, layout, &out));
+                out.push_str(&format!(".{} {}= ", name, op.symbol()));
+                out.push_str(&self.part(value, layout, &out));
+            },
+            Index(obj, index) => self.index(obj, index, layout, &mut out),
+            IndexSetter(obj, index, value) => {
+                self.index(obj, index, layout, &mut out);
+                out.push_str(" = ");
+                out.push_str(&self.part(value, layout, &out));
+            },
+            CompoundIndexSetter(obj, index, op, value) => {
+                self.index(obj, index, layout, &mut out);
+                out.push_str(&format!(" {}= ", op.symbol()));
+                out.push_str(&self.part(value, layout, &out));
+            },
+            ListLiteral(items) => {
+                let nested = items.iter().any(has_comments);
+                let items: Vec<&Expression> = items.iter().collect();
+                out.push_str(&self.bracketed(("[", "]"), &items, (true, &[], nested), layout, &out, |e, layout, so_far| self.part(e, layout, so_far)));
+            },
+            MapLiteral(entries) => {
+                let nested = entries.iter().any(|(k, v)| has_comments(k) || has_comments(v));
+                let entries: Vec<(&Expression, &Expression)> = entries.iter().map(|(k, v)| (&**k, &**v)).collect();
+                let text = self.bracketed(("{", "}"), &entries, (true, &[], nested), layout, &out, |(key, value), layout, so_far| {
+                    let mut entry = self.part(key, layout, so_far);
+                    entry.push_str(": ");
+                    let value = self.part(value, layout, &format!("{}{}", so_far, entry));
+                    entry.push_str(&value);
+                    entry
+                });
+                out.push_str(&text);
+            },
+            Spread(list) => {
+                out.push_str("...");
+                out.push_str(&self.part(list, layout, &out));
+            },
+            Named(name, value) => {
+                out.push_str(&format!("{}: ", name));
+                out.push_str(&self.part(value, layout, &out));
+            },
+            This(_) => out.push_str("this"),
+            Super(method) => out.push_str(&format!("super.{}", method)),
+        }
+        out
+    }
+
+    fn infix(&self, left: &Expression, op: &str, right: &Expression, layout: Layout, out: &mut String) {
+        out.push_str(&self.part(left, layout, out));
+        out.push_str(&format!(" {} ", op));
+        out.push_str(&self.part(right, layout, out));
+    }
+
+    fn index(&self, obj: &Expression, index: &Expression, layout: Layout, out: &mut String) {
+        out.push_str(&self.part(obj, layout, out));
+        out.push('[');
+        out.push_str(&self.part(index, layout, out));
+        out.push(']');
+    }
+
+    // A comma-separated list between brackets. When split, each item goes on its own line one
+    // level deeper, with a trailing comma where the grammar allows one and any comment after it.
+    // A list with comments in it is always split, since they run to the end of the line.
+    fn bracketed<T: Copy>(
+        &self,
+        (open, close): (&str, &str),
+        items: &[T],
+        (trailing_comma, comments, nested): (bool, &[(usize, String, usize)], bool), // Whether the last item takes a comma, comments after items, whether items have any inside
+        layout: Layout,
+        so_far: &str,
+        render: impl Fn(T, Layout, &str) -> String,
+    ) -> String {
+        let Layout::Fit(depth, column) = layout else {
+            let items: Vec<String> = items.iter().map(|item| render(*item, Layout::Flat, "")).collect();
+            return format!("{}{}{}", open, items.join(", "), close);
+        };
+
+        // Only split here if the list itself doesn't fit on what's left of the line.
+        let flat: Vec<String> = items.iter().map(|item| render(*item, Layout::Flat, "")).collect();
+        let flat = format!("{}{}{}", open, flat.join(", "), close);
+        let fits = column_after(column, so_far) + flat.chars().count() <= self.max_width;
+        if items.is_empty() || (!nested && comments.is_empty() && fits) {
+            return flat;
+        }
+
+        let inner = self.indent(depth + 1);
+        let mut text = format!("{}\n", open);
+        for (i, item) in items.iter().enumerate() {
+            let item = render(*item, Layout::Fit(depth + 1, inner.len()), &inner);
+            let comma = if trailing_comma || i + 1 < items.len() { "," } else { "" };
+            let note = comments.iter().find(|(at, ..)| *at == i).map_or(String::new(), |(_, text, _)| format!(" {}", comment(text)));
+            text.push_str(&format!("{}{}{}{}\n", inner, item, comma, note));
+        }
+        text.push_str(&format!("{}{}", self.indent(depth), close));
+        text
+    }
+
+    // Adds text that may span several lines. Only its first line needs indenting, since
+    // expressions indent their own continuation lines.
+    fn push_text(&self, text: String, depth: usize, lines: &mut Vec<String>) {
+        let mut text_lines = text.split('\n');
+        lines.push(format!("{}{}", self.indent(depth), text_lines.next().unwrap_or_default()));
+        lines.extend(text_lines.map(String::from));
+    }
+
+    fn indent(&self, depth: usize) -> String {
+        " ".repeat(depth * self.indent_width)
+    }
+
+    // The column just past some text written at the start of a line at the given depth.
+    fn column(&self, depth: usize, text: &str) -> usize {
+        depth * self.indent_width + text.chars().count()
+    }
+}
+
+fn column_after(column: usize, text: &str) -> usize {
+    match text.rfind('\n') {
+        Some(i) => text[i + 1..].chars().count(),
+        None => column + text.chars().count(),
+    }
+}
+
+fn comment(text: &str) -> String {
+    if text.is_empty() { String::from("//") } else { format!("// {}", text) }
+}
+
+// Whether an expression has a comment in an argument list somewhere, which keeps it from being
+// written on one line.
+fn has_comments(e: &Expression) -> bool {
+    use Expression::*;
+
+    match e {
+        Call(callee, args, comments, _) => !comments.is_empty() || has_comments(callee) || args.iter().any(has_comments),
+        LitExp(_) | Identifier(_) | This(_) | Super(_) => false,
+        Unary(_, inner) | Grouping(inner) | Spread(inner) | Named(_, inner) | Getter(inner, _) |
+        Assignment(_, inner, _) | CompoundAssignment(_, _, inner, _) => has_comments(inner),
+        Binary{left, op: _, right} | Logical{left, op: _, right} | Index(left, right) | Setter(left, _, right) |
+        CompoundSetter(left, _, _, right) => has_comments(left) || has_comments(right),
+        Ternary(first, second, third) | IndexSetter(first, second, third) | CompoundIndexSetter(first, second, _, third) => {
+            has_comments(first) || has_comments(second) || has_comments(third)
+        },
+        ListLiteral(items) => items.iter().any(has_comments),
+        MapLiteral(entries) => entries.iter().any(|(k, v)| has_comments(k) || has_comments(v)),
+    }
+}
+
+fn is_declaration(s: &Statement) -> bool {
+    use Statement::*;
+    matches!(s, Fun(..) | Generator(..) | Class(..) | Trait(..) | Enum(..) | PropertyGet(..) | PropertySet(..) | Test(..))
+}
+
+fn is_nil(e: &Expression) -> bool {
+    matches!(e, Expression::LitExp(Literal::Nil))
+}
+
+// Parentheses around a whole statement-level expression are redundant, as in 'print(x);'. An
+// expression statement keeps them, since '{' would start a block.
+fn unwrap_grouping(e: &Expression) -> &Expression {
+    match e {
+        Expression::Grouping(inner) => unwrap_grouping(inner),
+        _ => e,
+    }
+}
+
+fn literal_text(l: &Literal) -> String {
+    match l {
+        // Whole numbers keep a decimal point, or they would be read back as integers.
+        Literal::Number(n) if n.fract() == 0.0 => format!("{:.1}", n),
+        Literal::StringData(s) => format!("\"{}\"", s),
+        Literal::Nil => String::from("nil"),
+        other => other.to_string(),
+    }
+}
+
+fn pattern_text(p: &Pattern) -> String {
+    match p {
+        Pattern::Literal(l) => literal_text(l),
+        Pattern::Binding(name) => name.clone(),
+        Pattern::Wildcard => String::from("_"),
+        Pattern::Instance(class, fields) => format!("{}({})", class, fields.join(", ")),
+        Pattern::Variant(name, variant, bindings) if bindings.is_empty() => format!("{}.{}", name, variant),
+        Pattern::Variant(name, variant, bindings) => format!("{}.{}({})", name, variant, bindings.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_string(source: &str) -> String {
+        LoxFormatter::new(4, 100).format_source(source).expect("Error while formatting input string.")
+    }
+
+    // Formatting the expected output again must leave it as it is.
+    fn test_format_generic(cases: &[(&str, &str)]) {
+        for (source, expected) in cases {
+            assert_eq!(*expected, format_string(source), "{}", source);
+            assert_eq!(*expected, format_string(expected), "{}", expected);
+        }
+    }
+
+    mod statements {
+        use super::*;
+
+        #[test]
+        fn test_format_simple_statements() {
+            test_format_generic(&[
+                ("print(i);", "print i;\n"),
+                ("print  1+2*3 ;", "print 1 + 2 * 3;\n"),
+                ("print (1 + 2) * 3;", "print (1 + 2) * 3;\n"),
+                ("var x;var y=nil;", "var x;\nvar y;\n"),
+                ("const  limit:Integer=10;", "const limit: Integer = 10;\n"),
+                ("x+=1;", "x += 1;\n"),
+                ("print 1.0; print 2.5; print \"s\"; print nil;", "print 1.0;\nprint 2.5;\nprint \"s\";\nprint nil;\n"),
+                ("print !a and -b or c;", "print !a and -b or c;\n"),
+                ("print a ? b : c;", "print a ? b : c;\n"),
+                ("l[0] = m[\"k\"] ~/ 2;", "l[0] = m[\"k\"] ~/ 2;\n"),
+                ("({}).x;", "({}).x;\n"),
+            ]);
+        }
+
+        #[test]
+        fn test_format_control_flow() {
+            test_format_generic(&[
+                ("if (x) print 1;", "if (x) print 1;\n"),
+                ("if ((x)) { print 1; } else { print 2; }", "if (x) {\n    print 1;\n} else {\n    print 2;\n}\n"),
+                ("if (x) print 1; else if (y) print 2; else print 3;", "if (x) print 1;\nelse if (y) print 2;\nelse print 3;\n"),
+                ("if (x) {} else if (y) { print 2; }", "if (x) {} else if (y) {\n    print 2;\n}\n"),
+                ("while (x < 3) x = x + 1;", "while (x < 3) x = x + 1;\n"),
+                ("for(var i=0;i<3;i=i+1){print i;}", "for (var i = 0; i < 3; i = i + 1) {\n    print i;\n}\n"),
+                ("for (;x;) {}", "for (; x;) {}\n"),
+                ("for (var x in [1, 2]) print x;", "for (var x in [1, 2]) print x;\n"),
+                ("{ var a = 1; { print a; } }", "{\n    var a = 1;\n    {\n        print a;\n    }\n}\n"),
+            ]);
+        }
+
+        #[test]
+        fn test_format_match() {
+            let source = "match (x) { case 1, 2 if x > 1 => print \"small\"; case Shape.Circle(r) => { print r; } case Point(a, _) => print a; default => print \"other\"; }";
+            let expected = concat!(
+                "match (x) {\n",
+                "    case 1, 2 if x > 1 => print \"small\";\n",
+                "    case Shape.Circle(r) => {\n",
+                "        print r;\n",
+                "    }\n",
+                "    case Point(a, _) => print a;\n",
+                "    default => print \"other\";\n",
+                "}\n",
+            );
+            test_format_generic(&[(source, expected)]);
+        }
+    }
+
+    mod declarations {
+        use super::*;
+
+        #[test]
+        fn test_format_functions() {
+            test_format_generic(&[
+                ("fun f(){}", "fun f() {}\n"),
+                ("fun add(a:Number,b=2,...rest):Number{return a+b;}", "fun add(a: Number, b = 2, ...rest): Number {\n    return a + b;\n}\n"),
+                ("fun* count(n) { yield n; yield; return; }", "fun* count(n) {\n    yield n;\n    yield;\n    return;\n}\n"),
+                ("f(1, ...rest, b: 2);", "f(1, ...rest, b: 2);\n"),
+            ]);
+        }
+
+        #[test]
+        fn test_format_classes() {
+            let source = "class Point<Shape with Show,Eq{class origin(){return Point(0,0);} init(x){this.x=x;} norm{return this.x;} set first(v){this.x=v;} }";
+            let expected = concat!(
+                "class Point < Shape with Show, Eq {\n",
+                "    class origin() {\n",
+                "        return Point(0, 0);\n",
+                "    }\n",
+                "\n",
+                "    init(x) {\n",
+                "        this.x = x;\n",
+                "    }\n",
+                "\n",
+                "    norm {\n",
+                "        return this.x;\n",
+                "    }\n",
+                "\n",
+                "    set first(v) {\n",
+                "        this.x = v;\n",
+                "    }\n",
+                "}\n",
+            );
+            test_format_generic(&[
+                (source, expected),
+                ("class Empty{}", "class Empty {}\n"),
+                ("trait Show{show(){print super.show;}}", "trait Show {\n    show() {\n        print super.show;\n    }\n}\n"),
+                ("enum Shape{Circle(r),Square(s),Empty}", "enum Shape { Circle(r), Square(s), Empty }\n"),
+                ("test \"adds\"{assertEqual(1+1,2);}", "test \"adds\" {\n    assertEqual(1 + 1, 2);\n}\n"),
+            ]);
+        }
+
+        #[test]
+        fn test_format_blank_lines() {
+            let source = "var a = 1;\n\n\nvar b = 2;\nfun f() {}\nfun g() {}\nprint a;";
+            let expected = "var a = 1;\nvar b = 2;\n\nfun f() {}\n\nfun g() {}\n\nprint a;\n";
+            test_format_generic(&[(source, expected)]);
+        }
+    }
+
+    mod comments {
+        use super::*;
+
+        #[test]
+        fn test_format_comments() {
+            let source = concat!(
+                "//Header\n",
+                "var a = 1;   //  trailing\n",
+                "fun f() {\n",
+                "  // inside\n",
+                "  return a;\n",
+                "} // after\n",
+                "// about g\n",
+                "fun g() {}\n",
+                "//\n",
+            );
+            let expected = concat!(
+                "// Header\n",
+                "var a = 1; // trailing\n",
+                "\n",
+                "fun f() {\n",
+                "    // inside\n",
+                "    return a;\n",
+                "} // after\n",
+                "\n",
+                "// about g\n",
+                "fun g() {}\n",
+                "//\n",
+            );
+            test_format_generic(&[(source, expected)]);
+        }
+
+        #[test]
+        fn test_format_member_comments() {
+            let source = "class A {\n// first\ninit() {} // ctor\n// second\nclass make() {}\n}";
+            let expected = concat!(
+                "class A {\n",
+                "    // first\n",
+                "    init() {} // ctor\n",
+                "\n",
+                "    // second\n",
+                "    class make() {}\n",
+                "}\n",
+            );
+            test_format_generic(&[(source, expected)]);
+        }
+
+        #[test]
+        fn test_format_members_in_source_order() {
+            let source = "class A {\nf() {}\nclass count = 0; // shared\nclass total = 0;\n// last\ng() {}\n// end\n}";
+            let expected = concat!(
+                "class A {\n",
+                "    f() {}\n",
+                "\n",
+                "    class count = 0; // shared\n",
+                "    class total = 0;\n",
+                "\n",
+                "    // last\n",
+                "    g() {}\n",
+                "    // end\n",
+                "}\n",
+            );
+            test_format_generic(&[(source, expected)]);
+        }
+
+        #[test]
+        fn test_format_list_comments() {
+            // A comment after a parameter or argument stays after it, which splits the list.
+            test_format_generic(&[
+                ("print f(1, // first\n    2);", "print f(\n    1, // first\n    2\n);\n"),
+                ("g(f(1, 2 // two\n), 3);", "g(\n    f(\n        1,\n        2 // two\n    ),\n    3\n);\n"),
+                ("class A {\n    f(a, // b\n        c) {}\n}", "class A {\n    f(\n        a, // b\n        c\n    ) {}\n}\n"),
+                ("fun f(a // one\n, b = 2 // two\n) {}", "fun f(\n    a, // one\n    b = 2 // two\n) {}\n"),
+            ]);
+        }
+
+        #[test]
+        fn test_format_refuses_comments_inside_statements() {
+            let formatter = LoxFormatter::new(4, 100);
+            let cases = [
+                ("var x = 1 +\n// two\n2;", 2),
+                ("{\n    if (x) // odd\n        print x;\n}", 2),
+                ("print f(\n    // first\n    1);", 2),
+            ];
+            for (source, line) in cases {
+                let expected = vec![lox::error::new_error_string(line, "Can't format around a comment inside a statement.")];
+                assert_eq!(Err(expected), formatter.format_source(source), "{}", source);
+            }
+        }
+    }
+
+    mod layout {
+        use super::*;
+
+        #[test]
+        fn test_format_indent_width() {
+            let formatter = LoxFormatter::new(2, 100);
+            let output = formatter.format_source("fun f() { if (x) { print 1; } }").expect("Error while formatting input string.");
+            assert_eq!("fun f() {\n  if (x) {\n    print 1;\n  }\n}\n", output);
+        }
+
+        #[test]
+        fn test_format_long_lines() {
+            let formatter = LoxFormatter::new(4, 30);
+            let cases = [
+                ("print f(100000, 200000, 300000);", "print f(\n    100000,\n    200000,\n    300000\n);\n"),
+                ("var list = [\"alpha\", \"beta\", \"gamma\"];", "var list = [\n    \"alpha\",\n    \"beta\",\n    \"gamma\",\n];\n"),
+                ("var m = {\"alpha\": [1, 2], \"beta\": 3};", "var m = {\n    \"alpha\": [1, 2],\n    \"beta\": 3,\n};\n"),
+                ("fun f(alpha, beta, gamma, delta) {}", "fun f(\n    alpha,\n    beta,\n    gamma,\n    delta\n) {}\n"),
+                (
+                    "enum Colour { Red, Green, Blue, Yellow }",
+                    "enum Colour {\n    Red,\n    Green,\n    Blue,\n    Yellow,\n}\n",
+                ),
+                (
+                    "{ print g([1000, 2000], [3000, 4000]); }",
+                    "{\n    print g(\n        [1000, 2000],\n        [3000, 4000]\n    );\n}\n",
+                ),
+            ];
+            for (source, expected) in cases {
+                assert_eq!(expected, formatter.format_source(source).expect(source), "{}", source);
+                assert_eq!(expected, formatter.format_source(expected).expect(expected), "{}", expected);
+            }
+        }
+
+        #[test]
+        fn test_format_errors() {
+            let formatter = LoxFormatter::new(4, 100);
+            assert!(formatter.format_source("print 1").is_err());
+            assert!(formatter.format_source("print \"open;").is_err());
+        }
+    }
+
+    mod semantics {
+        use super::*;
+        use lox::interpreter::LoxInterpreter;
+
+        fn run(source: &str) -> String {
+            let mut parser = LoxParser::new();
+            parser.load_string(source).expect("Error while scanning input string.");
+            let program = parser.parse().expect("Error while parsing input string.");
+            LoxInterpreter::new().interpret(program).expect("Error while running program.")
+        }
+
+        #[test]
+        fn test_format_preserves_behaviour() {
+            let source = concat!(
+                "fun fib(n){if(n<=1)return n;return fib(n-2)+fib(n-1);}\n",
+                "for(var i=0;i<8;i=i+1){print(fib(i));}\n",
+                "var total=0;for(var x in [1,2,3])total+=x;print total;\n",
+                "fun greet(name,mark=\"!\"){return name+mark;}print greet(mark:\"?\",name:\"crab\");\n",
+                "print -(1+2)*3;\n",
+            );
+            let formatter = LoxFormatter::new(4, 20);
+            let formatted = formatter.format_source(source).expect("Error while formatting input string.");
+            assert_eq!(run(source), run(&formatted));
+        }
+    }
+}
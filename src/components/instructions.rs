@@ -2,4 +2,8 @@ pub mod statement;
 pub mod expression;
 pub mod node;
 pub mod callable;
-pub mod instance;
\ No newline at end of file
+pub mod instance;
+pub mod pattern;
+pub mod generator;
+pub mod iteration;
+pub mod enumeration;
\ No newline at end of file
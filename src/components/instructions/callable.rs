@@ -1,5 +1,8 @@
 use crate::components as lox;
 use lox::instructions::statement::*;
+use lox::instructions::node::Literal;
+use lox::instructions::generator::Generator;
+use lox::instructions::enumeration::Enum;
 use lox::environment::LoxEnvironment;
 
 use std::collections::HashMap;
@@ -11,80 +14,311 @@ use std::cell::RefCell;
 #[derive(PartialEq)]
 #[derive(Clone)]
 pub enum Callable {
-    Function(String, Vec<String>, Vec<Box<Statement>>, Option<Rc<RefCell<LoxEnvironment>>>, bool),
-    Class(String, Option<Box<Callable>>, HashMap<String, Callable>),
+    // The larger payloads are boxed or shared to keep Literal small, since the interpreter's
+    // recursive functions hold many Literal temporaries per frame.
+    Function(String, Vec<String>, Vec<Statement>, Option<Rc<RefCell<LoxEnvironment>>>, bool, Rc<Signature>), // Name, parameters, body, closure, is initializer, defaults and rest
+    Class(String, Option<Box<Callable>>, HashMap<String, Callable>, Box<Accessors>, Rc<RefCell<HashMap<String, Literal>>>, usize), // Name, superclass, methods, property accessors, static members, id
+    Trait(String, HashMap<String, Callable>, Box<Accessors>), // Name, methods, property accessors
+    Generator(String, Vec<String>, Vec<Statement>, Option<Rc<RefCell<LoxEnvironment>>>, Rc<Signature>), // Calling one creates a generator object
+    Resume(Generator), // A generator object's next() method
+    Variant(String, String, Vec<String>), // Constructor for an enum variant with a payload: enum name, variant, field names
+    EnumValues(Rc<Enum>), // An enum's values() method
     Clock,
+    TypeOf,
+    ClassOf,
+    SuperclassOf,
+    Fields,
+    Methods,
+    HasField,
+    GetField,
+    SetField,
+    Arity,
+    InstanceOf,
+    Range,
+    Assert,
+    AssertEqual,
+}
+
+// Computed properties declared in a class body, keyed by property name.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Default)]
+pub struct Accessors {
+    pub getters: HashMap<String, Callable>,
+    pub setters: HashMap<String, Callable>,
+}
+
+impl Accessors {
+    fn decouple_closures(&mut self) {
+        for (_, m) in self.getters.iter_mut().chain(self.setters.iter_mut()) {
+            m.decouple_closures();
+        }
+    }
+}
+
+// The range of argument counts a callable accepts. max is None when a rest parameter
+// takes any number of extra arguments.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Copy)]
+pub struct ArityRange {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl ArityRange {
+    pub fn exactly(n: usize) -> ArityRange {
+        ArityRange{ min: n, max: Some(n) }
+    }
+
+    pub fn of(params: &[String], signature: &Signature) -> ArityRange {
+        let optional = signature.defaults.len() + signature.rest as usize;
+        let min = params.len() - optional;
+        if signature.rest { ArityRange{ min, max: None } }
+        else { ArityRange{ min, max: Some(params.len()) } }
+    }
+
+    pub fn accepts(&self, n: usize) -> bool {
+        n >= self.min && self.max.is_none_or(|max| n <= max)
+    }
+}
+
+impl fmt::Display for ArityRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{}", max),
+            Some(max) => write!(f, "{} to {}", self.min, max),
+            None => write!(f, "at least {}", self.min),
+        }
+    }
 }
 
 use Callable::*;
 impl Callable {
     pub fn native_fn_list() -> Vec<(String, Callable)> {
-        vec![(String::from("clock"), Clock)]
+        [Clock, TypeOf, ClassOf, SuperclassOf, Fields, Methods, HasField, GetField, SetField, Arity, InstanceOf, Range]
+            .into_iter()
+            .map(|f| (String::from(f.get_name()), f))
+            .collect()
+    }
+
+    // Only defined for programs run by the test runner.
+    pub fn assertion_fn_list() -> Vec<(String, Callable)> {
+        [Assert, AssertEqual]
+            .into_iter()
+            .map(|f| (String::from(f.get_name()), f))
+            .collect()
     }
 
-    pub fn arity(&self) -> usize {
+    pub fn arity(&self) -> ArityRange {
         match self {
-            Function(_, arg_names, _, _, _) => arg_names.len(),
-            Class(_, _, methods) => {
+            Function(_, arg_names, _, _, _, signature) => ArityRange::of(arg_names, signature),
+            Class(_, _, methods, _, _, _) => {
                 if let Some(c) = methods.get("init") { c.arity() }
-                else { 0 }
+                else { ArityRange::exactly(0) }
             },
-            Clock => 0,
+            Trait(_, _, _) => ArityRange::exactly(0),
+            Generator(_, arg_names, _, _, signature) => ArityRange::of(arg_names, signature),
+            Resume(_) => ArityRange::exactly(0),
+            Variant(_, _, fields) => ArityRange::exactly(fields.len()),
+            EnumValues(_) => ArityRange::exactly(0),
+            Clock => ArityRange::exactly(0),
+            TypeOf | ClassOf | SuperclassOf | Fields | Methods | Arity => ArityRange::exactly(1),
+            HasField | GetField | InstanceOf => ArityRange::exactly(2),
+            Range => ArityRange{ min: 2, max: Some(3) },
+            Assert => ArityRange{ min: 1, max: Some(2) },
+            AssertEqual => ArityRange{ min: 2, max: Some(3) },
+            SetField => ArityRange::exactly(3),
+        }
+    }
+
+    // The parameter list that named arguments are matched against, if this callable has one.
+    pub fn parameters(&self) -> Option<(&[String], &Signature)> {
+        match self {
+            Function(_, arg_names, _, _, _, signature) => Some((arg_names, signature)),
+            Class(_, _, methods, _, _, _) => methods.get("init").and_then(|c| c.parameters()),
+            Generator(_, arg_names, _, _, signature) => Some((arg_names, signature)),
+            _ => None,
         }
     }
 
     pub fn get_name(&self) -> &str {
         match self {
-            Function(name, _, _, _, _) => &name,
-            Class(name, _, _) => &name,
+            Function(name, _, _, _, _, _) => name,
+            Class(name, _, _, _, _, _) => name,
+            Trait(name, _, _) => name,
+            Generator(name, _, _, _, _) => name,
+            Resume(_) => "next",
+            Variant(_, variant, _) => variant,
+            EnumValues(_) => "values",
             Clock => "clock",
+            TypeOf => "typeOf",
+            ClassOf => "classOf",
+            SuperclassOf => "superclassOf",
+            Fields => "fields",
+            Methods => "methods",
+            HasField => "hasField",
+            GetField => "getField",
+            SetField => "setField",
+            Arity => "arity",
+            InstanceOf => "instanceOf",
+            Range => "range",
+            Assert => "assert",
+            AssertEqual => "assertEqual",
         }
     }
 
     pub fn is_native(&self) -> bool {
         match self {
-            Function(_, _, _, _, _) => false,
-            Class(_, _, _) => false,
+            Function(_, _, _, _, _, _) => false,
+            Class(_, _, _, _, _, _) => false,
+            Trait(_, _, _) => false,
+            Generator(_, _, _, _, _) => false,
             _ => true,
         }
     }
 
+    // Natives every Lox has. The others can be replaced by a program's own declarations.
+    pub fn is_core_native(&self) -> bool {
+        matches!(self, Clock)
+    }
+
     pub fn is_initializer(&self) -> bool {
         match self {
-            Function(_, _, _, _, is_init) => *is_init,
-            Class(_, _, _) => false,
+            Function(_, _, _, _, is_init, _) => *is_init,
+            Class(_, _, _, _, _, _) => false,
+            Trait(_, _, _) => false,
+            Generator(_, _, _, _, _) => false,
             _ => true,
         }
     }
 
     pub fn find_method(&self, name: &str) -> Result<Callable, String> {
         match self {
-            Class(_, super_class, methods) => {
+            Class(_, super_class, methods, _, _, _) => {
                 if let Some(c) = methods.get(name) {
                     Ok(c.clone())
                 } else if let Some(sc) = super_class {
                     sc.find_method(name)
                 } else {
-                    Err(format!("Undefined property {}.", name))
+                    Err(format!("Undefined property '{}'.", name))
                 }
             }
             _ => Err(format!("Cannot find method on non-class {}.", self.get_name())),
         }
     }
 
+    pub fn get_superclass(&self) -> Option<&Callable> {
+        match self {
+            Class(_, Some(super_class), _, _, _, _) => Some(super_class),
+            _ => None,
+        }
+    }
+
+    // Names of every method callable on instances of this class or trait, including inherited ones.
+    pub fn method_names(&self) -> Vec<String> {
+        let mut names = match self {
+            Class(_, super_class, methods, _, _, _) => {
+                let mut names: Vec<String> = methods.keys().cloned().collect();
+                if let Some(sc) = super_class {
+                    names.extend(sc.method_names());
+                }
+                names
+            },
+            Trait(_, methods, _) => methods.keys().cloned().collect(),
+            _ => vec![],
+        };
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn find_getter(&self, name: &str) -> Option<Callable> {
+        match self {
+            Class(_, super_class, _, accessors, _, _) => {
+                if let Some(c) = accessors.getters.get(name) { Some(c.clone()) }
+                else if let Some(sc) = super_class { sc.find_getter(name) }
+                else { None }
+            }
+            _ => None,
+        }
+    }
+
+    pub fn find_setter(&self, name: &str) -> Option<Callable> {
+        match self {
+            Class(_, super_class, _, accessors, _, _) => {
+                if let Some(c) = accessors.setters.get(name) { Some(c.clone()) }
+                else if let Some(sc) = super_class { sc.find_setter(name) }
+                else { None }
+            }
+            _ => None,
+        }
+    }
+
+    pub fn find_static(&self, name: &str) -> Result<Literal, String> {
+        match self {
+            Class(_, super_class, _, _, statics, _) => {
+                if let Some(lit) = statics.borrow().get(name) {
+                    Ok(lit.clone())
+                } else if let Some(sc) = super_class {
+                    sc.find_static(name)
+                } else {
+                    Err(format!("Undefined static property '{}'.", name))
+                }
+            }
+            _ => Err(format!("Cannot find static property on non-class {}.", self.get_name())),
+        }
+    }
+
+    pub fn set_static(&self, name: &str, value: Literal) -> Result<(), String> {
+        match self {
+            Class(_, _, _, _, statics, _) => {
+                statics.borrow_mut().insert(String::from(name), value);
+                Ok(())
+            }
+            _ => Err(format!("Cannot set static property on non-class {}.", self.get_name())),
+        }
+    }
+
+    // Checks whether this class is the named class or descends from it.
+    // Classes are compared by which declaration made them, so a class doesn't match another that
+    // only shares its name.
+    pub fn inherits_from(&self, class: &Callable) -> bool {
+        let Class(.., target) = class else { return false };
+        match self {
+            Class(_, super_class, .., id) => {
+                if id == target { true }
+                else if let Some(sc) = super_class { sc.inherits_from(class) }
+                else { false }
+            },
+            _ => false,
+        }
+    }
+
     pub fn decouple_closures(&mut self) {
         match self {
-            Function(_, _, _, ref mut closure, _) => {
+            Function(_, _, _, ref mut closure, _, _) | Generator(_, _, _, ref mut closure, _) => {
                 let mut temp = None;
                 if let Some(inner) = closure {
                     temp = Some(inner.borrow_mut().spawn_closure());
                 }
                 *closure = temp;
             },
-            Class(_, _, methods) => {
-                for (_, m) in methods {
+            Class(_, _, methods, accessors, statics, _) => {
+                for m in methods.values_mut() {
+                    m.decouple_closures();
+                }
+                accessors.decouple_closures();
+                let static_copy = statics.borrow().clone();
+                *statics = Rc::new(RefCell::new(static_copy));
+            },
+            Trait(_, methods, accessors) => {
+                for m in methods.values_mut() {
                     m.decouple_closures();
                 }
+                accessors.decouple_closures();
             },
             _ => (),
         };
@@ -94,7 +328,8 @@ impl Callable {
 impl fmt::Display for Callable {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self {
-            Class(name, _, _) => write!(f, "<class {}>", name),
+            Class(name, _, _, _, _, _) => write!(f, "<class {}>", name),
+            Trait(name, _, _) => write!(f, "<trait {}>", name),
             other => write!(f, "<fn {}>", other.get_name()),
         }
     }
@@ -0,0 +1,102 @@
+use crate::components as lox;
+use lox::instructions::node::Literal;
+
+use std::rc::Rc;
+use std::fmt;
+
+// An enum declaration. Variants without a payload are created once, up front, so that every
+// use of one refers to the same value.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Enum {
+    name: String,
+    variants: Vec<(String, Vec<String>, Option<EnumValue>)>, // Name, payload field names, value if it has no payload
+}
+
+// A value of an enum type. Values compare by identity rather than by contents.
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct EnumValue {
+    enum_name: String,
+    variant: String,
+    fields: Vec<String>,
+    payload: Vec<Literal>,
+    identity: Rc<()>,
+}
+
+impl Enum {
+    pub fn new(name: &str, variant_decls: Vec<(String, Vec<String>)>) -> Enum {
+        let variants = variant_decls.into_iter().map(|(variant, fields)| {
+            let unit = if fields.is_empty() { Some(EnumValue::new(name, &variant, vec![], vec![])) } else { None };
+            (variant, fields, unit)
+        }).collect();
+        Enum{ name: String::from(name), variants }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    // Returns the payload field names of the named variant, if it exists.
+    pub fn find_variant(&self, variant: &str) -> Option<&Vec<String>> {
+        self.variants.iter().find(|(name, _, _)| name == variant).map(|(_, fields, _)| fields)
+    }
+
+    pub fn find_unit(&self, variant: &str) -> Option<EnumValue> {
+        self.variants.iter().find(|(name, _, _)| name == variant).and_then(|(_, _, unit)| unit.clone())
+    }
+
+    // The variants without a payload, in declaration order.
+    pub fn units(&self) -> Vec<EnumValue> {
+        self.variants.iter().filter_map(|(_, _, unit)| unit.clone()).collect()
+    }
+}
+
+impl EnumValue {
+    pub fn new(enum_name: &str, variant: &str, fields: Vec<String>, payload: Vec<Literal>) -> EnumValue {
+        EnumValue{
+            enum_name: String::from(enum_name),
+            variant: String::from(variant),
+            fields,
+            payload,
+            identity: Rc::new(()),
+        }
+    }
+
+    pub fn is_variant(&self, enum_name: &str, variant: &str) -> bool {
+        self.enum_name == enum_name && self.variant == variant
+    }
+
+    pub fn get_payload(&self) -> &Vec<Literal> {
+        &self.payload
+    }
+
+    pub fn get(&self, name: &str) -> Result<Literal, String> {
+        self.fields.iter().position(|field| field == name)
+            .map(|i| self.payload[i].clone())
+            .ok_or_else(|| format!("Undefined property '{}'.", name))
+    }
+}
+
+impl PartialEq for EnumValue {
+    fn eq(&self, other: &EnumValue) -> bool {
+        Rc::ptr_eq(&self.identity, &other.identity)
+    }
+}
+
+impl fmt::Display for Enum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<enum {}>", self.name)
+    }
+}
+
+impl fmt::Display for EnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.enum_name, self.variant)?;
+        if !self.payload.is_empty() {
+            let parts: Vec<String> = self.payload.iter().map(|item| item.to_string()).collect();
+            write!(f, "({})", parts.join(", "))?;
+        }
+        Ok(())
+    }
+}
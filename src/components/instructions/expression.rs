@@ -12,11 +12,21 @@ pub enum Expression {
     Logical { left: Box<Expression>, op: LogicOp, right: Box<Expression> },
     Identifier(String),
     Grouping(Box<Expression>),
-    Assignment(String, Box<Expression>),
-    Call(Box<Expression>, Vec<Box<Expression>>, usize),
+    Assignment(String, Box<Expression>, usize),
+    CompoundAssignment(String, BinaryOp, Box<Expression>, usize),
+    Ternary(Box<Expression>, Box<Expression>, Box<Expression>), // Condition, then, else
+    Call(Box<Expression>, Vec<Expression>, Vec<(usize, String, usize)>, usize), // Callee, arguments, comments after arguments with their index and line (only when the parser keeps comments), line
     Getter(Box<Expression>, String),
     Setter(Box<Expression>, String, Box<Expression>), // Object, name, value
-    This,
+    CompoundSetter(Box<Expression>, String, BinaryOp, Box<Expression>), // Object, name, op, value
+    Index(Box<Expression>, Box<Expression>), // Object, index
+    IndexSetter(Box<Expression>, Box<Expression>, Box<Expression>), // Object, index, value
+    CompoundIndexSetter(Box<Expression>, Box<Expression>, BinaryOp, Box<Expression>), // Object, index, op, value
+    ListLiteral(Vec<Expression>),
+    MapLiteral(Vec<(Box<Expression>, Box<Expression>)>), // Key, value pairs in source order
+    Spread(Box<Expression>), // Only valid as a call argument
+    Named(String, Box<Expression>), // Parameter name, value; only valid as a call argument
+    This(usize),
     Super(String),
     // usize is used for line numbers in error reporting
     // TODO: maybe should shift the program to use this more broadly?
@@ -31,6 +41,9 @@ impl Expression {
     pub fn boxed_number(n: f64) -> Box<Expression> {
         Box::new(LitExp(Number(n)))
     }
+    pub fn boxed_integer(n: i64) -> Box<Expression> {
+        Box::new(LitExp(Integer(n)))
+    }
     pub fn boxed_string(s: &str) -> Box<Expression> {
         Box::new(LitExp(StringData(String::from(s))))
     }
@@ -65,11 +78,21 @@ impl Expression {
     pub fn boxed_grouping(e: Box<Expression>) -> Box<Expression> {
         Box::new(Grouping(e))
     }
-    pub fn boxed_assignment(s: &str, e: Box<Expression>) -> Box<Expression> {
-        Box::new(Assignment(String::from(s), e))
+    pub fn boxed_assignment(s: &str, e: Box<Expression>, line: usize) -> Box<Expression> {
+        Box::new(Assignment(String::from(s), e, line))
+    }
+    pub fn boxed_compound_assignment(s: &str, op: BinaryOp, e: Box<Expression>, line: usize) -> Box<Expression> {
+        Box::new(CompoundAssignment(String::from(s), op, e, line))
+    }
+    pub fn boxed_ternary(
+        cond: Box<Expression>,
+        then_branch: Box<Expression>,
+        else_branch: Box<Expression>
+    ) -> Box<Expression> {
+        Box::new(Ternary(cond, then_branch, else_branch))
     }
-    pub fn boxed_call(f: Box<Expression>, args: Vec<Box<Expression>>, line: usize) -> Box<Expression> {
-        Box::new(Call(f, args, line))
+    pub fn boxed_call(f: Box<Expression>, args: Vec<Expression>, line: usize) -> Box<Expression> {
+        Box::new(Call(f, args, vec![], line))
     }
     pub fn boxed_getter(obj: Box<Expression>, name: &str) -> Box<Expression> {
         Box::new(Getter(obj, String::from(name)))
@@ -77,9 +100,38 @@ impl Expression {
     pub fn boxed_setter(obj: Box<Expression>, name: &str, value: Box<Expression>) -> Box<Expression> {
         Box::new(Setter(obj, String::from(name), value))
     }
+    pub fn boxed_compound_setter(obj: Box<Expression>, name: &str, op: BinaryOp, value: Box<Expression>) -> Box<Expression> {
+        Box::new(CompoundSetter(obj, String::from(name), op, value))
+    }
+    pub fn boxed_list(items: Vec<Expression>) -> Box<Expression> {
+        Box::new(ListLiteral(items))
+    }
+    pub fn boxed_map(entries: Vec<(Box<Expression>, Box<Expression>)>) -> Box<Expression> {
+        Box::new(MapLiteral(entries))
+    }
+    pub fn boxed_spread(list: Box<Expression>) -> Box<Expression> {
+        Box::new(Spread(list))
+    }
+    pub fn boxed_named(name: &str, value: Box<Expression>) -> Box<Expression> {
+        Box::new(Named(String::from(name), value))
+    }
+    pub fn boxed_index(obj: Box<Expression>, index: Box<Expression>) -> Box<Expression> {
+        Box::new(Index(obj, index))
+    }
+    pub fn boxed_index_setter(obj: Box<Expression>, index: Box<Expression>, value: Box<Expression>) -> Box<Expression> {
+        Box::new(IndexSetter(obj, index, value))
+    }
+    pub fn boxed_compound_index_setter(
+        obj: Box<Expression>,
+        index: Box<Expression>,
+        op: BinaryOp,
+        value: Box<Expression>
+    ) -> Box<Expression> {
+        Box::new(CompoundIndexSetter(obj, index, op, value))
+    }
     
-    pub fn boxed_this() -> Box<Expression> {
-        Box::new(This)
+    pub fn boxed_this(line: usize) -> Box<Expression> {
+        Box::new(This(line))
     }
     pub fn boxed_super(name: &str) -> Box<Expression> {
         Box::new(Super(String::from(name)))
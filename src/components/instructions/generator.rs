@@ -0,0 +1,66 @@
+use crate::components as lox;
+use lox::instructions::statement::Statement;
+use lox::instructions::expression::Expression;
+use lox::instructions::iteration::Cursor;
+use lox::environment::LoxEnvironment;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::fmt;
+
+// The suspended state of a call to a generator function. Ordinary calls recurse through their body,
+// which leaves nowhere to pause; generators instead walk their body with an explicit stack of frames
+// so that execution can stop at a yield and pick up from the same point on the next resume.
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Generator {
+    name: String,
+    state: Rc<RefCell<GeneratorState>>,
+}
+
+#[derive(Debug)]
+pub struct GeneratorState {
+    pub frames: Vec<Frame>,
+    pub env: Rc<RefCell<LoxEnvironment>>, // Private to this generator, so its scopes survive between resumes
+    pub done: bool,
+    pub running: bool,
+}
+
+#[derive(Debug)]
+pub enum Frame {
+    Statements(Vec<Statement>, usize, bool), // Statements, index of the next to run, whether the frame owns a scope
+    Loop(Box<Expression>, Box<Statement>), // Condition, body
+    ForIn(String, Cursor, Box<Statement>), // Loop variable, position in the iterable, body
+}
+
+impl Generator {
+    pub fn new(name: &str, body: Vec<Statement>, env: Rc<RefCell<LoxEnvironment>>) -> Generator {
+        let state = GeneratorState{ frames: vec![Frame::Statements(body, 0, false)], env, done: false, running: false };
+        Generator{ name: String::from(name), state: Rc::new(RefCell::new(state)) }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state.borrow().done
+    }
+
+    pub fn get_state(&self) -> &Rc<RefCell<GeneratorState>> {
+        &self.state
+    }
+}
+
+// Generators are stateful objects, so two are only equal if they are the same generator.
+impl PartialEq for Generator {
+    fn eq(&self, other: &Generator) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+impl fmt::Display for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<generator {}>", self.name)
+    }
+}
@@ -27,7 +27,7 @@ impl Instance {
             if let Ok(c) = self.class.find_method(name) {
                 Ok(Literal::CallLit(c))
             } else {
-                Err(format!("Undefined property {}.", name))
+                Err(format!("Undefined property '{}'.", name))
             }
         }
     }
@@ -44,6 +44,16 @@ impl Instance {
         self.fields.borrow_mut().insert(String::from(name), value);
     }
 
+    pub fn has_field(&self, name: &str) -> bool {
+        self.fields.borrow().contains_key(name)
+    }
+
+    pub fn field_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.fields.borrow().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
     pub fn get_class(&self) -> &Callable {
         &self.class
     }
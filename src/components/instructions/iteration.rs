@@ -0,0 +1,13 @@
+use crate::components as lox;
+use lox::instructions::node::Literal;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+// How far a for-in loop has got through the value it iterates over.
+#[derive(Debug)]
+pub enum Cursor {
+    List(Rc<RefCell<Vec<Literal>>>, usize), // Read live, so items appended during the loop are visited
+    Values(Vec<Literal>, usize), // A snapshot, used for the characters of a string and the keys of a map
+    Protocol(Literal), // A generator, or an instance with a next() method and a done property
+}
@@ -1,28 +1,61 @@
 use core::fmt;
 use crate::components::instructions::callable::Callable;
 use crate::components::instructions::instance::Instance;
+use crate::components::instructions::generator::Generator;
+use crate::components::instructions::enumeration::{Enum, EnumValue};
+
+use std::rc::Rc;
+use std::cell::RefCell;
 
 #[derive(Debug)]
 #[derive(PartialEq)]
 #[derive(Clone)]
 pub enum Literal {
     Number(f64),
+    Integer(i64),
     StringData(String),
     Boolean(bool),
     Nil,
     CallLit(Callable),
     InstLit(Instance),
+    List(Rc<RefCell<Vec<Literal>>>),
+    Map(Rc<RefCell<Vec<(Literal, Literal)>>>), // Entries are kept in insertion order
+    GenLit(Generator),
+    EnumLit(Rc<Enum>),
+    VariantLit(EnumValue),
+}
+
+impl Literal {
+    pub fn new_list(items: Vec<Literal>) -> Literal {
+        Literal::List(Rc::new(RefCell::new(items)))
+    }
+
+    pub fn new_map(entries: Vec<(Literal, Literal)>) -> Literal {
+        Literal::Map(Rc::new(RefCell::new(entries)))
+    }
 }
 
 impl fmt::Display for Literal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Literal::Number(n) => write!(f, "{}", n),
+            Literal::Integer(n) => write!(f, "{}", n),
             Literal::StringData(s) => write!(f, "{}", s),
             Literal::Boolean(b) => write!(f, "{}", b),
             Literal::Nil => write!(f, "Nil"),
             Literal::CallLit(c) => c.fmt(f),
             Literal::InstLit(i) => i.fmt(f),
+            Literal::List(items) => {
+                let parts: Vec<String> = items.borrow().iter().map(|item| item.to_string()).collect();
+                write!(f, "[{}]", parts.join(", "))
+            },
+            Literal::Map(entries) => {
+                let parts: Vec<String> = entries.borrow().iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                write!(f, "{{{}}}", parts.join(", "))
+            },
+            Literal::GenLit(g) => g.fmt(f),
+            Literal::EnumLit(e) => e.fmt(f),
+            Literal::VariantLit(v) => v.fmt(f),
         }
     }
 }
@@ -51,7 +84,61 @@ pub enum BinaryOp {
     Subtract,
     Multiply,
     Divide,
+    IntDivide,
     Modulo,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl BinaryOp {
+    // Name of the method a class defines to overload this operator.
+    // != has no method of its own and is derived from __eq__.
+    pub fn special_method(&self) -> &'static str {
+        match self {
+            BinaryOp::Equal | BinaryOp::NotEqual => "__eq__",
+            BinaryOp::Less => "__lt__",
+            BinaryOp::LessEqual => "__le__",
+            BinaryOp::Greater => "__gt__",
+            BinaryOp::GreaterEqual => "__ge__",
+            BinaryOp::Add => "__add__",
+            BinaryOp::Subtract => "__sub__",
+            BinaryOp::Multiply => "__mul__",
+            BinaryOp::Divide => "__div__",
+            BinaryOp::IntDivide => "__intdiv__",
+            BinaryOp::Modulo => "__mod__",
+            BinaryOp::BitAnd => "__and__",
+            BinaryOp::BitOr => "__or__",
+            BinaryOp::BitXor => "__xor__",
+            BinaryOp::ShiftLeft => "__lshift__",
+            BinaryOp::ShiftRight => "__rshift__",
+        }
+    }
+
+    // The operator as written in source.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::IntDivide => "~/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::ShiftLeft => "<<",
+            BinaryOp::ShiftRight => ">>",
+        }
+    }
 }
 
 #[derive(Debug)]
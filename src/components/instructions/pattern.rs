@@ -0,0 +1,30 @@
+use crate::components as lox;
+use lox::instructions::node::Literal;
+use lox::instructions::statement::Statement;
+use lox::instructions::expression::Expression;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum Pattern {
+    Literal(Literal),
+    Binding(String),
+    Wildcard,
+    Instance(String, Vec<String>), // Class name, names of fields to bind
+    Variant(String, String, Vec<String>), // Enum name, variant name, names to bind the payload to in order
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub struct MatchArm {
+    pub patterns: Vec<Pattern>,
+    pub guard: Option<Box<Expression>>,
+    pub body: Box<Statement>,
+}
+
+impl MatchArm {
+    pub fn new(patterns: Vec<Pattern>, guard: Option<Box<Expression>>, body: Box<Statement>) -> MatchArm {
+        MatchArm{patterns, guard, body}
+    }
+}
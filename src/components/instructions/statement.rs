@@ -1,17 +1,93 @@
 use crate::components as lox;
 use lox::instructions::expression::Expression;
+use lox::instructions::pattern::MatchArm;
+
+use std::rc::Rc;
+use std::collections::HashMap;
 
 #[derive(Debug)]
 #[derive(PartialEq)]
 #[derive(Clone)]
 pub enum Statement {
-    Decl(String, Box<Expression>),
-    Expr(Box<Expression>),
-    Print(Box<Expression>),
-    Block(Vec<Box<Statement>>),
+    Decl(String, Box<Expression>, Option<String>, usize), // Name, initializer, type annotation, line
+    Const(String, Box<Expression>, Option<String>, usize), // Like Decl, but the binding can't be reassigned
+    Expr(Box<Expression>, usize), // Expression, line
+    Print(Box<Expression>, usize),
+    Block(Vec<Statement>),
     Return(Box<Expression>, usize), // usize is line number for error reporting
-    If(Box<Expression>, Box<Statement>, Option<Box<Statement>>),
-    While(Box<Expression>, Box<Statement>),
-    Fun(String, Vec<String>, Vec<Box<Statement>>), // The strings are unwrapped identifiers
-    Class(String, String, Vec<Box<Statement>>), // The statements should all be function declarations
-}
\ No newline at end of file
+    If(Box<Expression>, Box<Statement>, Option<Box<Statement>>, usize), // Lines are where the keyword is
+    While(Box<Expression>, Box<Statement>, usize),
+    For(Option<Box<Statement>>, Box<Expression>, Option<Box<Expression>>, Box<Statement>, usize), // Initializer, condition, increment, body, line
+    ForIn(String, Box<Expression>, Box<Statement>, usize), // Loop variable, iterable, body, line
+    Fun(String, Vec<String>, Vec<Statement>, Rc<Signature>), // The strings are unwrapped identifiers
+    Generator(String, Vec<String>, Vec<Statement>, Rc<Signature>), // Declared with 'fun*'; same layout as Fun
+    Yield(Box<Expression>, usize), // Only valid inside a generator body
+    Class(String, String, Vec<String>, Vec<Statement>, Vec<Statement>, Vec<bool>), // Name, superclass name, trait names, methods, static members, whether each member in source order is static
+    Trait(String, Vec<Statement>), // The statements should all be method or accessor declarations
+    Enum(String, Vec<(String, Vec<String>)>), // Name, variants with their payload field names
+    PropertyGet(String, Vec<Statement>), // Only valid as a class member
+    PropertySet(String, String, Vec<Statement>), // Property name, parameter name, body; only valid as a class member
+    Match(Box<Expression>, Vec<MatchArm>, Option<Box<Statement>>, usize), // Subject, cases, default, line
+    Test(String, Vec<Statement>, usize), // Name, body, line; only valid at the top level, and only run by the test runner
+    Comment(String, bool, usize), // Text, whether it trails code on its line, line; only produced when the parser keeps comments
+}
+
+impl Statement {
+    // The block a C-style for loop runs as: the initializer, then a while loop whose body is
+    // followed by the increment.
+    pub fn desugar_for(
+        init: Option<Box<Statement>>,
+        cond: Box<Expression>,
+        incr: Option<Box<Expression>>,
+        body: Statement,
+        line: usize,
+    ) -> Statement {
+        let mut while_body = vec![body];
+        if let Some(incr) = incr {
+            while_body.push(Statement::Expr(incr, line));
+        }
+        let mut for_vec: Vec<Statement> = init.into_iter().map(|s| *s).collect();
+        for_vec.push(Statement::While(cond, Box::new(Statement::Block(while_body)), line));
+        Statement::Block(for_vec)
+    }
+
+    // The keyword and name of a declaration of a function, class or the like.
+    pub fn declaration(&self) -> Option<(&'static str, &str)> {
+        use Statement::*;
+        match self {
+            Fun(name, ..) => Some(("fun", name)),
+            Generator(name, ..) => Some(("fun*", name)),
+            Class(name, ..) => Some(("class", name)),
+            Trait(name, _) => Some(("trait", name)),
+            Enum(name, _) => Some(("enum", name)),
+            _ => None,
+        }
+    }
+
+    // The line of a statement that does something when it runs. Blocks and declarations of
+    // functions, classes and the like have none.
+    pub fn line(&self) -> Option<usize> {
+        use Statement::*;
+        match self {
+            Decl(.., line) | Const(.., line) | Expr(_, line) | Print(_, line) | Return(_, line) | If(.., line)
+            | While(.., line) | For(.., line) | ForIn(.., line) | Yield(_, line) | Match(.., line) => Some(*line),
+            _ => None,
+        }
+    }
+}
+
+// Everything about a function's parameter list beyond the names themselves. Type names are
+// resolved by the checker, since they may refer to classes. Shared with the functions declared
+// from it, and kept out of line so that statements stay small.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Default)]
+pub struct Signature {
+    pub param_types: HashMap<String, String>, // Annotated parameters only, keyed by parameter name
+    pub returns: Option<String>,
+    pub defaults: HashMap<String, Box<Expression>>, // Default values, keyed by parameter name
+    pub comments: HashMap<String, (String, usize)>, // Comments after parameters and their lines, keyed by parameter name; only when the parser keeps comments
+    pub rest: bool, // Whether the last parameter collects any extra arguments into a list
+    pub line: usize, // Where the function's name is, since the declaration itself has no line
+}
@@ -1,22 +1,88 @@
 use crate::components as lox;
-use lox::instructions::{statement, expression, node, callable, instance};
-use statement::Statement;
+use lox::instructions::{statement, expression, node, callable, instance, pattern, generator, iteration, enumeration};
+use statement::{Statement, Signature};
 use expression::Expression;
 use expression::Expression::*;
 use callable::*;
 use node::*;
 use node::Literal::*;
 use instance::*;
+use pattern::*;
+use generator::*;
+use iteration::*;
+use enumeration::*;
 use lox::environment::*;
 
 use std::vec::*;
 use std::collections::HashMap;
+use std::rc::Rc;
+use std::cell::RefCell;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
+// Named call arguments, in source order.
+type NamedArgs = Vec<(String, Literal)>;
+
+// Lets a tool such as a debugger or profiler watch a program run. It's called before each statement
+// that has a line, while the interpreter can be inspected; returning an error stops the program
+// with it. It also hears when Lox functions are entered and left, with their frame on top, and
+// which way branches go, keyed by the line of the statement they're in.
+pub trait ExecutionHook {
+    fn before_statement(&mut self, interpreter: &mut LoxInterpreter, line: usize) -> Result<(), String>;
+
+    // Called once the parameters are bound, with their values.
+    fn enter_function(&mut self, _interpreter: &mut LoxInterpreter, _params: &[(String, Literal)]) {}
+
+    // Returned is the value the call produced, or for a generator, what it yielded; None if it failed.
+    fn exit_function(&mut self, _interpreter: &mut LoxInterpreter, _returned: Option<&Literal>) {}
+
+    fn branch(&mut self, _interpreter: &mut LoxInterpreter, _line: usize, _branch: Branch) {}
+
+    // Called once a function, class, trait or enum has been declared, with the declaring keyword.
+    fn declaration(&mut self, _interpreter: &mut LoxInterpreter, _keyword: &str, _name: &str) {}
+
+    // Whether expression() should be called, which costs a copy of every expression evaluated.
+    fn traces_expressions(&self) -> bool { false }
+
+    // Called with each expression once it has a value, inner ones first. Literals and
+    // parentheses are left out, since they only repeat what's already shown.
+    fn expression(&mut self, _interpreter: &mut LoxInterpreter, _expression: &Expression, _value: &Literal) {}
+
+    // Called when a loop goes around again, after its line has come up for the check. The next
+    // iteration may run on that same line, as with a loop written on one.
+    fn next_iteration(&mut self, _interpreter: &mut LoxInterpreter, _line: usize) {}
+
+    // Called after a scope is opened and before one is closed, so the scope is still visible.
+    fn push_scope(&mut self, _interpreter: &mut LoxInterpreter) {}
+
+    fn pop_scope(&mut self, _interpreter: &mut LoxInterpreter) {}
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Copy)]
+pub enum Branch {
+    If(bool), // Whether the then branch was taken
+    Logical(bool), // Whether the right operand was evaluated
+}
+
+// A function call in progress, or the top level of the script.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub struct CallFrame {
+    pub function: String,
+    pub line: usize, // Of the statement running in this frame; 0 before the first one
+}
+
 pub struct LoxInterpreter {
     env: LoxEnvironment,
     output: String,
+    frames: Vec<CallFrame>, // Outermost first
+    hook: Option<Box<dyn ExecutionHook>>,
+    error_line: Option<usize>, // Of the innermost statement that failed in the last run
+    classes_declared: usize, // Gives each class its id
 }
 
 impl LoxInterpreter {
@@ -24,22 +90,63 @@ impl LoxInterpreter {
         let mut env = LoxEnvironment::new(); // parens prevent overlap w/ function namespace
         let native_fns = Callable::native_fn_list();
         for f in native_fns.iter() {
-            env.define(&f.0, Literal::CallLit(f.1.clone())); // TODO: assess clone call
+            env.declare(&f.0, Literal::CallLit(f.1.clone()), false).expect("Native functions should have unique names"); // TODO: assess clone call
         }
-        LoxInterpreter{ env, output: String::new() }
+        let frames = vec![CallFrame{ function: String::from("script"), line: 0 }];
+        LoxInterpreter{ env, output: String::new(), frames, hook: None, error_line: None, classes_declared: 0 }
+    }
+
+    pub fn set_hook(&mut self, hook: Box<dyn ExecutionHook>) {
+        self.hook = Some(hook);
+    }
+
+    // Adds assert and assertEqual, which only tests have, so other programs keep those names.
+    pub fn define_assertions(&mut self) {
+        for (name, f) in Callable::assertion_fn_list() {
+            self.env.declare(&name, Literal::CallLit(f), false).expect("Assertions should be defined before the program runs");
+        }
+    }
+
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    pub fn error_line(&self) -> Option<usize> {
+        self.error_line
+    }
+
+    // The bindings visible where the program is running, innermost scope first. The last scope
+    // holds the globals as the running code sees them.
+    pub fn scopes(&self) -> Vec<Vec<(String, Literal)>> {
+        self.env.visible_scopes()
+    }
+
+    // Closures hold a copy of the globals from when they were made; these are the current ones.
+    pub fn globals(&self) -> Vec<(String, Literal)> {
+        self.env.globals()
+    }
+
+    // Falls back to the current globals, which a closure's copy may be missing.
+    pub fn lookup(&mut self, name: &str) -> Result<Literal, String> {
+        self.env.get(name).or_else(|e| {
+            self.globals().into_iter().find(|(n, _)| n == name).map(|(_, v)| v).ok_or(e)
+        })
+    }
+
+    // Hands over what the program has printed so far, so it can be shown before the run ends.
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
     }
 
-    // This is mainly a helper function to let functions add variables.
-    // Don't love that it's visible as an interface to the end-user, may rework.
-    pub fn define_external(&mut self, name: &str, value: Literal) {
-        self.env.define(name, value);
+    pub fn display(&mut self, value: Literal) -> Result<String, String> {
+        self.stringify(value)
     }
 
     pub fn interpret(&mut self, program: Vec<Statement>) -> Result<String, String> {
         self.output = String::new();
+        self.error_line = None;
         for s in program {
-            let result = self.evaluate_stmt(s);
-            if let Err(e) = result { return Err(e); }
+            self.evaluate_stmt(s)?;
         }
         self.output.pop();
         Ok(self.output.clone())
@@ -47,33 +154,129 @@ impl LoxInterpreter {
 
     // Returns Some if returning a value from a block, None for other valid outcomes
     pub fn evaluate_stmt(&mut self, s: Statement) -> Result<Option<Literal>, String> {
+        let line = s.line();
+        let declaration = s.declaration().map(|(keyword, name)| (keyword, String::from(name)));
+        let result = match line {
+            Some(line) => self.before_statement(line).and_then(|()| self.execute_stmt(s)),
+            None => self.execute_stmt(s),
+        };
+        if result.is_err() && self.error_line.is_none() {
+            self.error_line = line;
+        }
+        if let (Ok(_), Some((keyword, name))) = (&result, declaration) {
+            if let Some(mut hook) = self.hook.take() {
+                hook.declaration(self, keyword, &name);
+                self.hook = Some(hook);
+            }
+        }
+        result
+    }
+
+    fn before_statement(&mut self, line: usize) -> Result<(), String> {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.line = line;
+        }
+        // Taken out while it runs, so that it can be handed the interpreter.
+        if let Some(mut hook) = self.hook.take() {
+            let result = hook.before_statement(self, line);
+            self.hook = Some(hook);
+            result?;
+        }
+        Ok(())
+    }
+
+    // The loop's line comes up again for each check, and then the next iteration begins.
+    fn repeat_loop(&mut self, line: usize) -> Result<(), String> {
+        self.before_statement(line)?;
+        if let Some(mut hook) = self.hook.take() {
+            hook.next_iteration(self, line);
+            self.hook = Some(hook);
+        }
+        Ok(())
+    }
+
+    fn report_branch(&mut self, branch: Branch) {
+        let line = self.frames.last().map_or(0, |f| f.line);
+        if let Some(mut hook) = self.hook.take() {
+            hook.branch(self, line, branch);
+            self.hook = Some(hook);
+        }
+    }
+
+    // The parameters are read back from the current scope, where they have been bound.
+    fn push_frame(&mut self, function: &str, params: &[String]) {
+        self.frames.push(CallFrame{ function: String::from(function), line: 0 });
+        if let Some(mut hook) = self.hook.take() {
+            let params: Vec<(String, Literal)> = params.iter()
+                .map(|p| (p.clone(), self.env.get(p).unwrap_or(Nil)))
+                .collect();
+            hook.enter_function(self, &params);
+            self.hook = Some(hook);
+        }
+    }
+
+    fn pop_frame(&mut self, returned: Option<&Literal>) {
+        if let Some(mut hook) = self.hook.take() {
+            hook.exit_function(self, returned);
+            self.hook = Some(hook);
+        }
+        self.frames.pop();
+    }
+
+    pub fn scope_depth(&self) -> usize {
+        self.env.depth()
+    }
+
+    fn lower_scope(&mut self) {
+        self.env.lower_scope();
+        if let Some(mut hook) = self.hook.take() {
+            hook.push_scope(self);
+            self.hook = Some(hook);
+        }
+    }
+
+    fn raise_scope(&mut self) -> Result<(), String> {
+        if let Some(mut hook) = self.hook.take() {
+            hook.pop_scope(self);
+            self.hook = Some(hook);
+        }
+        self.env.raise_scope()
+    }
+
+    fn execute_stmt(&mut self, s: Statement) -> Result<Option<Literal>, String> {
         use Statement::*;
         match s {
-            Decl(id, expr) => {
+            Decl(id, expr, _, _) => {
+                let data = self.evaluate_expr(*expr)?;
+                self.env.declare(&id, data, true)?;
+                Ok(None)
+            },
+            Const(id, expr, _, _) => {
                 let data = self.evaluate_expr(*expr)?;
-                self.env.define(&id, data);
+                self.env.declare(&id, data, false)?;
                 Ok(None)
             },
             Block(v) => {
-                self.env.lower_scope();
+                self.lower_scope();
                 for s in v {
-                    let current = self.evaluate_stmt(*s)?;
+                    let current = self.evaluate_stmt(s)?;
                     if let Some(lit) = current {
-                        self.env.raise_scope().expect("Block execution structure should guarantee valid scope raise");
+                        self.raise_scope().expect("Block execution structure should guarantee valid scope raise");
                         return Ok(Some(lit));
                     }
                 }
-                self.env.raise_scope().expect("Block execution structure should guarantee valid scope raise");
+                self.raise_scope().expect("Block execution structure should guarantee valid scope raise");
                 Ok(None)
             }
-            Print(e) => {
+            Print(e, _) => {
                 //self.env.print_cur_closure();
-                let text = &format!("{}", self.evaluate_expr(*e)?);
+                let value = self.evaluate_expr(*e)?;
+                let text = &self.stringify(value)?;
                 self.output.push_str(text);
-                self.output.push_str("\n");
+                self.output.push('\n');
                 Ok(None)
             },
-            Expr(e) => {
+            Expr(e, _) => {
                 self.evaluate_expr(*e)?;
                 Ok(None)
             },
@@ -82,8 +285,10 @@ impl LoxInterpreter {
                 let result = self.evaluate_expr(*e)?;
                 Ok(Some(result))
             }
-            If(cond, then_branch, else_option) => {
-                if is_truthful(self.evaluate_expr(*cond)?) {
+            If(cond, then_branch, else_option, _) => {
+                let taken = is_truthful(self.evaluate_expr(*cond)?);
+                self.report_branch(Branch::If(taken));
+                if taken {
                     self.evaluate_stmt(*then_branch)
                 } else if let Some(else_branch) = else_option {
                     self.evaluate_stmt(*else_branch)
@@ -91,7 +296,7 @@ impl LoxInterpreter {
                     Ok(None)
                 }
             },
-            While(cond, body) => {
+            While(cond, body, line) => {
                 // TODO: clone statements here are horrifically inefficient.
                 // Probably need to restructure everything to pass by reference...
                 while is_truthful(self.evaluate_expr(*cond.clone())?) {
@@ -99,236 +304,1277 @@ impl LoxInterpreter {
                     if let Some(lit) = current {
                         return Ok(Some(lit));
                     }
+                    self.repeat_loop(line)?;
+                }
+                Ok(None)
+            }
+            For(init, cond, incr, body, line) => self.evaluate_stmt(Statement::desugar_for(init, cond, incr, *body, line)),
+            ForIn(name, iterable, body, line) => {
+                let value = self.evaluate_expr(*iterable)?;
+                let mut cursor = self.make_cursor(value)?;
+                while let Some(item) = self.advance_cursor(&mut cursor)? {
+                    // Each iteration gets its own scope, so closures capture that iteration's value.
+                    self.lower_scope();
+                    self.env.define(&name, item);
+                    let result = self.evaluate_stmt(*body.clone());
+                    self.raise_scope().expect("Loop execution structure should guarantee valid scope raise");
+                    if let Some(lit) = result? {
+                        return Ok(Some(lit));
+                    }
+                    self.repeat_loop(line)?;
                 }
                 Ok(None)
             }
-            Fun(name, args, body) => {
-                let inner_func = Callable::Function(name.clone(), args.clone(), body.clone(), None, false);
+            Fun(name, args, body, signature) => {
+                let inner_func = Callable::Function(name.clone(), args.clone(), body.clone(), None, false, signature.clone());
+                let closure = self.env.spawn_closure();
+                closure.borrow_mut().define(&name, Literal::CallLit(inner_func));
+                let data = Callable::Function(name.clone(), args, body, Some(closure), false, signature);
+                self.env.declare(&name, Literal::CallLit(data), true)?;
+                Ok(None)
+            }
+            Generator(name, args, body, signature) => {
+                let inner_func = Callable::Generator(name.clone(), args.clone(), body.clone(), None, signature.clone());
                 let closure = self.env.spawn_closure();
                 closure.borrow_mut().define(&name, Literal::CallLit(inner_func));
-                let data = Callable::Function(name.clone(), args, body, Some(closure), false);
-                self.env.define(&name, Literal::CallLit(data));
+                let data = Callable::Generator(name.clone(), args, body, Some(closure), signature);
+                self.env.declare(&name, Literal::CallLit(data), true)?;
+                Ok(None)
+            }
+            Yield(..) => Err(String::from("Can't yield outside a generator.")),
+            Class(name, super_name, trait_names, method_defs, static_defs, _) => self.declare_class(name, super_name, trait_names, method_defs, static_defs),
+            Trait(name, method_defs) => {
+                let (methods, accessors) = self.build_methods(&name, method_defs);
+                self.env.declare(&name, Literal::CallLit(Callable::Trait(name.clone(), methods, Box::new(accessors))), true)?;
+                Ok(None)
+            }
+            Enum(name, variants) => {
+                let declared = Rc::new(enumeration::Enum::new(&name, variants));
+                self.env.declare(&name, Literal::EnumLit(declared), true)?;
                 Ok(None)
             }
-            Class(name, super_name, method_defs) => {
-                let mut methods = HashMap::new();
-                for stmt in method_defs.clone() {
-                    if let Fun(fn_name, args, body) = *stmt {
-                        let func = Callable::Function(fn_name.clone(), args, body, Some(self.env.spawn_closure()), fn_name == "init");
-                        methods.insert(fn_name.clone(), func);
+            PropertyGet(name, _) | PropertySet(name, _, _) => {
+                Err(format!("Property accessor {} must be declared inside a class.", name))
+            }
+            Match(subject, arms, default, _) => self.evaluate_match(*subject, arms, default),
+            Comment(..) | Test(..) => Ok(None), // Tests are only run by the test runner
+        }
+    }
+
+    fn build_methods(&mut self, owner: &str, method_defs: Vec<Statement>) -> (HashMap<String, Callable>, Accessors) {
+        use Statement::*;
+
+        let mut methods = HashMap::new();
+        let mut accessors = Accessors::default();
+        for stmt in method_defs {
+            match stmt {
+                Fun(fn_name, args, body, signature) => {
+                    let func = Callable::Function(fn_name.clone(), args, body, Some(self.env.spawn_closure()), fn_name == "init", signature);
+                    methods.insert(fn_name.clone(), func);
+                },
+                PropertyGet(prop_name, body) => {
+                    let func = Callable::Function(prop_name.clone(), vec![], body, Some(self.env.spawn_closure()), false, Rc::default());
+                    accessors.getters.insert(prop_name, func);
+                },
+                PropertySet(prop_name, param, body) => {
+                    let func = Callable::Function(prop_name.clone(), vec![param], body, Some(self.env.spawn_closure()), false, Rc::default());
+                    accessors.setters.insert(prop_name, func);
+                },
+                Comment(..) => (),
+                _ => panic!("Found non-function statement while processing methods for {}.", owner), // should be impossible
+            }
+        }
+        (methods, accessors)
+    }
+
+    // Copies trait members into a class's tables. Members declared in the class body win over
+    // trait members; the same member arriving from two different traits is an error unless the
+    // class body overrides it. Trait members take priority over superclass members.
+    fn mix_in_traits(
+        &mut self,
+        class_name: &str,
+        trait_names: &[String],
+        methods: &mut HashMap<String, Callable>,
+        accessors: &mut Accessors,
+    ) -> Result<(), String> {
+        let mut method_sources: HashMap<String, String> = HashMap::new();
+        let mut getter_sources: HashMap<String, String> = HashMap::new();
+        let mut setter_sources: HashMap<String, String> = HashMap::new();
+
+        for trait_name in trait_names {
+            let Literal::CallLit(Callable::Trait(_, trait_methods, trait_accessors)) = self.env.get(trait_name)? else {
+                return Err(format!("{} is not a trait.", trait_name));
+            };
+            let tables = [
+                (trait_methods, &mut *methods, &mut method_sources),
+                (trait_accessors.getters, &mut accessors.getters, &mut getter_sources),
+                (trait_accessors.setters, &mut accessors.setters, &mut setter_sources),
+            ];
+            for (provided, target, sources) in tables {
+                for (member, func) in provided {
+                    let declared_in_class = target.contains_key(&member) && !sources.contains_key(&member);
+                    if declared_in_class { continue; }
+                    if let Some(other) = sources.get(&member) {
+                        return Err(format!(
+                            "Class {} inherits conflicting member {} from traits {} and {}; override it in the class to resolve.",
+                            class_name, member, other, trait_name,
+                        ));
                     }
-                    else { panic!("Found non-function statement while processing methods for class {}.", name); } // should be impossible
+                    sources.insert(member.clone(), trait_name.clone());
+                    target.insert(member, func);
                 }
+            }
+        }
+        Ok(())
+    }
 
-                let mut super_class = None;
-                if super_name != "" {
-                    let super_eval = self.env.get(&super_name)?;
-                    if let CallLit(Callable::Class(n, sn, md)) = super_eval {
-                        super_class = Some(Box::new(Callable::Class(n, sn, md)));
-                    } else {
-                        return Err(String::from("Superclass must be a class."));
+    // Returns the names a pattern binds if it matches, or None if it does not.
+    fn match_pattern(&mut self, pattern: &Pattern, value: &Literal) -> Result<Option<Vec<(String, Literal)>>, String> {
+        match pattern {
+            Pattern::Literal(lit) => {
+                if literals_equal(lit, value) { Ok(Some(vec![])) }
+                else { Ok(None) }
+            },
+            Pattern::Binding(name) => Ok(Some(vec![(name.clone(), value.clone())])),
+            Pattern::Wildcard => Ok(Some(vec![])),
+            Pattern::Instance(class_name, fields) => {
+                let class = self.env.get(class_name)?;
+                let CallLit(target @ Callable::Class(..)) = class else {
+                    return Err(format!("{} in class pattern is not a class.", class_name));
+                };
+                let InstLit(inst) = value else { return Ok(None) };
+                if !inst.get_class().inherits_from(&target) { return Ok(None); }
+
+                let mut bindings = Vec::new();
+                for field in fields {
+                    match inst.get(field) {
+                        Ok(lit) => bindings.push((field.clone(), lit)),
+                        Err(_) => return Ok(None),
                     }
                 }
+                Ok(Some(bindings))
+            },
+            Pattern::Variant(enum_name, variant, names) => {
+                let EnumLit(declared) = self.env.get(enum_name)? else {
+                    return Err(format!("{} in variant pattern is not an enum.", enum_name));
+                };
+                let Some(fields) = declared.find_variant(variant) else {
+                    return Err(format!("Enum {} has no variant {}.", enum_name, variant));
+                };
+                if !names.is_empty() && names.len() != fields.len() {
+                    return Err(format!(
+                        "Pattern {}.{} binds {} values but the variant has {}.",
+                        enum_name, variant, names.len(), fields.len(),
+                    ));
+                }
 
-                let class = Callable::Class(name.clone(), super_class, methods);
+                let VariantLit(value) = value else { return Ok(None) };
+                if !value.is_variant(enum_name, variant) { return Ok(None); }
+                Ok(Some(names.iter().cloned().zip(value.get_payload().iter().cloned()).collect()))
+            },
+        }
+    }
 
-                self.env.define(&name, Literal::CallLit(class));
-                Ok(None)
+    // Runs a matched arm in a fresh scope holding its bindings.
+    // Returns None if the arm's guard rejects the match, or Some with the body's outcome otherwise.
+    fn evaluate_match_arm(&mut self, arm: &MatchArm, bindings: Vec<(String, Literal)>) -> Result<Option<Option<Literal>>, String> {
+        if !self.enter_match_arm(arm, bindings)? {
+            return Ok(None);
+        }
+        let outcome = self.evaluate_stmt(*arm.body.clone()).map(Some);
+        self.raise_scope().expect("Match execution structure should guarantee valid scope raise");
+        outcome
+    }
+
+    // Opens the arm's scope and checks its guard. The scope is left open only if the arm was accepted.
+    fn enter_match_arm(&mut self, arm: &MatchArm, bindings: Vec<(String, Literal)>) -> Result<bool, String> {
+        self.lower_scope();
+        for (name, lit) in bindings {
+            self.env.define(&name, lit);
+        }
+
+        let accepted = match &arm.guard {
+            Some(guard) => self.evaluate_expr(*guard.clone()).map(is_truthful),
+            None => Ok(true),
+        };
+        if accepted != Ok(true) {
+            self.raise_scope().expect("Match execution structure should guarantee valid scope raise");
+        }
+        accepted
+    }
+
+    fn make_cursor(&mut self, value: Literal) -> Result<Cursor, String> {
+        match value {
+            List(items) => Ok(Cursor::List(items, 0)),
+            Map(entries) => Ok(Cursor::Values(entries.borrow().iter().map(|(k, _)| k.clone()).collect(), 0)),
+            StringData(s) => Ok(Cursor::Values(s.chars().map(|c| StringData(c.to_string())).collect(), 0)),
+            GenLit(_) => Ok(Cursor::Protocol(value)),
+            InstLit(ref inst) => {
+                // Iterables hand out an iterator from iter(); an iterator can also be looped over directly.
+                let iterator = match inst.get_class().find_method("iter") {
+                    Ok(_) => {
+                        let Some(result) = self.call_special(inst, "iter", vec![]) else {
+                            panic!("iter() disappeared while being called"); // should be impossible
+                        };
+                        result?
+                    },
+                    Err(_) => value.clone(),
+                };
+                match iterator {
+                    GenLit(_) => Ok(Cursor::Protocol(iterator)),
+                    InstLit(ref it) if it.get_class().find_method("next").is_ok() => Ok(Cursor::Protocol(iterator)),
+                    other => Err(format!("{} is not an iterator; iterators need a next() method and a done property.", other)),
+                }
+            },
+            other => Err(format!("Can't iterate over {}.", other)),
+        }
+    }
+
+    // Returns the next item for a for-in loop, or None once the iterable is exhausted.
+    fn advance_cursor(&mut self, cursor: &mut Cursor) -> Result<Option<Literal>, String> {
+        match cursor {
+            Cursor::List(items, next) => {
+                let item = items.borrow().get(*next).cloned();
+                *next += 1;
+                Ok(item)
+            },
+            Cursor::Values(values, next) => {
+                let item = values.get(*next).cloned();
+                *next += 1;
+                Ok(item)
+            },
+            Cursor::Protocol(GenLit(generator)) => {
+                let item = self.resume_generator(generator)?;
+                Ok(if generator.is_done() { None } else { Some(item) })
+            },
+            Cursor::Protocol(InstLit(inst)) => {
+                let item = self.call_special(inst, "next", vec![])
+                    .unwrap_or_else(|| Err(String::from("Iterator lost its next() method.")))?;
+                let done = self.get_property(inst.clone(), "done")?;
+                Ok(if is_truthful(done) { None } else { Some(item) })
+            },
+            Cursor::Protocol(other) => panic!("{} was accepted as an iterator", other), // should be impossible
+        }
+    }
+
+    // Runs a generator until its next yield. Returns nil once the generator is exhausted.
+    fn resume_generator(&mut self, generator: &Generator) -> Result<Literal, String> {
+        let state = generator.get_state();
+        if state.borrow().done { return Ok(Nil); }
+        if state.borrow().running {
+            return Err(format!("Generator {} is already running.", generator.get_name()));
+        }
+
+        state.borrow_mut().running = true;
+        let env = state.borrow().env.clone();
+        self.env.mount_closure(&Some(env));
+        self.push_frame(generator.get_name(), &[]);
+        let result = self.run_generator(state);
+        match &result {
+            Ok(yielded) => self.pop_frame(Some(yielded.as_ref().unwrap_or(&Nil))),
+            Err(_) => self.pop_frame(None),
+        }
+        self.env.unmount_closure().expect("Generator execution structure should guarantee valid unmount");
+
+        let mut state = state.borrow_mut();
+        state.running = false;
+        match result {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => {
+                state.done = true;
+                Ok(Nil)
+            },
+            Err(e) => {
+                state.done = true;
+                state.frames.clear();
+                Err(e)
+            },
+        }
+    }
+
+    // Steps through a generator's frames, returning Some with the value of the next yield
+    // or None if the body runs to completion.
+    fn run_generator(&mut self, state: &Rc<RefCell<GeneratorState>>) -> Result<Option<Literal>, String> {
+        loop {
+            let Some(frame) = state.borrow_mut().frames.pop() else { return Ok(None) };
+            match frame {
+                Frame::Statements(stmts, next, scoped) => {
+                    if next < stmts.len() {
+                        let stmt = stmts[next].clone();
+                        state.borrow_mut().frames.push(Frame::Statements(stmts, next + 1, scoped));
+                        if let Some(value) = self.step_generator(state, stmt)? {
+                            return Ok(Some(value));
+                        }
+                    } else if scoped {
+                        self.raise_scope().expect("Generator execution structure should guarantee valid scope raise");
+                    }
+                },
+                Frame::Loop(cond, body) => {
+                    if is_truthful(self.evaluate_expr(*cond.clone())?) {
+                        let body_frame = Frame::Statements(vec![(*body).clone()], 0, false);
+                        state.borrow_mut().frames.push(Frame::Loop(cond, body));
+                        state.borrow_mut().frames.push(body_frame);
+                    }
+                },
+                Frame::ForIn(name, mut cursor, body) => {
+                    if let Some(item) = self.advance_cursor(&mut cursor)? {
+                        self.lower_scope();
+                        self.env.define(&name, item);
+                        let body_frame = Frame::Statements(vec![(*body).clone()], 0, true);
+                        state.borrow_mut().frames.push(Frame::ForIn(name, cursor, body));
+                        state.borrow_mut().frames.push(body_frame);
+                    }
+                },
             }
         }
     }
 
+    // Statements that can contain a yield push frames rather than running to completion;
+    // anything else is handed to evaluate_stmt whole.
+    fn step_generator(&mut self, state: &Rc<RefCell<GeneratorState>>, s: Statement) -> Result<Option<Literal>, String> {
+        use Statement::*;
+
+        if let Some(line) = s.line() {
+            self.before_statement(line)?;
+        }
+
+        let push = |frame| state.borrow_mut().frames.push(frame);
+        match s {
+            Yield(e, _) => Ok(Some(self.evaluate_expr(*e)?)),
+            Block(v) => {
+                self.lower_scope();
+                push(Frame::Statements(v, 0, true));
+                Ok(None)
+            },
+            If(cond, then_branch, else_option, _) => {
+                let taken = is_truthful(self.evaluate_expr(*cond)?);
+                self.report_branch(Branch::If(taken));
+                if taken {
+                    push(Frame::Statements(vec![*then_branch], 0, false));
+                } else if let Some(else_branch) = else_option {
+                    push(Frame::Statements(vec![*else_branch], 0, false));
+                }
+                Ok(None)
+            },
+            While(cond, body, _) => {
+                push(Frame::Loop(cond, body));
+                Ok(None)
+            },
+            For(init, cond, incr, body, line) => self.step_generator(state, Statement::desugar_for(init, cond, incr, *body, line)),
+            ForIn(name, iterable, body, _) => {
+                let value = self.evaluate_expr(*iterable)?;
+                let cursor = self.make_cursor(value)?;
+                push(Frame::ForIn(name, cursor, body));
+                Ok(None)
+            },
+            Match(subject, arms, default, _) => {
+                let value = self.evaluate_expr(*subject)?;
+                for arm in arms {
+                    for pattern in arm.patterns.iter() {
+                        if let Some(bindings) = self.match_pattern(pattern, &value)? {
+                            if self.enter_match_arm(&arm, bindings)? {
+                                push(Frame::Statements(vec![*arm.body], 0, true));
+                                return Ok(None);
+                            }
+                        }
+                    }
+                }
+                if let Some(stmt) = default {
+                    push(Frame::Statements(vec![*stmt], 0, false));
+                }
+                Ok(None)
+            },
+            Return(_, _) => {
+                // Returning finishes the generator, so unwind every open scope. There is no value
+                // to hand back: the parser rejects 'return <expr>' inside a generator.
+                let frames = std::mem::take(&mut state.borrow_mut().frames);
+                for frame in frames {
+                    if let Frame::Statements(_, _, true) = frame {
+                        self.raise_scope().expect("Generator execution structure should guarantee valid scope raise");
+                    }
+                }
+                Ok(None)
+            },
+            other => {
+                self.execute_stmt(other)?;
+                Ok(None)
+            },
+        }
+    }
+
     pub fn evaluate_expr(&mut self, e: Expression) -> Result<Literal, String> {
+        if matches!(e, LitExp(_) | Grouping(_)) || !self.hook.as_ref().is_some_and(|h| h.traces_expressions()) {
+            return self.evaluate_expr_untraced(e);
+        }
+        let traced = e.clone();
+        let value = self.evaluate_expr_untraced(e)?;
+        if let Some(mut hook) = self.hook.take() {
+            hook.expression(self, &traced, &value);
+            self.hook = Some(hook);
+        }
+        Ok(value)
+    }
+
+    fn evaluate_expr_untraced(&mut self, e: Expression) -> Result<Literal, String> {
         match e {
             LitExp(lit) => Ok(lit),
-            Unary(op, e) => self.evaluate_expr_unary(op, e),
-            Binary{left, op, right} => self.evaluate_expr_binary(left, op, right),
-            Logical{left, op, right} => self.evaluate_expr_logical(left, op, right),
+            Unary(op, e) => self.evaluate_expr_unary(op, *e),
+            Binary{left, op, right} => self.evaluate_expr_binary(*left, op, *right),
+            Logical{left, op, right} => self.evaluate_expr_logical(*left, op, *right),
             Identifier(id) => Ok(self.env.get(&id)?),
             Grouping(boxed_exp) => self.evaluate_expr(*boxed_exp),
-            Assignment(id, boxed_exp) => {
+            Assignment(id, boxed_exp, _) => {
                 let lit = self.evaluate_expr(*boxed_exp)?;
                 Ok(self.env.assign(&id, lit)?)
             },
-            Call(f, args, line) => {
-                let mut callee = self.evaluate_expr(*f)?;
-                if let CallLit(ref mut c) = callee {
-                    if args.len() != c.arity() {
-                        return Err(format!("Expected {} arguments but got {}.", c.arity(), args.len()));
-                    }
-                    let mut evaled_args = Vec::<Literal>::new();
-                    for arg in args.iter() {
-                        evaled_args.push(self.evaluate_expr(*arg.clone())?); // TODO: get rid of clone statement if possible
-                    }
-                    self.call(c, evaled_args)
+            CompoundAssignment(id, op, boxed_exp, _) => {
+                let current = self.env.get(&id)?;
+                let operand = self.evaluate_expr(*boxed_exp)?;
+                let lit = self.apply_binary(current, op, operand)?;
+                Ok(self.env.assign(&id, lit)?)
+            },
+            Ternary(cond, then_branch, else_branch) => {
+                if is_truthful(self.evaluate_expr(*cond)?) {
+                    self.evaluate_expr(*then_branch)
                 } else {
-                    Err(String::from("Can only call functions and classes."))
-                }
-            },
-            Getter(obj, name) => {
-                let src = self.evaluate_expr(*obj);
-                let cpy = src.clone();
-                match src {
-                    Ok(Literal::InstLit(inst)) => {
-                        let out = inst.get(&name)?;
-                        if let CallLit(Callable::Function(_, _, _, Some(ref c), _)) = out {
-                            let Literal::InstLit(this) = cpy.unwrap() else { panic!("Copy of an instance was somehow not an instance") };
-                            if let Callable::Class(_, Some(sc), _) = this.get_class() {
-                                c.borrow_mut().define("super", Literal::CallLit(*sc.clone()));  
-                            }
-                            c.borrow_mut().define("this", Literal::InstLit(this));  
-                        }
-                        Ok(out)
-                    },
-                    Ok(_) => Err(String::from("Only instances have properties.")),
-                    Err(e) => Err(e),
+                    self.evaluate_expr(*else_branch)
                 }
+            },
+            Call(f, args, ..) => self.evaluate_call(*f, args),
+            Getter(obj, name) => self.evaluate_getter(*obj, name),
+            Setter(obj, name, value) => self.evaluate_setter(*obj, name, *value),
+            CompoundSetter(obj, name, op, value) => self.evaluate_compound_setter(*obj, name, op, *value),
+            Index(obj, index) => {
+                let target = self.evaluate_expr(*obj)?;
+                let key = self.evaluate_expr(*index)?;
+                self.get_index(target, key)
             }
-            Setter(obj, name, value) => {
-                match self.evaluate_expr(*obj) {
-                    Ok(Literal::InstLit(ref mut inst)) => {
-                        let resolved_value = self.evaluate_expr(*value);
-                        inst.set(&name, resolved_value?);
-                        Ok(inst.get(&name)?)
-                    },
-                    Ok(_) => Err(String::from("Only instances have fields.")),
-                    Err(e) => Err(e),
-                }
+            IndexSetter(obj, index, value) => {
+                let target = self.evaluate_expr(*obj)?;
+                let key = self.evaluate_expr(*index)?;
+                let resolved_value = self.evaluate_expr(*value)?;
+                self.set_index(target, key, resolved_value)
             }
-            This => {
-                Ok(self.env.get("this")?)
+            CompoundIndexSetter(obj, index, op, value) => {
+                // As with fields, the object and index are evaluated once for both the read and the write.
+                let target = self.evaluate_expr(*obj)?;
+                let key = self.evaluate_expr(*index)?;
+                let current = self.get_index(target.clone(), key.clone())?;
+                let operand = self.evaluate_expr(*value)?;
+                let resolved_value = self.apply_binary(current, op, operand)?;
+                self.set_index(target, key, resolved_value)
             }
-            Super(method) => {
-                let super_class = self.env.get("super")?;
-                if let Literal::CallLit(sc) = super_class {
-                    Ok(Literal::CallLit(sc.find_method(&method)?))
-                } else {
-                    Err(String::from("Call to superclass somehow returned non-method."))
+            ListLiteral(items) => {
+                let mut values = Vec::new();
+                for item in items {
+                    values.push(self.evaluate_expr(item)?);
                 }
+                Ok(Literal::new_list(values))
+            }
+            MapLiteral(entries) => {
+                let map = Literal::new_map(vec![]);
+                for (key, value) in entries {
+                    let key = self.evaluate_expr(*key)?;
+                    let value = self.evaluate_expr(*value)?;
+                    self.set_index(map.clone(), key, value)?;
+                }
+                Ok(map)
+            }
+            Spread(_) => Err(String::from("Spread arguments are only allowed in calls.")),
+            Named(_, _) => Err(String::from("Named arguments are only allowed in calls.")),
+            This(_) => {
+                Ok(self.env.get("this")?)
             }
+            Super(method) => self.evaluate_super(method),
         }
     }
 
-    fn evaluate_expr_unary(&mut self, op: node::UnaryOp, e: Box<Expression>) -> Result<Literal, String> {
-        use node::UnaryOp::*;
+    // Getters declared on the class take priority over fields, which take priority over methods.
+    fn get_property(&mut self, inst: Instance, name: &str) -> Result<Literal, String> {
+        if let Some(mut getter) = inst.get_class().find_getter(name) {
+            bind_this(&getter, Literal::InstLit(inst.clone()), inst.get_class());
+            return self.call(&mut getter, vec![]);
+        }
 
-        let arg = self.evaluate_expr(*e)?;
-        match op {
-            Negative => Ok(Number(-get_number(arg)?)),
-            Not => Ok(Boolean(is_truthful(arg))),
+        let out = inst.get(name)?;
+        if let CallLit(ref c) = out {
+            bind_this(c, Literal::InstLit(inst.clone()), inst.get_class());
         }
+        Ok(out)
     }
 
-    fn evaluate_expr_binary(&mut self, left: Box<Expression>, op: node::BinaryOp, right: Box<Expression>) -> Result<Literal, String> {
-        use node::BinaryOp::*;
-        
-        let left = self.evaluate_expr(*left)?;
-        let right = self.evaluate_expr(*right)?;
-
-        match op {
-            Add => {
-                let error_str = format!("Attempted to add mismatched operands {} and {}.", left, right);
-                match (left, right) {
-                    (Number(m), Number(n)) => Ok(Number(m + n)),
-                    (StringData(s), StringData(t)) => Ok(StringData(format!("{}{}", s, t))),
-                    _ => Err(error_str),
-                }
-            }
-            Subtract => Ok(Number(get_number(left)? - get_number(right)?)),
-            Multiply => Ok(Number(get_number(left)? * get_number(right)?)),
-            Divide => Ok(Number(get_number(left)? / get_number(right)?)),
-            Modulo => Ok(Number(get_number(left)? % get_number(right)?)),
-
-            Less => Ok(Boolean(get_number(left)? < get_number(right)?)),
-            LessEqual => Ok(Boolean(get_number(left)? <= get_number(right)?)),
-            Greater => Ok(Boolean(get_number(left)? > get_number(right)?)),
-            GreaterEqual => Ok(Boolean(get_number(left)? >= get_number(right)?)),
-
-            Equal => Ok(Boolean(left == right)),
-            NotEqual => Ok(Boolean(left != right)),
+    fn set_property(&mut self, mut inst: Instance, name: &str, value: Literal) -> Result<Literal, String> {
+        if let Some(mut setter) = inst.get_class().find_setter(name) {
+            bind_this(&setter, Literal::InstLit(inst.clone()), inst.get_class());
+            self.call(&mut setter, vec![value.clone()])?;
+            return Ok(value);
+        }
+        if inst.get_class().find_getter(name).is_some() {
+            return Err(format!("Cannot assign to read-only property {}.", name));
         }
-    }
 
-    fn evaluate_expr_logical(&mut self, left: Box<Expression>, op: node::LogicOp, right: Box<Expression>) -> Result<Literal, String> {
-        use node::LogicOp::*;
-        
-        let left_lit = self.evaluate_expr(*left)?;
-        let left_truthful = is_truthful(left_lit.clone()); // TODO: clone operation here is needlessly costly
+        inst.set(name, value);
+        inst.get(name)
+    }
 
-        match op {
-            And => {
-                if left_truthful { Ok(self.evaluate_expr(*right)?) }
-                else { Ok(left_lit) }
-            }
-            Or => {
-                if !left_truthful { Ok(self.evaluate_expr(*right)?) }
-                else { Ok(left_lit) }
-            }
+    // Calls a special method such as __add__ on an instance, or returns None if its class doesn't define one.
+    fn call_special(&mut self, inst: &Instance, name: &str, args: Vec<Literal>) -> Option<Result<Literal, String>> {
+        let mut method = inst.get_class().find_method(name).ok()?;
+        if !method.arity().accepts(args.len()) {
+            return Some(Err(format!("Special method {} must take {} arguments but takes {}.", name, args.len(), method.arity())));
         }
+        bind_this(&method, Literal::InstLit(inst.clone()), inst.get_class());
+        Some(self.call(&mut method, args))
     }
 
-    fn call(&mut self, callee: &mut Callable, args: Vec<Literal>) -> Result<Literal, String> {
-        match callee {
-            Callable::Function(name, arg_names, body, ref mut closure, is_init) => {
-                self.env.mount_closure(closure);
-                self.env.lower_scope();
-
-                let mut name_iter = arg_names.iter().peekable();
-                let mut arg_iter = args.iter().peekable();
-                while name_iter.peek() != None && arg_iter.peek() != None {
-                    let var = name_iter.next().unwrap_or_else(|| panic!("Impossible unwrap fail"));
-                    let arg = arg_iter.next().unwrap_or_else(|| panic!("Impossible unwrap fail")).clone();
-                    self.env.define(var, arg);
+    // Formats a value for printing, using __str__ on instances that define it,
+    // including those held in lists and maps.
+    fn stringify(&mut self, value: Literal) -> Result<String, String> {
+        match value {
+            InstLit(ref inst) => {
+                if let Some(result) = self.call_special(inst, "__str__", vec![]) {
+                    return match result? {
+                        StringData(s) => Ok(s),
+                        other => Err(format!("__str__ must return a string, not {}.", other)),
+                    };
                 }
+            },
+            List(ref items) => {
+                let items = items.borrow().clone();
+                let parts = items.into_iter().map(|item| self.stringify(item)).collect::<Result<Vec<String>, String>>()?;
+                return Ok(format!("[{}]", parts.join(", ")));
+            },
+            Map(ref entries) => {
+                let entries = entries.borrow().clone();
+                let parts = entries.into_iter()
+                    .map(|(k, v)| Ok(format!("{}: {}", self.stringify(k)?, self.stringify(v)?)))
+                    .collect::<Result<Vec<String>, String>>()?;
+                return Ok(format!("{{{}}}", parts.join(", ")));
+            },
+            _ => {},
+        }
+        Ok(format!("{}", value))
+    }
 
-                let result = self.evaluate_stmt(Statement::Block(body.clone()));
-                let mut output = match result {
-                    Ok(None) => Ok(Literal::Nil),
-                    Ok(Some(lit)) => Ok(lit),
-                    Err(e) => Err(e),
-                };
-                if *is_init {
-                    output = match output {
-                        Ok(_) => self.env.get("this"),
-                        Err(e) => Err(e),
-                    };
+    fn get_index(&mut self, target: Literal, key: Literal) -> Result<Literal, String> {
+        match target {
+            InstLit(ref inst) => {
+                self.call_special(inst, "__index__", vec![key])
+                    .unwrap_or_else(|| Err(format!("Instance of {} does not define __index__.", inst.get_class().get_name())))
+            },
+            StringData(s) => {
+                let Integer(i) = key else {
+                    return Err(format!("String index must be an Integer, not {}.", key));
                 };
+                usize::try_from(i).ok()
+                    .and_then(|i| s.chars().nth(i))
+                    .map(|c| StringData(c.to_string()))
+                    .ok_or_else(|| format!("String index {} is out of range.", i))
+            },
+            List(items) => {
+                let i = list_index(&items.borrow(), key)?;
+                Ok(items.borrow()[i].clone())
+            },
+            Map(entries) => {
+                entries.borrow().iter()
+                    .find(|(k, _)| literals_equal(k, &key))
+                    .map(|(_, v)| v.clone())
+                    .ok_or_else(|| format!("Undefined key {}.", key))
+            },
+            _ => Err(String::from("Only strings, lists, maps and instances can be indexed.")),
+        }
+    }
 
-                self.env.raise_scope().expect("Call execution structure should guarantee valid scope raise");
-                self.env.unmount_closure().expect("Call execution structure should guarantee valid unmount");
-                
-                output
-                
+    fn set_index(&mut self, target: Literal, key: Literal, value: Literal) -> Result<Literal, String> {
+        match target {
+            InstLit(ref inst) => {
+                self.call_special(inst, "__setindex__", vec![key, value.clone()])
+                    .unwrap_or_else(|| Err(format!("Instance of {} does not define __setindex__.", inst.get_class().get_name())))?;
             },
-            Callable::Class(name, super_class, methods) => {
-                let inst = Instance::new(Callable::Class(name.clone(), super_class.clone(), methods.clone()));
-                if inst.has_initializer() {
-                    let mut init = self.evaluate_expr(Getter(Box::new(LitExp(InstLit(inst.clone()))), String::from("init")));
-                    if let Ok(CallLit(ref mut c)) = init { self.call(c, args)?; }
-                    else { panic!("Initializer failed to resolve to callable.") }
+            List(items) => {
+                let i = list_index(&items.borrow(), key)?;
+                items.borrow_mut()[i] = value.clone();
+            },
+            Map(entries) => {
+                let mut entries = entries.borrow_mut();
+                match entries.iter_mut().find(|(k, _)| literals_equal(k, &key)) {
+                    Some(entry) => entry.1 = value.clone(),
+                    None => entries.push((key, value.clone())),
                 }
-                Ok(Literal::InstLit(inst))
-            }
-            Callable::Clock => {
-                let now = SystemTime::now();
-                let time_ms = now.duration_since(UNIX_EPOCH).expect("Got time before unix epoch").as_millis() as f64;
-                Ok(Literal::Number(time_ms/1000.0))
             },
+            _ => return Err(String::from("Only lists, maps and instances support index assignment.")),
         }
+        Ok(value)
     }
-}
 
-fn is_truthful(l: Literal) -> bool {
-    match l {
-        Boolean(false) => false,
-        Nil => false,
+    fn evaluate_call(&mut self, f: Expression, args: Vec<Expression>) -> Result<Literal, String> {
+        let mut callee = self.evaluate_expr(f)?;
+        if let CallLit(ref mut c) = callee {
+            let (evaled_args, named) = self.evaluate_args(args)?;
+            if !named.is_empty() {
+                check_named_args(c, evaled_args.len(), &named)?;
+            } else if !c.arity().accepts(evaled_args.len()) {
+                return Err(format!("Expected {} arguments but got {}.", c.arity(), evaled_args.len()));
+            }
+            self.call_with_named(c, evaled_args, named)
+        } else {
+            Err(String::from("Can only call functions and classes."))
+        }
+    }
+
+    // Spread arguments contribute each item of their list as a separate argument. Named arguments
+    // are kept apart, in source order, since they can only be placed once the callee is known.
+    fn evaluate_args(&mut self, args: Vec<Expression>) -> Result<(Vec<Literal>, NamedArgs), String> {
+        let mut evaled_args = Vec::<Literal>::new();
+        let mut named = Vec::new();
+        for arg in args {
+            match arg {
+                Named(name, value) => {
+                    let value = self.evaluate_expr(*value)?;
+                    named.push((name, value));
+                },
+                Spread(list) => match self.evaluate_expr(*list)? {
+                    List(items) => evaled_args.extend(items.borrow().iter().cloned()),
+                    other => return Err(format!("Can only spread a list into arguments, not {}.", other)),
+                },
+                arg => evaled_args.push(self.evaluate_expr(arg)?),
+            }
+        }
+        Ok((evaled_args, named))
+    }
+
+    // The larger statement and expression cases live in their own functions so that the frames
+    // of evaluate_stmt() and evaluate_expr(), which recurse for every nested call, stay small.
+
+    fn declare_class(
+        &mut self,
+        name: String,
+        super_name: String,
+        trait_names: Vec<String>,
+        method_defs: Vec<Statement>,
+        static_defs: Vec<Statement>,
+    ) -> Result<Option<Literal>, String> {
+        use Statement::*;
+
+        let (mut methods, mut accessors) = self.build_methods(&name, method_defs);
+        self.mix_in_traits(&name, &trait_names, &mut methods, &mut accessors)?;
+
+        let mut statics = HashMap::new();
+        for stmt in static_defs {
+            match stmt {
+                Fun(fn_name, args, body, signature) => {
+                    let func = Callable::Function(fn_name.clone(), args, body, Some(self.env.spawn_closure()), false, signature);
+                    statics.insert(fn_name, Literal::CallLit(func));
+                },
+                Decl(field, expr, _, _) => {
+                    let value = self.evaluate_expr(*expr)?;
+                    statics.insert(field, value);
+                },
+                Comment(..) => (),
+                _ => panic!("Found invalid statement while processing static members for class {}.", name), // should be impossible
+            }
+        }
+
+        let mut super_class = None;
+        if !super_name.is_empty() {
+            let super_eval = self.env.get(&super_name)?;
+            if let CallLit(sc @ Callable::Class(..)) = super_eval {
+                super_class = Some(Box::new(sc));
+            } else {
+                return Err(String::from("Superclass must be a class."));
+            }
+        }
+
+        self.classes_declared += 1;
+        let class = Callable::Class(name.clone(), super_class, methods, Box::new(accessors), Rc::new(RefCell::new(statics)), self.classes_declared);
+
+        self.env.declare(&name, Literal::CallLit(class), true)?;
+        Ok(None)
+    }
+
+    fn evaluate_match(&mut self, subject: Expression, arms: Vec<MatchArm>, default: Option<Box<Statement>>) -> Result<Option<Literal>, String> {
+        let value = self.evaluate_expr(subject)?;
+        for arm in arms {
+            for pattern in arm.patterns.iter() {
+                if let Some(bindings) = self.match_pattern(pattern, &value)? {
+                    if let Some(outcome) = self.evaluate_match_arm(&arm, bindings)? {
+                        return Ok(outcome);
+                    }
+                }
+            }
+        }
+        match default {
+            Some(stmt) => self.evaluate_stmt(*stmt),
+            None => Ok(None),
+        }
+    }
+
+    fn evaluate_getter(&mut self, obj: Expression, name: String) -> Result<Literal, String> {
+        match self.evaluate_expr(obj) {
+            Ok(Literal::InstLit(inst)) => self.get_property(inst, &name),
+            Ok(Literal::EnumLit(declared)) => {
+                if let Some(unit) = declared.find_unit(&name) {
+                    Ok(VariantLit(unit))
+                } else if let Some(fields) = declared.find_variant(&name) {
+                    Ok(CallLit(Callable::Variant(String::from(declared.get_name()), name, fields.clone())))
+                } else if name == "values" {
+                    Ok(CallLit(Callable::EnumValues(declared)))
+                } else {
+                    Err(format!("Enum {} has no variant {}.", declared.get_name(), name))
+                }
+            },
+            Ok(Literal::VariantLit(value)) => value.get(&name),
+            Ok(Literal::GenLit(generator)) => {
+                match name.as_str() {
+                    "next" => Ok(CallLit(Callable::Resume(generator))),
+                    "done" => Ok(Boolean(generator.is_done())),
+                    _ => Err(format!("Undefined property '{}'.", name)),
+                }
+            },
+            Ok(Literal::CallLit(class @ Callable::Class(..))) => {
+                // Static methods see the class they were accessed through as 'this'.
+                let out = class.find_static(&name)?;
+                if let CallLit(ref c) = out {
+                    bind_this(c, Literal::CallLit(class.clone()), &class);
+                }
+                Ok(out)
+            },
+            Ok(_) => Err(String::from("Only instances and classes have properties.")),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn evaluate_setter(&mut self, obj: Expression, name: String, value: Expression) -> Result<Literal, String> {
+        match self.evaluate_expr(obj) {
+            Ok(Literal::InstLit(inst)) => {
+                let resolved_value = self.evaluate_expr(value)?;
+                self.set_property(inst, &name, resolved_value)
+            },
+            Ok(Literal::CallLit(class @ Callable::Class(..))) => {
+                let resolved_value = self.evaluate_expr(value)?;
+                class.set_static(&name, resolved_value)?;
+                class.find_static(&name)
+            },
+            Ok(_) => Err(String::from("Only instances and classes have fields.")),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn evaluate_compound_setter(&mut self, obj: Expression, name: String, op: node::BinaryOp, value: Expression) -> Result<Literal, String> {
+        // The object expression is evaluated once and reused for both the read and the write.
+        match self.evaluate_expr(obj) {
+            Ok(Literal::InstLit(inst)) => {
+                let current = self.get_property(inst.clone(), &name)?;
+                let operand = self.evaluate_expr(value)?;
+                let resolved_value = self.apply_binary(current, op, operand)?;
+                self.set_property(inst, &name, resolved_value)
+            },
+            Ok(Literal::CallLit(class @ Callable::Class(..))) => {
+                let current = class.find_static(&name)?;
+                let operand = self.evaluate_expr(value)?;
+                let resolved_value = self.apply_binary(current, op, operand)?;
+                class.set_static(&name, resolved_value)?;
+                class.find_static(&name)
+            },
+            Ok(_) => Err(String::from("Only instances and classes have fields.")),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn evaluate_super(&mut self, method: String) -> Result<Literal, String> {
+        let super_class = self.env.get("super")?;
+        if let Literal::CallLit(sc) = super_class {
+            match sc.find_method(&method) {
+                Ok(m) => Ok(Literal::CallLit(m)),
+                Err(e) => {
+                    // Static methods reached through super keep the current 'this'.
+                    let out = sc.find_static(&method).map_err(|_| e)?;
+                    if let CallLit(Callable::Function(_, _, _, Some(ref c), _, _)) = out {
+                        c.borrow_mut().define("this", self.env.get("this")?);
+                    }
+                    Ok(out)
+                },
+            }
+        } else {
+            Err(String::from("Call to superclass somehow returned non-method."))
+        }
+    }
+
+    fn evaluate_expr_unary(&mut self, op: node::UnaryOp, e: Expression) -> Result<Literal, String> {
+        use node::UnaryOp::*;
+
+        let arg = self.evaluate_expr(e)?;
+        match op {
+            Negative => {
+                if let InstLit(ref inst) = arg {
+                    if let Some(result) = self.call_special(inst, "__neg__", vec![]) {
+                        return result;
+                    }
+                }
+                if let Integer(n) = arg {
+                    n.checked_neg().map(Integer).ok_or_else(|| format!("Integer overflow while negating {}.", n))
+                } else {
+                    Ok(Number(-get_number(arg)?))
+                }
+            },
+            Not => Ok(Boolean(!is_truthful(arg))),
+        }
+    }
+
+    fn evaluate_expr_binary(&mut self, left: Expression, op: node::BinaryOp, right: Expression) -> Result<Literal, String> {
+        let left = self.evaluate_expr(left)?;
+        let right = self.evaluate_expr(right)?;
+        self.apply_binary(left, op, right)
+    }
+
+    fn apply_binary(&mut self, left: Literal, op: node::BinaryOp, right: Literal) -> Result<Literal, String> {
+        use node::BinaryOp::*;
+
+        // Instances on the left-hand side may overload the operator; != negates __eq__.
+        if let InstLit(ref inst) = left {
+            if let Some(result) = self.call_special(inst, op.special_method(), vec![right.clone()]) {
+                return match op {
+                    NotEqual => Ok(Boolean(!is_truthful(result?))),
+                    _ => result,
+                };
+            }
+        }
+
+        match op {
+            Add => {
+                let error_str = format!("Attempted to add mismatched operands {} and {}.", left, right);
+                match (left, right) {
+                    (StringData(s), StringData(t)) => Ok(StringData(format!("{}{}", s, t))),
+                    (l, r) if is_numeric(&l) && is_numeric(&r) => arithmetic(l, r, i64::checked_add, |m, n| m + n),
+                    _ => Err(error_str),
+                }
+            }
+            Subtract => arithmetic(left, right, i64::checked_sub, |m, n| m - n),
+            Multiply => arithmetic(left, right, i64::checked_mul, |m, n| m * n),
+            Divide => Ok(Number(get_number(left)? / get_number(right)?)),
+            IntDivide => {
+                check_integer_divisor(&left, &right)?;
+                arithmetic(left, right, i64::checked_div, |m, n| (m / n).trunc())
+            },
+            Modulo => {
+                check_integer_divisor(&left, &right)?;
+                arithmetic(left, right, i64::checked_rem, |m, n| m % n)
+            },
+
+            BitAnd => Ok(Integer(get_integer(left)? & get_integer(right)?)),
+            BitOr => Ok(Integer(get_integer(left)? | get_integer(right)?)),
+            BitXor => Ok(Integer(get_integer(left)? ^ get_integer(right)?)),
+            ShiftLeft => {
+                let (m, n) = (get_integer(left)?, get_shift(right)?);
+                let shifted = m << n;
+                if shifted >> n == m { Ok(Integer(shifted)) }
+                else { Err(format!("Integer overflow while shifting {} left by {}.", m, n)) }
+            },
+            ShiftRight => Ok(Integer(get_integer(left)? >> get_shift(right)?)),
+
+            Less => comparison(left, right, i64::lt, f64::lt),
+            LessEqual => comparison(left, right, i64::le, f64::le),
+            Greater => comparison(left, right, i64::gt, f64::gt),
+            GreaterEqual => comparison(left, right, i64::ge, f64::ge),
+
+            Equal => Ok(Boolean(literals_equal(&left, &right))),
+            NotEqual => Ok(Boolean(!literals_equal(&left, &right))),
+        }
+    }
+
+    fn evaluate_expr_logical(&mut self, left: Expression, op: node::LogicOp, right: Expression) -> Result<Literal, String> {
+        use node::LogicOp::*;
+        
+        let left_lit = self.evaluate_expr(left)?;
+        let left_truthful = is_truthful(left_lit.clone()); // TODO: clone operation here is needlessly costly
+
+        let evaluate_right = match op {
+            And => left_truthful,
+            Or => !left_truthful,
+        };
+        self.report_branch(Branch::Logical(evaluate_right));
+        if evaluate_right { Ok(self.evaluate_expr(right)?) }
+        else { Ok(left_lit) }
+    }
+
+    fn call(&mut self, callee: &mut Callable, args: Vec<Literal>) -> Result<Literal, String> {
+        self.call_with_named(callee, args, Vec::new())
+    }
+
+    // Named arguments must already have been checked against the callee's parameters.
+    fn call_with_named(&mut self, callee: &mut Callable, args: Vec<Literal>, named: NamedArgs) -> Result<Literal, String> {
+        match callee {
+            Callable::Function(name, arg_names, body, ref mut closure, is_init, signature) => {
+                self.env.mount_closure(closure);
+                self.lower_scope();
+
+                // The frame starts once the parameters are bound, so that it can be entered with them.
+                let bound = self.bind_params(arg_names, signature, args, named);
+                let output = match bound {
+                    Ok(()) => {
+                        self.push_frame(name, arg_names);
+                        let result = self.evaluate_stmt(Statement::Block(body.clone()));
+                        let mut output = match result {
+                            Ok(None) => Ok(Literal::Nil),
+                            Ok(Some(lit)) => Ok(lit),
+                            Err(e) => Err(e),
+                        };
+                        if *is_init {
+                            output = match output {
+                                Ok(_) => self.env.get("this"),
+                                Err(e) => Err(e),
+                            };
+                        };
+                        self.pop_frame(output.as_ref().ok());
+                        output
+                    },
+                    Err(e) => Err(e),
+                };
+
+                self.raise_scope().expect("Call execution structure should guarantee valid scope raise");
+                self.env.unmount_closure().expect("Call execution structure should guarantee valid unmount");
+                
+                output
+                
+            },
+            class @ Callable::Class(..) => {
+                let inst = Instance::new(class.clone());
+                if inst.has_initializer() {
+                    let mut init = self.evaluate_expr(Getter(Box::new(LitExp(InstLit(inst.clone()))), String::from("init")));
+                    if let Ok(CallLit(ref mut c)) = init { self.call_with_named(c, args, named)?; }
+                    else { panic!("Initializer failed to resolve to callable.") }
+                }
+                Ok(Literal::InstLit(inst))
+            }
+            Callable::Trait(name, _, _) => Err(format!("Cannot instantiate trait {}.", name)),
+            Callable::Generator(name, arg_names, body, closure, signature) => {
+                // Each call runs in its own copy of the closure so that suspended generators don't share scopes.
+                let env = match closure {
+                    Some(c) => Rc::new(RefCell::new(c.borrow().clone())),
+                    None => self.env.spawn_closure(),
+                };
+                self.env.mount_closure(&Some(env.clone()));
+                self.lower_scope();
+                let bound = self.bind_params(arg_names, signature, args, named);
+                self.env.unmount_closure().expect("Call execution structure should guarantee valid unmount");
+                bound?;
+                Ok(GenLit(Generator::new(name, body.clone(), env)))
+            },
+            Callable::Resume(generator) => self.resume_generator(generator),
+            Callable::Variant(enum_name, variant, fields) => {
+                Ok(VariantLit(EnumValue::new(enum_name, variant, fields.clone(), args)))
+            },
+            Callable::EnumValues(declared) => {
+                Ok(Literal::new_list(declared.units().into_iter().map(VariantLit).collect()))
+            },
+            Callable::Clock => {
+                let now = SystemTime::now();
+                let time_ms = now.duration_since(UNIX_EPOCH).expect("Got time before unix epoch").as_millis() as f64;
+                Ok(Literal::Number(time_ms/1000.0))
+            },
+            Callable::Range => range(args),
+            assertion @ (Callable::Assert | Callable::AssertEqual) => self.check_assertion(assertion, args),
+            native => self.call_reflection(native, args),
+        }
+    }
+
+    // Binds a call's arguments to the callee's parameters in the current scope. Parameters left
+    // without an argument take their default, evaluated after the parameters before them are
+    // bound; a rest parameter collects whatever arguments are left over into a list.
+    fn bind_params(&mut self, names: &[String], signature: &Signature, args: Vec<Literal>, mut named: NamedArgs) -> Result<(), String> {
+        let mut args = args.into_iter();
+        for (i, name) in names.iter().enumerate() {
+            let value = if signature.rest && i == names.len()-1 {
+                Literal::new_list(args.by_ref().collect())
+            } else if let Some(arg) = args.next() {
+                arg
+            } else if let Some(at) = named.iter().position(|(n, _)| n == name) {
+                named.swap_remove(at).1
+            } else if let Some(default) = signature.defaults.get(name) {
+                self.evaluate_expr(*default.clone())?
+            } else {
+                Nil
+            };
+            self.env.define(name, value);
+        }
+        Ok(())
+    }
+
+    // Fails with "Assertion failed", followed by the message if one was given.
+    fn check_assertion(&mut self, assertion: &Callable, args: Vec<Literal>) -> Result<Literal, String> {
+        let mut args = args.into_iter();
+        let mut next_arg = || args.next().unwrap_or(Nil);
+
+        let (holds, mismatch) = match assertion {
+            Callable::AssertEqual => {
+                let (left, right) = (next_arg(), next_arg());
+                let equal = is_truthful(self.apply_binary(left.clone(), node::BinaryOp::Equal, right.clone())?);
+                (equal, Some((left, right)))
+            },
+            _ => (is_truthful(next_arg()), None),
+        };
+        if holds { return Ok(Nil); }
+
+        let message = match next_arg() {
+            Nil => None,
+            m => Some(self.stringify(m)?),
+        };
+        match (message, mismatch) {
+            (Some(m), _) => Err(format!("Assertion failed: {}", m)),
+            (None, Some((left, right))) => Err(format!("Assertion failed: {} is not equal to {}.", self.stringify(left)?, self.stringify(right)?)),
+            (None, None) => Err(String::from("Assertion failed.")),
+        }
+    }
+
+    fn call_reflection(&mut self, native: &Callable, args: Vec<Literal>) -> Result<Literal, String> {
+        let name = native.get_name();
+        let mut args = args.into_iter();
+        let mut next_arg = || args.next().unwrap_or(Nil);
+
+        match native {
+            Callable::TypeOf => Ok(StringData(String::from(type_name(&next_arg())))),
+            Callable::ClassOf => {
+                let inst = expect_instance(name, next_arg())?;
+                Ok(CallLit(inst.get_class().clone()))
+            },
+            Callable::SuperclassOf => {
+                let class = expect_class(name, next_arg())?;
+                Ok(class.get_superclass().map_or(Nil, |sc| CallLit(sc.clone())))
+            },
+            Callable::Fields => {
+                let inst = expect_instance(name, next_arg())?;
+                Ok(Literal::new_list(inst.field_names().into_iter().map(StringData).collect()))
+            },
+            Callable::Methods => {
+                let owner = next_arg();
+                let names = match owner {
+                    CallLit(ref c @ (Callable::Class(..) | Callable::Trait(..))) => c.method_names(),
+                    other => return Err(format!("{} expects a class or trait, not {}.", name, other)),
+                };
+                Ok(Literal::new_list(names.into_iter().map(StringData).collect()))
+            },
+            Callable::HasField => {
+                let inst = expect_instance(name, next_arg())?;
+                let field = expect_string(name, next_arg())?;
+                Ok(Boolean(inst.has_field(&field)))
+            },
+            Callable::GetField => {
+                let inst = expect_instance(name, next_arg())?;
+                let field = expect_string(name, next_arg())?;
+                self.get_property(inst, &field)
+            },
+            Callable::SetField => {
+                let inst = expect_instance(name, next_arg())?;
+                let field = expect_string(name, next_arg())?;
+                self.set_property(inst, &field, next_arg())
+            },
+            Callable::Arity => {
+                match next_arg() {
+                    CallLit(c) => Ok(Integer(c.arity().min as i64)),
+                    other => Err(format!("{} expects a function or class, not {}.", name, other)),
+                }
+            },
+            Callable::InstanceOf => {
+                let value = next_arg();
+                let class = expect_class(name, next_arg())?;
+                match value {
+                    InstLit(inst) => Ok(Boolean(inst.get_class().inherits_from(&class))),
+                    _ => Ok(Boolean(false)),
+                }
+            },
+            _ => panic!("Callable {} is not a reflection native.", name), // should be impossible
+        }
+    }
+}
+
+fn type_name(value: &Literal) -> &'static str {
+    match value {
+        Number(_) => "number",
+        Integer(_) => "integer",
+        StringData(_) => "string",
+        Boolean(_) => "boolean",
+        Nil => "nil",
+        CallLit(Callable::Class(..)) => "class",
+        CallLit(Callable::Trait(..)) => "trait",
+        CallLit(_) => "function",
+        InstLit(_) => "instance",
+        List(_) => "list",
+        Map(_) => "map",
+        GenLit(_) => "generator",
+        EnumLit(_) => "enum",
+        VariantLit(_) => "variant",
+    }
+}
+
+fn expect_instance(native: &str, value: Literal) -> Result<Instance, String> {
+    if let InstLit(inst) = value { Ok(inst) }
+    else { Err(format!("{} expects an instance, not {}.", native, value)) }
+}
+
+fn expect_class(native: &str, value: Literal) -> Result<Callable, String> {
+    if let CallLit(class @ Callable::Class(..)) = value { Ok(class) }
+    else { Err(format!("{} expects a class, not {}.", native, value)) }
+}
+
+fn expect_string(native: &str, value: Literal) -> Result<String, String> {
+    if let StringData(s) = value { Ok(s) }
+    else { Err(format!("{} expects a string, not {}.", native, value)) }
+}
+
+// Makes 'this' (and 'super', where the class has a superclass) visible inside a method's closure.
+fn bind_this(method: &Callable, this: Literal, class: &Callable) {
+    if let Callable::Function(_, _, _, Some(c), _, _) = method {
+        if let Callable::Class(_, Some(sc), ..) = class {
+            c.borrow_mut().define("super", Literal::CallLit(*sc.clone()));
+        }
+        c.borrow_mut().define("this", this);
+    }
+}
+
+// Named arguments fill the parameters left over once the positional ones have been placed. Each
+// must name one of those parameters, and every parameter without a default must end up filled.
+fn check_named_args(callee: &Callable, positional: usize, named: &[(String, Literal)]) -> Result<(), String> {
+    let Some((params, signature)) = callee.parameters() else {
+        return Err(format!("{} does not take named arguments.", callee.get_name()));
+    };
+    let fixed = &params[..params.len() - signature.rest as usize];
+    if positional > fixed.len() && !signature.rest {
+        return Err(format!("Expected {} arguments but got {}.", callee.arity(), positional + named.len()));
+    }
+
+    for (i, (name, _)) in named.iter().enumerate() {
+        match fixed.iter().position(|p| p == name) {
+            None => return Err(format!("{} has no parameter named {}.", callee.get_name(), name)),
+            Some(at) if at < positional || named[..i].iter().any(|(n, _)| n == name) => {
+                return Err(format!("Argument {} was passed more than once.", name));
+            },
+            Some(_) => (),
+        }
+    }
+    for param in fixed.iter().skip(positional) {
+        if !signature.defaults.contains_key(param) && !named.iter().any(|(n, _)| n == param) {
+            return Err(format!("Missing argument for parameter {}.", param));
+        }
+    }
+    Ok(())
+}
+
+// Builds the list of numbers from start up to (but not including) end. Integer bounds and step
+// produce Integers; any float among them produces Numbers.
+fn range(args: Vec<Literal>) -> Result<Literal, String> {
+    let mut args = args.into_iter();
+    let (Some(start), Some(end)) = (args.next(), args.next()) else {
+        return Err(String::from("range expects a start, an end and an optional step."));
+    };
+    let step = args.next().unwrap_or(Integer(1));
+
+    if let (Integer(start), Integer(end), Integer(step)) = (&start, &end, &step) {
+        if *step == 0 { return Err(String::from("range step cannot be zero.")); }
+        let mut values = Vec::new();
+        let mut n = *start;
+        while (*step > 0 && n < *end) || (*step < 0 && n > *end) {
+            values.push(Integer(n));
+            n = match n.checked_add(*step) { Some(m) => m, None => break };
+        }
+        return Ok(Literal::new_list(values));
+    }
+
+    let (start, end, step) = (get_number(start)?, get_number(end)?, get_number(step)?);
+    if step == 0.0 || step.is_nan() { return Err(String::from("range step cannot be zero.")); }
+    let mut values = Vec::new();
+    let mut i = 0.0;
+    loop {
+        let n = start + i * step;
+        if (step > 0.0 && n >= end) || (step < 0.0 && n <= end) { break; }
+        values.push(Number(n));
+        i += 1.0;
+    }
+    Ok(Literal::new_list(values))
+}
+
+fn list_index(items: &[Literal], key: Literal) -> Result<usize, String> {
+    let Integer(i) = key else {
+        return Err(format!("List index must be an Integer, not {}.", key));
+    };
+    usize::try_from(i).ok()
+        .filter(|i| *i < items.len())
+        .ok_or_else(|| format!("List index {} is out of range.", i))
+}
+
+fn is_truthful(l: Literal) -> bool {
+    match l {
+        Boolean(false) => false,
+        Nil => false,
         _ => true,
     }
 }
 
+fn is_numeric(l: &Literal) -> bool {
+    matches!(l, Number(_) | Integer(_))
+}
+
+// Integers are promoted to floats when mixed with them.
 fn get_number(l: Literal) -> Result<f64, String> {
-    if let Number(n) = l { Ok(n) }
-    else { Err(format!("Attempted to use literal {} in place of a Number.", l)) }
+    match l {
+        Number(n) => Ok(n),
+        Integer(n) => Ok(n as f64),
+        _ => Err(format!("Attempted to use literal {} in place of a Number.", l)),
+    }
+}
+
+fn get_integer(l: Literal) -> Result<i64, String> {
+    if let Integer(n) = l { Ok(n) }
+    else { Err(format!("Attempted to use literal {} in place of an Integer.", l)) }
+}
+
+fn get_shift(l: Literal) -> Result<i64, String> {
+    let n = get_integer(l)?;
+    if (0..64).contains(&n) { Ok(n) }
+    else { Err(format!("Shift amount {} is out of range.", n)) }
+}
+
+// Integer operands are combined exactly, with overflow reported as an error.
+// Any other pair of numeric operands is promoted to floats.
+fn arithmetic(
+    left: Literal,
+    right: Literal,
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Literal, String> {
+    match (left, right) {
+        (Integer(m), Integer(n)) => {
+            int_op(m, n).map(Integer).ok_or_else(|| format!("Integer overflow in arithmetic on {} and {}.", m, n))
+        },
+        (l, r) => Ok(Number(float_op(get_number(l)?, get_number(r)?))),
+    }
+}
+
+fn check_integer_divisor(left: &Literal, right: &Literal) -> Result<(), String> {
+    if let (Integer(_), Integer(0)) = (left, right) {
+        Err(String::from("Attempted to divide an Integer by zero."))
+    } else {
+        Ok(())
+    }
+}
+
+fn comparison(
+    left: Literal,
+    right: Literal,
+    int_cmp: fn(&i64, &i64) -> bool,
+    float_cmp: fn(&f64, &f64) -> bool,
+) -> Result<Literal, String> {
+    match (left, right) {
+        (Integer(m), Integer(n)) => Ok(Boolean(int_cmp(&m, &n))),
+        (l, r) => Ok(Boolean(float_cmp(&get_number(l)?, &get_number(r)?))),
+    }
+}
+
+// Integers and floats compare by numeric value, so 2 == 2.0.
+fn literals_equal(left: &Literal, right: &Literal) -> bool {
+    match (left, right) {
+        (Integer(m), Number(n)) | (Number(n), Integer(m)) => (*m as f64) == *n,
+        _ => left == right,
+    }
 }
 
 
@@ -345,7 +1591,7 @@ mod tests {
 
     fn string_to_expr(s: &str) -> Expression {
         let statements = string_to_program(s);
-        if let Statement::Expr(e) = statements[0].clone() { *e }
+        if let Statement::Expr(e, _) = statements[0].clone() { *e }
         else { panic!("Attempted to convert a non-statement to an expression."); }
     }
 
@@ -389,6 +1635,8 @@ mod tests {
             #[test]
             fn test_expression_unary_not() {
                 test_expression_generic("!!false;", Boolean(false));
+                test_expression_generic("!true;", Boolean(false));
+                test_expression_generic("!nil;", Boolean(true));
             }
 
             #[test]
@@ -402,7 +1650,7 @@ mod tests {
 
             #[test]
             fn test_expression_modulo() {
-                test_expression_generic("5 % 3;", Number(2.0));
+                test_expression_generic("5 % 3;", Integer(2));
             }
 
             #[test]
@@ -486,7 +1734,7 @@ mod tests {
             #[test]
             fn test_expression_math_ops() {
                 let test_str = "3 + -4 * -5 - 6;";
-                test_expression_generic(test_str, Number(17.0));
+                test_expression_generic(test_str, Integer(17));
             }
 
             #[test]
@@ -498,7 +1746,61 @@ mod tests {
             #[test]
             fn test_expression_grouping() {
                 let test_str = "(3 + -4) * (-5 - 6);";
-                test_expression_generic(test_str, Number(11.0));
+                test_expression_generic(test_str, Integer(11));
+            }
+        }
+
+        mod integer_expressions {
+            use super::*;
+
+            #[test]
+            fn test_expression_integer_precision() {
+                test_expression_generic("9007199254740993 + 2;", Integer(9007199254740995));
+            }
+
+            #[test]
+            fn test_expression_mixed_promotion() {
+                test_expression_generic("3 + 0.5;", Number(3.5));
+                test_expression_generic("7 % 2.5;", Number(2.0));
+                test_expression_generic("2 == 2.0;", Boolean(true));
+            }
+
+            #[test]
+            fn test_expression_int_divide() {
+                test_expression_generic("7 ~/ 2;", Integer(3));
+                test_expression_generic("-7 ~/ 2;", Integer(-3));
+                test_expression_generic("7.5 ~/ 2;", Number(3.0));
+            }
+
+            #[test]
+            fn test_expression_bitwise() {
+                test_expression_generic("12 & 10;", Integer(8));
+                test_expression_generic("12 | 10;", Integer(14));
+                test_expression_generic("12 ^ 10;", Integer(6));
+                test_expression_generic("1 << 4;", Integer(16));
+                test_expression_generic("-16 >> 2;", Integer(-4));
+                test_expression_generic("1 | 2 == 3;", Boolean(true));
+            }
+
+            #[test]
+            fn test_expression_integer_errors() {
+                let mut intp = LoxInterpreter::new();
+                let cases = [
+                    ("9223372036854775807 + 1;", "Integer overflow"),
+                    ("5 % 0;", "divide an Integer by zero"),
+                    ("5 ~/ 0;", "divide an Integer by zero"),
+                    ("1 << 64;", "out of range"),
+                    ("1 << 63;", "Integer overflow"),
+                    ("1.5 & 1;", "in place of an Integer"),
+                ];
+                for (source, message) in cases {
+                    let result = intp.evaluate_expr(string_to_expr(source));
+                    if let Err(e) = result {
+                        assert!(e.contains(message), "Unexpected error for {}: {}", source, e);
+                    } else {
+                        panic!("Expected {} to produce an error.", source);
+                    }
+                }
             }
         }
 
@@ -520,7 +1822,7 @@ mod tests {
             let result = intp.env.get("i").expect("Failed to retrieve an uninitialized variable");
             assert_eq!(expected, result, "Uninitialized variable should be Nil; instead recieved {}", result);
 
-            let expected = Number(2.0);
+            let expected = Integer(2);
             let result = intp.env.get("j").expect("Failed to retrieve an initialized variable");
             assert_eq!(expected, result, "Variable should equal 2; instead recieved {}", result);
         }
@@ -534,7 +1836,7 @@ mod tests {
             ));
             intp.interpret(program).expect("Error while interpreting program");
 
-            let expected = Number(2.0);
+            let expected = Integer(2);
             let result = intp.env.get("i").expect("Failed to retrieve an initialized variable");
             assert_eq!(expected, result, "Variable should equal 2; instead recieved {}", result);
         }
@@ -552,15 +1854,15 @@ mod tests {
             ));
             intp.interpret(program).expect("Error while interpreting program");
             
-            let expected = Number(1.0);
+            let expected = Integer(1);
             let result = intp.env.get("i").expect("Failed to retrieve an initialized variable");
             assert_eq!(expected, result, "Variable should equal 1; instead recieved {}", result);
 
-            let expected = Number(2.0);
+            let expected = Integer(2);
             let result = intp.env.get("j").expect("Failed to retrieve an initialized variable");
             assert_eq!(expected, result, "Variable should equal 2; instead recieved {}", result);
 
-            let expected = Number(3.0);
+            let expected = Integer(3);
             let result = intp.env.get("k").expect("Failed to retrieve an initialized variable");
             assert_eq!(expected, result, "Variable should equal 3; instead recieved {}", result);
         }
@@ -607,34 +1909,112 @@ mod tests {
         }
     }
 
-    mod control_flow {
+    mod compound_assignment {
         use super::*;
 
-        mod conditionals {
-            use super::*;
-    
-            #[test]
-            fn test_if_else() {
-                let mut intp = LoxInterpreter::new();
-                let program = string_to_program(concat!(
-                    "if (2 <= 3) print \"Math is working\";\n",
-                    "var three = 3;\n",
-                    "if (three == 3) {\n",
-                    "   print 333;\n",
-                    "} else {\n",
-                    "   print 4444;\n",
-                    "}",
-                ));
-                let output = intp.interpret(program).expect("Error while interpreting program");
-            
-                let expected = concat!(
-                    "Math is working\n",
-                    "333",
-                );
-    
-                assert_eq!(expected, output, "Expected left output; recieved right");
-            }
-    
+        #[test]
+        fn test_compound_assignment() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "var i = 10;\n",
+                "i += 5; print i;\n",
+                "i -= 3; print i;\n",
+                "i *= 2; print i;\n",
+                "i %= 7; print i;\n",
+                "i /= 4; print i;\n",
+                "var s = \"crab\";\n",
+                "s += \"lox\"; print s;\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "15\n12\n24\n3\n0.75\ncrablox";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_compound_setter_evaluates_target_once() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Counter {\n",
+                "    lookup() {\n",
+                "        this.lookups += 1;\n",
+                "        return this;\n",
+                "    }\n",
+                "}\n",
+                "var counter = Counter();\n",
+                "counter.count = 0;\n",
+                "counter.lookups = 0;\n",
+                "counter.lookup().count += 1;\n",
+                "counter.lookup().count += 41;\n",
+                "print counter.count;\n",
+                "print counter.lookups;\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "42\n2";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_compound_assignment_undeclared() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program("missing += 1;");
+            let result = intp.interpret(program);
+            if let Err(e) = result {
+                assert!(e.contains("Undefined variable"));
+            } else {
+                panic!("Unexpectedly recieved valid output.");
+            }
+        }
+    }
+
+    mod control_flow {
+        use super::*;
+
+        mod conditionals {
+            use super::*;
+    
+            #[test]
+            fn test_if_else() {
+                let mut intp = LoxInterpreter::new();
+                let program = string_to_program(concat!(
+                    "if (2 <= 3) print \"Math is working\";\n",
+                    "var three = 3;\n",
+                    "if (three == 3) {\n",
+                    "   print 333;\n",
+                    "} else {\n",
+                    "   print 4444;\n",
+                    "}",
+                ));
+                let output = intp.interpret(program).expect("Error while interpreting program");
+            
+                let expected = concat!(
+                    "Math is working\n",
+                    "333",
+                );
+    
+                assert_eq!(expected, output, "Expected left output; recieved right");
+            }
+    
+            #[test]
+            fn test_ternary() {
+                let mut intp = LoxInterpreter::new();
+                let program = string_to_program(concat!(
+                    "var i = 3;\n",
+                    "print i > 2 ? \"big\" : \"small\";\n",
+                    "print i > 5 ? \"big\" : i > 1 ? \"medium\" : \"small\";\n",
+                    "var j = nil ? (i = 100) : i;\n",
+                    "print i;\n",
+                ));
+                let output = intp.interpret(program).expect("Error while interpreting program");
+
+                let expected = "big\nmedium\n3";
+
+                assert_eq!(expected, output, "Expected left output; recieved right");
+            }
+
             #[test]
             fn test_logical_short_circuiting() {
                 let mut intp = LoxInterpreter::new();
@@ -698,6 +2078,129 @@ mod tests {
 
     }
     
+    mod matching {
+        use super::*;
+
+        #[test]
+        fn test_match_literals() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "fun describe(v) {\n",
+                "    match (v) {\n",
+                "        case 1, 2 => return \"one or two\";\n",
+                "        case 3.0 => return \"three\";\n",
+                "        case \"x\" => return \"the letter x\";\n",
+                "        case nil, false => return \"falsey\";\n",
+                "        default => return \"something else\";\n",
+                "    }\n",
+                "}\n",
+                "print describe(2);\n",
+                "print describe(3);\n",
+                "print describe(\"x\");\n",
+                "print describe(false);\n",
+                "print describe(4);\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "one or two\nthree\nthe letter x\nfalsey\nsomething else";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_match_bindings_and_guards() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "var n = \"outer\";\n",
+                "for (var i = 0; i < 3; i += 1) {\n",
+                "    match (i * 10) {\n",
+                "        case n if n > 15 => print \"big \" + \"number\";\n",
+                "        case n if n > 5 => print n;\n",
+                "        case _ => print \"small\";\n",
+                "    }\n",
+                "}\n",
+                "print n;\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "small\n10\nbig number\nouter";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_match_class_patterns() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Shape {}\n",
+                "class Point < Shape {\n",
+                "    init(x, y) { this.x = x; this.y = y; }\n",
+                "}\n",
+                "class Circle < Shape {\n",
+                "    init(r) { this.r = r; }\n",
+                "}\n",
+                "class Other {}\n",
+                "fun describe(v) {\n",
+                "    match (v) {\n",
+                "        case Point(x, y) => print \"point \" + x + \",\" + y;\n",
+                "        case Shape() => print \"some shape\";\n",
+                "        default => print \"not a shape\";\n",
+                "    }\n",
+                "}\n",
+                "describe(Point(\"1\", \"2\"));\n",
+                "describe(Circle(3));\n",
+                "describe(Other());\n",
+                "describe(4);\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "point 1,2\nsome shape\nnot a shape\nnot a shape";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_shadowing_class_is_different() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Shape {}\n",
+                "var outer = Shape();\n",
+                "fun isShape(v) { return instanceOf(v, Shape); }\n",
+                "{\n",
+                "    class Shape {}\n",
+                "    print instanceOf(outer, Shape);\n",
+                "    print instanceOf(Shape(), Shape);\n",
+                "    match (outer) {\n",
+                "        case Shape() => print \"inner shape\";\n",
+                "        default => print \"not the inner shape\";\n",
+                "    }\n",
+                "    print isShape(Shape());\n",
+                "}\n",
+                "print isShape(outer);\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "false\ntrue\nnot the inner shape\nfalse\ntrue";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_match_non_class_pattern() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "var notClass = 3;\n",
+                "match (4) { case notClass() => print \"unreachable\"; }\n",
+            ));
+            let result = intp.interpret(program);
+            if let Err(e) = result {
+                assert!(e.contains("is not a class"));
+            } else {
+                panic!("Unexpectedly recieved valid output.");
+            }
+        }
+    }
+
     mod functions {
         use super::*;
 
@@ -1000,6 +2503,998 @@ mod tests {
             assert_eq!(expected, output, "Running method from inheritance chain provided unexpected output");
         }
 
+
+        #[test]
+        fn test_static_members() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Math {\n",
+                "    class pi = 3.5;\n",
+                "    class square(n) {\n",
+                "        return n * n;\n",
+                "    }\n",
+                "    class circleArea(r) {\n",
+                "        return this.pi * this.square(r);\n",
+                "    }\n",
+                "}\n",
+                "print Math.square(3);\n",
+                "print Math.pi;\n",
+                "print Math.circleArea(2);\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "9\n3.5\n14";
+
+            assert_eq!(expected, output, "Static members provided unexpected output");
+        }
+
+        #[test]
+        fn test_static_fields_shared() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Counter {\n",
+                "    class count = 0;\n",
+                "    class bump() {\n",
+                "        this.count += 1;\n",
+                "        return this.count;\n",
+                "    }\n",
+                "}\n",
+                "Counter.bump();\n",
+                "Counter.bump();\n",
+                "print Counter.count;\n",
+                "Counter.count = 10;\n",
+                "print Counter.bump();\n",
+                "var alias = Counter;\n",
+                "alias.count += 5;\n",
+                "print Counter.count;\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "2\n11\n16";
+
+            assert_eq!(expected, output, "Static fields provided unexpected output");
+        }
+
+        #[test]
+        fn test_inherited_static_members() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Shape {\n",
+                "    class sides = \"no\";\n",
+                "    class describe() {\n",
+                "        return this.name() + \" has \" + this.sides + \" sides\";\n",
+                "    }\n",
+                "    class name() { return \"shape\"; }\n",
+                "}\n",
+                "class Square < Shape {\n",
+                "    class sides = \"four\";\n",
+                "    class name() { return \"square\"; }\n",
+                "    class base() { return super.name(); }\n",
+                "}\n",
+                "print Square.describe();\n",
+                "print Shape.describe();\n",
+                "print Square.base();\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "square has four sides\nshape has no sides\nshape";
+
+            assert_eq!(expected, output, "Inherited static members provided unexpected output");
+        }
+
+        #[test]
+        fn test_static_not_on_instances() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Math {\n",
+                "    class square(n) { return n * n; }\n",
+                "    twice(n) { return n + n; }\n",
+                "}\n",
+                "Math().square(3);\n",
+            ));
+            let result = intp.interpret(program);
+            if let Err(e) = result {
+                assert!(e.contains("Undefined property 'square'"));
+            } else {
+                panic!("Unexpectedly recieved valid output.");
+            }
+
+            let program = string_to_program("Math.twice(3);");
+            let result = intp.interpret(program);
+            if let Err(e) = result {
+                assert!(e.contains("Undefined static property 'twice'"));
+            } else {
+                panic!("Unexpectedly recieved valid output.");
+            }
+        }
+
+        #[test]
+        fn test_property_accessors() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Rect {\n",
+                "    init(w, h) { this.w = w; this.h = h; }\n",
+                "    area { return this.w * this.h; }\n",
+                "    width { return this.w; }\n",
+                "    set width(w) {\n",
+                "        print \"resizing\";\n",
+                "        this.w = w;\n",
+                "    }\n",
+                "}\n",
+                "var r = Rect(2, 3);\n",
+                "print r.area;\n",
+                "print r.width = 5;\n",
+                "print r.area;\n",
+                "r.width += 1;\n",
+                "print r.width;\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "6\nresizing\n5\n15\nresizing\n6";
+
+            assert_eq!(expected, output, "Property accessors provided unexpected output");
+        }
+
+        #[test]
+        fn test_inherited_accessors() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Named {\n",
+                "    label { return \"<\" + this.name + \">\"; }\n",
+                "}\n",
+                "class Crab < Named {\n",
+                "    init() { this.name = \"crab\"; }\n",
+                "}\n",
+                "print Crab().label;\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "<crab>";
+
+            assert_eq!(expected, output, "Inherited getter provided unexpected output");
+        }
+
+        #[test]
+        fn test_read_only_property() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Circle {\n",
+                "    diameter { return 2; }\n",
+                "}\n",
+                "Circle().diameter = 4;\n",
+            ));
+            let result = intp.interpret(program);
+            if let Err(e) = result {
+                assert!(e.contains("read-only property diameter"));
+            } else {
+                panic!("Unexpectedly recieved valid output.");
+            }
+        }
+
+        #[test]
+        fn test_trait_methods() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "trait Greets {\n",
+                "    greet() { print \"Hello from \" + this.name; }\n",
+                "    shout { return this.name + \"!\"; }\n",
+                "}\n",
+                "trait Waves {\n",
+                "    wave() { print this.name + \" waves\"; }\n",
+                "}\n",
+                "class Crab with Greets, Waves {\n",
+                "    init() { this.name = \"crab\"; }\n",
+                "}\n",
+                "class Lobster with Greets {\n",
+                "    init() { this.name = \"lobster\"; }\n",
+                "}\n",
+                "var crab = Crab();\n",
+                "crab.greet();\n",
+                "crab.wave();\n",
+                "print crab.shout;\n",
+                "Lobster().greet();\n",
+                "print Greets;\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "Hello from crab\ncrab waves\ncrab!\nHello from lobster\n<trait Greets>";
+
+            assert_eq!(expected, output, "Trait methods provided unexpected output");
+        }
+
+        #[test]
+        fn test_trait_precedence_and_super() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Animal {\n",
+                "    speak() { print \"...\"; }\n",
+                "    move() { print \"walks\"; }\n",
+                "}\n",
+                "trait Loud {\n",
+                "    speak() {\n",
+                "        super.speak();\n",
+                "        print \"LOUD NOISES\";\n",
+                "    }\n",
+                "    move() { print \"stomps\"; }\n",
+                "}\n",
+                "class Dog < Animal with Loud {\n",
+                "    move() { print \"runs\"; }\n",
+                "}\n",
+                "Dog().speak();\n",
+                "Dog().move();\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "...\nLOUD NOISES\nruns";
+
+            assert_eq!(expected, output, "Trait precedence provided unexpected output");
+        }
+
+        #[test]
+        fn test_trait_conflicts() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "trait A { name() { return \"A\"; } }\n",
+                "trait B { name() { return \"B\"; } }\n",
+                "class Resolved with A, B {\n",
+                "    name() { return \"mine\"; }\n",
+                "}\n",
+                "print Resolved().name();\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+            assert_eq!("mine", output, "Class override failed to resolve trait conflict");
+
+            let program = string_to_program("class Conflicted with A, B {}");
+            let result = intp.interpret(program);
+            if let Err(e) = result {
+                assert!(e.contains("conflicting member name from traits A and B"));
+            } else {
+                panic!("Unexpectedly recieved valid output.");
+            }
+
+            let program = string_to_program("A();");
+            let result = intp.interpret(program);
+            if let Err(e) = result {
+                assert!(e.contains("Cannot instantiate trait A"));
+            } else {
+                panic!("Unexpectedly recieved valid output.");
+            }
+
+            let program = string_to_program("class NotATrait {}\nclass Broken with NotATrait {}");
+            let result = intp.interpret(program);
+            if let Err(e) = result {
+                assert!(e.contains("NotATrait is not a trait"));
+            } else {
+                panic!("Unexpectedly recieved valid output.");
+            }
+        }
+    }
+
+    mod operator_overloading {
+        use super::*;
+
+        #[test]
+        fn test_binary_operators() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Money {\n",
+                "    init(cents) { this.cents = cents; }\n",
+                "    __add__(other) { return this.cents + other.cents; }\n",
+                "    __mul__(k) { this.cents *= k; return this; }\n",
+                "    __neg__() { return -this.cents; }\n",
+                "    __eq__(other) { return this.cents == other.cents; }\n",
+                "    __lt__(other) { return this.cents < other.cents; }\n",
+                "}\n",
+                "var a = Money(150);\n",
+                "var b = Money(75);\n",
+                "print a + b;\n",
+                "print (b * 3).cents;\n",
+                "print -a;\n",
+                "print a == b;\n",
+                "print a != b;\n",
+                "print a < b;\n",
+                "a *= 2;\n",
+                "print a.cents;\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "225\n225\n-150\nfalse\ntrue\ntrue\n300";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_str_and_index() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Pair {\n",
+                "    init(first, second) { this.first = first; this.second = second; }\n",
+                "    __str__() { return \"(\" + this.first + \", \" + this.second + \")\"; }\n",
+                "    __index__(i) { if (i == 0) return this.first; return this.second; }\n",
+                "    __setindex__(i, value) { if (i == 0) this.first = value; else this.second = value; }\n",
+                "}\n",
+                "var p = Pair(\"a\", \"b\");\n",
+                "print p;\n",
+                "print p[1];\n",
+                "p[0] = \"z\";\n",
+                "p[1] += \"c\";\n",
+                "print p;\n",
+                "print \"crab\"[2];\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "(a, b)\nb\n(z, bc)\na";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_str_in_collections() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Point {\n",
+                "    init(x, y) { this.x = x; this.y = y; }\n",
+                "    __str__() { return \"<\" + this.x + \" \" + this.y + \">\"; }\n",
+                "}\n",
+                "print [Point(\"1\", \"2\"), [Point(\"3\", \"4\")]];\n",
+                "print {\"origin\": Point(\"0\", \"0\")};\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "[<1 2>, [<3 4>]]\n{origin: <0 0>}";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_overloading_errors() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Plain {}\n",
+                "class BadStr { __str__() { return 1; } }\n",
+                "class BadAdd { __add__() { return 1; } }\n",
+            ));
+            intp.interpret(program).expect("Error while interpreting program");
+
+            let cases = [
+                ("Plain() + 1;", "Attempted to add mismatched operands"),
+                ("Plain()[0];", "Plain does not define __index__"),
+                ("Plain()[0] = 1;", "Plain does not define __setindex__"),
+                ("print BadStr();", "__str__ must return a string"),
+                ("BadAdd() + 1;", "Special method __add__ must take 1 arguments"),
+                ("\"crab\"[4];", "String index 4 is out of range"),
+                ("\"crab\"[1.0];", "String index must be an Integer"),
+            ];
+            for (source, message) in cases {
+                let result = intp.interpret(string_to_program(source));
+                if let Err(e) = result {
+                    assert!(e.contains(message), "Unexpected error for {}: {}", source, e);
+                } else {
+                    panic!("Unexpectedly recieved valid output for {}.", source);
+                }
+            }
+        }
+    }
+    mod reflection {
+        use super::*;
+
+        #[test]
+        fn test_type_of() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Point {}\n",
+                "trait Shape {}\n",
+                "fun f() {}\n",
+                "print typeOf(1);\n",
+                "print typeOf(1.5);\n",
+                "print typeOf(\"s\");\n",
+                "print typeOf(true);\n",
+                "print typeOf(nil);\n",
+                "print typeOf(f);\n",
+                "print typeOf(clock);\n",
+                "print typeOf(Point);\n",
+                "print typeOf(Shape);\n",
+                "print typeOf(Point());\n",
+                "print typeOf(fields(Point()));\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "integer\nnumber\nstring\nboolean\nnil\nfunction\nfunction\nclass\ntrait\ninstance\nlist";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_class_introspection() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Shape { area() { return 0; } describe() {} }\n",
+                "class Square < Shape {\n",
+                "    init(side) { this.side = side; }\n",
+                "    area() { return this.side * this.side; }\n",
+                "}\n",
+                "var sq = Square(3);\n",
+                "sq.label = \"box\";\n",
+                "print classOf(sq);\n",
+                "print superclassOf(Square);\n",
+                "print superclassOf(Shape);\n",
+                "print fields(sq);\n",
+                "print fields(sq)[1];\n",
+                "print methods(Square);\n",
+                "print arity(Square);\n",
+                "print arity(sq.area);\n",
+                "print instanceOf(sq, Shape);\n",
+                "print instanceOf(Shape(), Square);\n",
+                "print instanceOf(3, Shape);\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = concat!(
+                "<class Square>\n<class Shape>\nNil\n[label, side]\nside\n",
+                "[area, describe, init]\n1\n0\ntrue\nfalse\nfalse",
+            );
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_fields_by_name() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Box {\n",
+                "    init() { this.width = 2; }\n",
+                "    area { return this.width * this.width; }\n",
+                "}\n",
+                "var b = Box();\n",
+                "print hasField(b, \"width\");\n",
+                "print hasField(b, \"height\");\n",
+                "setField(b, \"width\", 5);\n",
+                "print getField(b, \"width\");\n",
+                "print getField(b, \"area\");\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "true\nfalse\n5\n25";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_reflection_errors() {
+            let mut intp = LoxInterpreter::new();
+            let cases = [
+                ("classOf(1);", "classOf expects an instance, not 1"),
+                ("superclassOf(\"a\");", "superclassOf expects a class"),
+                ("hasField(clock, \"x\");", "hasField expects an instance"),
+                ("class A {} getField(A(), 1);", "getField expects a string"),
+                ("class B {} getField(B(), \"missing\");", "Undefined property 'missing'"),
+                ("arity(1);", "arity expects a function or class"),
+                ("fields(nil)[0];", "fields expects an instance"),
+                ("class C {} fields(C())[0];", "List index 0 is out of range"),
+            ];
+            for (source, message) in cases {
+                let result = intp.interpret(string_to_program(source));
+                if let Err(e) = result {
+                    assert!(e.contains(message), "Unexpected error for {}: {}", source, e);
+                } else {
+                    panic!("Unexpectedly recieved valid output for {}.", source);
+                }
+            }
+        }
+    }
+    mod generators {
+        use super::*;
+
+        #[test]
+        fn test_generator_yields_lazily() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "fun* count(limit) {\n",
+                "    print \"start\";\n",
+                "    for (var i = 0; i < limit; i += 1) {\n",
+                "        yield i;\n",
+                "    }\n",
+                "    print \"end\";\n",
+                "}\n",
+                "var g = count(2);\n",
+                "print g;\n",
+                "print g.done;\n",
+                "print g.next();\n",
+                "print g.next();\n",
+                "print g.done;\n",
+                "print g.next();\n",
+                "print g.done;\n",
+                "print g.next();\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "<generator count>\nfalse\nstart\n0\n1\nfalse\nend\nNil\ntrue\nNil";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_generators_are_independent() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "fun* naturals() {\n",
+                "    var n = 0;\n",
+                "    while (true) { n += 1; yield n; }\n",
+                "}\n",
+                "var a = naturals();\n",
+                "var b = naturals();\n",
+                "a.next(); a.next();\n",
+                "print a.next();\n",
+                "print b.next();\n",
+                "var next = b.next;\n",
+                "print next();\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "3\n1\n2";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_generator_control_flow() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "fun* classify(values) {\n",
+                "    var i = 0;\n",
+                "    while (true) {\n",
+                "        var v = values[i];\n",
+                "        match (v) {\n",
+                "            case 0 => yield \"zero\";\n",
+                "            case \"stop\" => return;\n",
+                "            case n if n < 0 => { yield \"negative\"; yield n; }\n",
+                "            default => if (v > 100) yield \"big\"; else yield v;\n",
+                "        }\n",
+                "        i += 1;\n",
+                "    }\n",
+                "}\n",
+                "fun* source() { yield 0; yield -4; yield 7; yield 500; yield \"stop\"; yield 9; }\n",
+                "class Feed {\n",
+                "    init(src) { this.src = src; }\n",
+                "    __index__(i) { return this.src.next(); }\n",
+                "}\n",
+                "var g = classify(Feed(source()));\n",
+                "var v = g.next();\n",
+                "while (!g.done) { print v; v = g.next(); }\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "zero\nnegative\n-4\n7\nbig";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_generator_errors() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "fun* broken() { yield 1; yield missing; yield 3; }\n",
+                "var g = broken();\n",
+                "g.next();\n",
+            ));
+            intp.interpret(program).expect("Error while interpreting program");
+
+            let result = intp.interpret(string_to_program("g.next();"));
+            if let Err(e) = result {
+                assert!(e.contains("Undefined variable 'missing'"));
+            } else {
+                panic!("Unexpectedly recieved valid output.");
+            }
+
+            let output = intp.interpret(string_to_program("print g.done; print g.next();"))
+                .expect("Error while interpreting program");
+            assert_eq!("true\nNil", output, "Failed generator should be finished");
+
+            let result = intp.interpret(string_to_program("g.value;"));
+            if let Err(e) = result {
+                assert!(e.contains("Undefined property 'value'"));
+            } else {
+                panic!("Unexpectedly recieved valid output.");
+            }
+        }
+    }
+    mod iteration {
+        use super::*;
+
+        #[test]
+        fn test_collections() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "var primes = [2, 3, 5,];\n",
+                "primes[1] += 4;\n",
+                "print primes;\n",
+                "print [];\n",
+                "var ages = {\"crab\": 3, \"lox\": 1 + 1};\n",
+                "ages[\"clam\"] = 9;\n",
+                "ages[\"crab\"] += 1;\n",
+                "print ages;\n",
+                "print ages[\"lox\"];\n",
+                "print {};\n",
+                "print typeOf(ages);\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "[2, 7, 5]\n[]\n{crab: 4, lox: 2, clam: 9}\n2\n{}\nmap";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_for_in_builtins() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "for (var x in [1, 2]) print x;\n",
+                "for (var k in {\"a\": 1, \"b\": 2}) print k;\n",
+                "for (var c in \"hi\") print c;\n",
+                "for (var i in range(10, 0, -4)) print i;\n",
+                "for (var f in range(0, 1, 0.5)) print f;\n",
+                "for (var e in []) print e;\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "1\n2\na\nb\nh\ni\n10\n6\n2\n0\n0.5";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_for_in_scopes_each_iteration() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "var getters = [nil, nil, nil];\n",
+                "var i = 0;\n",
+                "for (var x in [\"a\", \"b\", \"c\"]) {\n",
+                "    fun get() { return x; }\n",
+                "    getters[i] = get;\n",
+                "    i += 1;\n",
+                "}\n",
+                "print getters[0]() + getters[1]() + getters[2]();\n",
+                "fun first(items) {\n",
+                "    for (var item in items) return item;\n",
+                "}\n",
+                "print first([7, 8]);\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "abc\n7";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_for_in_protocol() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "class Countdown {\n",
+                "    init(n) { this.n = n; this.done = false; }\n",
+                "    next() {\n",
+                "        if (this.n == 0) { this.done = true; return nil; }\n",
+                "        this.n -= 1;\n",
+                "        return this.n + 1;\n",
+                "    }\n",
+                "}\n",
+                "class Rocket {\n",
+                "    iter() { return Countdown(3); }\n",
+                "}\n",
+                "fun* evens(limit) {\n",
+                "    for (var i in range(0, limit, 1)) {\n",
+                "        if (i % 2 == 0) yield i;\n",
+                "    }\n",
+                "}\n",
+                "for (var t in Rocket()) print t;\n",
+                "for (var t in Countdown(1)) print t;\n",
+                "for (var e in evens(5)) print e;\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "3\n2\n1\n1\n0\n2\n4";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_iteration_errors() {
+            let mut intp = LoxInterpreter::new();
+            intp.interpret(string_to_program("class Plain {}\nclass Broken { iter() { return 1; } }"))
+                .expect("Error while interpreting program");
+
+            let cases = [
+                ("for (var x in 5) print x;", "Can't iterate over 5"),
+                ("for (var x in Plain()) print x;", "is not an iterator"),
+                ("for (var x in Broken()) print x;", "1 is not an iterator"),
+                ("range(0, 5, 0);", "range step cannot be zero"),
+                ("[1][3];", "List index 3 is out of range"),
+                ("({\"a\": 1})[\"b\"];", "Undefined key b"),
+                ("5[0] = 1;", "Only lists, maps and instances support index assignment"),
+            ];
+            for (source, message) in cases {
+                let result = intp.interpret(string_to_program(source));
+                if let Err(e) = result {
+                    assert!(e.contains(message), "Unexpected error for {}: {}", source, e);
+                } else {
+                    panic!("Unexpectedly recieved valid output for {}.", source);
+                }
+            }
+        }
+    }
+    mod constants {
+        use super::*;
+
+        #[test]
+        fn test_constants() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "const limit = 3;\n",
+                "print limit;\n",
+                "{\n",
+                "    var limit = 10;\n",
+                "    limit += 1;\n",
+                "    print limit;\n",
+                "}\n",
+                "fun read() { return limit; }\n",
+                "print read();\n",
+                "{ var clock = \"shadowed\"; print clock; }\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "3\n11\n3\nshadowed";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_replacing_natives() {
+            // Only clock is kept from declarations; the other natives give way to the program's own.
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "var fields = 1;\n",
+                "fields = fields + 1;\n",
+                "fun range() { return \"mine\"; }\n",
+                "class typeOf {}\n",
+                "fun assert(condition) { return \"only in tests\"; }\n",
+                "print fields;\n",
+                "print range();\n",
+                "print typeOf;\n",
+                "print assert(false);\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "2\nmine\n<class typeOf>\nonly in tests";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_constant_errors() {
+            let mut intp = LoxInterpreter::new();
+            intp.interpret(string_to_program("const limit = 3;\nfun bump() { limit = 4; }"))
+                .expect("Error while interpreting program");
+
+            let cases = [
+                ("limit = 4;", "Cannot assign to constant limit"),
+                ("limit += 1;", "Cannot assign to constant limit"),
+                ("bump();", "Cannot assign to constant limit"),
+                ("var limit = 5;", "Cannot redefine constant limit"),
+                ("const limit = 5;", "Cannot redefine constant limit"),
+                ("clock = nil;", "Cannot assign to native function clock"),
+                ("var clock = 1;", "Cannot redefine native function clock"),
+                ("fun clock() {}", "Cannot redefine native function clock"),
+                ("range = nil;", "Cannot assign to native function range"),
+            ];
+            for (source, message) in cases {
+                let result = intp.interpret(string_to_program(source));
+                if let Err(e) = result {
+                    assert!(e.contains(message), "Unexpected error for {}: {}", source, e);
+                } else {
+                    panic!("Unexpectedly recieved valid output for {}.", source);
+                }
+            }
+        }
+    }
+    mod enums {
+        use super::*;
+
+        #[test]
+        fn test_enum_values() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "enum Color { Red, Green, Blue }\n",
+                "enum Shape { Circle(r), Rect(w, h), Empty, }\n",
+                "print Color;\n",
+                "print Color.Red;\n",
+                "print Color.Red == Color.Red;\n",
+                "print Color.Red == Color.Blue;\n",
+                "var c = Shape.Circle(2);\n",
+                "print c;\n",
+                "print c.r;\n",
+                "print c == c;\n",
+                "print c == Shape.Circle(2);\n",
+                "print Shape.Rect(1, 2.5);\n",
+                "print Color.values();\n",
+                "print Shape.values();\n",
+                "for (var color in Color.values()) print color;\n",
+                "print typeOf(Color) + \" \" + typeOf(Color.Green);\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = concat!(
+                "<enum Color>\nColor.Red\ntrue\nfalse\nShape.Circle(2)\n2\ntrue\nfalse\n",
+                "Shape.Rect(1, 2.5)\n[Color.Red, Color.Green, Color.Blue]\n[Shape.Empty]\n",
+                "Color.Red\nColor.Green\nColor.Blue\nenum variant",
+            );
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_enum_patterns() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "enum Shape { Circle(r), Rect(w, h), Empty }\n",
+                "for (var s in [Shape.Circle(1), Shape.Rect(2, 3), Shape.Empty, Shape.Rect(4, 4)]) {\n",
+                "    match (s) {\n",
+                "        case Shape.Circle(radius) => print 3 * radius * radius;\n",
+                "        case Shape.Rect(w, h) if w == h => print \"square \" + typeOf(w);\n",
+                "        case Shape.Rect(w, h) => print w * h;\n",
+                "        case Shape.Empty => print \"empty\";\n",
+                "    }\n",
+                "}\n",
+                "match (Shape.Rect(1, 1)) {\n",
+                "    case Shape.Rect => print \"any rect\";\n",
+                "}\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = "3\n6\nempty\nsquare integer\nany rect";
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_enum_errors() {
+            let mut intp = LoxInterpreter::new();
+            intp.interpret(string_to_program("enum Shape { Circle(r), Empty }\nclass Plain {}"))
+                .expect("Error while interpreting program");
+
+            let cases = [
+                ("Shape.Square;", "Enum Shape has no variant Square"),
+                ("Shape.Circle();", "Expected 1 arguments but got 0"),
+                ("Shape.Circle(1).d;", "Undefined property 'd'"),
+                ("match (1) { case Shape.Square => print 1; }", "Enum Shape has no variant Square"),
+                ("match (1) { case Shape.Circle(a, b) => print 1; }", "binds 2 values but the variant has 1"),
+                ("match (1) { case Plain.Circle => print 1; }", "Plain in variant pattern is not an enum"),
+            ];
+            for (source, message) in cases {
+                let result = intp.interpret(string_to_program(source));
+                if let Err(e) = result {
+                    assert!(e.contains(message), "Unexpected error for {}: {}", source, e);
+                } else {
+                    panic!("Unexpectedly recieved valid output for {}.", source);
+                }
+            }
+        }
+    }
+    mod variadics {
+        use super::*;
+
+        #[test]
+        fn test_defaults_and_rest() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "fun greet(name, greeting = \"Hello\", mark = greeting + \"!\") {\n",
+                "    print greeting + \", \" + name + mark;\n",
+                "}\n",
+                "greet(\"crab\");\n",
+                "greet(\"crab\", \"Hi\");\n",
+                "greet(\"crab\", \"Hi\", \"?\");\n",
+                "fun count(first, ...others) { print first; return others; }\n",
+                "print count(1);\n",
+                "print count(1, 2, 3);\n",
+                "var args = [4, 5];\n",
+                "count(...args, 6);\n",
+                "count(0, ...[]);\n",
+                "fun* upTo(n, step = 1) { for (var i in range(0, n, step)) yield i; }\n",
+                "for (var i in upTo(5, 2)) print i;\n",
+                "for (var i in range(0, 2)) print i;\n",
+                "class Point { init(x = 0, y = x) { this.x = x; this.y = y; } }\n",
+                "var p = Point(2);\n",
+                "print p.x + p.y;\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = concat!(
+                "Hello, crabHello!\nHi, crabHi!\nHi, crab?\n",
+                "1\n[]\n1\n[2, 3]\n4\n0\n",
+                "0\n2\n4\n0\n1\n4",
+            );
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_variadic_errors() {
+            let mut intp = LoxInterpreter::new();
+            intp.interpret(string_to_program("fun pair(a, b = 1) {}\nfun some(a, ...b) {}"))
+                .expect("Error while interpreting program");
+
+            let cases = [
+                ("pair();", "Expected 1 to 2 arguments but got 0"),
+                ("pair(1, 2, 3);", "Expected 1 to 2 arguments but got 3"),
+                ("pair(...[1, 2, 3]);", "Expected 1 to 2 arguments but got 3"),
+                ("some();", "Expected at least 1 arguments but got 0"),
+                ("some(...1);", "Can only spread a list into arguments, not 1"),
+                ("range(1);", "Expected 2 to 3 arguments but got 1"),
+            ];
+            for (source, message) in cases {
+                let result = intp.interpret(string_to_program(source));
+                if let Err(e) = result {
+                    assert!(e.contains(message), "Unexpected error for {}: {}", source, e);
+                } else {
+                    panic!("Unexpectedly recieved valid output for {}.", source);
+                }
+            }
+        }
+    }
+    mod named_arguments {
+        use super::*;
+
+        #[test]
+        fn test_named_arguments() {
+            let mut intp = LoxInterpreter::new();
+            let program = string_to_program(concat!(
+                "fun draw(x, y, color = \"black\", width = 1) {\n",
+                "    print [color, x, y, width];\n",
+                "}\n",
+                "draw(1, 2);\n",
+                "draw(y: 2, x: 1);\n",
+                "draw(1, 2, width: 3);\n",
+                "draw(1, color: \"red\", y: 2);\n",
+                "fun* repeat(value, times = 2) { for (var i in range(0, times)) yield value; }\n",
+                "for (var v in repeat(times: 3, value: \"hey\")) print v;\n",
+                "class Point { init(x, y = 0) { this.x = x; this.y = y; } }\n",
+                "var p = Point(y: 5, x: 4);\n",
+                "print p.x * p.y;\n",
+            ));
+            let output = intp.interpret(program).expect("Error while interpreting program");
+
+            let expected = concat!(
+                "[black, 1, 2, 1]\n[black, 1, 2, 1]\n[black, 1, 2, 3]\n[red, 1, 2, 1]\n",
+                "hey\nhey\nhey\n20",
+            );
+
+            assert_eq!(expected, output, "Expected left output; recieved right");
+        }
+
+        #[test]
+        fn test_named_argument_errors() {
+            let mut intp = LoxInterpreter::new();
+            intp.interpret(string_to_program("fun pair(a, b = 1) {}\nfun some(a, ...rest) {}\nclass Empty {}"))
+                .expect("Error while interpreting program");
+
+            let cases = [
+                ("pair(c: 1);", "pair has no parameter named c"),
+                ("pair(1, a: 2);", "Argument a was passed more than once"),
+                ("pair(b: 2);", "Missing argument for parameter a"),
+                ("pair(1, 2, 3, b: 4);", "Expected 1 to 2 arguments but got 4"),
+                ("some(1, 2, a: 3);", "Argument a was passed more than once"),
+                ("some(rest: 1);", "some has no parameter named rest"),
+                ("Empty(a: 1);", "Empty does not take named arguments"),
+                ("range(start: 0, end: 1);", "range does not take named arguments"),
+            ];
+            for (source, message) in cases {
+                let result = intp.interpret(string_to_program(source));
+                if let Err(e) = result {
+                    assert!(e.contains(message), "Unexpected error for {}: {}", source, e);
+                } else {
+                    panic!("Unexpectedly recieved valid output for {}.", source);
+                }
+            }
+        }
     }
 
 }
\ No newline at end of file
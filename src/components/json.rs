@@ -0,0 +1,380 @@
+use core::fmt;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+
+// Just enough JSON for the tools that talk to editors over stdio. Objects keep their keys in
+// insertion order, so output is predictable.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut reader = JsonReader{ chars: text.chars().collect(), current: 0 };
+        let value = reader.value()?;
+        reader.skip_whitespace();
+        if reader.current < reader.chars.len() {
+            return Err(format!("Unexpected '{}' after JSON value.", reader.chars[reader.current]));
+        }
+        Ok(value)
+    }
+
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(k, v)| (String::from(k), v)).collect())
+    }
+
+    // Looks up a key of an object; anything else has no keys.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        if let Json::String(s) = self { Some(s) } else { None }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        if let Json::Bool(b) = self { Some(*b) } else { None }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        if let Json::Array(v) = self { Some(v) } else { None }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Json {
+        Json::String(String::from(s))
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Json {
+        Json::String(s)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Json {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json {
+        Json::Bool(b)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(v: Vec<Json>) -> Json {
+        Json::Array(v)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Json::Number(n) if n.is_finite() => write!(f, "{}", n),
+            Json::Number(_) => write!(f, "null"), // JSON has no infinities or NaN
+            Json::String(s) => write_string(f, s),
+            Json::Array(v) => {
+                write!(f, "[")?;
+                for (i, item) in v.iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// Reads one message framed the way the language server and debug adapter protocols do it: headers
+// ending in a blank line, of which only Content-Length matters, then that many bytes of JSON.
+// Returns None at the end of the input.
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            if length.is_some() { break; }
+            continue; // Tolerate blank lines between messages
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let mut body = vec![0; length.unwrap_or(0)];
+    input.read_exact(&mut body)?;
+    let text = String::from_utf8(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Json::parse(&text).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message<W: Write>(output: &mut W, message: &Json) -> io::Result<()> {
+    let text = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", text.len(), text)?;
+    output.flush()
+}
+
+struct JsonReader {
+    chars: Vec<char>,
+    current: usize,
+}
+
+impl JsonReader {
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.chars.get(self.current) {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => {
+                self.current += 1;
+                let mut items = Vec::new();
+                if self.consume(']') { return Ok(Json::Array(items)); }
+                loop {
+                    items.push(self.value()?);
+                    if self.consume(']') { return Ok(Json::Array(items)); }
+                    self.expect(',')?;
+                }
+            },
+            Some('{') => {
+                self.current += 1;
+                let mut fields = Vec::new();
+                if self.consume('}') { return Ok(Json::Object(fields)); }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    if self.consume('}') { return Ok(Json::Object(fields)); }
+                    self.expect(',')?;
+                }
+            },
+            Some(c) if *c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(format!("Unexpected '{}' in JSON.", c)),
+            None => Err(String::from("Unexpected end of JSON.")),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.current).is_some_and(|c| c.is_whitespace()) {
+            self.current += 1;
+        }
+    }
+
+    // Skips whitespace, then consumes c if it comes next.
+    fn consume(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.chars.get(self.current) == Some(&c) {
+            self.current += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.consume(c) { Ok(()) }
+        else { Err(format!("Expected '{}' in JSON.", c)) }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        let end = self.current + word.chars().count();
+        if end <= self.chars.len() && self.chars[self.current..end].iter().copied().eq(word.chars()) {
+            self.current = end;
+            Ok(value)
+        } else {
+            Err(format!("Expected '{}' in JSON.", word))
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let begin = self.current;
+        while self.chars.get(self.current).is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+            self.current += 1;
+        }
+        let text: String = self.chars[begin..self.current].iter().collect();
+        text.parse().map(Json::Number).map_err(|_| format!("Invalid number {} in JSON.", text))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.chars.get(self.current) != Some(&'"') {
+            return Err(String::from("Expected a string in JSON."));
+        }
+        self.current += 1;
+        let mut out = String::new();
+        loop {
+            let Some(c) = self.chars.get(self.current).copied() else {
+                return Err(String::from("Unterminated string in JSON."));
+            };
+            self.current += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let Some(escaped) = self.chars.get(self.current).copied() else {
+                        return Err(String::from("Unterminated string in JSON."));
+                    };
+                    self.current += 1;
+                    match escaped {
+                        'n' => out.push('\n'),
+                        'r' => out.push('\r'),
+                        't' => out.push('\t'),
+                        'b' => out.push('\u{8}'),
+                        'f' => out.push('\u{c}'),
+                        'u' => out.push(self.unicode_escape()?),
+                        other => out.push(other), // Covers '"', '\\' and '/'
+                    }
+                },
+                c => out.push(c),
+            }
+        }
+    }
+
+    // The part of a \u escape after the 'u'. Characters outside the basic plane come as a
+    // surrogate pair of two escapes.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex_digits()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| String::from("Invalid escape in JSON."));
+        }
+        if !(self.consume('\\') && self.consume('u')) {
+            return Err(String::from("Unpaired surrogate in JSON."));
+        }
+        let low = self.hex_digits()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(String::from("Unpaired surrogate in JSON."));
+        }
+        let combined = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+        char::from_u32(combined).ok_or_else(|| String::from("Invalid escape in JSON."))
+    }
+
+    fn hex_digits(&mut self) -> Result<u32, String> {
+        let end = self.current + 4;
+        if end > self.chars.len() { return Err(String::from("Invalid escape in JSON.")); }
+        let text: String = self.chars[self.current..end].iter().collect();
+        self.current = end;
+        u32::from_str_radix(&text, 16).map_err(|_| String::from("Invalid escape in JSON."))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_parse_values() {
+        let cases = [
+            ("null", Json::Null),
+            (" true ", Json::Bool(true)),
+            ("-12.5e1", Json::Number(-125.0)),
+            ("\"a\\\"b\\\\c\\n\\u00e9\"", Json::from("a\"b\\c\né")),
+            ("\"\\ud83e\\udd80\"", Json::from("🦀")),
+            ("[]", Json::Array(vec![])),
+            ("[1, [2]]", Json::from(vec![Json::Number(1.0), Json::from(vec![Json::Number(2.0)])])),
+            ("{\"a\": {}, \"b\": [null]}", Json::object(vec![("a", Json::Object(vec![])), ("b", Json::from(vec![Json::Null]))])),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(Ok(expected), Json::parse(text), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_json_parse_errors() {
+        for text in ["", "nul", "[1,", "{\"a\" 1}", "{a: 1}", "\"open", "1 2", "\"\\ud83e\""] {
+            assert!(Json::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_json_display() {
+        let value = Json::object(vec![
+            ("id", Json::from(3)),
+            ("ratio", Json::Number(0.5)),
+            ("text", Json::from("say \"hi\"\n")),
+            ("items", Json::from(vec![Json::Null, Json::from(true)])),
+        ]);
+        let text = value.to_string();
+        assert_eq!("{\"id\":3,\"ratio\":0.5,\"text\":\"say \\\"hi\\\"\\n\",\"items\":[null,true]}", text);
+        assert_eq!(Ok(value), Json::parse(&text));
+    }
+
+    #[test]
+    fn test_json_accessors() {
+        let value = Json::parse("{\"n\": 4, \"s\": \"x\", \"b\": false, \"v\": [1]}").expect("Valid JSON");
+        assert_eq!(Some(4), value.get("n").and_then(Json::as_usize));
+        assert_eq!(Some("x"), value.get("s").and_then(Json::as_str));
+        assert_eq!(Some(false), value.get("b").and_then(Json::as_bool));
+        assert_eq!(Some(1), value.get("v").and_then(Json::as_array).map(|v| v.len()));
+        assert_eq!(None, value.get("missing"));
+        assert_eq!(None, Json::Number(-1.0).as_usize());
+    }
+
+    #[test]
+    fn test_json_messages() {
+        let mut output = Vec::new();
+        write_message(&mut output, &Json::object(vec![("id", Json::from(1))])).expect("Writing to memory");
+        write_message(&mut output, &Json::from("é")).expect("Writing to memory");
+        assert!(output.starts_with(b"Content-Length: 8\r\n\r\n{\"id\":1}"));
+
+        let mut input = io::Cursor::new(output);
+        assert_eq!(Some(Json::object(vec![("id", Json::from(1))])), read_message(&mut input).expect("Valid message"));
+        assert_eq!(Some(Json::from("é")), read_message(&mut input).expect("Valid message"));
+        assert_eq!(None, read_message(&mut input).expect("End of input"));
+    }
+}
@@ -0,0 +1,530 @@
+use crate::components as lox;
+use lox::instructions::{statement as lox_statement, expression as lox_expression, pattern as lox_pattern};
+use lox::instructions::callable::Callable;
+use lox::parser::LoxParser;
+use lox_statement::{Statement, Signature};
+use lox_expression::Expression;
+use lox_pattern::*;
+
+use core::fmt;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Eq)]
+#[derive(Hash)]
+#[derive(Clone)]
+#[derive(Copy)]
+pub enum Rule {
+    UnusedVariable,
+    UnreachableCode,
+    UndeclaredAssignment,
+    Shadowing,
+    CapturedThis, // 'this' inside a function nested in a method
+    NonCallable, // A literal in call position
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Rule::UnusedVariable,
+        Rule::UnreachableCode,
+        Rule::UndeclaredAssignment,
+        Rule::Shadowing,
+        Rule::CapturedThis,
+        Rule::NonCallable,
+    ];
+
+    // The name used for the rule in warnings and disable comments.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::UndeclaredAssignment => "undeclared-assignment",
+            Rule::Shadowing => "shadowing",
+            Rule::CapturedThis => "captured-this",
+            Rule::NonCallable => "non-callable",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Rule> {
+        Rule::ALL.into_iter().find(|r| r.id() == id)
+    }
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct LintWarning {
+    pub rule: Rule,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", lox::error::new_warning_string(self.line, self.rule.id(), &self.message))
+    }
+}
+
+#[derive(Debug)]
+struct Binding {
+    declared: Option<(usize, &'static str)>, // Line and kind of var and const declarations, the only bindings checked for use
+    used: bool,
+    replaceable: bool, // A native other than clock, which declarations may take over without a warning
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+enum FunctionKind {
+    Plain,
+    Member, // Methods, accessors and static methods, which get their own 'this'
+    Nested(String), // Functions declared anywhere inside a member
+}
+
+// Looks for likely mistakes that are still valid programs. Unlike the checker, it never stops a
+// program from running; it only reports warnings, each under a rule that can be switched off
+// with a comment:
+//     // lint-disable rule-id ...       on its own line, for the line after it
+//     // lint-disable rule-id ...       after code, for that line
+//     // lint-disable-file rule-id ...  anywhere, for the whole file
+pub struct LoxLinter {
+    scopes: Vec<HashMap<String, Binding>>,
+    functions: Vec<FunctionKind>, // Enclosing function bodies, innermost last
+    disabled_lines: HashSet<(usize, Rule)>,
+    disabled_rules: HashSet<Rule>,
+    warnings: Vec<LintWarning>,
+}
+
+impl LoxLinter {
+    pub fn new() -> LoxLinter {
+        LoxLinter {
+            scopes: Vec::new(),
+            functions: Vec::new(),
+            disabled_lines: HashSet::new(),
+            disabled_rules: HashSet::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    // Parses with comments kept, so that disable comments are seen.
+    pub fn lint_source(&mut self, source: &str) -> Result<Vec<LintWarning>, Vec<String>> {
+        let mut parser = LoxParser::new();
+        parser.keep_comments();
+        parser.load_string(source)?;
+        let program = parser.parse()?;
+        Ok(self.lint(&program))
+    }
+
+    // Warnings come back ordered by line.
+    pub fn lint(&mut self, program: &[Statement]) -> Vec<LintWarning> {
+        self.functions = Vec::new();
+        self.disabled_lines = HashSet::new();
+        self.disabled_rules = HashSet::new();
+        self.warnings = Vec::new();
+
+        // Globals can be used by functions declared before them, so they're all known up front.
+        let mut globals = HashMap::new();
+        for (name, native) in Callable::native_fn_list() {
+            globals.insert(name, Binding{ declared: None, used: true, replaceable: !native.is_core_native() });
+        }
+        for s in program {
+            let (name, declared) = match s {
+                Statement::Decl(name, _, _, line) => (name, Some((*line, "Variable"))),
+                Statement::Const(name, _, _, line) => (name, Some((*line, "Constant"))),
+                Statement::Fun(name, ..) | Statement::Generator(name, ..) | Statement::Class(name, ..) |
+                Statement::Trait(name, _) | Statement::Enum(name, _) => (name, None),
+                _ => continue,
+            };
+            globals.insert(name.clone(), Binding{ declared, used: false, replaceable: false });
+        }
+        self.scopes = vec![globals];
+
+        self.statements(program.iter());
+        self.end_scope();
+
+        let mut warnings: Vec<LintWarning> = std::mem::take(&mut self.warnings).into_iter()
+            .filter(|w| !self.disabled_rules.contains(&w.rule) && !self.disabled_lines.contains(&(w.line, w.rule)))
+            .collect();
+        warnings.sort_by_key(|w| w.line);
+        warnings
+    }
+
+    fn warn(&mut self, rule: Rule, line: usize, message: &str) {
+        self.warnings.push(LintWarning{ rule, line, message: String::from(message) });
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    // Names starting with an underscore are left alone, as a way to mark them unused on purpose.
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().expect("Scopes should be balanced");
+        for (name, binding) in scope {
+            if let Some((line, kind)) = binding.declared {
+                if !binding.used && !name.starts_with('_') {
+                    self.warn(Rule::UnusedVariable, line, &format!("{} {} is never used.", kind, name));
+                }
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str, declared: Option<(usize, &'static str)>) {
+        let last = self.scopes.len()-1;
+        if last == 0 {
+            // Already known from the start; keep any uses seen since.
+            self.scopes[0].entry(String::from(name)).or_insert(Binding{ declared, used: false, replaceable: false });
+            return;
+        }
+        if let Some((line, kind)) = declared {
+            if self.scopes[..last].iter().any(|scope| scope.get(name).is_some_and(|b| !b.replaceable)) {
+                self.warn(Rule::Shadowing, line, &format!("{} {} shadows a declaration in an outer scope.", kind, name));
+            }
+        }
+        self.scopes[last].insert(String::from(name), Binding{ declared, used: false, replaceable: false });
+    }
+
+    fn use_name(&mut self, name: &str) {
+        if let Some(binding) = self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+            binding.used = true;
+        }
+    }
+
+    fn is_declared(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains_key(name))
+    }
+
+    // Only the first statement after a return is reported, rather than every one. It's reported
+    // on its own line, or the return's if it has none, like a block or declaration.
+    fn statements<'a>(&mut self, stmts: impl Iterator<Item = &'a Statement>) {
+        let mut returned = None;
+        for s in stmts {
+            if let Some(line) = returned {
+                if !matches!(s, Statement::Comment(..)) {
+                    let dead = s.line().unwrap_or(line);
+                    self.warn(Rule::UnreachableCode, dead, &format!("Code after the return on line {} is never run.", line));
+                    returned = None;
+                }
+            }
+            if let Statement::Return(_, line) = s {
+                returned = Some(*line);
+            }
+            self.statement(s);
+        }
+    }
+
+    fn statement(&mut self, s: &Statement) {
+        use Statement::*;
+
+        match s {
+            Decl(name, init, _, line) => {
+                self.expr(init);
+                self.declare(name, Some((*line, "Variable")));
+            },
+            Const(name, init, _, line) => {
+                self.expr(init);
+                self.declare(name, Some((*line, "Constant")));
+            },
+            Expr(e, _) | Print(e, _) | Return(e, _) | Yield(e, _) => self.expr(e),
+            Block(v) | Test(_, v, _) => {
+                self.begin_scope();
+                self.statements(v.iter());
+                self.end_scope();
+            },
+            If(cond, then_branch, else_option, _) => {
+                self.expr(cond);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_option { self.statement(else_branch); }
+            },
+            While(cond, body, _) => {
+                self.expr(cond);
+                self.statement(body);
+            },
+            For(init, cond, incr, body, _) => {
+                self.begin_scope();
+                if let Some(init) = init { self.statement(init); }
+                self.expr(cond);
+                if let Some(incr) = incr { self.expr(incr); }
+                self.statement(body);
+                self.end_scope();
+            },
+            ForIn(name, iterable, body, _) => {
+                self.expr(iterable);
+                self.begin_scope();
+                self.declare(name, None);
+                self.statement(body);
+                self.end_scope();
+            },
+            Fun(name, params, body, signature) | Generator(name, params, body, signature) => {
+                self.declare(name, None);
+                let kind = if self.functions.iter().any(|f| *f != FunctionKind::Plain) {
+                    FunctionKind::Nested(name.clone())
+                } else {
+                    FunctionKind::Plain
+                };
+                self.function(kind, params, body, signature);
+            },
+            Class(name, super_name, traits, methods, statics, _) => {
+                self.declare(name, None);
+                if !super_name.is_empty() { self.use_name(super_name); }
+                for t in traits { self.use_name(t); }
+                for member in statics.iter().chain(methods.iter()) { self.member(member); }
+            },
+            Trait(name, methods) => {
+                self.declare(name, None);
+                for member in methods { self.member(member); }
+            },
+            Enum(name, _) => self.declare(name, None),
+            PropertyGet(..) | PropertySet(..) => self.member(s),
+            Match(subject, arms, default, _) => {
+                self.expr(subject);
+                for arm in arms {
+                    self.begin_scope();
+                    for p in &arm.patterns { self.declare_pattern(p); }
+                    if let Some(guard) = &arm.guard { self.expr(guard); }
+                    self.statement(&arm.body);
+                    self.end_scope();
+                }
+                if let Some(stmt) = default { self.statement(stmt); }
+            },
+            Comment(text, trailing, line) => self.comment(text, *trailing, *line),
+        }
+    }
+
+    // Class and trait members. Static fields aren't variables, so only their initializers matter.
+    fn member(&mut self, s: &Statement) {
+        use Statement::*;
+
+        match s {
+            Fun(_, params, body, signature) => self.function(FunctionKind::Member, params, body, signature),
+            PropertyGet(_, body) => self.function(FunctionKind::Member, &[], body, &Signature::default()),
+            PropertySet(_, param, body) => {
+                self.function(FunctionKind::Member, std::slice::from_ref(param), body, &Signature::default());
+            },
+            Decl(_, init, _, _) => self.expr(init),
+            other => self.statement(other),
+        }
+    }
+
+    fn function(&mut self, kind: FunctionKind, params: &[String], body: &[Statement], signature: &Signature) {
+        self.functions.push(kind);
+        self.begin_scope();
+        for param in params { self.declare(param, None); }
+        for default in signature.defaults.values() { self.expr(default); }
+        for (text, line) in signature.comments.values() { self.comment(text, true, *line); }
+        self.statements(body.iter());
+        self.end_scope();
+        self.functions.pop();
+    }
+
+    fn declare_pattern(&mut self, p: &Pattern) {
+        match p {
+            Pattern::Binding(name) => self.declare(name, None),
+            Pattern::Instance(class, fields) => {
+                self.use_name(class);
+                for field in fields { self.declare(field, None); }
+            },
+            Pattern::Variant(name, _, bindings) => {
+                self.use_name(name);
+                for binding in bindings { self.declare(binding, None); }
+            },
+            Pattern::Literal(_) | Pattern::Wildcard => (),
+        }
+    }
+
+    fn comment(&mut self, text: &str, trailing: bool, line: usize) {
+        let mut words = text.split(|c: char| c.is_whitespace() || c == ',').filter(|w| !w.is_empty());
+        let file_wide = match words.next() {
+            Some("lint-disable") => false,
+            Some("lint-disable-file") => true,
+            _ => return,
+        };
+        let target = if trailing { line } else { line + 1 };
+        for rule in words.filter_map(Rule::from_id) {
+            if file_wide { self.disabled_rules.insert(rule); }
+            else { self.disabled_lines.insert((target, rule)); }
+        }
+    }
+
+    fn expr(&mut self, e: &Expression) {
+        use Expression::*;
+
+        match e {
+            LitExp(_) | Super(_) => (),
+            Identifier(name) => self.use_name(name),
+            Unary(_, inner) | Grouping(inner) | Spread(inner) | Named(_, inner) | Getter(inner, _) => self.expr(inner),
+            Binary{left, op: _, right} | Logical{left, op: _, right} | Index(left, right) |
+            Setter(left, _, right) | CompoundSetter(left, _, _, right) => {
+                self.expr(left);
+                self.expr(right);
+            },
+            Assignment(name, value, line) | CompoundAssignment(name, _, value, line) => {
+                if !self.is_declared(name) {
+                    self.warn(Rule::UndeclaredAssignment, *line, &format!("Assignment to undeclared variable {}.", name));
+                }
+                self.expr(value);
+            },
+            Ternary(first, second, third) | IndexSetter(first, second, third) |
+            CompoundIndexSetter(first, second, _, third) => {
+                self.expr(first);
+                self.expr(second);
+                self.expr(third);
+            },
+            Call(callee, args, comments, line) => {
+                let mut target = &**callee;
+                while let Grouping(inner) = target { target = inner; }
+                if matches!(target, LitExp(_) | ListLiteral(_) | MapLiteral(_)) {
+                    self.warn(Rule::NonCallable, *line, "Calling a literal value always fails.");
+                }
+                self.expr(callee);
+                for arg in args { self.expr(arg); }
+                for (_, text, line) in comments { self.comment(text, true, *line); }
+            },
+            ListLiteral(items) => {
+                for item in items { self.expr(item); }
+            },
+            MapLiteral(entries) => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            },
+            This(line) => {
+                if let Some(FunctionKind::Nested(name)) = self.functions.last() {
+                    let message = format!(
+                        "'this' in {} is the instance of the method around it, even if {} is later stored on another object.",
+                        name, name,
+                    );
+                    self.warn(Rule::CapturedThis, *line, &message);
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint_string(s: &str) -> Vec<LintWarning> {
+        LoxLinter::new().lint_source(s).expect("Error while parsing input string.")
+    }
+
+    // Compares only the rule and line of each warning.
+    fn test_warnings_generic(cases: &[(&str, &[(Rule, usize)])]) {
+        for (source, expected) in cases {
+            let warnings: Vec<(Rule, usize)> = lint_string(source).iter().map(|w| (w.rule, w.line)).collect();
+            assert_eq!(expected.to_vec(), warnings, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_lint_clean_programs() {
+        let sources = [
+            "var a = 1; print a;",
+            "fun f() { return g; } var g = 2; print f();",
+            "fun count(n) { var i = 0; while (i < n) i = i + 1; return i; } print count(3);",
+            "for (var i = 0; i < 3; i = i + 1) print i;",
+            "class A { init(x) { this.x = x; } get() { return this.x; } } print A(1).get();",
+            "class B { class make() { return this; } } print B.make();",
+            "var _ignored = 1;",
+            "print clock();",
+            "enum Shape { Circle(r) } match (Shape.Circle(1)) { case Shape.Circle(r) => print r; }",
+        ];
+        for source in sources {
+            assert_eq!(Vec::<LintWarning>::new(), lint_string(source), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_lint_unused_variables() {
+        test_warnings_generic(&[
+            ("var a = 1;", &[(Rule::UnusedVariable, 1)]),
+            ("const a = 1;", &[(Rule::UnusedVariable, 1)]),
+            ("fun f() {\n    var unused = 1;\n    return 2;\n}\nprint f();", &[(Rule::UnusedVariable, 2)]),
+            ("{\n    var a = 1;\n    a = 2;\n}", &[(Rule::UnusedVariable, 2)]),
+            ("var total = 0;\ntotal += 1;", &[(Rule::UnusedVariable, 1)]),
+        ]);
+        let warnings = lint_string("const limit = 1;");
+        assert_eq!("[Line 1] Warning (unused-variable): Constant limit is never used.", warnings[0].to_string());
+    }
+
+    #[test]
+    fn test_lint_unreachable_code() {
+        test_warnings_generic(&[
+            ("fun f() {\n    return 1;\n    print 2;\n    print 3;\n}\nprint f();", &[(Rule::UnreachableCode, 3)]),
+            ("fun f() {\n    return 1;\n\n    // Never\n    print 2;\n}\nprint f();", &[(Rule::UnreachableCode, 5)]),
+            ("fun f() {\n    return 1;\n    {\n        print 2;\n    }\n}\nprint f();", &[(Rule::UnreachableCode, 2)]),
+            ("fun f() {\n    return 1;\n    // Only a comment\n}\nprint f();", &[]),
+            ("fun f(x) {\n    if (x) return 1;\n    return 2;\n}\nprint f(true);", &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_lint_undeclared_assignments() {
+        test_warnings_generic(&[
+            ("i = i + 1;", &[(Rule::UndeclaredAssignment, 1)]),
+            ("fun f() {\n    count += 1;\n}\nf();", &[(Rule::UndeclaredAssignment, 2)]),
+            ("fun f() {\n    later = 1;\n}\nvar later;\nf();\nprint later;", &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_lint_shadowing() {
+        test_warnings_generic(&[
+            ("var a = 1;\n{\n    var a = 2;\n    print a;\n}\nprint a;", &[(Rule::Shadowing, 3)]),
+            ("fun f(x) {\n    {\n        var x = 1;\n        print x;\n    }\n}\nf(1);", &[(Rule::Shadowing, 3)]),
+            ("fun f() {\n    var clock = 1;\n    print clock;\n}\nf();", &[(Rule::Shadowing, 2)]),
+            ("fun f() {\n    var range = 1;\n    print range;\n}\nf();", &[]),
+            ("var a = 1;\nprint a;\nfun f(a) { print a; }\nf(2);", &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_lint_captured_this() {
+        let source = concat!(
+            "class Counter {\n",
+            "    init() { this.count = 0; }\n",
+            "    callback() {\n",
+            "        fun bump() {\n",
+            "            this.count = this.count + 1;\n",
+            "        }\n",
+            "        return bump;\n",
+            "    }\n",
+            "}\n",
+            "print Counter().callback();\n",
+        );
+        test_warnings_generic(&[(source, &[(Rule::CapturedThis, 5), (Rule::CapturedThis, 5)])]);
+    }
+
+    #[test]
+    fn test_lint_non_callable() {
+        test_warnings_generic(&[
+            ("\"not a function\"();", &[(Rule::NonCallable, 1)]),
+            ("print (1)(2);", &[(Rule::NonCallable, 1)]),
+            ("[1, 2]();", &[(Rule::NonCallable, 1)]),
+        ]);
+    }
+
+    #[test]
+    fn test_lint_disable_comments() {
+        test_warnings_generic(&[
+            ("var a = 1; // lint-disable unused-variable", &[]),
+            ("// lint-disable unused-variable\nvar a = 1;", &[]),
+            ("// lint-disable unused-variable\n\nvar a = 1;", &[(Rule::UnusedVariable, 3)]),
+            ("var a = 1; // lint-disable shadowing", &[(Rule::UnusedVariable, 1)]),
+            ("var a = 1; // lint-disable shadowing, unused-variable", &[]),
+            ("var a = 1;\nb = 2;\n// lint-disable-file unused-variable undeclared-assignment", &[]),
+            ("var a = 1; // lint-disable-file shadowing", &[(Rule::UnusedVariable, 1)]),
+            ("print max(1, // lint-disable-file non-callable\n    2);\n1();", &[]),
+            ("fun f(a, // lint-disable-file unused-variable\n    b) { var c; }\nf(1, 2);", &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_lint_rule_ids() {
+        for rule in Rule::ALL {
+            assert_eq!(Some(rule), Rule::from_id(rule.id()));
+        }
+        assert_eq!(None, Rule::from_id("no-such-rule"));
+    }
+}
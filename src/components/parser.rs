@@ -1,5 +1,5 @@
-mod token;
-mod scanner;
+pub mod token;
+pub mod scanner;
 
 use crate::components as lox;
 
@@ -9,10 +9,13 @@ use lox_scanner::*;
 use lox::parser::token as lox_token;
 use lox_token::*;
 
-use lox::instructions::{statement as lox_statement, expression as lox_expression, node as lox_node};
-use lox_statement::Statement;
+use lox::instructions::{statement as lox_statement, expression as lox_expression, node as lox_node, pattern as lox_pattern};
+use lox_statement::{Statement, Signature};
 use lox_expression::Expression;
 use lox_node::*;
+use lox_pattern::*;
+
+use std::rc::Rc;
 
 #[derive(Clone)]
 #[derive(Copy)]
@@ -35,6 +38,10 @@ pub struct LoxParser {
     valid: bool,
     access: AccessType,
     is_init: bool,
+    in_generator: bool,
+    keep_comments: bool,
+    comments: Vec<(usize, Token)>, // Comment tokens with the index of the token that follows each
+    interruptions: Vec<usize>, // Lines of kept comments that were inside a statement
 }
 
 impl LoxParser {
@@ -50,11 +57,29 @@ impl LoxParser {
         let valid = true;
         let access = NoAccess;
         let is_init = false;
-        LoxParser{tokens, error_strings, output, current, line, inited, loaded, valid, access, is_init}
+        let in_generator = false;
+        let keep_comments = false;
+        let comments = Vec::new();
+        let interruptions = Vec::new();
+        LoxParser{tokens, error_strings, output, current, line, inited, loaded, valid, access, is_init, in_generator, keep_comments, comments, interruptions}
+    }
+
+    // Makes parse() keep comments as Statement::Comment wherever a statement or class member could
+    // begin, and with the call or signature they follow on a parameter's or argument's line.
+    // Comments anywhere else are kept after the statement they interrupt, and noted in
+    // interruptions().
+    pub fn keep_comments(&mut self) {
+        self.keep_comments = true;
+    }
+
+    // The lines of kept comments that were moved after the statement they interrupted.
+    pub fn interruptions(&self) -> &[usize] {
+        &self.interruptions
     }
 
     pub fn load_string(&mut self, s: &str) -> Result<(), Vec<String>> {
         let mut scanner = LoxScanner::new(s);
+        if self.keep_comments { scanner.keep_comments(); }
         let scanner_out = scanner.scan_tokens();
         match scanner_out {
             Ok(tokens) => {
@@ -66,7 +91,13 @@ impl LoxParser {
     }
 
     pub fn load_token_vec(&mut self, tokens: Vec<Token>) {
-        self.tokens = tokens;
+        self.tokens = Vec::new();
+        self.comments = Vec::new();
+        self.interruptions = Vec::new();
+        for t in tokens {
+            if let TokenData::Comment(_) = t.data { self.comments.push((self.tokens.len(), t)); }
+            else { self.tokens.push(t); }
+        }
         if self.loaded { 
             self.error_strings = Vec::new();
             self.output = Vec::new();
@@ -83,7 +114,10 @@ impl LoxParser {
             self.loaded = true;
 
             while !self.is_at_end() && self.consume(TokenData::EndOfFile) == None {
-                let r = self.statement();
+                let comments = self.take_comments(self.current + 1);
+                self.output.extend(comments);
+                let r = if self.at_test() { self.stmt_test() } else { self.statement() };
+                self.note_interruptions();
                 if let Ok(st) = r {
                     self.output.push(st);
                 }
@@ -92,6 +126,8 @@ impl LoxParser {
                     self.synchronize();
                 }
             }
+            let comments = self.take_comments(usize::MAX);
+            self.output.extend(comments);
         }
 
         if !self.loaded { Err(vec![String::from("Error: parser has not recieved input.")]) }
@@ -102,10 +138,19 @@ impl LoxParser {
     fn statement(&mut self) -> Result<Statement, ()> {
         if self.consume(TokenData::Var).is_some() {
             self.stmt_decl_var()
+        } else if self.consume(TokenData::Const).is_some() {
+            self.stmt_decl_const()
         } else if self.consume(TokenData::Fun).is_some() {
             self.stmt_decl_fun("function")
         } else if self.consume(TokenData::Class).is_some() {
             self.stmt_decl_class()
+        } else if self.consume(TokenData::Trait).is_some() {
+            self.stmt_decl_trait()
+        } else if self.consume(TokenData::Enum).is_some() {
+            self.stmt_decl_enum()
+        } else if self.at_test() {
+            self.add_error("Tests can only be declared at the top level.");
+            Err(())
         } else if let Some(_) = self.peek() {
             self.stmt_nestable()
         } else {
@@ -114,15 +159,34 @@ impl LoxParser {
         }
     }
 
+    // 'test' is only a keyword when a string follows it, so it can still name variables.
+    fn at_test(&self) -> bool {
+        matches!(self.peek().map(|t| t.data), Some(TokenData::Identifier(ref id)) if id == "test")
+            && matches!(self.peek_second().map(|t| t.data), Some(TokenData::StringData(_)))
+    }
+
+    fn stmt_test(&mut self) -> Result<Statement, ()> {
+        let line = self.advance()?.line;
+        let Ok(Token{ data: TokenData::StringData(name), .. }) = self.advance() else {
+            unreachable!("at_test() should guarantee a name");
+        };
+        if self.consume(TokenData::LeftBrace).is_none() {
+            self.add_error("Expected '{' before test body.");
+            return Err(());
+        }
+        Ok(Statement::Test(name, self.block()?, line))
+    }
+
     fn stmt_decl_var(&mut self) -> Result<Statement, ()> {
         let next = self.advance()?;
 
         if let TokenData::Identifier(id) = next.data {
+            let annotation = self.type_annotation()?;
             let mut expr = Expression::boxed_nil();
             if !self.is_at_end() && self.consume(TokenData::Equal).is_some() {
                 expr = self.expression()?;
             }
-            let d = Statement::Decl(id, expr);
+            let d = Statement::Decl(id, expr, annotation, next.line);
             self.pass_semicolon();
             Ok(d)
         }
@@ -133,7 +197,26 @@ impl LoxParser {
         }
     }
 
+    fn stmt_decl_const(&mut self) -> Result<Statement, ()> {
+        let next = self.advance()?;
+
+        let TokenData::Identifier(id) = next.data else {
+            self.add_error("Expected constant name after 'const'.");
+            return Err(());
+        };
+        let annotation = self.type_annotation()?;
+        if self.consume(TokenData::Equal).is_none() {
+            self.add_error(&format!("Constant {} must be initialized.", id));
+            return Err(());
+        }
+        let d = Statement::Const(id, self.expression()?, annotation, next.line);
+        self.pass_semicolon();
+        Ok(d)
+    }
+
     fn stmt_decl_fun(&mut self, context: &str) -> Result<Statement, ()> {
+        // Only plain function declarations can be generators ('fun* name() {}').
+        let is_generator = context == "function" && self.consume(TokenData::Star).is_some();
         let next = self.advance()?;
 
         if let TokenData::Identifier(name) = next.data {
@@ -143,25 +226,49 @@ impl LoxParser {
             };
 
             let mut args = Vec::new();
+            let mut signature = Signature{ line: next.line, ..Signature::default() };
 
             if !self.consume(TokenData::RightParen).is_some() {
                 loop {
+                    let is_rest = self.consume(TokenData::Ellipsis).is_some();
                     let next = self.advance()?;
                     if let TokenData::Identifier(arg) = next.data {
+                        if let Some(annotation) = self.type_annotation()? {
+                            signature.param_types.insert(arg.clone(), annotation);
+                        }
+                        if is_rest {
+                            signature.rest = true;
+                        } else if self.consume(TokenData::Equal).is_some() {
+                            signature.defaults.insert(arg.clone(), self.expression()?);
+                        } else if !signature.defaults.is_empty() {
+                            self.add_error(&format!("Parameter {} needs a default value, since it follows one that has one.", arg));
+                        }
+                        let (more, comment) = self.list_separator();
+                        if let Some(comment) = comment {
+                            signature.comments.insert(arg.clone(), comment);
+                        }
                         args.push(arg);
+                        if !more { break; }
                     } else {
                         self.add_error(&format!("Expected parameter name."));
                         return Err(());
                     }
 
-                    if !self.consume(TokenData::Comma).is_some() { break; }
+                    if signature.rest {
+                        self.add_error("A rest parameter must be the last parameter.");
+                    }
                 }
 
                 if !self.consume(TokenData::RightParen).is_some() {
                     self.add_error(&format!("Expected ')' after {} parameters.", context));
                     return Err(());
                 };
-            }            
+            }
+
+            signature.returns = self.type_annotation()?;
+            if is_generator && signature.returns.is_some() {
+                self.add_error("A generator can't declare a return type.");
+            }
 
             if !self.consume(TokenData::LeftBrace).is_some() {
                 self.add_error(&format!("Expected '{{' before {} body.", context));
@@ -170,13 +277,17 @@ impl LoxParser {
 
             let old_init = self.is_init;
             if self.access == Class && name == "init" { self.is_init = true; }
+            let old_generator = self.in_generator;
+            self.in_generator = is_generator;
 
             let body = self.block();
 
             self.is_init = old_init;
+            self.in_generator = old_generator;
 
             match body {
-                Ok(b) => Ok(Statement::Fun(name, args, b)),
+                Ok(b) if is_generator => Ok(Statement::Generator(name, args, b, Rc::new(signature))),
+                Ok(b) => Ok(Statement::Fun(name, args, b, Rc::new(signature))),
                 Err(e) => Err(e),
             }
         }
@@ -207,6 +318,20 @@ impl LoxParser {
                 }
             }
 
+            let mut traits = Vec::new();
+            if self.consume(TokenData::With).is_some() {
+                loop {
+                    let next = self.advance()?;
+                    if let TokenData::Identifier(tn) = next.data {
+                        traits.push(tn);
+                    } else {
+                        self.add_error("Expected trait name after 'with'.");
+                        return Err(());
+                    }
+                    if self.consume(TokenData::Comma).is_none() { break; }
+                }
+            }
+
             if !self.consume(TokenData::LeftBrace).is_some() {
                 self.add_error("Expected '{' before class body.");
                 return Err(());
@@ -217,14 +342,35 @@ impl LoxParser {
             else { self.access = Subclass; }
 
             let mut methods = Vec::new();
+            let mut statics = Vec::new();
+            let mut order = Vec::new();
             while !self.is_at_end() && self.peek().unwrap().data != TokenData::RightBrace {
-                let method = self.stmt_decl_fun("method");
-                if let Ok(m) = method { methods.push(Box::new(m)); }
-                else { // stmt_decl_fun will have written the error, so just return
+                let comments = self.take_comments(self.current + 1);
+                let (is_static, member) = if self.consume(TokenData::Class).is_some() {
+                    (true, self.stmt_decl_static())
+                } else if self.is_accessor_decl() {
+                    (false, self.stmt_decl_accessor())
+                } else {
+                    (false, self.stmt_decl_fun("method"))
+                };
+                self.note_interruptions();
+                // Comments go in the list of the member they come before, or trail on its line.
+                let member = member.map(|m| {
+                    let list = if is_static { &mut statics } else { &mut methods };
+                    let start = list.len();
+                    list.extend(comments);
+                    list.push(m);
+                    list.extend(self.trailing_comment());
+                    order.extend(std::iter::repeat_n(is_static, list.len() - start));
+                });
+                if member.is_err() { // the declaration will have written the error, so just return
                     self.access = old_access;
                     return Err(());
                 }
             }
+            let closing = self.take_comments(self.current + 1);
+            order.extend(std::iter::repeat_n(false, closing.len()));
+            methods.extend(closing);
             
             self.access = old_access;
 
@@ -233,7 +379,7 @@ impl LoxParser {
                 Err(())
             } else {
                 let _ = self.advance(); // consumes right brace
-                Ok(Statement::Class(name, super_name, methods))
+                Ok(Statement::Class(name, super_name, traits, methods, statics, order))
             }
 
         }
@@ -244,9 +390,197 @@ impl LoxParser {
         }
     }
 
+    fn stmt_decl_trait(&mut self) -> Result<Statement, ()> {
+        let next = self.advance()?;
+
+        if let TokenData::Identifier(name) = next.data {
+            if self.consume(TokenData::LeftBrace).is_none() {
+                self.add_error("Expected '{' before trait body.");
+                return Err(());
+            };
+
+            // Traits can be mixed into subclasses, so their methods may use 'super'.
+            let old_access = self.access;
+            self.access = Subclass;
+
+            let mut methods = Vec::new();
+            while !self.is_at_end() && self.peek().unwrap().data != TokenData::RightBrace {
+                methods.extend(self.take_comments(self.current + 1));
+                let member = if self.is_accessor_decl() {
+                    self.stmt_decl_accessor()
+                } else if let Some(Token { data: TokenData::Identifier(ref m), line: _ }) = self.peek() {
+                    if m == "init" {
+                        self.add_error("A trait can't define 'init'.");
+                        Err(())
+                    } else {
+                        self.stmt_decl_fun("method")
+                    }
+                } else {
+                    self.stmt_decl_fun("method")
+                };
+                self.note_interruptions();
+                match member {
+                    Ok(m) => methods.push(m),
+                    Err(_) => {
+                        self.access = old_access;
+                        return Err(());
+                    },
+                }
+            }
+            methods.extend(self.take_comments(self.current + 1));
+
+            self.access = old_access;
+
+            if self.is_at_end() {
+                self.add_error("Unexpectedly reached end of file while parsing trait methods.");
+                Err(())
+            } else {
+                let _ = self.advance(); // consumes right brace
+                Ok(Statement::Trait(name, methods))
+            }
+        }
+
+        else {
+            self.add_error("Expected trait name after 'trait'.");
+            Err(())
+        }
+    }
+
+    // Getters are declared as 'name { ... }' and setters as 'set name(value) { ... }'.
+    fn stmt_decl_enum(&mut self) -> Result<Statement, ()> {
+        let TokenData::Identifier(name) = self.advance()?.data else {
+            self.add_error("Expected enum name.");
+            return Err(());
+        };
+        if self.consume(TokenData::LeftBrace).is_none() {
+            self.add_error("Expected '{' before enum body.");
+            return Err(());
+        }
+
+        let mut variants: Vec<(String, Vec<String>)> = Vec::new();
+        while self.consume(TokenData::RightBrace).is_none() {
+            let TokenData::Identifier(variant) = self.advance()?.data else {
+                self.add_error("Expected variant name.");
+                return Err(());
+            };
+            if variants.iter().any(|(existing, _)| *existing == variant) {
+                self.add_error(&format!("Enum {} declares variant {} more than once.", name, variant));
+            }
+
+            let mut fields = Vec::new();
+            if self.consume(TokenData::LeftParen).is_some() {
+                fields = self.name_list("variant field")?;
+                if fields.is_empty() {
+                    self.add_error(&format!("Variant {} needs at least one field; leave off the parentheses for none.", variant));
+                }
+            }
+            variants.push((variant, fields));
+
+            if self.consume(TokenData::Comma).is_none() {
+                if self.consume(TokenData::RightBrace).is_none() {
+                    self.add_error("Expected '}' after enum variants.");
+                    return Err(());
+                }
+                break;
+            }
+        }
+        Ok(Statement::Enum(name, variants))
+    }
+
+    // Parses an optional ': TypeName' annotation.
+    fn type_annotation(&mut self) -> Result<Option<String>, ()> {
+        if self.consume(TokenData::Colon).is_none() {
+            return Ok(None);
+        }
+        match self.advance()?.data {
+            TokenData::Identifier(name) => Ok(Some(name)),
+            _ => {
+                self.add_error("Expected type name after ':'.");
+                Err(())
+            },
+        }
+    }
+
+    // Parses a comma-separated list of names up to and including the closing parenthesis.
+    fn name_list(&mut self, context: &str) -> Result<Vec<String>, ()> {
+        let mut names = Vec::new();
+        if self.consume(TokenData::RightParen).is_some() {
+            return Ok(names);
+        }
+        loop {
+            if let TokenData::Identifier(name) = self.advance()?.data {
+                names.push(name);
+            } else {
+                self.add_error(&format!("Expected {} name.", context));
+                return Err(());
+            }
+            if self.consume(TokenData::Comma).is_none() { break; }
+        }
+        if self.consume(TokenData::RightParen).is_none() {
+            self.add_error(&format!("Expected ')' after {} names.", context));
+            return Err(());
+        }
+        Ok(names)
+    }
+
+    fn is_accessor_decl(&self) -> bool {
+        match (self.peek(), self.peek_second()) {
+            (
+                Some(Token { data: TokenData::Identifier(_), line: _ }),
+                Some(Token { data: TokenData::LeftBrace, line: _ }),
+            ) => true,
+            (
+                Some(Token { data: TokenData::Identifier(keyword), line: _ }),
+                Some(Token { data: TokenData::Identifier(_), line: _ }),
+            ) => keyword == "set",
+            _ => false,
+        }
+    }
+
+    fn stmt_decl_accessor(&mut self) -> Result<Statement, ()> {
+        if let Some(Token { data: TokenData::LeftBrace, line: _ }) = self.peek_second() {
+            let Ok(Token { data: TokenData::Identifier(name), line: _ }) = self.advance() else {
+                panic!("is_accessor_decl() should guarantee a getter name");
+            };
+            self.advance().expect("is_accessor_decl() should guarantee advance()"); // consumes left brace
+            let body = self.block()?;
+            return Ok(Statement::PropertyGet(name, body));
+        }
+
+        self.advance().expect("is_accessor_decl() should guarantee advance()"); // consumes 'set'
+        match self.stmt_decl_fun("setter")? {
+            Statement::Fun(name, mut params, body, _) => {
+                if params.len() != 1 {
+                    self.add_error("A setter must take exactly one parameter.");
+                    return Err(());
+                }
+                Ok(Statement::PropertySet(name, params.remove(0), body))
+            },
+            _ => panic!("stmt_decl_fun() should only return function declarations"),
+        }
+    }
+
+    // Static members are either fields ('class name = value;') or methods ('class name() {}').
+    fn stmt_decl_static(&mut self) -> Result<Statement, ()> {
+        let is_field = matches!(
+            self.peek_second(),
+            Some(Token { data: TokenData::Equal | TokenData::Semicolon | TokenData::Colon, line: _ })
+        );
+        if is_field { return self.stmt_decl_var(); }
+
+        if let Some(Token { data: TokenData::Identifier(name), line: _ }) = self.peek() {
+            if name == "init" {
+                self.add_error("A static method can't be named 'init'.");
+                return Err(());
+            }
+        }
+        self.stmt_decl_fun("static method")
+    }
+
     fn stmt_nestable(&mut self) -> Result<Statement, ()> {
         let next = self.peek();
         if let Some(t) = next {
+            let line = t.line;
             match t.data {
                 TokenData::LeftBrace => {
                     self.advance().expect("If-let condition should guarantee advance()");
@@ -254,7 +588,7 @@ impl LoxParser {
                 }
                 TokenData::Print => {
                     self.advance().expect("If-let condition should guarantee advance()");
-                    let e = Statement::Print(self.expression()?);
+                    let e = Statement::Print(self.expression()?, line);
                     self.pass_semicolon();
                     Ok(e)
                 }
@@ -266,16 +600,34 @@ impl LoxParser {
                         return Ok(Statement::Return(Expression::boxed_nil(), t.line));
                     }
 
-                    // Otherwise, it had better not be in the initializer.
+                    // Otherwise, it had better not be in the initializer or a generator.
                     if self.is_init {
                         self.add_error("Can't return a value from an initializer.");
                         return Err(());
                     }
+                    if self.in_generator {
+                        self.add_error("Can't return a value from a generator.");
+                        return Err(());
+                    }
 
                     let e = Statement::Return(self.expression()?, t.line);
                     self.pass_semicolon();
                     Ok(e)   
                 }
+                TokenData::Yield => {
+                    self.advance().expect("If-let condition should guarantee advance()");
+                    if !self.in_generator {
+                        self.add_error("Can't yield outside a generator.");
+                        return Err(());
+                    }
+
+                    if self.consume(TokenData::Semicolon).is_some() {
+                        return Ok(Statement::Yield(Expression::boxed_nil(), line));
+                    }
+                    let e = Statement::Yield(self.expression()?, line);
+                    self.pass_semicolon();
+                    Ok(e)
+                }
                 TokenData::If => {
                     self.advance().expect("If-let condition should guarantee advance()");
                     if !self.consume(TokenData::LeftParen).is_some() {
@@ -291,9 +643,9 @@ impl LoxParser {
 
                     if self.consume(TokenData::Else).is_some() {
                         let else_branch = Some(Box::new(self.stmt_nestable()?));
-                        Ok(Statement::If(condition, then_branch, else_branch))
+                        Ok(Statement::If(condition, then_branch, else_branch, line))
                     } else {
-                        Ok(Statement::If(condition, then_branch, None))
+                        Ok(Statement::If(condition, then_branch, None, line))
                     }
                 }
                 TokenData::While => {
@@ -308,7 +660,7 @@ impl LoxParser {
                         return Err(());
                     };
                     let body = Box::new(self.stmt_nestable()?);
-                    Ok(Statement::While(condition, body))
+                    Ok(Statement::While(condition, body, line))
                 }
                 TokenData::For => {
                     self.advance().expect("If-let condition should guarantee advance()");
@@ -316,14 +668,15 @@ impl LoxParser {
                         self.add_error("Expected '(' after if statement.");
                         return Err(());
                     };
-                    let mut for_vec: Vec<Box<Statement>> = Vec::new();
-    
-                    // Initializer: placed directly at the front of the block
+                    if self.is_for_in() {
+                        return self.stmt_for_in(line);
+                    }
+                    let mut init = None;
                     if !self.consume(TokenData::Semicolon).is_some() {
                         if self.consume(TokenData::Var).is_some() {
-                            for_vec.push(Box::new(self.stmt_decl_var()?)); // passes semicolon implicitly
+                            init = Some(Box::new(self.stmt_decl_var()?)); // passes semicolon implicitly
                         } else {
-                            for_vec.push(Box::new(Statement::Expr(self.expression()?)));
+                            init = Some(Box::new(Statement::Expr(self.expression()?, line)));
                             self.pass_semicolon();
                         }
                     }
@@ -331,30 +684,25 @@ impl LoxParser {
                     let cond = self.expression()?;
                     self.pass_semicolon();
                     
-                    let mut while_body: Vec<Box<Statement>> = Vec::new();
+                    let mut incr = None;
                     if !self.consume(TokenData::RightParen).is_some() {
-                        let incr = Box::new(Statement::Expr(self.expression()?));
+                        incr = Some(self.expression()?);
                         if !self.consume(TokenData::RightParen).is_some() {
                             self.add_error("Expected ')' after for clauses.");
                             return Err(());
                         };
-                        while_body.push(Box::new(self.stmt_nestable()?)); // for loop body
-                        while_body.push(incr);
-                    } else {
-                        while_body.push(Box::new(self.stmt_nestable()?));
                     }
+                    let body = Box::new(self.stmt_nestable()?);
 
-                    for_vec.push(
-                        Box::new(Statement::While(
-                            cond,
-                            Box::new(Statement::Block(while_body)),
-                        ))
-                    );
-
-                    Ok(Statement::Block(for_vec))
+                    // Kept as written so tools can reproduce it; it runs as Statement::desugar_for().
+                    Ok(Statement::For(init, cond, incr, body, line))
+                }
+                TokenData::Match => {
+                    self.advance().expect("If-let condition should guarantee advance()");
+                    self.stmt_match(line)
                 }
                 _ => { // Expression statement
-                    let e = Statement::Expr(self.expression()?);
+                    let e = Statement::Expr(self.expression()?, line);
                     self.pass_semicolon();
                     Ok(e)
                 },
@@ -365,17 +713,150 @@ impl LoxParser {
         }
     }
 
-    fn block(&mut self) -> Result<Vec<Box<Statement>>, ()> {
+    // Checks for 'var name in' following the opening parenthesis of a for loop.
+    fn is_for_in(&self) -> bool {
+        let is_var = matches!(self.peek(), Some(Token{ data: TokenData::Var, line: _ }));
+        let is_name = matches!(self.peek_second(), Some(Token{ data: TokenData::Identifier(_), line: _ }));
+        let is_in = matches!(self.tokens.get(self.current + 2), Some(Token{ data: TokenData::In, line: _ }));
+        is_var && is_name && is_in
+    }
+
+    fn stmt_for_in(&mut self, line: usize) -> Result<Statement, ()> {
+        self.advance().expect("is_for_in() should guarantee advance()");
+        let Token{ data: TokenData::Identifier(name), line: _ } = self.advance()? else {
+            panic!("is_for_in() should guarantee an identifier"); // should be impossible
+        };
+        self.advance().expect("is_for_in() should guarantee advance()");
+
+        let iterable = self.expression()?;
+        if self.consume(TokenData::RightParen).is_none() {
+            self.add_error("Expected ')' after for-in clause.");
+            return Err(());
+        }
+        let body = Box::new(self.stmt_nestable()?);
+        Ok(Statement::ForIn(name, iterable, body, line))
+    }
+
+    fn stmt_match(&mut self, line: usize) -> Result<Statement, ()> {
+        if self.consume(TokenData::LeftParen).is_none() {
+            self.add_error("Expected '(' after match.");
+            return Err(());
+        };
+        let subject = self.expression()?;
+        if self.consume(TokenData::RightParen).is_none() {
+            self.add_error("Expected ')' after match subject.");
+            return Err(());
+        };
+        if self.consume(TokenData::LeftBrace).is_none() {
+            self.add_error("Expected '{' before match cases.");
+            return Err(());
+        };
+
+        let mut arms = Vec::new();
+        let mut default = None;
+        while self.consume(TokenData::RightBrace).is_none() {
+            if default.is_some() {
+                self.add_error("Default case must be the last case in a match.");
+                return Err(());
+            }
+
+            if self.consume(TokenData::Default).is_some() {
+                self.pass_fat_arrow()?;
+                default = Some(Box::new(self.stmt_nestable()?));
+            } else if self.consume(TokenData::Case).is_some() {
+                let mut patterns = Vec::new();
+                loop {
+                    patterns.push(self.pattern()?);
+                    if self.consume(TokenData::Comma).is_none() { break; }
+                }
+
+                let mut guard = None;
+                if self.consume(TokenData::If).is_some() {
+                    guard = Some(self.expression()?);
+                }
+
+                self.pass_fat_arrow()?;
+                let body = Box::new(self.stmt_nestable()?);
+                arms.push(MatchArm::new(patterns, guard, body));
+            } else if self.is_at_end() {
+                self.add_error("Unexpectedly reached end of file while parsing match cases.");
+                return Err(());
+            } else {
+                self.add_error("Expected 'case' or 'default' in match body.");
+                return Err(());
+            }
+        }
+
+        Ok(Statement::Match(subject, arms, default, line))
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, ()> {
+        let t = self.advance()?;
+        match t.data {
+            TokenData::Number(n) => Ok(Pattern::Literal(Literal::Number(n))),
+            TokenData::Integer(n) => Ok(Pattern::Literal(Literal::Integer(n))),
+            TokenData::StringData(s) => Ok(Pattern::Literal(Literal::StringData(s))),
+            TokenData::True => Ok(Pattern::Literal(Literal::Boolean(true))),
+            TokenData::False => Ok(Pattern::Literal(Literal::Boolean(false))),
+            TokenData::Nil => Ok(Pattern::Literal(Literal::Nil)),
+            TokenData::Minus => {
+                match self.advance()?.data {
+                    TokenData::Number(n) => Ok(Pattern::Literal(Literal::Number(-n))),
+                    TokenData::Integer(n) => Ok(Pattern::Literal(Literal::Integer(-n))),
+                    _ => {
+                        self.add_error("Expected a number after '-' in pattern.");
+                        Err(())
+                    },
+                }
+            },
+            TokenData::Identifier(id) => {
+                if id == "_" { return Ok(Pattern::Wildcard); }
+                if self.consume(TokenData::Dot).is_some() {
+                    let TokenData::Identifier(variant) = self.advance()?.data else {
+                        self.add_error("Expected variant name after '.' in pattern.");
+                        return Err(());
+                    };
+                    let mut bindings = Vec::new();
+                    if self.consume(TokenData::LeftParen).is_some() {
+                        bindings = self.name_list("payload binding")?;
+                    }
+                    return Ok(Pattern::Variant(id, variant, bindings));
+                }
+                if self.consume(TokenData::LeftParen).is_none() { return Ok(Pattern::Binding(id)); }
+
+                let fields = self.name_list("class pattern field")?;
+                Ok(Pattern::Instance(id, fields))
+            },
+            _ => {
+                self.add_error(&format!("Expected a pattern; recieved {:?}.", t.data));
+                Err(())
+            },
+        }
+    }
+
+    fn pass_fat_arrow(&mut self) -> Result<(), ()> {
+        if self.consume(TokenData::FatArrow).is_some() { Ok(()) }
+        else {
+            self.add_error("Expected '=>' after case pattern.");
+            Err(())
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Statement>, ()> {
+        self.note_interruptions(); // Anything before the opening brace
         let mut block = Vec::new();
         let mut block_valid = true;
         while !self.is_at_end() && self.consume(TokenData::RightBrace) == None {
+            block.extend(self.take_comments(self.current + 1));
             let r = self.statement();
-            if let Ok(st) = r { block.push(Box::new(st)); }
+            self.note_interruptions();
+            if let Ok(st) = r { block.push(st); }
             else {
                 block_valid = false;
                 self.synchronize();
             }
         }
+        block.extend(self.take_comments(self.current)); // Up to the closing brace
         if block_valid {Ok(block)}
         else { Err(()) }
     }
@@ -389,24 +870,71 @@ impl LoxParser {
         // Check for assignment to some variable
         if let TokenData::Identifier(ref id) = t.data {
             if self.consume(TokenData::Equal).is_some() {
-                return Ok(Expression::boxed_assignment(id, self.expression()?));
+                return Ok(Expression::boxed_assignment(id, self.expression()?, t.line));
+            }
+            if let Some(op) = self.consume_compound_op() {
+                return Ok(Expression::boxed_compound_assignment(id, op, self.expression()?, t.line));
             }
         }
 
-        let out = self.logic_or(t);
+        let out = self.ternary(t);
 
-        // Check for setter to some field
+        // Check for setter to some field or index
         if let Ok(contents) = out.clone() {
-            if let Expression::Getter(obj, name) = *contents {
-                if self.consume(TokenData::Equal).is_some() {
-                    return Ok(Expression::boxed_setter(obj, &name, self.expression()?));
-                }
+            match *contents {
+                Expression::Getter(obj, name) => {
+                    if self.consume(TokenData::Equal).is_some() {
+                        return Ok(Expression::boxed_setter(obj, &name, self.expression()?));
+                    }
+                    if let Some(op) = self.consume_compound_op() {
+                        return Ok(Expression::boxed_compound_setter(obj, &name, op, self.expression()?));
+                    }
+                },
+                Expression::Index(obj, index) => {
+                    if self.consume(TokenData::Equal).is_some() {
+                        return Ok(Expression::boxed_index_setter(obj, index, self.expression()?));
+                    }
+                    if let Some(op) = self.consume_compound_op() {
+                        return Ok(Expression::boxed_compound_index_setter(obj, index, op, self.expression()?));
+                    }
+                },
+                _ => (),
             }
         }
         
         out
     }
 
+    // Consumes a compound assignment operator, returning the operation it applies.
+    fn consume_compound_op(&mut self) -> Option<BinaryOp> {
+        let op = match self.peek() {
+            Some(Token { data: TokenData::PlusEqual, line: _ }) => BinaryOp::Add,
+            Some(Token { data: TokenData::MinusEqual, line: _ }) => BinaryOp::Subtract,
+            Some(Token { data: TokenData::StarEqual, line: _ }) => BinaryOp::Multiply,
+            Some(Token { data: TokenData::SlashEqual, line: _ }) => BinaryOp::Divide,
+            Some(Token { data: TokenData::PercentEqual, line: _ }) => BinaryOp::Modulo,
+            _ => return None,
+        };
+        self.advance().expect("peek() should guarantee advance()");
+        Some(op)
+    }
+
+    fn ternary(&mut self, t: Token) -> Result<Box<Expression>, ()> {
+        let cond = self.logic_or(t)?;
+        if self.consume(TokenData::Question).is_some() {
+            let then_branch = self.expression()?;
+            if self.consume(TokenData::Colon).is_none() {
+                self.add_error("Expected ':' after then branch of conditional expression.");
+                return Err(());
+            }
+            let right = self.advance()?;
+            let else_branch = self.ternary(right)?;
+            Ok(Expression::boxed_ternary(cond, then_branch, else_branch))
+        } else {
+            Ok(cond)
+        }
+    }
+
     fn logic_or(&mut self, t: Token) -> Result<Box<Expression>, ()> {
         let mut e = self.logic_and(t)?;
         loop {
@@ -474,7 +1002,7 @@ impl LoxParser {
     }
 
     fn comparison(&mut self, t: Token) -> Result<Box<Expression>, ()> {
-        let mut e = self.term(t)?;
+        let mut e = self.bit_or(t)?;
         loop {
             match self.peek() {
                 Some(Token { data: TokenData::Less, line: _ }) => {
@@ -483,7 +1011,7 @@ impl LoxParser {
                     e = Expression::boxed_binary(
                         e,
                         BinaryOp::Less,
-                        self.term(right)?,
+                        self.bit_or(right)?,
                     )
                 },
                 Some(Token { data: TokenData::LessEqual, line: _ }) => {
@@ -492,7 +1020,7 @@ impl LoxParser {
                     e = Expression::boxed_binary(
                         e,
                         BinaryOp::LessEqual,
-                        self.term(right)?,
+                        self.bit_or(right)?,
                     )
                 },
                 Some(Token { data: TokenData::Greater, line: _ }) => {
@@ -501,7 +1029,7 @@ impl LoxParser {
                     e = Expression::boxed_binary(
                         e,
                         BinaryOp::Greater,
-                        self.term(right)?,
+                        self.bit_or(right)?,
                     )
                 },
                 Some(Token { data: TokenData::GreaterEqual, line: _ }) => {
@@ -510,6 +1038,76 @@ impl LoxParser {
                     e = Expression::boxed_binary(
                         e,
                         BinaryOp::GreaterEqual,
+                        self.bit_or(right)?,
+                    )
+                },
+                _ => break,
+            };
+        }
+        Ok(e)
+    }
+
+    fn bit_or(&mut self, t: Token) -> Result<Box<Expression>, ()> {
+        let mut e = self.bit_xor(t)?;
+        while let Some(Token { data: TokenData::Pipe, .. }) = self.peek() {
+            self.advance()?;
+            let right = self.advance()?;
+            e = Expression::boxed_binary(
+                e,
+                BinaryOp::BitOr,
+                self.bit_xor(right)?,
+            )
+        }
+        Ok(e)
+    }
+
+    fn bit_xor(&mut self, t: Token) -> Result<Box<Expression>, ()> {
+        let mut e = self.bit_and(t)?;
+        while let Some(Token { data: TokenData::Caret, .. }) = self.peek() {
+            self.advance()?;
+            let right = self.advance()?;
+            e = Expression::boxed_binary(
+                e,
+                BinaryOp::BitXor,
+                self.bit_and(right)?,
+            )
+        }
+        Ok(e)
+    }
+
+    fn bit_and(&mut self, t: Token) -> Result<Box<Expression>, ()> {
+        let mut e = self.shift(t)?;
+        while let Some(Token { data: TokenData::Ampersand, .. }) = self.peek() {
+            self.advance()?;
+            let right = self.advance()?;
+            e = Expression::boxed_binary(
+                e,
+                BinaryOp::BitAnd,
+                self.shift(right)?,
+            )
+        }
+        Ok(e)
+    }
+
+    fn shift(&mut self, t: Token) -> Result<Box<Expression>, ()> {
+        let mut e = self.term(t)?;
+        loop {
+            match self.peek() {
+                Some(Token { data: TokenData::LessLess, line: _ }) => {
+                    self.advance()?;
+                    let right = self.advance()?;
+                    e = Expression::boxed_binary(
+                        e,
+                        BinaryOp::ShiftLeft,
+                        self.term(right)?,
+                    )
+                },
+                Some(Token { data: TokenData::GreaterGreater, line: _ }) => {
+                    self.advance()?;
+                    let right = self.advance()?;
+                    e = Expression::boxed_binary(
+                        e,
+                        BinaryOp::ShiftRight,
                         self.term(right)?,
                     )
                 },
@@ -578,6 +1176,15 @@ impl LoxParser {
                         self.unary(right)?,
                     );
                 },
+                Some(Token { data: TokenData::TildeSlash, line: _ }) => {
+                    self.advance()?;
+                    let right = self.advance()?;
+                    e = Expression::boxed_binary(
+                        e,
+                        BinaryOp::IntDivide,
+                        self.unary(right)?,
+                    );
+                },
                 _ => break,
             };
         }
@@ -611,10 +1218,35 @@ impl LoxParser {
                     continue;
                 }
 
-                let mut args: Vec<Box<Expression>> = Vec::new();
+                let mut args: Vec<Expression> = Vec::new();
+                let mut comments = Vec::new();
+                let mut names: Vec<String> = Vec::new();
                 loop {
-                    args.push(self.expression()?);
-                    if !self.consume(TokenData::Comma).is_some() { break; }
+                    let named = match (self.peek(), self.peek_second()) {
+                        (Some(Token{data: TokenData::Identifier(name), ..}), Some(Token{data: TokenData::Colon, ..})) => Some(name),
+                        _ => None,
+                    };
+                    if let Some(name) = named {
+                        self.advance()?;
+                        self.advance()?;
+                        if names.contains(&name) {
+                            self.add_error(&format!("Argument {} was passed more than once.", name));
+                        }
+                        args.push(Expression::Named(name.clone(), self.expression()?));
+                        names.push(name);
+                    } else {
+                        if !names.is_empty() {
+                            self.add_error("Positional arguments must come before named arguments.");
+                        }
+                        if self.consume(TokenData::Ellipsis).is_some() {
+                            args.push(Expression::Spread(self.expression()?));
+                        } else {
+                            args.push(*self.expression()?);
+                        }
+                    }
+                    let (more, comment) = self.list_separator();
+                    comments.extend(comment.map(|(text, line)| (args.len() - 1, text, line)));
+                    if !more { break; }
                 }
 
                 if self.consume(TokenData::RightParen).is_some() {
@@ -622,13 +1254,22 @@ impl LoxParser {
                         self.add_error(&concat!("Can't have more than 255 arguments."));
                         // State is otherwise still valid, so no need to return Err(())
                     }
-                    expr = Expression::boxed_call(expr, args, line);
+                    expr = Box::new(Expression::Call(expr, args, comments, line));
                 } else {
                     self.add_error("Unexpectedly reached end of file while parsing arguments.");
                     return Err(());
                 }
             }
 
+            else if self.consume(TokenData::LeftBracket).is_some() {
+                let index = self.expression()?;
+                if self.consume(TokenData::RightBracket).is_none() {
+                    self.add_error("Expected ']' after index.");
+                    return Err(());
+                }
+                expr = Expression::boxed_index(expr, index);
+            }
+
             else if self.consume(TokenData::Dot).is_some() {
                 let next = self.consume(TokenData::Identifier(String::from("")));
                 if let Some(Token{data: TokenData::Identifier(name), line}) = next {
@@ -649,6 +1290,7 @@ impl LoxParser {
         match t.data {
             TokenData::Identifier(id) => Ok(Expression::boxed_identifier(&id)),
             TokenData::Number(n) => Ok(Expression::boxed_number(n)),
+            TokenData::Integer(n) => Ok(Expression::boxed_integer(n)),
             TokenData::StringData(s) => Ok(Expression::boxed_string(&s)),
             TokenData::True => Ok(Expression::boxed_boolean(true)),
             TokenData::False => Ok(Expression::boxed_boolean(false)),
@@ -658,7 +1300,7 @@ impl LoxParser {
                     self.add_error("Can't use 'this' outside of a class.");
                     // no need to synchronize, though.
                 }
-                Ok(Expression::boxed_this())
+                Ok(Expression::boxed_this(t.line))
             },
             TokenData::Super => {
                 if self.access != Subclass {
@@ -679,11 +1321,46 @@ impl LoxParser {
                 
             },
 
-            TokenData::LeftParen => {
-                let e = self.expression()?;
-                if !self.is_at_end() {
-                    if let Some(Token{ data: TokenData::RightParen, line: _ }) = self.peek() {
-                        self.advance()?;
+            TokenData::LeftBracket => {
+                let mut items = Vec::new();
+                while self.consume(TokenData::RightBracket).is_none() {
+                    items.push(*self.expression()?);
+                    if self.consume(TokenData::Comma).is_none() {
+                        if self.consume(TokenData::RightBracket).is_none() {
+                            self.add_error("Expected ']' after list items.");
+                            return Err(());
+                        }
+                        break;
+                    }
+                }
+                Ok(Expression::boxed_list(items))
+            },
+
+            TokenData::LeftBrace => {
+                let mut entries = Vec::new();
+                while self.consume(TokenData::RightBrace).is_none() {
+                    let key = self.expression()?;
+                    if self.consume(TokenData::Colon).is_none() {
+                        self.add_error("Expected ':' after map key.");
+                        return Err(());
+                    }
+                    entries.push((key, self.expression()?));
+                    if self.consume(TokenData::Comma).is_none() {
+                        if self.consume(TokenData::RightBrace).is_none() {
+                            self.add_error("Expected '}' after map entries.");
+                            return Err(());
+                        }
+                        break;
+                    }
+                }
+                Ok(Expression::boxed_map(entries))
+            },
+
+            TokenData::LeftParen => {
+                let e = self.expression()?;
+                if !self.is_at_end() {
+                    if let Some(Token{ data: TokenData::RightParen, line: _ }) = self.peek() {
+                        self.advance()?;
                         Ok(Expression::boxed_grouping(e))
                     } else {
                         self.add_error("Missing close parenthesis.");
@@ -707,6 +1384,51 @@ impl LoxParser {
         }
     }
 
+    // The comment on the same line as the last token consumed, if there is one.
+    fn trailing_comment(&mut self) -> Option<Statement> {
+        match self.comments.first() {
+            Some((at, t)) if *at == self.current && self.tokens[at-1].line == t.line => {
+                let (_, t) = self.comments.remove(0);
+                let TokenData::Comment(text) = t.data else { panic!("Only comment tokens should be kept as comments") };
+                Some(Statement::Comment(text, true, t.line))
+            },
+            _ => None,
+        }
+    }
+
+    // Consumes the comma after an item in a parameter or argument list, if there is one, and
+    // takes a kept comment on the item's line from either side of it.
+    fn list_separator(&mut self) -> (bool, Option<(String, usize)>) {
+        let before = self.trailing_comment();
+        let more = self.consume(TokenData::Comma).is_some();
+        let after = if more && before.is_none() { self.trailing_comment() } else { None };
+        match before.or(after) {
+            Some(Statement::Comment(text, _, line)) => (more, Some((text, line))),
+            _ => (more, None),
+        }
+    }
+
+    // Turns the kept comments that come before token index 'end' into statements. A comment
+    // trails code if it's on the same line as the token before it.
+    fn take_comments(&mut self, end: usize) -> Vec<Statement> {
+        let count = self.comments.iter().take_while(|(at, _)| *at < end).count();
+        let tokens = &self.tokens;
+        self.comments.drain(..count)
+            .map(|(at, t)| {
+                let trailing = at > 0 && tokens[at-1].line == t.line;
+                let TokenData::Comment(text) = t.data else { panic!("Only comment tokens should be kept as comments") };
+                Statement::Comment(text, trailing, t.line)
+            })
+            .collect()
+    }
+
+    // Called after each statement or member in a list. Any kept comment still waiting before the
+    // current token came from inside the one just parsed, since nested lists take their own.
+    fn note_interruptions(&mut self) {
+        let inside = self.comments.iter().take_while(|(at, _)| *at < self.current);
+        self.interruptions.extend(inside.map(|(_, t)| t.line));
+    }
+
     fn synchronize(&mut self) {
         while !self.is_at_end() {
             let prev = self.advance().expect("While loop condition should guarantee advance()");
@@ -715,12 +1437,17 @@ impl LoxParser {
             if let Some(t) = next {
                 match t.data {
                     TokenData::Class |
+                    TokenData::Trait |
+                    TokenData::Enum |
                     TokenData::Fun |
                     TokenData::Var |
+                    TokenData::Const |
                     TokenData::For |
                     TokenData::If |
                     TokenData::While |
                     TokenData::Print |
+                    TokenData::Match |
+                    TokenData::Yield |
                     TokenData::Return => break,
                     _ => (),
                 }
@@ -737,6 +1464,11 @@ impl LoxParser {
         else { None }
     }
 
+    // Looks one token past peek(); also safe to call at end of file.
+    fn peek_second(&self) -> Option<Token> {
+        self.tokens.get(self.current + 1).cloned()
+    }
+
     // Will consume the next token iff it is of the same type as error_str.
     fn consume(&mut self, token_type: TokenData) -> Option<Token> {
         use std::mem::discriminant;
@@ -837,7 +1569,12 @@ mod tests {
             panic!("Error while parsing statement: {:?}", parser.error_strings);
         }
     }
-    
+
+    // The signature of a function with plain parameters, declared on the given line.
+    fn signature_at(line: usize) -> Rc<Signature> {
+        Rc::new(Signature{ line, ..Signature::default() })
+    }
+
     mod atomic_expressions {
         use super::*;
 
@@ -889,9 +1626,9 @@ mod tests {
         fn test_expression_modulo() {
             let test_str = "3 % 5";
             let expected = Expression::boxed_binary(
-                Expression::boxed_number(3.0),
+                Expression::boxed_integer(3),
                 BinaryOp::Modulo,
-                Expression::boxed_number(5.0),
+                Expression::boxed_integer(5),
             );
             test_expression_generic(test_str, expected);
         }
@@ -900,20 +1637,39 @@ mod tests {
         fn test_expression_divide() {
             let test_str = "3 / 5";
             let expected = Expression::boxed_binary(
-                Expression::boxed_number(3.0),
+                Expression::boxed_integer(3),
                 BinaryOp::Divide,
-                Expression::boxed_number(5.0),
+                Expression::boxed_integer(5),
+            );
+            test_expression_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_expression_int_divide() {
+            let test_str = "7 ~/ 2";
+            let expected = Expression::boxed_binary(
+                Expression::boxed_integer(7),
+                BinaryOp::IntDivide,
+                Expression::boxed_integer(2),
             );
             test_expression_generic(test_str, expected);
         }
 
+        // Integer division is spelled ~/ because // already starts a comment.
+        #[test]
+        fn test_double_slash_is_comment() {
+            let test_str = "print a // b;\n;";
+            let expected = vec![Statement::Print(Expression::boxed_identifier("a"), 1)];
+            test_program_generic(test_str, expected);
+        }
+
         #[test]
         fn test_expression_multiply() {
             let test_str = "4.1 * 5";
             let expected = Expression::boxed_binary(
                 Expression::boxed_number(4.1),
                 BinaryOp::Multiply,
-                Expression::boxed_number(5.0),
+                Expression::boxed_integer(5),
             );
             test_expression_generic(test_str, expected);
         }
@@ -924,7 +1680,7 @@ mod tests {
             let expected = Expression::boxed_binary(
                 Expression::boxed_number(4.1),
                 BinaryOp::Add,
-                Expression::boxed_number(5.0),
+                Expression::boxed_integer(5),
             );
             test_expression_generic(test_str, expected);
         }
@@ -935,7 +1691,7 @@ mod tests {
             let expected = Expression::boxed_binary(
                 Expression::boxed_number(4.1),
                 BinaryOp::Subtract,
-                Expression::boxed_number(5.0),
+                Expression::boxed_integer(5),
             );
             test_expression_generic(test_str, expected);
         }
@@ -946,7 +1702,7 @@ mod tests {
             let expected = Expression::boxed_binary(
                 Expression::boxed_number(4.1),
                 BinaryOp::Less,
-                Expression::boxed_number(5.0),
+                Expression::boxed_integer(5),
             );
             test_expression_generic(test_str, expected);
         }
@@ -957,7 +1713,7 @@ mod tests {
             let expected = Expression::boxed_binary(
                 Expression::boxed_number(4.1),
                 BinaryOp::LessEqual,
-                Expression::boxed_number(5.0),
+                Expression::boxed_integer(5),
             );
             test_expression_generic(test_str, expected);
         }
@@ -968,7 +1724,7 @@ mod tests {
             let expected = Expression::boxed_binary(
                 Expression::boxed_number(4.1),
                 BinaryOp::Greater,
-                Expression::boxed_number(5.0),
+                Expression::boxed_integer(5),
             );
             test_expression_generic(test_str, expected);
         }
@@ -979,7 +1735,7 @@ mod tests {
             let expected = Expression::boxed_binary(
                 Expression::boxed_number(4.1),
                 BinaryOp::GreaterEqual,
-                Expression::boxed_number(5.0),
+                Expression::boxed_integer(5),
             );
             test_expression_generic(test_str, expected);
         }
@@ -990,7 +1746,7 @@ mod tests {
             let expected = Expression::boxed_binary(
                 Expression::boxed_number(4.1),
                 BinaryOp::Equal,
-                Expression::boxed_number(5.0),
+                Expression::boxed_integer(5),
             );
             test_expression_generic(test_str, expected);
         }
@@ -1001,7 +1757,7 @@ mod tests {
             let expected = Expression::boxed_binary(
                 Expression::boxed_number(4.1),
                 BinaryOp::NotEqual,
-                Expression::boxed_number(5.0),
+                Expression::boxed_integer(5),
             );
             test_expression_generic(test_str, expected);
         }
@@ -1042,16 +1798,16 @@ mod tests {
             let test_str = "3 + -4 * -5 - 6";
             let expected = Expression::boxed_binary(
                 Expression::boxed_binary(
-                    Expression::boxed_number(3.0),
+                    Expression::boxed_integer(3),
                     BinaryOp::Add,
                     Expression::boxed_binary(
-                        Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_number(4.0)),
+                        Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_integer(4)),
                         BinaryOp::Multiply,
-                        Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_number(5.0)),
+                        Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_integer(5)),
                     ),
                 ),
                 BinaryOp::Subtract,
-                Expression::boxed_number(6.0),
+                Expression::boxed_integer(6),
             );
             test_expression_generic(test_str, expected);
         }
@@ -1062,22 +1818,22 @@ mod tests {
             let expected = Expression::boxed_logical(
                 Expression::boxed_logical(
                     Expression::boxed_logical(
-                        Expression::boxed_number(1.0),
+                        Expression::boxed_integer(1),
                         LogicOp::And,
-                        Expression::boxed_number(2.0),
+                        Expression::boxed_integer(2),
                     ),
                     LogicOp::Or,
                     Expression::boxed_logical(
-                        Expression::boxed_number(3.0),
+                        Expression::boxed_integer(3),
                         LogicOp::And,
-                        Expression::boxed_number(4.0),
+                        Expression::boxed_integer(4),
                     ),
                 ),
                 LogicOp::Or,
                 Expression::boxed_logical(
-                    Expression::boxed_number(5.0),
+                    Expression::boxed_integer(5),
                     LogicOp::And,
-                    Expression::boxed_number(6.0),
+                    Expression::boxed_integer(6),
                 ),
             );
             test_expression_generic(test_str, expected);
@@ -1089,12 +1845,12 @@ mod tests {
             let expected = Expression::boxed_binary(
                 Expression::boxed_binary(
                     Expression::boxed_binary(
-                        Expression::boxed_number(15.0),
+                        Expression::boxed_integer(15),
                         BinaryOp::Modulo,
-                        Expression::boxed_number(5.0),
+                        Expression::boxed_integer(5),
                     ),
                     BinaryOp::GreaterEqual,
-                    Expression::boxed_number(2.0),
+                    Expression::boxed_integer(2),
                 ),
                 BinaryOp::NotEqual,
                 Expression::boxed_binary(
@@ -1104,7 +1860,7 @@ mod tests {
                         Expression::boxed_number(1.5),
                     ),
                     BinaryOp::Less,
-                    Expression::boxed_number(2.0),
+                    Expression::boxed_integer(2),
                 ),
             );
             test_expression_generic(test_str, expected);
@@ -1115,15 +1871,15 @@ mod tests {
             let test_str = "(3 + -4) * (-5 - 6)";
             let expected = Expression::boxed_binary(
                 Expression::boxed_grouping(Expression::boxed_binary(
-                    Expression::boxed_number(3.0),
+                    Expression::boxed_integer(3),
                     BinaryOp::Add,
-                    Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_number(4.0)),
+                    Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_integer(4)),
                 )),
                 BinaryOp::Multiply,
                 Expression::boxed_grouping(Expression::boxed_binary(
-                    Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_number(5.0)),
+                    Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_integer(5)),
                     BinaryOp::Subtract,
-                    Expression::boxed_number(6.0),
+                    Expression::boxed_integer(6),
                 )),
             );
             test_expression_generic(test_str, expected);
@@ -1139,8 +1895,10 @@ mod tests {
             let expected = Statement::Expr(
                 Expression::boxed_assignment(
                     "i",
-                    Expression::boxed_number(0.0),
-                )
+                    Expression::boxed_integer(0),
+                    1,
+                ),
+                1,
             );
             test_statement_generic(test_str, expected);
         }
@@ -1153,14 +1911,146 @@ mod tests {
                     "i",
                     Expression::boxed_assignment(
                         "j",
-                        Expression::boxed_number(1.0),
-                    )
-                )
+                        Expression::boxed_integer(1),
+                        1,
+                    ),
+                    1,
+                ),
+                1,
+            );
+            test_statement_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_statement_compound_assignment() {
+            let test_str = "i *= j += 2;";
+            let expected = Statement::Expr(
+                Expression::boxed_compound_assignment(
+                    "i",
+                    BinaryOp::Multiply,
+                    Expression::boxed_compound_assignment(
+                        "j",
+                        BinaryOp::Add,
+                        Expression::boxed_integer(2),
+                        1,
+                    ),
+                    1,
+                ),
+                1,
+            );
+            test_statement_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_statement_compound_setter() {
+            let test_str = "obj.count -= 1;";
+            let expected = Statement::Expr(
+                Expression::boxed_compound_setter(
+                    Expression::boxed_identifier("obj"),
+                    "count",
+                    BinaryOp::Subtract,
+                    Expression::boxed_integer(1),
+                ),
+                1,
             );
             test_statement_generic(test_str, expected);
         }
     }
 
+    mod index_expressions {
+        use super::*;
+
+        #[test]
+        fn test_expression_index() {
+            let test_str = "grid[1][i + 1]";
+            let expected = Expression::boxed_index(
+                Expression::boxed_index(
+                    Expression::boxed_identifier("grid"),
+                    Expression::boxed_integer(1),
+                ),
+                Expression::boxed_binary(
+                    Expression::boxed_identifier("i"),
+                    BinaryOp::Add,
+                    Expression::boxed_integer(1),
+                ),
+            );
+            test_expression_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_expression_index_setter() {
+            let test_str = "obj.items[0] = 5";
+            let expected = Expression::boxed_index_setter(
+                Expression::boxed_getter(Expression::boxed_identifier("obj"), "items"),
+                Expression::boxed_integer(0),
+                Expression::boxed_integer(5),
+            );
+            test_expression_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_expression_compound_index_setter() {
+            let test_str = "counts[key] += 1";
+            let expected = Expression::boxed_compound_index_setter(
+                Expression::boxed_identifier("counts"),
+                Expression::boxed_identifier("key"),
+                BinaryOp::Add,
+                Expression::boxed_integer(1),
+            );
+            test_expression_generic(test_str, expected);
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_expression_index_unclosed() {
+            let test_str = "grid[1";
+            let expected = Expression::boxed_identifier("grid");
+            test_expression_generic(test_str, expected);
+        }
+    }
+
+    mod ternary_expressions {
+        use super::*;
+
+        #[test]
+        fn test_expression_ternary() {
+            let test_str = "a or b ? 1 : 2";
+            let expected = Expression::boxed_ternary(
+                Expression::boxed_logical(
+                    Expression::boxed_identifier("a"),
+                    LogicOp::Or,
+                    Expression::boxed_identifier("b"),
+                ),
+                Expression::boxed_integer(1),
+                Expression::boxed_integer(2),
+            );
+            test_expression_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_expression_ternary_right_associative() {
+            let test_str = "a ? 1 : b ? 2 : 3";
+            let expected = Expression::boxed_ternary(
+                Expression::boxed_identifier("a"),
+                Expression::boxed_integer(1),
+                Expression::boxed_ternary(
+                    Expression::boxed_identifier("b"),
+                    Expression::boxed_integer(2),
+                    Expression::boxed_integer(3),
+                ),
+            );
+            test_expression_generic(test_str, expected);
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_expression_ternary_expects_colon() {
+            let test_str = "a ? 1 2";
+            let expected = Expression::boxed_identifier("a");
+            test_expression_generic(test_str, expected);
+        }
+    }
+
     mod statements {
         use super::*;
 
@@ -1169,17 +2059,17 @@ mod tests {
             let test_str = "3 + -4 * -5 - 6;";
             let expected = Statement::Expr(Expression::boxed_binary(
                 Expression::boxed_binary(
-                    Expression::boxed_number(3.0),
+                    Expression::boxed_integer(3),
                     BinaryOp::Add,
                     Expression::boxed_binary(
-                        Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_number(4.0)),
+                        Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_integer(4)),
                         BinaryOp::Multiply,
-                        Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_number(5.0)),
+                        Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_integer(5)),
                     ),
                 ),
                 BinaryOp::Subtract,
-                Expression::boxed_number(6.0),
-            ));
+                Expression::boxed_integer(6),
+            ), 1);
             test_statement_generic(test_str, expected);
         }
 
@@ -1188,17 +2078,17 @@ mod tests {
             let test_str = "print 3 + -4 * -5 - 6;";
             let expected = Statement::Print(Expression::boxed_binary(
                 Expression::boxed_binary(
-                    Expression::boxed_number(3.0),
+                    Expression::boxed_integer(3),
                     BinaryOp::Add,
                     Expression::boxed_binary(
-                        Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_number(4.0)),
+                        Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_integer(4)),
                         BinaryOp::Multiply,
-                        Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_number(5.0)),
+                        Expression::boxed_unary(UnaryOp::Negative, Expression::boxed_integer(5)),
                     ),
                 ),
                 BinaryOp::Subtract,
-                Expression::boxed_number(6.0),
-            ));
+                Expression::boxed_integer(6),
+            ), 1);
             test_statement_generic(test_str, expected);
         }
 
@@ -1210,7 +2100,34 @@ mod tests {
                 let test_str = "var i = 0;";
                 let expected = Statement::Decl(
                     String::from("i"),
-                    Expression::boxed_number(0.0),
+                    Expression::boxed_integer(0),
+                    None,
+                    1,
+                );
+                test_statement_generic(test_str, expected);
+            }
+
+            #[test]
+            fn test_statement_const() {
+                let test_str = "const limit = 10;";
+                let expected = Statement::Const(
+                    String::from("limit"),
+                    Expression::boxed_integer(10),
+                    None,
+                    1,
+                );
+                test_statement_generic(test_str, expected);
+            }
+
+            #[test]
+            #[should_panic]
+            fn test_statement_const_expects_initializer() {
+                let test_str = "const limit;";
+                let expected = Statement::Const(
+                    String::from("limit"),
+                    Expression::boxed_nil(),
+                    None,
+                    1,
                 );
                 test_statement_generic(test_str, expected);
             }
@@ -1221,7 +2138,9 @@ mod tests {
                 let test_str = "var i 0;";
                 let expected = Statement::Decl(
                     String::from("i"),
-                    Expression::boxed_number(0.0),
+                    Expression::boxed_integer(0),
+                    None,
+                    1,
                 );
                 test_statement_generic(test_str, expected);
             }
@@ -1232,7 +2151,9 @@ mod tests {
                 let test_str = "var i = print 0;";
                 let expected = Statement::Decl(
                     String::from("i"),
-                    Expression::boxed_number(0.0),
+                    Expression::boxed_integer(0),
+                    None,
+                    1,
                 );
                 test_statement_generic(test_str, expected);
             }
@@ -1245,7 +2166,9 @@ mod tests {
                 Expression::boxed_boolean(true),
                 Box::new(Statement::Print(
                     Expression::boxed_string("This is the program that never ends~"),
+                    1,
                 )),
+                1,
             );
             test_statement_generic(test_str, expected);
         }
@@ -1254,153 +2177,611 @@ mod tests {
         fn test_statement_for() {
             let test_str = "for (var i = 0; i < 10; i = i * 2) print i;";
             let expected = Statement::Block(vec![
-                Box::new(Statement::Decl(
+                Statement::Decl(
                     String::from("i"),
-                    Expression::boxed_number(0.0),
-                )),
-                Box::new(Statement::While(
+                    Expression::boxed_integer(0),
+                    None,
+                    1,
+                ),
+                Statement::While(
                     Expression::boxed_binary(
                         Expression::boxed_identifier("i"),
                         BinaryOp::Less,
-                        Expression::boxed_number(10.0),
+                        Expression::boxed_integer(10),
                     ),
                     Box::new(Statement::Block(vec![
-                        Box::new(Statement::Print(
+                        Statement::Print(
                             Expression::boxed_identifier("i"),
-                        )),
-                        Box::new(Statement::Expr(Expression::boxed_assignment(
+                            1,
+                        ),
+                        Statement::Expr(Expression::boxed_assignment(
                             "i",
                             Expression::boxed_binary(
                                 Expression::boxed_identifier("i"),
                                 BinaryOp::Multiply,
-                                Expression::boxed_number(2.0),
+                                Expression::boxed_integer(2),
                             ),
-                        ))),
+                            1,
+                        ), 1),
                     ])),
-                )),
+                    1,
+                ),
             ]);
-            test_statement_generic(test_str, expected);
+            let mut parser = LoxParser::new();
+            parser.load_string(test_str).expect("Error while scanning input string");
+            let Ok(Statement::For(init, cond, incr, body, line)) = parser.statement() else {
+                panic!("Expected a for statement.");
+            };
+            assert_eq!(expected, Statement::desugar_for(init, cond, incr, *body, line), "Expected left output; recieved right");
         }
     }
 
-    mod programs {
+    mod collections {
         use super::*;
 
         #[test]
-        fn test_program_hello_world() {
-            let source = concat!(
-                "var my_var = \"Hello, world!\";\n",
-                "print my_var;\n",
-            );
-            let expected = vec![
-                Statement::Decl(
-                    String::from("my_var"),
-                    Expression::boxed_string("Hello, world!"),
-                ),
-                Statement::Print(
-                    Expression::boxed_identifier("my_var"),
-                ),
-            ];
-            test_program_generic(source, expected);
+        fn test_expression_list_literal() {
+            let test_str = "[1, \"two\", [],]";
+            let expected = Expression::boxed_list(vec![
+                *Expression::boxed_integer(1),
+                *Expression::boxed_string("two"),
+                *Expression::boxed_list(vec![]),
+            ]);
+            test_expression_generic(test_str, expected);
         }
 
         #[test]
-        fn test_program_assignments() {
-            let source = concat!(
-                "var i;\n",
-                "var j = 2;\n",
-                "var k = 3 + 4;\n",
-                "i = 3;\n",
-                "j = 3 - 1;\n",
-                "k = j + k + 3;\n",
-                "i = j = k;\n",
-            );
-            let expected = vec![
-                Statement::Decl(
-                    String::from("i"),
-                    Expression::boxed_nil(),
-                ),
-                Statement::Decl(
-                    String::from("j"),
-                    Expression::boxed_number(2.0),
-                ),
-                Statement::Decl(
-                    String::from("k"),
-                    Expression::boxed_binary(
-                        Expression::boxed_number(3.0),
-                        BinaryOp::Add,
-                        Expression::boxed_number(4.0),
-                    )
-                ),
-                Statement::Expr(
-                    Expression::boxed_assignment(
-                        "i",
-                        Expression::boxed_number(3.0),
-                    )
-                ),
-                Statement::Expr(
-                    Expression::boxed_assignment(
-                        "j",
-                        Expression::boxed_binary(
-                            Expression::boxed_number(3.0),
-                            BinaryOp::Subtract,
-                            Expression::boxed_number(1.0),
-                        )
-                    )
-                ),
-                Statement::Expr(
-                    Expression::boxed_assignment(
-                        "k",
-                        Expression::boxed_binary(
-                            Expression::boxed_binary(
-                                Expression::boxed_identifier("j"),
-                                BinaryOp::Add,
-                                Expression::boxed_identifier("k"),
-                            ),
-                            BinaryOp::Add,
-                            Expression::boxed_number(3.0),
-                        )
-                    )
-                ),
-                Statement::Expr(
-                    Expression::boxed_assignment(
-                        "i",
-                        Expression::boxed_assignment(
-                            "j",
-                            Expression::boxed_identifier("k"),
-                        )
-                    )
+        fn test_expression_map_literal() {
+            let test_str = "{\"a\": 1, k: flag ? 2 : 3}";
+            let expected = Expression::boxed_map(vec![
+                (Expression::boxed_string("a"), Expression::boxed_integer(1)),
+                (
+                    Expression::boxed_identifier("k"),
+                    Expression::boxed_ternary(
+                        Expression::boxed_identifier("flag"),
+                        Expression::boxed_integer(2),
+                        Expression::boxed_integer(3),
+                    ),
                 ),
-            ];
-            test_program_generic(source, expected);
+            ]);
+            test_expression_generic(test_str, expected);
         }
 
         #[test]
-        fn test_program_blocks() {
-            let source = concat!(
-                "{}\n",
-                "var global = 23;\n",
-                "{\n",
-                "   var local = 3;\n",
-                "   { print local; }\n",
-                "}\n",
+        #[should_panic]
+        fn test_expression_list_unclosed() {
+            let test_str = "[1, 2";
+            let expected = Expression::boxed_list(vec![]);
+            test_expression_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_statement_for_in() {
+            let test_str = "for (var x in items) print x;";
+            let expected = Statement::ForIn(
+                String::from("x"),
+                Expression::boxed_identifier("items"),
+                Box::new(Statement::Print(Expression::boxed_identifier("x"), 1)),
+                1,
             );
-            let expected = vec![
-                Statement::Block(vec![]),
-                Statement::Decl(
-                    String::from("global"),
-                    Expression::boxed_number(23.0),
+            test_statement_generic(test_str, expected);
+        }
+    }
+
+    mod enums {
+        use super::*;
+
+        #[test]
+        fn test_statement_enum() {
+            let test_str = "enum Shape { Circle(r), Rect(w, h), Empty, }";
+            let expected = Statement::Enum(
+                String::from("Shape"),
+                vec![
+                    (String::from("Circle"), vec![String::from("r")]),
+                    (String::from("Rect"), vec![String::from("w"), String::from("h")]),
+                    (String::from("Empty"), vec![]),
+                ],
+            );
+            test_statement_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_statement_match_variants() {
+            let test_str = "match (s) { case Shape.Rect(w, h), Shape.Empty => print w; }";
+            let expected = Statement::Match(
+                Expression::boxed_identifier("s"),
+                vec![MatchArm::new(
+                    vec![
+                        Pattern::Variant(String::from("Shape"), String::from("Rect"), vec![String::from("w"), String::from("h")]),
+                        Pattern::Variant(String::from("Shape"), String::from("Empty"), vec![]),
+                    ],
+                    None,
+                    Box::new(Statement::Print(Expression::boxed_identifier("w"), 1)),
+                )],
+                None,
+                1,
+            );
+            test_statement_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_enum_errors() {
+            let cases = [
+                ("enum Color { Red, Red }", "Enum Color declares variant Red more than once."),
+                ("enum Color { Red() }", "Variant Red needs at least one field"),
+                ("enum Color { Red Green }", "Expected '}' after enum variants."),
+            ];
+            for (source, message) in cases {
+                let mut parser = LoxParser::new();
+                parser.load_string(source).expect("Error while scanning input string");
+                let errors = parser.parse().expect_err("Unexpectedly parsed invalid enum code");
+                assert!(errors.iter().any(|e| e.contains(message)), "Unexpected errors for {}: {:?}", source, errors);
+            }
+        }
+    }
+
+    mod generators {
+        use super::*;
+
+        #[test]
+        fn test_statement_generator() {
+            let test_str = "fun* count(n) { yield n; yield; return; }";
+            let expected = Statement::Generator(
+                String::from("count"),
+                vec![String::from("n")],
+                vec![
+                    Statement::Yield(Expression::boxed_identifier("n"), 1),
+                    Statement::Yield(Expression::boxed_nil(), 1),
+                    Statement::Return(Expression::boxed_nil(), 1),
+                ],
+                signature_at(1),
+            );
+            test_statement_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_statement_nested_function_is_not_generator() {
+            let test_str = "fun* outer() { fun inner() { return 1; } yield inner(); }";
+            let expected = Statement::Generator(
+                String::from("outer"),
+                vec![],
+                vec![
+                    Statement::Fun(
+                        String::from("inner"),
+                        vec![],
+                        vec![Statement::Return(Expression::boxed_integer(1), 1)],
+                        signature_at(1),
+                    ),
+                    Statement::Yield(Expression::boxed_call(
+                        Expression::boxed_identifier("inner"),
+                        vec![],
+                        1,
+                    ), 1),
+                ],
+                signature_at(1),
+            );
+            test_statement_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_generator_errors() {
+            let cases = [
+                ("yield 1;", "Can't yield outside a generator."),
+                ("fun f() { yield 1; }", "Can't yield outside a generator."),
+                ("fun* g() { fun f() { yield 1; } }", "Can't yield outside a generator."),
+                ("fun* g() { return 1; }", "Can't return a value from a generator."),
+            ];
+            for (source, message) in cases {
+                let mut parser = LoxParser::new();
+                parser.load_string(source).expect("Error while scanning input string");
+                let errors = parser.parse().expect_err("Unexpectedly parsed invalid generator code");
+                assert!(errors.iter().any(|e| e.contains(message)), "Unexpected errors for {}: {:?}", source, errors);
+            }
+        }
+    }
+
+    mod annotations {
+        use super::*;
+        use std::collections::HashMap;
+
+        #[test]
+        fn test_statement_annotated_decl() {
+            let test_str = "var total: Number = 0;";
+            let expected = Statement::Decl(
+                String::from("total"),
+                Expression::boxed_integer(0),
+                Some(String::from("Number")),
+                1,
+            );
+            test_statement_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_statement_annotated_fun() {
+            let test_str = "fun scale(p: Point, k): Point { return p; }";
+            let expected = Statement::Fun(
+                String::from("scale"),
+                vec![String::from("p"), String::from("k")],
+                vec![Statement::Return(Expression::boxed_identifier("p"), 1)],
+                Rc::new(Signature {
+                    param_types: HashMap::from([(String::from("p"), String::from("Point"))]),
+                    returns: Some(String::from("Point")),
+                    line: 1,
+                    ..Signature::default()
+                }),
+            );
+            test_statement_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_statement_annotated_static_field() {
+            let test_str = "class Counter { class count: Integer = 0; }";
+            let expected = Statement::Class(
+                String::from("Counter"),
+                String::new(),
+                vec![],
+                vec![],
+                vec![Statement::Decl(
+                    String::from("count"),
+                    Expression::boxed_integer(0),
+                    Some(String::from("Integer")),
+                    1,
+                )],
+                vec![true],
+            );
+            test_statement_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_invalid_annotations() {
+            let cases = [
+                ("var x: = 1;", "Expected type name after ':'."),
+                ("fun f(a: 1) {}", "Expected type name after ':'."),
+                ("fun* f(): Generator { yield 1; }", "A generator can't declare a return type."),
+            ];
+            for (source, message) in cases {
+                let mut parser = LoxParser::new();
+                parser.load_string(source).expect("Error while scanning input string");
+                let errors = parser.parse().expect_err("Unexpectedly parsed invalid annotation");
+                assert!(errors.iter().any(|e| e.contains(message)), "Unexpected errors for {}: {:?}", source, errors);
+            }
+        }
+    }
+
+    mod variadics {
+        use super::*;
+        use std::collections::HashMap;
+
+        #[test]
+        fn test_statement_defaults_and_rest() {
+            let test_str = "fun log(level, prefix = \"> \", ...parts) {}";
+            let expected = Statement::Fun(
+                String::from("log"),
+                vec![String::from("level"), String::from("prefix"), String::from("parts")],
+                vec![],
+                Rc::new(Signature {
+                    defaults: HashMap::from([(String::from("prefix"), Expression::boxed_string("> "))]),
+                    rest: true,
+                    line: 1,
+                    ..Signature::default()
+                }),
+            );
+            test_statement_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_expression_spread() {
+            let test_str = "f(1, ...rest)";
+            let expected = Expression::boxed_call(
+                Expression::boxed_identifier("f"),
+                vec![
+                    *Expression::boxed_integer(1),
+                    *Expression::boxed_spread(Expression::boxed_identifier("rest")),
+                ],
+                1,
+            );
+            test_expression_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_invalid_parameters() {
+            let cases = [
+                ("fun f(a = 1, b) {}", "Parameter b needs a default value, since it follows one that has one."),
+                ("fun f(...a, b) {}", "A rest parameter must be the last parameter."),
+            ];
+            for (source, message) in cases {
+                let mut parser = LoxParser::new();
+                parser.load_string(source).expect("Error while scanning input string");
+                let errors = parser.parse().expect_err("Unexpectedly parsed invalid parameters");
+                assert!(errors.iter().any(|e| e.contains(message)), "Unexpected errors for {}: {:?}", source, errors);
+            }
+        }
+    }
+
+    mod named_arguments {
+        use super::*;
+
+        #[test]
+        fn test_expression_named_call() {
+            let test_str = "draw(1, y: 2, color: \"red\")";
+            let expected = Expression::boxed_call(
+                Expression::boxed_identifier("draw"),
+                vec![
+                    *Expression::boxed_integer(1),
+                    *Expression::boxed_named("y", Expression::boxed_integer(2)),
+                    *Expression::boxed_named("color", Expression::boxed_string("red")),
+                ],
+                1,
+            );
+            test_expression_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_expression_ternary_argument() {
+            let test_str = "f(a ? b : c)";
+            let expected = Expression::boxed_call(
+                Expression::boxed_identifier("f"),
+                vec![*Expression::boxed_ternary(
+                    Expression::boxed_identifier("a"),
+                    Expression::boxed_identifier("b"),
+                    Expression::boxed_identifier("c"),
+                )],
+                1,
+            );
+            test_expression_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_invalid_named_arguments() {
+            let cases = [
+                ("f(x: 1, x: 2);", "Argument x was passed more than once."),
+                ("f(x: 1, 2);", "Positional arguments must come before named arguments."),
+                ("f(x: 1, ...xs);", "Positional arguments must come before named arguments."),
+            ];
+            for (source, message) in cases {
+                let mut parser = LoxParser::new();
+                parser.load_string(source).expect("Error while scanning input string");
+                let errors = parser.parse().expect_err("Unexpectedly parsed invalid arguments");
+                assert!(errors.iter().any(|e| e.contains(message)), "Unexpected errors for {}: {:?}", source, errors);
+            }
+        }
+    }
+
+    mod test_blocks {
+        use super::*;
+
+        #[test]
+        fn test_statement_test_block() {
+            let test_str = "var test = 1;\ntest \"adds\" {\n    print test;\n}";
+            let expected = vec![
+                Statement::Decl(String::from("test"), Expression::boxed_literal(Literal::Integer(1)), None, 1),
+                Statement::Test(String::from("adds"), vec![Statement::Print(Expression::boxed_identifier("test"), 3)], 2),
+            ];
+            test_program_generic(test_str, expected);
+        }
+
+        #[test]
+        fn test_nested_test_block() {
+            let cases = [
+                ("{ test \"inner\" {} }", "Tests can only be declared at the top level."),
+                ("fun f() { test \"inner\" {} }", "Tests can only be declared at the top level."),
+                ("test \"unopened\" print 1;", "Expected '{' before test body."),
+            ];
+            for (source, message) in cases {
+                let mut parser = LoxParser::new();
+                parser.load_string(source).expect("Error while scanning input string");
+                let errors = parser.parse().expect_err("Unexpectedly parsed invalid test block");
+                assert!(errors.iter().any(|e| e.contains(message)), "Unexpected errors for {}: {:?}", source, errors);
+            }
+        }
+    }
+
+    mod matching {
+        use super::*;
+
+        #[test]
+        fn test_statement_match() {
+            let test_str = concat!(
+                "match (x) {\n",
+                "    case 1, -2 => print \"small\";\n",
+                "    case Point(x, y) if x == y => print \"diagonal\";\n",
+                "    case n => print n;\n",
+                "    default => {}\n",
+                "}",
+            );
+            let expected = Statement::Match(
+                Expression::boxed_identifier("x"),
+                vec![
+                    MatchArm::new(
+                        vec![
+                            Pattern::Literal(Literal::Integer(1)),
+                            Pattern::Literal(Literal::Integer(-2)),
+                        ],
+                        None,
+                        Box::new(Statement::Print(Expression::boxed_string("small"), 2)),
+                    ),
+                    MatchArm::new(
+                        vec![Pattern::Instance(
+                            String::from("Point"),
+                            vec![String::from("x"), String::from("y")],
+                        )],
+                        Some(Expression::boxed_binary(
+                            Expression::boxed_identifier("x"),
+                            BinaryOp::Equal,
+                            Expression::boxed_identifier("y"),
+                        )),
+                        Box::new(Statement::Print(Expression::boxed_string("diagonal"), 3)),
+                    ),
+                    MatchArm::new(
+                        vec![Pattern::Binding(String::from("n"))],
+                        None,
+                        Box::new(Statement::Print(Expression::boxed_identifier("n"), 4)),
+                    ),
+                ],
+                Some(Box::new(Statement::Block(vec![]))),
+                1,
+            );
+            test_statement_generic(test_str, expected);
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_statement_match_default_last() {
+            let test_str = "match (x) { default => {} case 1 => {} }";
+            let expected = Statement::Match(Expression::boxed_identifier("x"), vec![], None, 1);
+            test_statement_generic(test_str, expected);
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_statement_match_expects_arrow() {
+            let test_str = "match (x) { case 1 print x; }";
+            let expected = Statement::Match(Expression::boxed_identifier("x"), vec![], None, 1);
+            test_statement_generic(test_str, expected);
+        }
+    }
+
+    mod programs {
+        use super::*;
+
+        #[test]
+        fn test_program_hello_world() {
+            let source = concat!(
+                "var my_var = \"Hello, world!\";\n",
+                "print my_var;\n",
+            );
+            let expected = vec![
+                Statement::Decl(
+                    String::from("my_var"),
+                    Expression::boxed_string("Hello, world!"),
+                    None,
+                    1,
+                ),
+                Statement::Print(
+                    Expression::boxed_identifier("my_var"),
+                    2,
+                ),
+            ];
+            test_program_generic(source, expected);
+        }
+
+        #[test]
+        fn test_program_assignments() {
+            let source = concat!(
+                "var i;\n",
+                "var j = 2;\n",
+                "var k = 3 + 4;\n",
+                "i = 3;\n",
+                "j = 3 - 1;\n",
+                "k = j + k + 3;\n",
+                "i = j = k;\n",
+            );
+            let expected = vec![
+                Statement::Decl(
+                    String::from("i"),
+                    Expression::boxed_nil(),
+                    None,
+                    1,
+                ),
+                Statement::Decl(
+                    String::from("j"),
+                    Expression::boxed_integer(2),
+                    None,
+                    2,
+                ),
+                Statement::Decl(
+                    String::from("k"),
+                    Expression::boxed_binary(
+                        Expression::boxed_integer(3),
+                        BinaryOp::Add,
+                        Expression::boxed_integer(4),
+                    ),
+                    None,
+                    3,
+                ),
+                Statement::Expr(
+                    Expression::boxed_assignment(
+                        "i",
+                        Expression::boxed_integer(3),
+                        4,
+                    ),
+                    4,
+                ),
+                Statement::Expr(
+                    Expression::boxed_assignment(
+                        "j",
+                        Expression::boxed_binary(
+                            Expression::boxed_integer(3),
+                            BinaryOp::Subtract,
+                            Expression::boxed_integer(1),
+                        ),
+                        5,
+                    ),
+                    5,
+                ),
+                Statement::Expr(
+                    Expression::boxed_assignment(
+                        "k",
+                        Expression::boxed_binary(
+                            Expression::boxed_binary(
+                                Expression::boxed_identifier("j"),
+                                BinaryOp::Add,
+                                Expression::boxed_identifier("k"),
+                            ),
+                            BinaryOp::Add,
+                            Expression::boxed_integer(3),
+                        ),
+                        6,
+                    ),
+                    6,
+                ),
+                Statement::Expr(
+                    Expression::boxed_assignment(
+                        "i",
+                        Expression::boxed_assignment(
+                            "j",
+                            Expression::boxed_identifier("k"),
+                            7,
+                        ),
+                        7,
+                    ),
+                    7,
+                ),
+            ];
+            test_program_generic(source, expected);
+        }
+
+        #[test]
+        fn test_program_blocks() {
+            let source = concat!(
+                "{}\n",
+                "var global = 23;\n",
+                "{\n",
+                "   var local = 3;\n",
+                "   { print local; }\n",
+                "}\n",
+            );
+            let expected = vec![
+                Statement::Block(vec![]),
+                Statement::Decl(
+                    String::from("global"),
+                    Expression::boxed_integer(23),
+                    None,
+                    2,
                 ),
                 Statement::Block(vec![
-                    Box::new(Statement::Decl(
+                    Statement::Decl(
                         String::from("local"),
-                        Expression::boxed_number(3.0),
-                    )),
-                    Box::new(Statement::Block(vec![
-                        Box::new(Statement::Print(
+                        Expression::boxed_integer(3),
+                        None,
+                        4,
+                    ),
+                    Statement::Block(vec![
+                        Statement::Print(
                             Expression::boxed_identifier("local"),
-                        ))
-                    ])),
+                            5,
+                        )
+                    ]),
                 ]),
             ];
             test_program_generic(source, expected);
@@ -1420,35 +2801,42 @@ mod tests {
             let expected = vec![
                 Statement::If(
                     Expression::boxed_binary(
-                        Expression::boxed_number(2.0),
+                        Expression::boxed_integer(2),
                         BinaryOp::LessEqual,
-                        Expression::boxed_number(3.0),
+                        Expression::boxed_integer(3),
                     ),
                     Box::new(Statement::Print(
                         Expression::boxed_string("Math is working"),
+                        1,
                     )),
                     None,
+                    1,
                 ),
                 Statement::Decl(
                     String::from("three"),
-                    Expression::boxed_number(3.0),
+                    Expression::boxed_integer(3),
+                    None,
+                    2,
                 ),
                 Statement::If(
                     Expression::boxed_binary(
                         Expression::boxed_identifier("three"),
                         BinaryOp::Equal,
-                        Expression::boxed_number(3.0),
+                        Expression::boxed_integer(3),
                     ),
                     Box::new(Statement::Block(vec![
-                        Box::new(Statement::Print(
-                            Expression::boxed_number(333.0),
-                        )),
+                        Statement::Print(
+                            Expression::boxed_integer(333),
+                            4,
+                        ),
                     ])),
                     Some(Box::new(Statement::Block(vec![
-                        Box::new(Statement::Print(
-                            Expression::boxed_number(4444.0),
-                        )),
+                        Statement::Print(
+                            Expression::boxed_integer(4444),
+                            6,
+                        ),
                     ]))),
+                    3,
                 ),
             ];
             test_program_generic(source, expected);
@@ -1467,33 +2855,33 @@ mod tests {
                     Expression::boxed_identifier("function"),
                     vec![],
                     1,
-                )),
+                ), 1),
                 Statement::Expr(Expression::boxed_call(
                     Expression::boxed_identifier("argumentative"),
                     vec![
-                        Expression::boxed_identifier("yes"),
-                        Expression::boxed_identifier("very"),
+                        *Expression::boxed_identifier("yes"),
+                        *Expression::boxed_identifier("very"),
                     ],
                     2,
-                )),
+                ), 2),
                 Statement::Expr(Expression::boxed_call(
                     Expression::boxed_call(
                         Expression::boxed_identifier("nested"),
                         vec![
-                            Expression::boxed_identifier("one"),
+                            *Expression::boxed_identifier("one"),
                         ],
                         3,
                     ),
                     vec![
-                        Expression::boxed_identifier("two"),
+                        *Expression::boxed_identifier("two"),
                     ],
                     3,
-                )),
+                ), 3),
                 Statement::Expr(Expression::boxed_call(
                     Expression::boxed_string("This isn't a function, but that's the interpreter's problem"),
                     vec![],
                     4,
-                )),
+                ), 4),
             ];
             test_program_generic(source, expected);
         }
@@ -1511,17 +2899,19 @@ mod tests {
                     String::from("shortFunction"),
                     vec![],
                     vec![],
+                    signature_at(1),
                 ),
                 Statement::Fun(
                     String::from("longFunction"),
                     vec![String::from("arg1"), String::from("arg2")],
                     vec![
-                        Box::new(Statement::Expr(Expression::boxed_call(
+                        Statement::Expr(Expression::boxed_call(
                             Expression::boxed_identifier("clock"),
                             vec![],
                             3,
-                        ))),
+                        ), 3),
                     ],
+                    signature_at(2),
                 ),
             ];
             test_program_generic(source, expected);
@@ -1544,23 +2934,26 @@ mod tests {
                 Statement::Class(
                     String::from("Breakfast"),
                     String::from(""),
+                    vec![],
                     vec![
-                        Box::new(Statement::Fun(
+                        Statement::Fun(
                             String::from("cook"),
                             vec![],
                             vec![
-                                Box::new(Statement::Print(
+                                Statement::Print(
                                     Expression::boxed_string(
                                         "Eggs a-fryin'!"
-                                    )
-                                )),
+                                    ),
+                                    3,
+                                ),
                             ],
-                        )),
-                        Box::new(Statement::Fun(
+                            signature_at(2),
+                        ),
+                        Statement::Fun(
                             String::from("serve"),
                             vec![String::from("who")],
                             vec![
-                                Box::new(Statement::Print(
+                                Statement::Print(
                                     Expression::boxed_binary(
                                         Expression::boxed_binary(
                                             Expression::boxed_string("Enjoy your breakfast, "),
@@ -1569,11 +2962,15 @@ mod tests {
                                         ),
                                         BinaryOp::Add,
                                         Expression::boxed_string("."),
-                                    )
-                                )),
+                                    ),
+                                    7,
+                                ),
                             ],
-                        )),
+                            signature_at(6),
+                        ),
                     ],
+                    vec![],
+                    vec![false, false],
                 ),
             ];
             test_program_generic(source, expected);
@@ -1601,7 +2998,8 @@ mod tests {
                             1
                         ),
                         "three"
-                    )
+                    ),
+                    1,
                 )
             ];
             test_program_generic(source, expected);
@@ -1629,8 +3027,9 @@ mod tests {
                             1
                         ),
                         "three",
-                        Expression::boxed_number(0.0)
-                    )
+                        Expression::boxed_integer(0)
+                    ),
+                    1,
                 )
             ];
             test_program_generic(source, expected);
@@ -1659,23 +3058,26 @@ mod tests {
                 Statement::Class(
                     String::from("Breakfast"),
                     String::from(""),
+                    vec![],
                     vec![
-                        Box::new(Statement::Fun(
+                        Statement::Fun(
                             String::from("cook"),
                             vec![],
                             vec![
-                                Box::new(Statement::Print(
+                                Statement::Print(
                                     Expression::boxed_string(
                                         "Eggs a-fryin'!"
-                                    )
-                                )),
+                                    ),
+                                    3,
+                                ),
                             ],
-                        )),
-                        Box::new(Statement::Fun(
+                            signature_at(2),
+                        ),
+                        Statement::Fun(
                             String::from("serve"),
                             vec![String::from("who")],
                             vec![
-                                Box::new(Statement::Print(
+                                Statement::Print(
                                     Expression::boxed_binary(
                                         Expression::boxed_binary(
                                             Expression::boxed_string("Enjoy your breakfast, "),
@@ -1684,36 +3086,338 @@ mod tests {
                                         ),
                                         BinaryOp::Add,
                                         Expression::boxed_string("."),
-                                    )
-                                )),
+                                    ),
+                                    7,
+                                ),
                             ],
-                        )),
+                            signature_at(6),
+                        ),
                     ],
+                    vec![],
+                    vec![false, false],
                 ),
                 Statement::Class(
                     String::from("Continental"),
                     String::from("Breakfast"),
+                    vec![],
                     vec![
-                        Box::new(Statement::Fun(
+                        Statement::Fun(
                             String::from("serve"),
                             vec![String::from("who")],
                             vec![
-                                Box::new(Statement::Print(
+                                Statement::Print(
                                     Expression::boxed_string("You'll need to get it yourself, but there's a charm to that, right?"),
-                                )),
-                                Box::new(Statement::Expr(
+                                    11,
+                                ),
+                                Statement::Expr(
                                     Expression::boxed_call(
                                         Expression::boxed_super("serve"),
-                                        vec![Expression::boxed_identifier("who")],
+                                        vec![*Expression::boxed_identifier("who")],
                                         12,
                                     ),
-                                )),
+                                    12,
+                                ),
                             ],
-                        )),
+                            signature_at(10),
+                        ),
                     ],
+                    vec![],
+                    vec![false],
                 ),
             ];
             test_program_generic(source, expected);
         }
+
+        #[test]
+        fn test_program_static_members() {
+            let source = concat!(
+                "class Math {\n",
+                "    class ratio = 2.5;\n",
+                "    class unset;\n",
+                "    class square(n) {\n",
+                "        return n * n;\n",
+                "    }\n",
+                "    twice(n) {\n",
+                "        return n + n;\n",
+                "    }\n",
+                "}",
+            );
+            let expected = vec![
+                Statement::Class(
+                    String::from("Math"),
+                    String::from(""),
+                    vec![],
+                    vec![
+                        Statement::Fun(
+                            String::from("twice"),
+                            vec![String::from("n")],
+                            vec![
+                                Statement::Return(
+                                    Expression::boxed_binary(
+                                        Expression::boxed_identifier("n"),
+                                        BinaryOp::Add,
+                                        Expression::boxed_identifier("n"),
+                                    ),
+                                    8,
+                                ),
+                            ],
+                            signature_at(7),
+                        ),
+                    ],
+                    vec![
+                        Statement::Decl(
+                            String::from("ratio"),
+                            Expression::boxed_number(2.5),
+                            None,
+                            2,
+                        ),
+                        Statement::Decl(
+                            String::from("unset"),
+                            Expression::boxed_nil(),
+                            None,
+                            3,
+                        ),
+                        Statement::Fun(
+                            String::from("square"),
+                            vec![String::from("n")],
+                            vec![
+                                Statement::Return(
+                                    Expression::boxed_binary(
+                                        Expression::boxed_identifier("n"),
+                                        BinaryOp::Multiply,
+                                        Expression::boxed_identifier("n"),
+                                    ),
+                                    5,
+                                ),
+                            ],
+                            signature_at(4),
+                        ),
+                    ],
+                    vec![true, true, true, false],
+                ),
+            ];
+            test_program_generic(source, expected);
+        }
+
+        #[test]
+        fn test_program_accessors() {
+            let source = concat!(
+                "class Rect {\n",
+                "    area { return this.w * this.h; }\n",
+                "    set width(w) { this.w = w; }\n",
+                "    set(v) {}\n",
+                "}",
+            );
+            let expected = vec![
+                Statement::Class(
+                    String::from("Rect"),
+                    String::from(""),
+                    vec![],
+                    vec![
+                        Statement::PropertyGet(
+                            String::from("area"),
+                            vec![
+                                Statement::Return(
+                                    Expression::boxed_binary(
+                                        Expression::boxed_getter(Expression::boxed_this(2), "w"),
+                                        BinaryOp::Multiply,
+                                        Expression::boxed_getter(Expression::boxed_this(2), "h"),
+                                    ),
+                                    2,
+                                ),
+                            ],
+                        ),
+                        Statement::PropertySet(
+                            String::from("width"),
+                            String::from("w"),
+                            vec![
+                                Statement::Expr(Expression::boxed_setter(
+                                    Expression::boxed_this(3),
+                                    "w",
+                                    Expression::boxed_identifier("w"),
+                                ), 3),
+                            ],
+                        ),
+                        Statement::Fun(
+                            String::from("set"),
+                            vec![String::from("v")],
+                            vec![],
+                            signature_at(4),
+                        ),
+                    ],
+                    vec![],
+                    vec![false, false, false],
+                ),
+            ];
+            test_program_generic(source, expected);
+        }
+
+        #[test]
+        fn test_program_traits() {
+            let source = concat!(
+                "trait Printable {\n",
+                "    show() { print super.describe(); }\n",
+                "    label { return \"printable\"; }\n",
+                "}\n",
+                "class Doc < Base with Printable, Sortable {}\n",
+            );
+            let expected = vec![
+                Statement::Trait(
+                    String::from("Printable"),
+                    vec![
+                        Statement::Fun(
+                            String::from("show"),
+                            vec![],
+                            vec![
+                                Statement::Print(Expression::boxed_call(
+                                    Expression::boxed_super("describe"),
+                                    vec![],
+                                    2,
+                                ), 2),
+                            ],
+                            signature_at(2),
+                        ),
+                        Statement::PropertyGet(
+                            String::from("label"),
+                            vec![
+                                Statement::Return(Expression::boxed_string("printable"), 3),
+                            ],
+                        ),
+                    ],
+                ),
+                Statement::Class(
+                    String::from("Doc"),
+                    String::from("Base"),
+                    vec![String::from("Printable"), String::from("Sortable")],
+                    vec![],
+                    vec![],
+                    vec![],
+                ),
+            ];
+            test_program_generic(source, expected);
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_program_trait_init() {
+            let source = "trait Printable { init() {} }";
+            test_program_generic(source, vec![]);
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_program_setter_arity() {
+            let source = "class Rect { set size(w, h) {} }";
+            test_program_generic(source, vec![]);
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_program_static_init() {
+            let source = "class Math { class init() {} }";
+            test_program_generic(source, vec![]);
+        }
+    }
+    mod comments {
+        use super::*;
+
+        fn parse_with_comments(source: &str) -> Vec<Statement> {
+            let mut parser = LoxParser::new();
+            parser.keep_comments();
+            parser.load_string(source).expect("Error while scanning input string");
+            parser.parse().expect("Error while parsing input string")
+        }
+
+        #[test]
+        fn test_comments_dropped_by_default() {
+            let source = "// Greeting\nprint 1; // trailing\n";
+            test_program_generic(source, vec![Statement::Print(Expression::boxed_integer(1), 2)]);
+        }
+
+        #[test]
+        fn test_comments_kept() {
+            let source = concat!(
+                "// Greeting\n",
+                "print 1; // trailing\n",
+                "// Last\n",
+            );
+            let expected = vec![
+                Statement::Comment(String::from("Greeting"), false, 1),
+                Statement::Print(Expression::boxed_integer(1), 2),
+                Statement::Comment(String::from("trailing"), true, 2),
+                Statement::Comment(String::from("Last"), false, 3),
+            ];
+            assert_eq!(expected, parse_with_comments(source));
+        }
+
+        #[test]
+        fn test_comments_kept_in_blocks() {
+            let source = concat!(
+                "{\n",
+                "    // Inside\n",
+                "    print 1;\n",
+                "} // After\n",
+            );
+            let expected = vec![
+                Statement::Block(vec![
+                    Statement::Comment(String::from("Inside"), false, 2),
+                    Statement::Print(Expression::boxed_integer(1), 3),
+                ]),
+                Statement::Comment(String::from("After"), true, 4),
+            ];
+            assert_eq!(expected, parse_with_comments(source));
+        }
+
+        #[test]
+        fn test_comments_between_statements_not_interruptions() {
+            let mut parser = LoxParser::new();
+            parser.keep_comments();
+            parser.load_string("// a\nfun f() {\n    // b\n    print 1; // c\n} // d\nclass A {\n    // e\n    g() {} // f\n}\n").expect("Error while scanning input string");
+            parser.parse().expect("Error while parsing input string");
+            assert!(parser.interruptions().is_empty(), "{:?}", parser.interruptions());
+        }
+
+        #[test]
+        fn test_comments_in_lists() {
+            let source = "fun f(a, // first\n    b) {}\nf(1 // one\n, 2);\n";
+            let mut parser = LoxParser::new();
+            parser.keep_comments();
+            parser.load_string(source).expect("Error while scanning input string");
+            let program = parser.parse().expect("Error while parsing input string");
+            assert!(parser.interruptions().is_empty(), "{:?}", parser.interruptions());
+
+            let Statement::Fun(_, _, _, signature) = &program[0] else { panic!("Expected a function, got {:?}", program[0]) };
+            assert_eq!(Some(&(String::from("first"), 1)), signature.comments.get("a"));
+            let Statement::Expr(call, _) = &program[1] else { panic!("Expected a call, got {:?}", program[1]) };
+            let Expression::Call(_, _, comments, _) = &**call else { panic!("Expected a call, got {:?}", call) };
+            assert_eq!(&vec![(0, String::from("one"), 3)], comments);
+        }
+
+        #[test]
+        fn test_comments_inside_expressions() {
+            // Comments that can't sit between statements come after the one they interrupt.
+            let source = concat!(
+                "var x = 1 + // one\n",
+                "    2;\n",
+            );
+            let expected = vec![
+                Statement::Decl(
+                    String::from("x"),
+                    Expression::boxed_binary(
+                        Expression::boxed_integer(1),
+                        BinaryOp::Add,
+                        Expression::boxed_integer(2),
+                    ),
+                    None,
+                    1,
+                ),
+                Statement::Comment(String::from("one"), true, 1),
+            ];
+            let mut parser = LoxParser::new();
+            parser.keep_comments();
+            parser.load_string(source).expect("Error while scanning input string");
+            assert_eq!(Ok(expected), parser.parse());
+            assert_eq!(&[1], parser.interruptions());
+        }
     }
 }
\ No newline at end of file
@@ -4,12 +4,16 @@ use lox::parser::token::*;
 pub struct LoxScanner {
     source: Vec<char>,
     tokens: Vec<Token>,
+    columns: Vec<usize>, // Where each token starts on its line, counting from 0
     error_strings: Vec<String>,
     start: usize,
     current: usize,
     line: usize,
+    line_start: usize, // Offset of the current line's first character
+    start_column: usize,
     inited: bool,
     valid: bool,
+    keep_comments: bool,
 }
 
 impl LoxScanner {
@@ -22,15 +26,24 @@ impl LoxScanner {
         LoxScanner {
             source,
             tokens,
+            columns: Vec::new(),
             error_strings,
             start : 0,
             current : 0,
             line : 1,
+            line_start : 0,
+            start_column : 0,
             inited : false,
             valid : true,
+            keep_comments : false,
         }
     }
 
+    // Makes scan_tokens() produce Comment tokens instead of discarding comments.
+    pub fn keep_comments(&mut self) {
+        self.keep_comments = true;
+    }
+
     pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<String>> {
         if self.inited {
             if self.valid { Ok(self.tokens.clone()) }
@@ -41,6 +54,7 @@ impl LoxScanner {
 
             while !self.is_at_end() {
                 let c = self.source[self.current];
+                self.start_column = self.current - self.line_start;
                 self.current += 1;
 
                 match c {
@@ -48,46 +62,83 @@ impl LoxScanner {
                     ')' => self.add_token(TokenData::RightParen),
                     '{' => self.add_token(TokenData::LeftBrace),
                     '}' => self.add_token(TokenData::RightBrace),
+                    '[' => self.add_token(TokenData::LeftBracket),
+                    ']' => self.add_token(TokenData::RightBracket),
                     ',' => self.add_token(TokenData::Comma),
-                    '.' => self.add_token(TokenData::Dot),
+                    '.' => {
+                        if self.source[self.current..].starts_with(&['.', '.']) {
+                            self.current += 2;
+                            self.add_token(TokenData::Ellipsis)
+                        }
+                        else { self.add_token(TokenData::Dot) }
+                    },
                     ';' => self.add_token(TokenData::Semicolon),
-                    '+' => self.add_token(TokenData::Plus),
-                    '-' => self.add_token(TokenData::Minus),
-                    '*' => self.add_token(TokenData::Star),
+                    '?' => self.add_token(TokenData::Question),
+                    ':' => self.add_token(TokenData::Colon),
+                    '&' => self.add_token(TokenData::Ampersand),
+                    '|' => self.add_token(TokenData::Pipe),
+                    '^' => self.add_token(TokenData::Caret),
+
+                    '+' => {
+                        if self.match_char('=') { self.add_token(TokenData::PlusEqual) }
+                        else                    { self.add_token(TokenData::Plus) }
+                    },
+                    '-' => {
+                        if self.match_char('=') { self.add_token(TokenData::MinusEqual) }
+                        else                    { self.add_token(TokenData::Minus) }
+                    },
+                    '*' => {
+                        if self.match_char('=') { self.add_token(TokenData::StarEqual) }
+                        else                    { self.add_token(TokenData::Star) }
+                    },
                     // Slash needs extra handling for comments - see below
-                    '%' => self.add_token(TokenData::Percent),
+                    '%' => {
+                        if self.match_char('=') { self.add_token(TokenData::PercentEqual) }
+                        else                    { self.add_token(TokenData::Percent) }
+                    },
+                    '~' => {
+                        if self.match_char('/') { self.add_token(TokenData::TildeSlash) }
+                        else                    { self.add_error("Expected '/' after '~'.") }
+                    },
 
                     '!' => {
                         if self.match_char('=') { self.add_token(TokenData::BangEqual) }
                         else                    { self.add_token(TokenData::Bang) }
                     },
                     '=' => {
-                        if self.match_char('=') { self.add_token(TokenData::EqualEqual) }
-                        else                    { self.add_token(TokenData::Equal) }
+                        if self.match_char('=')      { self.add_token(TokenData::EqualEqual) }
+                        else if self.match_char('>') { self.add_token(TokenData::FatArrow) }
+                        else                         { self.add_token(TokenData::Equal) }
                     },
                     '<' => {
-                        if self.match_char('=') { self.add_token(TokenData::LessEqual) }
-                        else                    { self.add_token(TokenData::Less) }
+                        if self.match_char('=')      { self.add_token(TokenData::LessEqual) }
+                        else if self.match_char('<') { self.add_token(TokenData::LessLess) }
+                        else                         { self.add_token(TokenData::Less) }
                     },
                     '>' => {
-                        if self.match_char('=') { self.add_token(TokenData::GreaterEqual) }
-                        else                    { self.add_token(TokenData::Greater) }
+                        if self.match_char('=')      { self.add_token(TokenData::GreaterEqual) }
+                        else if self.match_char('>') { self.add_token(TokenData::GreaterGreater) }
+                        else                         { self.add_token(TokenData::Greater) }
                     },
 
                     '/' => {
                         if self.match_char('/') { self.process_comment() }
+                        else if self.match_char('=') { self.add_token(TokenData::SlashEqual) }
                         else { self.add_token(TokenData::Slash) }
                     },
 
                     '"' => self.process_string(),
                     '0'..='9' => self.process_number(),
-                    'A'..='z' => self.process_identifier(),
+                    'A'..='Z' | 'a'..='z' | '_' => self.process_identifier(),
 
                     ' ' => (),
                     '\r' => (),
                     '\t' => (),
 
-                    '\n' => self.line += 1,
+                    '\n' => {
+                        self.line += 1;
+                        self.line_start = self.current;
+                    },
 
                     _ => self.add_error(&format!("Unexpected character '{c}'.")),
                 };
@@ -95,12 +146,18 @@ impl LoxScanner {
                 self.start = self.current;
             }
 
+            self.start_column = self.current - self.line_start;
             self.add_token(TokenData::EndOfFile);
             if self.valid { Ok(self.tokens.clone()) }
             else { Err(self.error_strings.clone()) }
         }
     }
 
+    // The column of each token from scan_tokens(), in the same order.
+    pub fn token_columns(&self) -> &[usize] {
+        &self.columns
+    }
+
     // Returns false if the scanner cannot provide output.
     // Returns none if the scanner has yet to attempt parsing its string.
     pub fn is_valid(&self) -> Option<bool> {
@@ -124,17 +181,25 @@ impl LoxScanner {
     }
 
     fn process_comment(&mut self) {
+        let begin = self.current;
         while !self.is_at_end() && self.source[self.current] != '\n' {
             self.current += 1;
         }
+        if self.keep_comments {
+            let text: String = self.source[begin..self.current].iter().collect();
+            self.add_token(TokenData::Comment(String::from(text.trim())));
+        }
     }
 
     fn process_string(&mut self) {
         let begin = self.current;
         let start_line = self.line;
         while !self.is_at_end() && self.source[self.current] != '"' {
-            if self.source[self.current] == '\n' { self.line += 1 };
             self.current += 1;
+            if self.source[self.current-1] == '\n' {
+                self.line += 1;
+                self.line_start = self.current;
+            };
         };
         
         if self.is_at_end() {
@@ -153,7 +218,9 @@ impl LoxScanner {
         while !self.is_at_end() && is_number(self.source[self.current]) {
             self.current += 1;
         };
+        let mut is_float = false;
         if !self.is_at_end() && self.source[self.current] == '.' {
+            is_float = true;
             self.current += 1;
             while !self.is_at_end() && is_number(self.source[self.current]) {
                 self.current += 1;
@@ -161,8 +228,16 @@ impl LoxScanner {
         };
         let source_slice = &self.source[begin..self.current];
         let number_str: String = source_slice.iter().collect();
-        let input_number: f64 = number_str.parse().expect("FATAL: Recieved non-numeric character while parsing number. Note that this should be impossible.");
-        self.add_token(TokenData::Number(input_number));
+
+        // Literals without a fractional part are kept as exact integers.
+        if is_float {
+            let input_number: f64 = number_str.parse().expect("FATAL: Recieved non-numeric character while parsing number. Note that this should be impossible.");
+            self.add_token(TokenData::Number(input_number));
+        } else if let Ok(input_integer) = number_str.parse::<i64>() {
+            self.add_token(TokenData::Integer(input_integer));
+        } else {
+            self.add_error(&format!("Integer literal {number_str} is too large."));
+        }
     }
 
     fn process_identifier(&mut self) {
@@ -189,12 +264,22 @@ impl LoxScanner {
             "this" => self.add_token(TokenData::This),
             "var" => self.add_token(TokenData::Var),
             "while" => self.add_token(TokenData::While),
+            "match" => self.add_token(TokenData::Match),
+            "case" => self.add_token(TokenData::Case),
+            "default" => self.add_token(TokenData::Default),
+            "trait" => self.add_token(TokenData::Trait),
+            "with" => self.add_token(TokenData::With),
+            "yield" => self.add_token(TokenData::Yield),
+            "in" => self.add_token(TokenData::In),
+            "const" => self.add_token(TokenData::Const),
+            "enum" => self.add_token(TokenData::Enum),
             _ => self.add_token(TokenData::Identifier(input_string)),
         };
     }
 
     fn add_token(&mut self, data: TokenData) {
         self.tokens.push(Token{data, line: self.line});
+        self.columns.push(self.start_column);
     }
 
     fn add_error(&mut self, message: &str) {
@@ -213,7 +298,7 @@ fn is_number(c: char) -> bool {
 
 fn is_alpha(c: char) -> bool {
     match c {
-        'A'..='z' => true,
+        'A'..='Z' | 'a'..='z' | '_' => true,
         _ => false,
     }
 }
@@ -259,6 +344,18 @@ mod tests {
         test_scan_generic("(){}.,;", expected_tokens);
     }
 
+    #[test]
+    fn test_scan_brackets () {
+        let expected_tokens = vec![
+            Token::new(LeftBracket, 1),
+            Token::new(RightBracket, 1),
+            Token::new(LeftBracket, 1),
+            Token::new(RightBracket, 1),
+            Token::new(EndOfFile, 1),
+        ];
+        test_scan_generic("[][ ]", expected_tokens);
+    }
+
     #[test]
     fn test_scan_math_ops () {
         let expected_tokens = vec![
@@ -272,6 +369,36 @@ mod tests {
         test_scan_generic("+-*/%", expected_tokens);
     }
 
+    #[test]
+    fn test_scan_integer_ops () {
+        let expected_tokens = vec![
+            Token::new(TildeSlash, 1),
+            Token::new(Ampersand, 1),
+            Token::new(Pipe, 1),
+            Token::new(Caret, 1),
+            Token::new(LessLess, 1),
+            Token::new(GreaterGreater, 1),
+            Token::new(EndOfFile, 1),
+        ];
+        test_scan_generic("~/&|^<<>>", expected_tokens);
+    }
+
+    #[test]
+    fn test_scan_compound_assignment () {
+        let expected_tokens = vec![
+            Token::new(PlusEqual, 1),
+            Token::new(MinusEqual, 1),
+            Token::new(StarEqual, 1),
+            Token::new(SlashEqual, 1),
+            Token::new(PercentEqual, 1),
+            Token::new(Question, 1),
+            Token::new(Colon, 1),
+            Token::new(FatArrow, 1),
+            Token::new(EndOfFile, 1),
+        ];
+        test_scan_generic("+= -= *= /= %= ? : =>", expected_tokens);
+    }
+
     #[test]
     fn test_scan_comparators () {
         let expected_tokens = vec![
@@ -297,6 +424,29 @@ mod tests {
         test_scan_generic(comment_str, expected_tokens);
     }
 
+    #[test]
+    fn test_scan_kept_comments () {
+        let mut scanner = LoxScanner::new("print 1; //  trailing note \n// own line");
+        scanner.keep_comments();
+        let tokens = scanner.scan_tokens().expect("Unknown scanning failure.");
+        let expected_tokens = vec![
+            Token::new(Print, 1),
+            Token::new(Integer(1), 1),
+            Token::new(Semicolon, 1),
+            Token::new(Comment(String::from("trailing note")), 1),
+            Token::new(Comment(String::from("own line")), 2),
+            Token::new(EndOfFile, 2),
+        ];
+        assert_eq!(expected_tokens, tokens, "Expected to recieve tokens on left, got tokens on right.");
+    }
+
+    #[test]
+    fn test_scan_token_columns () {
+        let mut scanner = LoxScanner::new("var total = 10;\n  print \"a\nb\" + total;");
+        scanner.scan_tokens().expect("Unknown scanning failure.");
+        assert_eq!(&[0, 4, 10, 12, 14, 2, 8, 3, 5, 10, 11], scanner.token_columns());
+    }
+
     #[test]
     fn test_scan_strings () {
         let string_str = "\
@@ -318,13 +468,35 @@ mod tests {
 245.30";
         let expected_tokens = vec![
             Token::new(Number(3.45), 1),
-            Token::new(Number(83.0), 2),
+            Token::new(Integer(83), 2),
             Token::new(Number(245.3), 3),
             Token::new(EndOfFile, 3),
         ];
         test_scan_generic(number_str, expected_tokens);
     }
 
+    #[test]
+    fn test_scan_large_integers () {
+        let number_str = "9007199254740993 9223372036854775807";
+        let expected_tokens = vec![
+            Token::new(Integer(9007199254740993), 1),
+            Token::new(Integer(i64::MAX), 1),
+            Token::new(EndOfFile, 1),
+        ];
+        test_scan_generic(number_str, expected_tokens);
+    }
+
+    #[test]
+    fn test_error_integer_too_large () {
+        let mut scanner = LoxScanner::new("9223372036854775808");
+        let outcome = scanner.scan_tokens();
+        assert_eq!(
+            Err(vec![String::from("[Line 1] Error: Integer literal 9223372036854775808 is too large.")]),
+            outcome,
+            "Expected an out-of-range integer literal to produce an error."
+        );
+    }
+
     #[test]
     fn test_scan_identifiers() {
         let identifier_str = "\
@@ -357,6 +529,72 @@ this var while foo bar foobar2 cr4b";
         test_scan_generic(identifier_str, expected_tokens);
     }
 
+    #[test]
+    fn test_scan_match_keywords() {
+        let expected_tokens = vec![
+            Token::new(Match, 1),
+            Token::new(Case, 1),
+            Token::new(Default, 1),
+            Token::new(Identifier(String::from("matcher")), 1),
+            Token::new(EndOfFile, 1),
+        ];
+        test_scan_generic("match case default matcher", expected_tokens);
+    }
+
+    #[test]
+    fn test_scan_trait_keywords() {
+        let expected_tokens = vec![
+            Token::new(Trait, 1),
+            Token::new(With, 1),
+            Token::new(Identifier(String::from("traits")), 1),
+            Token::new(EndOfFile, 1),
+        ];
+        test_scan_generic("trait with traits", expected_tokens);
+    }
+
+    #[test]
+    fn test_scan_generator_keywords() {
+        let expected_tokens = vec![
+            Token::new(Fun, 1),
+            Token::new(Star, 1),
+            Token::new(Yield, 1),
+            Token::new(Identifier(String::from("yields")), 1),
+            Token::new(EndOfFile, 1),
+        ];
+        test_scan_generic("fun* yield yields", expected_tokens);
+    }
+
+    #[test]
+    fn test_scan_in_keyword() {
+        let expected_tokens = vec![
+            Token::new(For, 1),
+            Token::new(In, 1),
+            Token::new(Identifier(String::from("index")), 1),
+            Token::new(EndOfFile, 1),
+        ];
+        test_scan_generic("for in index", expected_tokens);
+    }
+
+    #[test]
+    fn test_scan_const_keyword() {
+        let expected_tokens = vec![
+            Token::new(Const, 1),
+            Token::new(Identifier(String::from("constant")), 1),
+            Token::new(EndOfFile, 1),
+        ];
+        test_scan_generic("const constant", expected_tokens);
+    }
+
+    #[test]
+    fn test_scan_enum_keyword() {
+        let expected_tokens = vec![
+            Token::new(Enum, 1),
+            Token::new(Identifier(String::from("enums")), 1),
+            Token::new(EndOfFile, 1),
+        ];
+        test_scan_generic("enum enums", expected_tokens);
+    }
+
     #[test]
     fn test_scan_misc() {
         let misc_str = "= !";
@@ -368,6 +606,23 @@ this var while foo bar foobar2 cr4b";
         test_scan_generic(misc_str, expected_tokens);
     }
 
+    #[test]
+    fn test_scan_ellipsis() {
+        let misc_str = "f(...xs) . ..";
+        let expected_tokens = vec![
+            Token::new(Identifier(String::from("f")), 1),
+            Token::new(LeftParen, 1),
+            Token::new(Ellipsis, 1),
+            Token::new(Identifier(String::from("xs")), 1),
+            Token::new(RightParen, 1),
+            Token::new(Dot, 1),
+            Token::new(Dot, 1),
+            Token::new(Dot, 1),
+            Token::new(EndOfFile, 1),
+        ];
+        test_scan_generic(misc_str, expected_tokens);
+    }
+
     #[test]
     fn test_scan_range_edges() {
         let misc_str = "000.0 999.9 AAA zzz \"AAA\" \"zzz\"";
@@ -398,17 +653,17 @@ for (var i = 0; i <= 10; i = i + 1) {
             Token::new(Var, 1),
             Token::new(Identifier(String::from("i")), 1),
             Token::new(Equal, 1),
-            Token::new(Number(0.0), 1),
+            Token::new(Integer(0), 1),
             Token::new(Semicolon, 1),
             Token::new(Identifier(String::from("i")), 1),
             Token::new(LessEqual, 1),
-            Token::new(Number(10.0), 1),
+            Token::new(Integer(10), 1),
             Token::new(Semicolon, 1),
             Token::new(Identifier(String::from("i")), 1),
             Token::new(Equal, 1),
             Token::new(Identifier(String::from("i")), 1),
             Token::new(Plus, 1),
-            Token::new(Number(1.0), 1),
+            Token::new(Integer(1), 1),
             Token::new(RightParen, 1),
             Token::new(LeftBrace, 1),
 
@@ -488,7 +743,7 @@ $";
         let mut valid_scanner = LoxScanner::new("...");
         let _ = valid_scanner.scan_tokens(); // discard
         let tokens = valid_scanner.scan_tokens().expect("Unknown scanning failure.");
-        let expected_tokens = vec![Token::new(Dot, 1), Token::new(Dot, 1), Token::new(Dot, 1), Token::new(EndOfFile, 1)];
+        let expected_tokens = vec![Token::new(Ellipsis, 1), Token::new(EndOfFile, 1)];
         assert_eq!(
             tokens,
             expected_tokens,
@@ -22,16 +22,25 @@ impl Token {
 pub enum TokenData {
     // Delimiters
     LeftParen, RightParen, LeftBrace, RightBrace,
+    LeftBracket, RightBracket,
     Comma, Dot, Semicolon,
+    Ellipsis, // Rest parameters and spread arguments
+    Question, Colon, FatArrow,
 
     // Arithmetic operators
     Minus, Plus, Slash, Star, Percent,
+    TildeSlash, // Integer division
+
+    // Bitwise operators
+    Ampersand, Pipe, Caret,
+    LessLess, GreaterGreater,
 
     // Negation operator
     Bang,
 
     // Assignment
     Equal, 
+    PlusEqual, MinusEqual, StarEqual, SlashEqual, PercentEqual,
     
     // Comparators
     EqualEqual,
@@ -43,11 +52,17 @@ pub enum TokenData {
     Identifier(String),
     StringData(String),
     Number(f64),
+    Integer(i64),
 
     // Reserved words
     Nil, True, False,
     And, Class, Else, Fun, For, If, Or,
     Print, Return, Super, This, Var, While,
+    Match, Case, Default,
+    Trait, With, Yield, In, Const, Enum,
+
+    // Trivia, only produced when the scanner is asked to keep it
+    Comment(String), // Text after the '//', trimmed
 
     // End of file
     EndOfFile,
@@ -77,11 +92,14 @@ impl TokenData {
             TokenData::Identifier(val) => format!("Identifier | {val}"),
             TokenData::StringData(val) => format!("String | \"{val}\""),
             TokenData::Number(val) => format!("Number | {val}"),
+            TokenData::Integer(val) => format!("Integer | {val}"),
             
             TokenData::LeftParen => String::from("("),
             TokenData::RightParen => String::from(")"),
             TokenData::LeftBrace => String::from("{"),
             TokenData::RightBrace => String::from("}"),
+            TokenData::LeftBracket => String::from("["),
+            TokenData::RightBracket => String::from("]"),
 
             _ => String::from("Unidentified static token"), // todo
         }
@@ -128,4 +146,13 @@ mod token_data_tests {
         );
     }
 
+    #[test]
+    fn construct_integer () {
+        let my_token = TokenData::Integer(42);
+        assert_eq!(
+            "Integer | 42", 
+            my_token.to_string(),
+        );
+    }
+
 }
\ No newline at end of file
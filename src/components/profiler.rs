@@ -0,0 +1,260 @@
+use crate::components as lox;
+use lox::interpreter::*;
+use lox::instructions::node::Literal;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
+
+const SCRIPT: &str = "script";
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Default)]
+pub struct FunctionStats {
+    pub calls: usize,
+    pub inclusive: Duration, // Including the functions it called. Recursive calls aren't counted twice.
+    pub exclusive: Duration, // In the function's own statements
+}
+
+// A call that hasn't returned yet.
+struct ActiveCall {
+    function: String,
+    started: Instant,
+    children: Duration, // Spent in the calls it has made so far
+}
+
+// Where a program spent its time, by function and by call stack, and how many statements ran on
+// each line. A line holding several statements counts each of them.
+// The top level of the script counts as a function called once.
+pub struct Profile {
+    functions: HashMap<String, FunctionStats>,
+    stacks: BTreeMap<String, Duration>, // Exclusive time, keyed by the call stack, outermost first
+    lines: BTreeMap<usize, usize>, // Statements run, keyed by line
+    active: Vec<ActiveCall>,
+}
+
+impl Profile {
+    pub fn new(started: Instant) -> Profile {
+        let mut profile = Profile{ functions: HashMap::new(), stacks: BTreeMap::new(), lines: BTreeMap::new(), active: Vec::new() };
+        profile.enter(SCRIPT, started);
+        profile
+    }
+
+    pub fn enter(&mut self, function: &str, at: Instant) {
+        self.functions.entry(String::from(function)).or_default().calls += 1;
+        self.active.push(ActiveCall{ function: String::from(function), started: at, children: Duration::ZERO });
+    }
+
+    pub fn exit(&mut self, at: Instant) {
+        let stack = self.active.iter().map(|c| c.function.as_str()).collect::<Vec<&str>>().join(";");
+        let Some(call) = self.active.pop() else { return; };
+        let elapsed = at.saturating_duration_since(call.started);
+        let own = elapsed.saturating_sub(call.children);
+
+        let recursive = self.active.iter().any(|c| c.function == call.function);
+        let stats = self.functions.entry(call.function).or_default();
+        stats.exclusive += own;
+        if !recursive {
+            stats.inclusive += elapsed;
+        }
+        *self.stacks.entry(stack).or_default() += own;
+        if let Some(caller) = self.active.last_mut() {
+            caller.children += elapsed;
+        }
+    }
+
+    pub fn count_statement(&mut self, line: usize) {
+        *self.lines.entry(line).or_default() += 1;
+    }
+
+    // Ends the calls still in progress, such as the script itself, or those a runtime error left.
+    pub fn finish(&mut self, at: Instant) {
+        while !self.active.is_empty() {
+            self.exit(at);
+        }
+    }
+
+    pub fn function(&self, name: &str) -> Option<&FunctionStats> {
+        self.functions.get(name)
+    }
+
+    pub fn statements_by_line(&self) -> &BTreeMap<usize, usize> {
+        &self.lines
+    }
+
+    // Functions by exclusive time, most first, then lines by number.
+    pub fn report(&self) -> String {
+        let mut functions: Vec<(&String, &FunctionStats)> = self.functions.iter().collect();
+        functions.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive).then(a.0.cmp(b.0)));
+        let millis = |d: Duration| d.as_secs_f64() * 1000.0;
+
+        let mut report = format!("{:>10} {:>14} {:>14}  {}\n", "calls", "inclusive ms", "exclusive ms", "function");
+        for (name, stats) in functions {
+            report.push_str(&format!("{:>10} {:>14.3} {:>14.3}  {}\n", stats.calls, millis(stats.inclusive), millis(stats.exclusive), name));
+        }
+        report.push_str(&format!("\n{:>10} {:>10}\n", "line", "statements"));
+        for (line, statements) in self.lines.iter() {
+            report.push_str(&format!("{:>10} {:>10}\n", line, statements));
+        }
+        report
+    }
+
+    // One line per call stack, with its exclusive time in microseconds, as flamegraph tools take it.
+    pub fn folded(&self) -> String {
+        self.stacks.iter()
+            .map(|(stack, time)| format!("{} {}\n", stack, time.as_micros()))
+            .collect()
+    }
+}
+
+// Feeds a profile from a running program. The profile is shared so that it can be read once the
+// interpreter, which owns the hook, is done.
+pub struct ProfileHook(pub Rc<RefCell<Profile>>);
+
+impl ExecutionHook for ProfileHook {
+    fn before_statement(&mut self, _interpreter: &mut LoxInterpreter, line: usize) -> Result<(), String> {
+        self.0.borrow_mut().count_statement(line);
+        Ok(())
+    }
+
+    fn enter_function(&mut self, interpreter: &mut LoxInterpreter, _params: &[(String, Literal)]) {
+        let function = interpreter.frames().last().map_or("", |f| f.function.as_str());
+        self.0.borrow_mut().enter(function, Instant::now());
+    }
+
+    fn exit_function(&mut self, _interpreter: &mut LoxInterpreter, _returned: Option<&Literal>) {
+        self.0.borrow_mut().exit(Instant::now());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lox::parser::LoxParser;
+
+    fn profile_of(source: &str) -> Profile {
+        let mut parser = LoxParser::new();
+        parser.load_string(source).expect("Error while scanning input string");
+        let program = parser.parse().expect("Error while parsing input string");
+
+        let profile = Rc::new(RefCell::new(Profile::new(Instant::now())));
+        let mut interpreter = LoxInterpreter::new();
+        interpreter.set_hook(Box::new(ProfileHook(Rc::clone(&profile))));
+        interpreter.interpret(program).expect("Error while interpreting program");
+        profile.borrow_mut().finish(Instant::now());
+        drop(interpreter);
+        Rc::try_unwrap(profile).ok().expect("The interpreter is done with the profile").into_inner()
+    }
+
+    mod timing {
+        use super::*;
+
+        fn ms(n: u64) -> Duration {
+            Duration::from_millis(n)
+        }
+
+        #[test]
+        fn test_inclusive_and_exclusive() {
+            let start = Instant::now();
+            let mut profile = Profile::new(start);
+            profile.enter("outer", start + ms(10));
+            profile.enter("inner", start + ms(20));
+            profile.exit(start + ms(50));
+            profile.exit(start + ms(60));
+            profile.finish(start + ms(100));
+
+            let outer = profile.function("outer").expect("Outer was called");
+            assert_eq!(ms(50), outer.inclusive);
+            assert_eq!(ms(20), outer.exclusive);
+            let script = profile.function(SCRIPT).expect("The script ran");
+            assert_eq!(ms(100), script.inclusive);
+            assert_eq!(ms(50), script.exclusive);
+        }
+
+        #[test]
+        fn test_recursion_counted_once() {
+            let start = Instant::now();
+            let mut profile = Profile::new(start);
+            profile.enter("f", start);
+            profile.enter("f", start + ms(10));
+            profile.exit(start + ms(30));
+            profile.exit(start + ms(40));
+            profile.finish(start + ms(40));
+
+            let f = profile.function("f").expect("F was called");
+            assert_eq!(2, f.calls);
+            assert_eq!(ms(40), f.inclusive);
+            assert_eq!(ms(40), f.exclusive);
+        }
+
+        #[test]
+        fn test_folded_stacks() {
+            let start = Instant::now();
+            let mut profile = Profile::new(start);
+            profile.enter("f", start + ms(1));
+            profile.enter("g", start + ms(2));
+            profile.exit(start + ms(4));
+            profile.exit(start + ms(5));
+            profile.enter("g", start + ms(6));
+            profile.exit(start + ms(7));
+            profile.finish(start + ms(10));
+            assert_eq!("script 5000\nscript;f 2000\nscript;f;g 2000\nscript;g 1000\n", profile.folded());
+        }
+    }
+
+    mod counting {
+        use super::*;
+
+        const FIB: &str = "\
+fun fib(n) {
+    if (n <= 1) return n;
+    return fib(n - 2) + fib(n - 1);
+}
+print fib(5);";
+
+        #[test]
+        fn test_call_counts() {
+            let profile = profile_of(FIB);
+            assert_eq!(Some(15), profile.function("fib").map(|f| f.calls));
+            assert_eq!(Some(1), profile.function(SCRIPT).map(|f| f.calls));
+        }
+
+        #[test]
+        fn test_statements_by_line() {
+            let profile = profile_of(FIB);
+            let counts: Vec<(usize, usize)> = profile.statements_by_line().iter().map(|(l, n)| (*l, *n)).collect();
+            // Line 2 counts the if for every call, and the return as well in the base case.
+            assert_eq!(vec![(2, 23), (3, 7), (5, 1)], counts);
+        }
+
+        #[test]
+        fn test_generators_and_methods() {
+            let profile = profile_of("\
+fun* count() { yield 1; yield 2; }
+class A { init() { this.x = 1; } get() { return this.x; } }
+var g = count();
+g.next(); g.next(); g.next();
+A().get();");
+            assert_eq!(Some(3), profile.function("count").map(|f| f.calls));
+            assert_eq!(Some(1), profile.function("init").map(|f| f.calls));
+            assert_eq!(Some(1), profile.function("get").map(|f| f.calls));
+        }
+
+        #[test]
+        fn test_report() {
+            let profile = profile_of(FIB);
+            let report = profile.report();
+            assert!(report.lines().any(|l| l.trim_start().starts_with("15 ") && l.ends_with("  fib")), "{}", report);
+            assert!(report.contains("\n      line statements\n         2         23\n"), "{}", report);
+            let folded = profile.folded();
+            let stacks: Vec<&str> = folded.lines().filter_map(|l| l.rsplit_once(' ').map(|(s, _)| s)).collect();
+            assert_eq!(vec!["script", "script;fib", "script;fib;fib", "script;fib;fib;fib", "script;fib;fib;fib;fib", "script;fib;fib;fib;fib;fib"], stacks);
+        }
+    }
+}
@@ -0,0 +1,746 @@
+use crate::components as lox;
+use lox::parser::scanner::LoxScanner;
+use lox::parser::token::TokenData;
+use lox::instructions::callable::Callable;
+
+use std::collections::HashMap;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Eq)]
+#[derive(PartialOrd)]
+#[derive(Ord)]
+#[derive(Clone)]
+#[derive(Copy)]
+pub struct Position {
+    pub line: usize, // Counting from 1, like tokens
+    pub column: usize, // Counting from 0
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Copy)]
+pub enum SymbolKind {
+    Variable,
+    Constant,
+    Parameter,
+    Function,
+    Class,
+    Trait,
+    Enum,
+    Method, // Including accessors and static methods
+    Field, // Static fields
+    Variant,
+}
+
+impl SymbolKind {
+    // Members are only reached through a '.', never by their bare name.
+    pub fn is_member(&self) -> bool {
+        matches!(self, SymbolKind::Method | SymbolKind::Field | SymbolKind::Variant)
+    }
+
+    fn has_members(&self) -> bool {
+        matches!(self, SymbolKind::Class | SymbolKind::Trait | SymbolKind::Enum)
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub position: Position, // Where the name is declared
+    pub end: Position, // The end of the declaration's body, or of its name if it has none
+    pub detail: String, // The declaration as written, up to its body or initializer
+    pub container: Option<usize>, // The class, trait or enum a member belongs to
+    scope: usize,
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: SymbolKind,
+    pub detail: String,
+}
+
+#[derive(Debug)]
+struct Scope {
+    parent: Option<usize>,
+    start: Position,
+    end: Position,
+    owner: Option<usize>, // The declaration this scope is the body of
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+enum Target {
+    Symbol(usize),
+    Property(String), // A name after a '.', which could be a member of any class
+}
+
+#[derive(Debug)]
+struct Occurrence {
+    position: Position,
+    length: usize,
+    target: Target,
+}
+
+// Where names are declared and used in a source file, for editor tooling. It's built from
+// tokens rather than the syntax tree, so that it still works on code that is mid-edit and
+// doesn't parse. Scopes follow braces, and names resolve the way the interpreter looks them
+// up: to the latest declaration before the use in the nearest enclosing scope, except that
+// globals may be used before they're declared.
+pub struct SymbolIndex {
+    symbols: Vec<Symbol>,
+    scopes: Vec<Scope>, // The first is the global scope
+    occurrences: Vec<Occurrence>, // Ordered by position
+    tokens: Vec<(Position, TokenData)>,
+}
+
+impl SymbolIndex {
+    // A source that can't be scanned gives an empty index.
+    pub fn build(source: &str) -> SymbolIndex {
+        let mut scanner = LoxScanner::new(source);
+        let tokens = match scanner.scan_tokens() {
+            Ok(tokens) => tokens.into_iter()
+                .zip(scanner.token_columns())
+                .map(|(t, column)| (Position{ line: t.line, column: *column }, t.data))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        IndexBuilder::new(source, tokens).build()
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    // Indices of the symbols declared in the global scope, in source order.
+    pub fn globals(&self) -> Vec<usize> {
+        (0..self.symbols.len())
+            .filter(|i| self.symbols[*i].scope == 0 && self.symbols[*i].kind != SymbolKind::Parameter && !self.symbols[*i].kind.is_member())
+            .collect()
+    }
+
+    pub fn members(&self, container: usize) -> Vec<usize> {
+        (0..self.symbols.len()).filter(|i| self.symbols[*i].container == Some(container)).collect()
+    }
+
+    // A name after a '.' may refer to a member of any class with one by that name.
+    pub fn definitions(&self, at: Position) -> Vec<&Symbol> {
+        match self.occurrence_at(at).map(|o| &o.target) {
+            Some(Target::Symbol(id)) => vec![&self.symbols[*id]],
+            Some(Target::Property(name)) => self.symbols.iter().filter(|s| s.kind.is_member() && s.name == *name).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // The start and length of each use of the name at the given position.
+    pub fn references(&self, at: Position, include_declaration: bool) -> Vec<(Position, usize)> {
+        let Some(target) = self.occurrence_at(at).map(|o| &o.target) else { return Vec::new(); };
+        let member_name = match target {
+            Target::Property(name) => Some(name),
+            Target::Symbol(id) if self.symbols[*id].kind.is_member() => Some(&self.symbols[*id].name),
+            Target::Symbol(_) => None,
+        };
+        self.occurrences.iter()
+            .filter(|o| match (&o.target, member_name) {
+                (Target::Symbol(id), Some(name)) => self.symbols[*id].kind.is_member() && self.symbols[*id].name == *name,
+                (Target::Property(other), Some(name)) => other == name,
+                (other, None) => other == target,
+            })
+            .filter(|o| include_declaration || !matches!(o.target, Target::Symbol(id) if self.symbols[id].position == o.position))
+            .map(|o| (o.position, o.length))
+            .collect()
+    }
+
+    pub fn hover(&self, at: Position) -> Option<String> {
+        let details: Vec<&str> = self.definitions(at).iter().map(|s| s.detail.as_str()).collect();
+        if details.is_empty() { None } else { Some(details.join("\n")) }
+    }
+
+    // After a '.', the members of whatever is before it, if that can be told from the source,
+    // and otherwise every method. Elsewhere, every name in scope at the position.
+    pub fn completions(&self, at: Position) -> Vec<Completion> {
+        let mut before = self.tokens.iter().rposition(|(p, _)| *p < at);
+        if let Some(i) = before {
+            let (p, data) = &self.tokens[i];
+            if let TokenData::Identifier(name) = data {
+                if p.line == at.line && p.column + name.chars().count() == at.column {
+                    before = i.checked_sub(1); // Skip the part of the name typed so far
+                }
+            }
+        }
+        match before {
+            Some(i) if i > 0 && self.tokens[i].1 == TokenData::Dot => self.member_completions(i - 1),
+            _ => self.scope_completions(at),
+        }
+    }
+
+    fn member_completions(&self, receiver: usize) -> Vec<Completion> {
+        let (position, data) = &self.tokens[receiver];
+        let owner = match data {
+            TokenData::This => self.enclosing_owner(*position),
+            TokenData::Identifier(_) => match self.occurrence_at(*position).map(|o| &o.target) {
+                Some(Target::Symbol(id)) if self.symbols[*id].kind.has_members() => Some(*id),
+                _ => None,
+            },
+            _ => None,
+        };
+        let mut completions: Vec<Completion> = Vec::new();
+        let candidates = match owner {
+            Some(owner) => self.members(owner),
+            None => (0..self.symbols.len()).filter(|i| self.symbols[*i].kind == SymbolKind::Method).collect(),
+        };
+        for i in candidates {
+            let symbol = &self.symbols[i];
+            if completions.iter().all(|c| c.label != symbol.name) {
+                completions.push(Completion{ label: symbol.name.clone(), kind: symbol.kind, detail: symbol.detail.clone() });
+            }
+        }
+        completions
+    }
+
+    fn scope_completions(&self, at: Position) -> Vec<Completion> {
+        let mut completions: Vec<Completion> = Vec::new();
+        let mut scope = Some(self.scope_at(at));
+        while let Some(s) = scope {
+            for symbol in self.symbols.iter().filter(|sym| sym.scope == s && !sym.kind.is_member()) {
+                let visible = s == 0 || symbol.position < at;
+                if visible && completions.iter().all(|c| c.label != symbol.name) {
+                    completions.push(Completion{ label: symbol.name.clone(), kind: symbol.kind, detail: symbol.detail.clone() });
+                }
+            }
+            scope = self.scopes[s].parent;
+        }
+        for (name, _) in Callable::native_fn_list() {
+            if completions.iter().all(|c| c.label != name) {
+                completions.push(Completion{ label: name, kind: SymbolKind::Function, detail: String::from("native function") });
+            }
+        }
+        completions
+    }
+
+    // The innermost scope containing the position.
+    fn scope_at(&self, at: Position) -> usize {
+        let mut innermost = 0;
+        for (i, scope) in self.scopes.iter().enumerate() {
+            if scope.start < at && at <= scope.end && scope.start >= self.scopes[innermost].start {
+                innermost = i;
+            }
+        }
+        innermost
+    }
+
+    // The class or trait whose body contains the position.
+    fn enclosing_owner(&self, at: Position) -> Option<usize> {
+        let mut scope = Some(self.scope_at(at));
+        while let Some(s) = scope {
+            if let Some(owner) = self.scopes[s].owner {
+                if matches!(self.symbols[owner].kind, SymbolKind::Class | SymbolKind::Trait) { return Some(owner); }
+            }
+            scope = self.scopes[s].parent;
+        }
+        None
+    }
+
+    // The name at the position; a position just past the end of a name still counts.
+    fn occurrence_at(&self, at: Position) -> Option<&Occurrence> {
+        self.occurrences.iter().find(|o| {
+            o.position.line == at.line && o.position.column <= at.column && at.column <= o.position.column + o.length
+        })
+    }
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Copy)]
+enum Bracket {
+    Call,
+    Params,
+    Group,
+    List,
+    Block(usize), // A brace that opened the given scope
+    Map,
+}
+
+#[derive(Debug)]
+enum Pending {
+    Function(usize, Vec<usize>), // A function or method and its parameters, waiting for its body
+    Body(usize), // A class, trait or enum waiting for its body
+}
+
+struct IndexBuilder {
+    lines: Vec<Vec<char>>,
+    tokens: Vec<(Position, TokenData)>,
+    current: usize,
+    symbols: Vec<Symbol>,
+    scopes: Vec<Scope>,
+    scope_stack: Vec<usize>,
+    brackets: Vec<Bracket>,
+    pending: Option<Pending>,
+    header_start: Position, // Where the text of the pending declaration starts
+    expect_params: bool, // Whether the next '(' opens a parameter list
+    in_pattern: bool, // Between 'case' and '=>' or 'if'
+    references: Vec<(Position, String, usize)>, // Bare names with the scope they're used in
+    properties: Vec<(Position, String)>,
+}
+
+impl IndexBuilder {
+    fn new(source: &str, tokens: Vec<(Position, TokenData)>) -> IndexBuilder {
+        let end = tokens.last().map(|(p, _)| *p).unwrap_or(Position{ line: 1, column: 0 });
+        IndexBuilder {
+            lines: source.lines().map(|l| l.chars().collect()).collect(),
+            tokens,
+            current: 0,
+            symbols: Vec::new(),
+            scopes: vec![Scope{ parent: None, start: Position{ line: 1, column: 0 }, end, owner: None }],
+            scope_stack: vec![0],
+            brackets: Vec::new(),
+            pending: None,
+            header_start: Position{ line: 1, column: 0 },
+            expect_params: false,
+            in_pattern: false,
+            references: Vec::new(),
+            properties: Vec::new(),
+        }
+    }
+
+    fn build(mut self) -> SymbolIndex {
+        use TokenData::*;
+
+        while self.current < self.tokens.len() {
+            let (position, data) = self.tokens[self.current].clone();
+            self.current += 1;
+            match data {
+                LeftBrace => self.open_brace(position),
+                RightBrace => self.close_brace(position),
+                LeftParen => {
+                    let bracket = if self.expect_params {
+                        self.expect_params = false;
+                        Bracket::Params
+                    } else if matches!(self.previous(), Some(Identifier(_) | RightParen | RightBracket)) {
+                        Bracket::Call
+                    } else {
+                        Bracket::Group
+                    };
+                    self.brackets.push(bracket);
+                },
+                RightParen => self.close_bracket(|b| matches!(b, Bracket::Call | Bracket::Params | Bracket::Group)),
+                LeftBracket => self.brackets.push(Bracket::List),
+                RightBracket => self.close_bracket(|b| b == Bracket::List),
+                Dot => {
+                    if let Some((p, Identifier(name))) = self.tokens.get(self.current).cloned() {
+                        self.properties.push((p, name));
+                        self.current += 1;
+                    }
+                },
+                Var => self.variable(position, SymbolKind::Variable),
+                Const => self.variable(position, SymbolKind::Constant),
+                Fun => {
+                    if self.peek() == Some(&Star) { self.current += 1; }
+                    self.function(position, SymbolKind::Function);
+                },
+                Class if self.at_member_start(SymbolKind::Class) => self.static_member(position),
+                Class => self.body_declaration(position, SymbolKind::Class),
+                Trait => self.body_declaration(position, SymbolKind::Trait),
+                Enum => self.body_declaration(position, SymbolKind::Enum),
+                Case => self.in_pattern = true,
+                FatArrow | If => self.in_pattern = false,
+                Identifier(name) => self.identifier(position, name),
+                _ => (),
+            }
+        }
+
+        // Anything left open runs to the end of the file.
+        let end = self.scopes[0].end;
+        while self.scope_stack.len() > 1 {
+            let scope = self.scope_stack.pop().expect("Stack length was checked");
+            self.end_scope(scope, end);
+        }
+        self.finish()
+    }
+
+    fn previous(&self) -> Option<&TokenData> {
+        self.current.checked_sub(2).map(|i| &self.tokens[i].1)
+    }
+
+    fn peek(&self) -> Option<&TokenData> {
+        self.tokens.get(self.current).map(|(_, data)| data)
+    }
+
+    fn peek_second(&self) -> Option<&TokenData> {
+        self.tokens.get(self.current + 1).map(|(_, data)| data)
+    }
+
+    // Consumes the next token if it's an identifier.
+    fn name(&mut self) -> Option<(Position, String)> {
+        match self.tokens.get(self.current).cloned() {
+            Some((position, TokenData::Identifier(name))) => {
+                self.current += 1;
+                Some((position, name))
+            },
+            _ => None,
+        }
+    }
+
+    fn declare(&mut self, name: String, kind: SymbolKind, position: Position, detail: String) -> usize {
+        let scope = *self.scope_stack.last().expect("The global scope is never closed");
+        let container = if kind.is_member() { self.scopes[scope].owner } else { None };
+        let end = Position{ line: position.line, column: position.column + name.chars().count() };
+        self.symbols.push(Symbol{ name, kind, position, end, detail, container, scope });
+        self.symbols.len() - 1
+    }
+
+    // The kind of declaration whose body the current token is directly inside.
+    fn body_kind(&self) -> Option<SymbolKind> {
+        let Some(Bracket::Block(scope)) = self.brackets.last() else { return None; };
+        self.scopes[*scope].owner.map(|o| self.symbols[o].kind).filter(|k| k.has_members())
+    }
+
+    // Whether the current token starts a member of a body of the given kind, as opposed to
+    // being part of an initializer.
+    fn at_member_start(&self, kind: SymbolKind) -> bool {
+        let starts = match kind {
+            SymbolKind::Enum => matches!(self.previous(), Some(TokenData::LeftBrace | TokenData::Comma)),
+            _ => matches!(self.previous(), None | Some(TokenData::LeftBrace | TokenData::RightBrace | TokenData::Semicolon)),
+        };
+        starts && self.body_kind() == Some(kind)
+    }
+
+    fn variable(&mut self, start: Position, kind: SymbolKind) {
+        let Some((position, name)) = self.name() else { return; };
+        let detail = self.declaration_text(start);
+        self.declare(name, kind, position, detail);
+    }
+
+    fn function(&mut self, start: Position, kind: SymbolKind) {
+        let Some((position, name)) = self.name() else { return; };
+        let symbol = self.declare(name, kind, position, String::new());
+        self.pending = Some(Pending::Function(symbol, Vec::new()));
+        self.header_start = start;
+        self.expect_params = true;
+    }
+
+    fn body_declaration(&mut self, start: Position, kind: SymbolKind) {
+        let Some((position, name)) = self.name() else { return; };
+        let symbol = self.declare(name, kind, position, String::new());
+        self.pending = Some(Pending::Body(symbol));
+        self.header_start = start;
+    }
+
+    // A 'class' member: a static method or field.
+    fn static_member(&mut self, start: Position) {
+        if self.peek_second() == Some(&TokenData::LeftParen) {
+            self.function(start, SymbolKind::Method);
+        } else {
+            self.variable(start, SymbolKind::Field);
+        }
+    }
+
+    fn identifier(&mut self, position: Position, name: String) {
+        use TokenData::*;
+
+        match self.brackets.last() {
+            Some(Bracket::Params) if matches!(self.previous(), Some(LeftParen | Comma | Ellipsis)) => {
+                let detail = format!("(parameter) {}", name);
+                let symbol = self.declare(name, SymbolKind::Parameter, position, detail);
+                if let Some(Pending::Function(_, params)) = &mut self.pending { params.push(symbol); }
+                return;
+            },
+            Some(Bracket::Call) if matches!(self.previous(), Some(LeftParen | Comma)) && self.peek() == Some(&Colon) => {
+                return; // The label of a named argument
+            },
+            _ => (),
+        }
+
+        if self.in_pattern && !matches!(self.peek(), Some(LeftParen | Dot)) {
+            if name != "_" {
+                let detail = format!("(binding) {}", name);
+                self.declare(name, SymbolKind::Variable, position, detail);
+            }
+            return;
+        }
+
+        if self.at_member_start(SymbolKind::Class) || self.at_member_start(SymbolKind::Trait) {
+            match self.peek() {
+                Some(Identifier(_)) if name == "set" => {
+                    // A setter's text starts at 'set', but it's named after the token that follows.
+                    self.function(position, SymbolKind::Method);
+                    return;
+                },
+                Some(LeftParen) => {
+                    self.current -= 1; // The method is named by this token
+                    self.function(position, SymbolKind::Method);
+                    return;
+                },
+                Some(LeftBrace) => {
+                    let symbol = self.declare(name, SymbolKind::Method, position, String::new());
+                    self.pending = Some(Pending::Function(symbol, Vec::new()));
+                    self.header_start = position;
+                    return;
+                },
+                _ => (),
+            }
+        }
+
+        if self.at_member_start(SymbolKind::Enum) {
+            // Payload names are only ever bound by patterns, so the variant's text is enough.
+            let mut end = Position{ line: position.line, column: position.column + name.chars().count() };
+            if self.peek() == Some(&LeftParen) {
+                while let Some((p, data)) = self.tokens.get(self.current) {
+                    self.current += 1;
+                    if *data == RightParen {
+                        end = Position{ line: p.line, column: p.column + 1 };
+                        break;
+                    }
+                }
+            }
+            let detail = self.text(position, end);
+            self.declare(name, SymbolKind::Variant, position, detail);
+            return;
+        }
+
+        let scope = *self.scope_stack.last().expect("The global scope is never closed");
+        self.references.push((position, name, scope));
+    }
+
+    fn open_brace(&mut self, position: Position) {
+        use TokenData::*;
+
+        match self.pending.take() {
+            Some(Pending::Function(symbol, params)) => {
+                let scope = self.open_scope(position, Some(symbol));
+                for p in params { self.symbols[p].scope = scope; }
+                self.symbols[symbol].detail = self.text(self.header_start, position);
+            },
+            Some(Pending::Body(symbol)) => {
+                self.open_scope(position, Some(symbol));
+                self.symbols[symbol].detail = self.text(self.header_start, position);
+            },
+            None if matches!(self.previous(), None | Some(RightParen | Else | Semicolon | LeftBrace | RightBrace | FatArrow)) => {
+                self.open_scope(position, None);
+            },
+            None => self.brackets.push(Bracket::Map),
+        }
+    }
+
+    fn open_scope(&mut self, start: Position, owner: Option<usize>) -> usize {
+        let parent = self.scope_stack.last().copied();
+        self.scopes.push(Scope{ parent, start, end: self.scopes[0].end, owner });
+        let scope = self.scopes.len() - 1;
+        self.scope_stack.push(scope);
+        self.brackets.push(Bracket::Block(scope));
+        scope
+    }
+
+    // Unbalanced parentheses or brackets inside a brace are closed along with it.
+    fn close_brace(&mut self, position: Position) {
+        while let Some(bracket) = self.brackets.pop() {
+            match bracket {
+                Bracket::Block(scope) => {
+                    self.scope_stack.pop();
+                    self.end_scope(scope, Position{ line: position.line, column: position.column + 1 });
+                    return;
+                },
+                Bracket::Map => return,
+                _ => (),
+            }
+        }
+    }
+
+    fn close_bracket(&mut self, matches: impl Fn(Bracket) -> bool) {
+        if self.brackets.last().is_some_and(|b| matches(*b)) {
+            self.brackets.pop();
+        }
+    }
+
+    fn end_scope(&mut self, scope: usize, end: Position) {
+        self.scopes[scope].end = end;
+        if let Some(owner) = self.scopes[scope].owner {
+            self.symbols[owner].end = end;
+        }
+    }
+
+    // The text of a variable declaration, up to its initializer.
+    fn declaration_text(&self, start: Position) -> String {
+        let end = self.tokens[self.current..].iter()
+            .find(|(_, data)| !matches!(data, TokenData::Colon | TokenData::Identifier(_)))
+            .map(|(p, _)| *p)
+            .unwrap_or(self.scopes[0].end);
+        self.text(start, end)
+    }
+
+    // Source text between two positions, on one line with single spaces.
+    fn text(&self, start: Position, end: Position) -> String {
+        let mut text = String::new();
+        for line in start.line..=end.line {
+            let Some(chars) = self.lines.get(line - 1) else { break; };
+            let from = if line == start.line { start.column } else { 0 };
+            let to = if line == end.line { end.column.min(chars.len()) } else { chars.len() };
+            if from < to { text.extend(&chars[from..to]); }
+            text.push(' ');
+        }
+        text.split_whitespace().collect::<Vec<&str>>().join(" ").replace("( ", "(").replace(" )", ")")
+    }
+
+    fn finish(self) -> SymbolIndex {
+        let mut declarations: HashMap<(usize, &str), Vec<usize>> = HashMap::new();
+        for (i, symbol) in self.symbols.iter().enumerate() {
+            if !symbol.kind.is_member() {
+                declarations.entry((symbol.scope, &symbol.name)).or_default().push(i);
+            }
+        }
+
+        let mut occurrences: Vec<Occurrence> = self.symbols.iter()
+            .enumerate()
+            .map(|(i, s)| Occurrence{ position: s.position, length: s.name.chars().count(), target: Target::Symbol(i) })
+            .collect();
+        for (position, name, scope) in self.references.iter() {
+            let mut scope = Some(*scope);
+            while let Some(s) = scope {
+                if let Some(candidates) = declarations.get(&(s, name.as_str())) {
+                    let before = candidates.iter().rev().find(|c| self.symbols[**c].position < *position);
+                    let found = if s == 0 { before.or(candidates.first()) } else { before };
+                    if let Some(symbol) = found {
+                        occurrences.push(Occurrence{ position: *position, length: name.chars().count(), target: Target::Symbol(*symbol) });
+                        break;
+                    }
+                }
+                scope = self.scopes[s].parent;
+            }
+        }
+        for (position, name) in self.properties.iter() {
+            occurrences.push(Occurrence{ position: *position, length: name.chars().count(), target: Target::Property(name.clone()) });
+        }
+        occurrences.sort_by_key(|o| o.position);
+
+        SymbolIndex{ symbols: self.symbols, scopes: self.scopes, occurrences, tokens: self.tokens }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(line: usize, column: usize) -> Position {
+        Position{ line, column }
+    }
+
+    // The declaration positions the name at each position resolves to.
+    fn test_definitions_generic(source: &str, cases: &[(Position, &[Position])]) {
+        let index = SymbolIndex::build(source);
+        for (position, expected) in cases {
+            let found: Vec<Position> = index.definitions(*position).iter().map(|s| s.position).collect();
+            assert_eq!(expected.to_vec(), found, "at {:?}", position);
+        }
+    }
+
+    mod resolution {
+        use super::*;
+
+        #[test]
+        fn test_scopes() {
+            let source = "\
+var a = 1;
+{
+    print a;
+    var a = 2;
+    print a;
+}
+print a;
+";
+            test_definitions_generic(source, &[
+                (at(3, 10), &[at(1, 4)]),
+                (at(5, 10), &[at(4, 8)]),
+                (at(7, 6), &[at(1, 4)]),
+            ]);
+        }
+
+        #[test]
+        fn test_globals_before_declaration() {
+            let source = "fun f() { return g(); }\nfun g() { return 1; }\nprint missing;\n";
+            test_definitions_generic(source, &[
+                (at(1, 17), &[at(2, 4)]),
+                (at(3, 6), &[]),
+            ]);
+        }
+
+        #[test]
+        fn test_parameters_and_labels() {
+            let source = "fun area(width, height = width) { return width * height; }\narea(height: 2, width: 3);\n";
+            test_definitions_generic(source, &[
+                (at(1, 41), &[at(1, 9)]),
+                (at(1, 50), &[at(1, 16)]),
+                (at(1, 25), &[]), // Not yet in scope in a default
+                (at(2, 6), &[]), // A label, not a variable
+                (at(2, 0), &[at(1, 4)]),
+            ]);
+        }
+
+        #[test]
+        fn test_patterns_and_variants() {
+            let source = "\
+enum Shape { Circle(r), Square(side) }
+match (Shape.Circle(1)) {
+    case Shape.Circle(radius) => print radius;
+    default => print 0;
+}
+";
+            test_definitions_generic(source, &[
+                (at(3, 39), &[at(3, 22)]),
+                (at(3, 16), &[at(1, 13)]),
+                (at(2, 7), &[at(1, 5)]),
+            ]);
+        }
+
+        #[test]
+        fn test_maps_are_not_scopes() {
+            let source = "var m = {\"k\": 1};\nvar n = m;\n";
+            test_definitions_generic(source, &[(at(2, 8), &[at(1, 4)])]);
+            assert_eq!(1, SymbolIndex::build(source).scopes.len());
+        }
+    }
+
+    mod members {
+        use super::*;
+
+        #[test]
+        fn test_member_details() {
+            let source = "\
+class Box < Base with Shown {
+    init(value) { this.value = value; }
+    class empty() { return Box(nil); }
+    class count = 0;
+    size { return 1; }
+    set size(n) {}
+}
+";
+            let index = SymbolIndex::build(source);
+            let details: Vec<(&str, SymbolKind, &str, Option<usize>)> = index.symbols().iter()
+                .filter(|s| s.kind != SymbolKind::Parameter)
+                .map(|s| (s.name.as_str(), s.kind, s.detail.as_str(), s.container))
+                .collect();
+            assert_eq!(vec![
+                ("Box", SymbolKind::Class, "class Box < Base with Shown", None),
+                ("init", SymbolKind::Method, "init(value)", Some(0)),
+                ("empty", SymbolKind::Method, "class empty()", Some(0)),
+                ("count", SymbolKind::Field, "class count", Some(0)),
+                ("size", SymbolKind::Method, "size", Some(0)),
+                ("size", SymbolKind::Method, "set size(n)", Some(0)),
+            ], details);
+            assert_eq!(at(7, 1), index.symbols()[0].end);
+        }
+
+        #[test]
+        fn test_unscannable_source() {
+            let index = SymbolIndex::build("var s = \"open;\n");
+            assert!(index.symbols().is_empty());
+            assert!(index.completions(at(1, 0)).iter().any(|c| c.label == "clock"));
+        }
+    }
+}
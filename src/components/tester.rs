@@ -0,0 +1,162 @@
+use crate::components as lox;
+use lox::interpreter::*;
+use lox::instructions::statement::Statement;
+use lox::instructions::expression::Expression;
+
+use std::time::Duration;
+use std::time::Instant;
+
+const FUNCTION_PREFIX: &str = "test_";
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub struct TestResult {
+    pub name: String,
+    pub duration: Duration,
+    pub failure: Option<String>, // The error, prefixed with the line it came from when that's known
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+// A test and the statements that run it.
+struct TestCase {
+    name: String,
+    body: Vec<Statement>,
+}
+
+// Runs every test in the program: its test blocks, then its functions named test_* that take no
+// arguments. Each test gets a fresh interpreter that first runs the rest of the program as setup,
+// so a test can't see what another one changed, and one failing doesn't stop the others.
+pub fn run_tests(program: Vec<Statement>) -> Vec<TestResult> {
+    let (setup, cases) = split_tests(program);
+    cases.into_iter().map(|case| run_case(&setup, case)).collect()
+}
+
+fn split_tests(program: Vec<Statement>) -> (Vec<Statement>, Vec<TestCase>) {
+    let mut setup = Vec::new();
+    let mut blocks = Vec::new();
+    let mut functions = Vec::new();
+    for statement in program {
+        match statement {
+            Statement::Test(name, body, _) => blocks.push(TestCase{ name, body: vec![Statement::Block(body)] }),
+            Statement::Fun(ref name, ref params, _, _) if name.starts_with(FUNCTION_PREFIX) && params.is_empty() => {
+                let call = Expression::Call(Box::new(Expression::Identifier(name.clone())), vec![], vec![], 0);
+                functions.push(TestCase{ name: name.clone(), body: vec![Statement::Expr(Box::new(call), 0)] });
+                setup.push(statement);
+            },
+            other => setup.push(other),
+        }
+    }
+    blocks.append(&mut functions);
+    (setup, blocks)
+}
+
+fn run_case(setup: &[Statement], case: TestCase) -> TestResult {
+    let started = Instant::now();
+    let mut interpreter = LoxInterpreter::new();
+    interpreter.define_assertions();
+    let outcome = interpreter.interpret(setup.to_vec())
+        .map_err(|e| format!("setup failed: {}", e))
+        .and_then(|_| interpreter.interpret(case.body));
+    let failure = outcome.err().map(|e| match interpreter.error_line() {
+        Some(line) => format!("line {}: {}", line, e),
+        None => e,
+    });
+    TestResult{ name: case.name, duration: started.elapsed(), failure }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lox::parser::LoxParser;
+
+    fn results_of(source: &str) -> Vec<(String, Option<String>)> {
+        let mut parser = LoxParser::new();
+        parser.load_string(source).expect("Error while scanning input string");
+        let program = parser.parse().expect("Error while parsing input string");
+        run_tests(program).into_iter().map(|r| (r.name, r.failure)).collect()
+    }
+
+    mod discovery {
+        use super::*;
+
+        #[test]
+        fn test_blocks_then_functions() {
+            let results = results_of("\
+fun test_later() {}
+test \"first\" {}
+fun helper(x) {}
+fun test_with_args(x) {}
+test \"second\" {}");
+            let names: Vec<&str> = results.iter().map(|(n, _)| n.as_str()).collect();
+            assert_eq!(vec!["first", "second", "test_later"], names);
+        }
+
+        #[test]
+        fn test_no_tests() {
+            assert!(results_of("var test = 1;\nprint test;").is_empty());
+        }
+    }
+
+    mod running {
+        use super::*;
+
+        #[test]
+        fn test_pass_and_fail() {
+            let results = results_of("\
+fun double(x) { return x * 2; }
+test \"doubles\" { assertEqual(double(2), 4); }
+test \"wrong\" {
+    assertEqual(double(2), 5);
+}
+test \"message\" { assert(false, \"no good\"); }
+fun test_function() {
+    var x = 1;
+    assert(x > 1);
+}");
+            assert_eq!(vec![
+                (String::from("doubles"), None),
+                (String::from("wrong"), Some(String::from("line 4: Assertion failed: 4 is not equal to 5."))),
+                (String::from("message"), Some(String::from("line 6: Assertion failed: no good"))),
+                (String::from("test_function"), Some(String::from("line 9: Assertion failed."))),
+            ], results);
+        }
+
+        #[test]
+        fn test_isolated() {
+            let results = results_of("\
+var count = 0;
+test \"one\" { count = count + 1; assertEqual(count, 1); }
+test \"two\" { count = count + 1; assertEqual(count, 1); }");
+            assert!(results.iter().all(|(_, failure)| failure.is_none()), "{:?}", results);
+        }
+
+        #[test]
+        fn test_setup_failure() {
+            let results = results_of("var broken = missing;\ntest \"any\" {}");
+            assert_eq!(vec![(String::from("any"), Some(String::from("line 1: setup failed: Undefined variable 'missing'.")))], results);
+        }
+
+        #[test]
+        fn test_equality_overload() {
+            let results = results_of("\
+class Point { init(x) { this.x = x; } __eq__(other) { return this.x == other.x; } }
+test \"points\" { assertEqual(Point(1), Point(1), \"same x\"); }");
+            assert_eq!(None, results[0].1);
+        }
+
+        #[test]
+        fn test_own_assert() {
+            let results = results_of("\
+fun assert(condition) { if (!condition) return missing; }
+test \"own\" { assert(false); }");
+            assert_eq!(vec![(String::from("own"), Some(String::from("line 1: Undefined variable 'missing'.")))], results);
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use crate::components as lox;
+use lox::interpreter::*;
+use lox::instructions::node::Literal;
+use lox::instructions::expression::Expression;
+use lox::formatter::LoxFormatter;
+use lox::json::Json;
+
+use std::io::Write;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Copy)]
+pub enum TraceFormat {
+    Text, // Indented lines for reading
+    JsonLines, // One JSON object per event
+}
+
+// Writes out everything a program does as it runs: each statement, each declaration, each
+// expression with its value, each call with its arguments and what it returned, and each scope
+// opened and closed. Lines are indented by call depth. Values are shown as print would show them;
+// the __str__ calls that takes aren't traced themselves.
+pub struct LoxTracer<W: Write> {
+    source: Vec<String>,
+    output: W,
+    format: TraceFormat,
+    formatter: LoxFormatter, // Shows expressions as source
+    failed: Option<String>, // Writing failed where the error couldn't be returned
+}
+
+impl<W: Write> LoxTracer<W> {
+    pub fn new(source: &str, output: W, format: TraceFormat) -> LoxTracer<W> {
+        LoxTracer {
+            source: source.lines().map(String::from).collect(),
+            output,
+            format,
+            formatter: LoxFormatter::new(4, 100), // Expressions are kept on one line, whatever the width
+            failed: None,
+        }
+    }
+
+    // The event's fields after its name and depth, and how it reads as text.
+    fn emit(&mut self, event: &str, depth: usize, fields: Vec<(&str, Json)>, text: String) {
+        let line = match self.format {
+            TraceFormat::Text => format!("{}{}", "  ".repeat(depth), text),
+            TraceFormat::JsonLines => {
+                let mut object = vec![("event", Json::from(event)), ("depth", Json::from(depth))];
+                object.extend(fields);
+                Json::object(object).to_string()
+            },
+        };
+        if let Err(e) = writeln!(self.output, "{}", line) {
+            self.failed.get_or_insert(format!("Tracing stopped: {}", e));
+        }
+    }
+
+    fn scope(&mut self, interpreter: &LoxInterpreter, action: &str) {
+        let scopes = interpreter.scope_depth();
+        let fields = vec![("action", Json::from(action)), ("scopes", Json::from(scopes))];
+        self.emit("scope", call_depth(interpreter), fields, format!("{} scope {}", action, scopes));
+    }
+}
+
+// Calls in progress, not counting the script itself.
+fn call_depth(interpreter: &LoxInterpreter) -> usize {
+    interpreter.frames().len().saturating_sub(1)
+}
+
+fn function_name(interpreter: &LoxInterpreter) -> String {
+    interpreter.frames().last().map_or(String::new(), |f| f.function.clone())
+}
+
+// Falls back to the plain value when its __str__ fails, leaving the error to the program.
+fn shown(interpreter: &mut LoxInterpreter, value: &Literal) -> String {
+    interpreter.display(value.clone()).unwrap_or_else(|_| value.to_string())
+}
+
+impl<W: Write> ExecutionHook for LoxTracer<W> {
+    fn before_statement(&mut self, interpreter: &mut LoxInterpreter, line: usize) -> Result<(), String> {
+        let source = self.source.get(line.wrapping_sub(1)).map_or("", |s| s.trim()).to_string();
+        let fields = vec![("line", Json::from(line)), ("source", Json::from(source.as_str()))];
+        self.emit("statement", call_depth(interpreter), fields, format!("{}: {}", line, source));
+        match self.failed.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    // Calls are shown at their caller's depth, with the statements they run indented beneath.
+    fn enter_function(&mut self, interpreter: &mut LoxInterpreter, params: &[(String, Literal)]) {
+        let function = function_name(interpreter);
+        let shown_params = params.iter().map(|(name, value)| (name.clone(), shown(interpreter, value))).collect::<Vec<(String, String)>>();
+        let args = shown_params.iter().map(|(name, value)| format!("{} = {}", name, value)).collect::<Vec<String>>().join(", ");
+        let fields = vec![
+            ("function", Json::from(function.as_str())),
+            ("args", Json::Object(shown_params.into_iter().map(|(name, value)| (name, Json::from(value))).collect())),
+        ];
+        self.emit("enter", call_depth(interpreter) - 1, fields, format!("call {}({})", function, args));
+    }
+
+    fn exit_function(&mut self, interpreter: &mut LoxInterpreter, returned: Option<&Literal>) {
+        let function = function_name(interpreter);
+        let (value, text) = match returned {
+            Some(value) => {
+                let value = shown(interpreter, value);
+                (Json::from(value.as_str()), format!("return {} -> {}", function, value))
+            },
+            None => (Json::Null, format!("return {} with an error", function)),
+        };
+        let fields = vec![("function", Json::from(function.as_str())), ("value", value)];
+        self.emit("exit", call_depth(interpreter) - 1, fields, text);
+    }
+
+    fn declaration(&mut self, interpreter: &mut LoxInterpreter, keyword: &str, name: &str) {
+        let fields = vec![("keyword", Json::from(keyword)), ("name", Json::from(name))];
+        self.emit("declare", call_depth(interpreter), fields, format!("declare {} {}", keyword, name));
+    }
+
+    fn traces_expressions(&self) -> bool {
+        true
+    }
+
+    fn expression(&mut self, interpreter: &mut LoxInterpreter, expression: &Expression, value: &Literal) {
+        let text = self.formatter.format_expression(expression);
+        let value = shown(interpreter, value);
+        let fields = vec![("expression", Json::from(text.as_str())), ("value", Json::from(value.as_str()))];
+        self.emit("expression", call_depth(interpreter), fields, format!("{} => {}", text, value));
+    }
+
+    fn push_scope(&mut self, interpreter: &mut LoxInterpreter) {
+        self.scope(interpreter, "push");
+    }
+
+    fn pop_scope(&mut self, interpreter: &mut LoxInterpreter) {
+        self.scope(interpreter, "pop");
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lox::parser::LoxParser;
+    use std::cell::RefCell;
+    use std::io;
+    use std::rc::Rc;
+
+    // Output the test can still read after the tracer that owns it is done.
+    #[derive(Clone)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trace_of(source: &str, format: TraceFormat) -> Vec<String> {
+        let mut parser = LoxParser::new();
+        parser.load_string(source).expect("Error while scanning input string");
+        let program = parser.parse().expect("Error while parsing input string");
+
+        let buffer = SharedBuffer(Rc::new(RefCell::new(Vec::new())));
+        let mut interpreter = LoxInterpreter::new();
+        interpreter.set_hook(Box::new(LoxTracer::new(source, buffer.clone(), format)));
+        let _ = interpreter.interpret(program);
+        let trace = String::from_utf8(buffer.0.borrow().clone()).expect("Trace should be UTF-8");
+        trace.lines().map(String::from).collect()
+    }
+
+    const AREA: &str = "\
+fun area(width, height) {
+    return width * height;
+}
+print area(2, 3);";
+
+    mod text {
+        use super::*;
+
+        #[test]
+        fn test_calls_and_scopes() {
+            // Expressions come once they have a value, so a call comes after everything it ran.
+            assert_eq!(vec![
+                "declare fun area",
+                "4: print area(2, 3);",
+                "area => <fn area>",
+                "push scope 2",
+                "call area(width = 2, height = 3)",
+                "  push scope 3",
+                "  2: return width * height;",
+                "  width => 2",
+                "  height => 3",
+                "  width * height => 6",
+                "  pop scope 3",
+                "return area -> 6",
+                "pop scope 2",
+                "area(2, 3) => 6",
+            ], trace_of(AREA, TraceFormat::Text));
+        }
+
+        #[test]
+        fn test_declarations_and_literals() {
+            // Declarations have no line of their own, and literals and parentheses add nothing.
+            let trace = trace_of("class A {}\ntrait T {}\nenum E { X }\nfun* g() {}\nvar x = (1);", TraceFormat::Text);
+            assert_eq!(vec!["declare class A", "declare trait T", "declare enum E", "declare fun* g", "5: var x = (1);"], trace);
+        }
+
+        #[test]
+        fn test_failed_call() {
+            let trace = trace_of("fun f() {\n    return missing;\n}\nf();", TraceFormat::Text);
+            assert!(trace.contains(&String::from("return f with an error")), "{:?}", trace);
+        }
+
+        #[test]
+        fn test_nested_calls_indented() {
+            let trace = trace_of("\
+fun inner() { return 1; }
+fun outer() { return inner(); }
+outer();", TraceFormat::Text);
+            let calls: Vec<&str> = trace.iter().filter(|l| l.trim_start().starts_with("call") || l.trim_start().starts_with("return ")).map(|l| l.as_str()).collect();
+            assert_eq!(vec!["call outer()", "  call inner()", "  return inner -> 1", "return outer -> 1"], calls);
+        }
+
+        #[test]
+        fn test_values_use_str() {
+            // The __str__ calls made to show a value don't show up as calls themselves.
+            let trace = trace_of("\
+class P { __str__() { return \"p\"; } }
+var ps = [P()];", TraceFormat::Text);
+            assert_eq!(vec!["declare class P", "2: var ps = [P()];", "P => <class P>", "P() => p", "[P()] => [p]"], trace);
+        }
+    }
+
+    mod json_lines {
+        use super::*;
+
+        #[test]
+        fn test_events() {
+            let events: Vec<Json> = trace_of(AREA, TraceFormat::JsonLines).iter()
+                .map(|l| Json::parse(l).expect("Each line should be JSON"))
+                .collect();
+            let names: Vec<&str> = events.iter().filter_map(|e| e.get("event").and_then(Json::as_str)).collect();
+            assert_eq!(vec![
+                "declare", "statement", "expression", "scope", "enter", "scope", "statement",
+                "expression", "expression", "expression", "scope", "exit", "scope", "expression",
+            ], names);
+
+            assert_eq!(Some("fun"), events[0].get("keyword").and_then(Json::as_str));
+            assert_eq!(Some("area"), events[0].get("name").and_then(Json::as_str));
+            let enter = &events[4];
+            assert_eq!(Some("area"), enter.get("function").and_then(Json::as_str));
+            assert_eq!(Some("3"), enter.get("args").and_then(|a| a.get("height")).and_then(Json::as_str));
+            assert_eq!(Some(1), events[6].get("depth").and_then(Json::as_usize));
+            assert_eq!(Some("width * height"), events[9].get("expression").and_then(Json::as_str));
+            assert_eq!(Some("6"), events[9].get("value").and_then(Json::as_str));
+            assert_eq!(Some("6"), events[11].get("value").and_then(Json::as_str));
+        }
+    }
+}
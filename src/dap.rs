@@ -0,0 +1,742 @@
+use crate::components as lox;
+use lox::parser::*;
+use lox::checker::*;
+use lox::interpreter::*;
+use lox::debugger::*;
+use lox::json::*;
+use lox::instructions::statement::Statement;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+const THREAD_ID: usize = 1; // Programs only ever run on the one thread
+const LOCALS_REFERENCE: usize = 1;
+const GLOBALS_REFERENCE: usize = 2;
+const RUNTIME_ERROR_EXIT_CODE: usize = 70;
+
+const STOPPED: &str = "Debugging stopped.";
+
+// A debug adapter for editors, speaking the Debug Adapter Protocol with the same framing as the
+// language server. It debugs one program per session. The program starts once it's launched and
+// the client has finished configuring breakpoints. Requests are only read while the program is
+// paused or not running, so a running program can't be interrupted.
+pub struct LoxDebugAdapter<R: BufRead, W: Write> {
+    session: Rc<RefCell<Session<R, W>>>,
+}
+
+impl<R: BufRead + 'static, W: Write + 'static> LoxDebugAdapter<R, W> {
+    pub fn new(input: R, output: W) -> LoxDebugAdapter<R, W> {
+        LoxDebugAdapter{ session: Rc::new(RefCell::new(Session::new(input, output))) }
+    }
+
+    // Handles requests until the client disconnects or the input ends.
+    pub fn serve(&self) -> io::Result<()> {
+        loop {
+            let request = read_message(&mut self.session.borrow_mut().input)?;
+            let Some(request) = request else { return Ok(()); };
+            let flow = self.session.borrow_mut().handle(&request, None)?;
+            if let Flow::Start = flow {
+                self.run()?;
+            }
+            if self.session.borrow().over {
+                return Ok(());
+            }
+        }
+    }
+
+    // The session is shared with the hook, which answers requests while the program is paused.
+    fn run(&self) -> io::Result<()> {
+        let program = self.session.borrow_mut().launch.as_mut().and_then(|l| l.program.take()).unwrap_or_default();
+        let mut interpreter = LoxInterpreter::new();
+        interpreter.set_hook(Box::new(PauseHook(Rc::clone(&self.session))));
+        let result = interpreter.interpret(program);
+        self.session.borrow_mut().finish(&mut interpreter, result)
+    }
+}
+
+// What to do once a request has been answered.
+enum Flow {
+    Wait, // For the next request
+    Start, // The program
+    Resume, // The paused program
+    Stop, // The program, wherever it is
+}
+
+// The program being debugged, checked and ready to run.
+struct Launch {
+    path: String,
+    lines: usize,
+    program: Option<Vec<Statement>>, // Taken when it starts running
+}
+
+struct Session<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    seq: usize, // Of the last message sent
+    line_base: usize, // The number the client gives the first line, 0 or 1
+    column_base: usize,
+    breakpoints: HashMap<String, BTreeSet<usize>>, // Lines, keyed by source path
+    stepper: Stepper,
+    launch: Option<Launch>,
+    configured: bool,
+    stop_on_entry: bool, // Until the first stop
+    over: bool, // Once the client has disconnected
+}
+
+impl<R: BufRead, W: Write> Session<R, W> {
+    fn new(input: R, output: W) -> Session<R, W> {
+        Session {
+            input,
+            output,
+            seq: 0,
+            line_base: 1,
+            column_base: 1,
+            breakpoints: HashMap::new(),
+            stepper: Stepper::new(),
+            launch: None,
+            configured: false,
+            stop_on_entry: false,
+            over: false,
+        }
+    }
+
+    // Answers one request. The interpreter is only there while the program is paused.
+    fn handle(&mut self, request: &Json, interpreter: Option<&mut LoxInterpreter>) -> io::Result<Flow> {
+        let command = request.get("command").and_then(Json::as_str).unwrap_or("");
+        let arguments = request.get("arguments").unwrap_or(&Json::Null);
+        let paused = interpreter.is_some();
+        let (result, flow) = match (command, interpreter) {
+            ("initialize", _) => (Ok(self.initialize(arguments)), Flow::Wait),
+            ("launch", _) => match self.launch(arguments) {
+                Ok(()) => (Ok(Json::Null), self.ready()),
+                Err(e) => (Err(e), Flow::Wait),
+            },
+            ("setBreakpoints", _) => (self.set_breakpoints(arguments), Flow::Wait),
+            ("configurationDone", _) => {
+                self.configured = true;
+                (Ok(Json::Null), self.ready())
+            },
+            ("threads", _) => (Ok(Json::object(vec![
+                ("threads", Json::from(vec![Json::object(vec![("id", Json::from(THREAD_ID)), ("name", Json::from("main"))])])),
+            ])), Flow::Wait),
+            ("disconnect", _) => {
+                self.over = true;
+                (Ok(Json::Null), Flow::Stop)
+            },
+            ("terminate", _) => (Ok(Json::Null), Flow::Stop),
+            (_, Some(interpreter)) => self.inspect(command, arguments, interpreter),
+            ("stackTrace" | "scopes" | "variables" | "evaluate" | "continue" | "next" | "stepIn" | "stepOut" | "pause", None) => {
+                (Err(String::from("The program isn't paused.")), Flow::Wait)
+            },
+            _ => (Err(format!("Unknown command '{}'.", command)), Flow::Wait),
+        };
+        let succeeded = result.is_ok();
+        self.respond(request, result)?;
+
+        if command == "initialize" && succeeded {
+            self.event("initialized", Json::Null)?;
+        }
+        // A paused program sends this once it has been stopped.
+        if command == "terminate" && !paused {
+            self.event("terminated", Json::Null)?;
+        }
+        Ok(flow)
+    }
+
+    // Answers a request that needs the paused program.
+    fn inspect(&mut self, command: &str, arguments: &Json, interpreter: &mut LoxInterpreter) -> (Result<Json, String>, Flow) {
+        let depth = interpreter.frames().len();
+        let mode = match command {
+            "continue" => StepMode::Continue,
+            "next" => StepMode::Over(depth),
+            "stepIn" => StepMode::Into,
+            "stepOut" => StepMode::Out(depth),
+            _ => {
+                let result = match command {
+                    "stackTrace" => Ok(self.stack_trace(interpreter)),
+                    "scopes" => Ok(Session::<R, W>::scopes(arguments)),
+                    "variables" => self.variables(arguments, interpreter),
+                    "evaluate" => Session::<R, W>::evaluate(arguments, interpreter),
+                    "pause" => Ok(Json::Null), // Already there
+                    _ => Err(format!("Unknown command '{}'.", command)),
+                };
+                return (result, Flow::Wait);
+            },
+        };
+        self.stepper.mode = mode;
+        let body = match mode {
+            StepMode::Continue => Json::object(vec![("allThreadsContinued", Json::from(true))]),
+            _ => Json::Null,
+        };
+        (Ok(body), Flow::Resume)
+    }
+
+    fn initialize(&mut self, arguments: &Json) -> Json {
+        let base = |key: &str| if arguments.get(key).and_then(Json::as_bool) == Some(false) { 0 } else { 1 };
+        self.line_base = base("linesStartAt1");
+        self.column_base = base("columnsStartAt1");
+        Json::object(vec![
+            ("supportsConfigurationDoneRequest", Json::from(true)),
+            ("supportsTerminateRequest", Json::from(true)),
+            ("supportsEvaluateForHovers", Json::from(true)),
+        ])
+    }
+
+    // Reads and checks the program, so that it can't fail to start for anything but a runtime error.
+    fn launch(&mut self, arguments: &Json) -> Result<(), String> {
+        if self.launch.is_some() {
+            return Err(String::from("A program has already been launched."));
+        }
+        let path = arguments.get("program").and_then(Json::as_str)
+            .ok_or_else(|| String::from("Expected a program to launch."))?;
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+
+        let mut parser = LoxParser::new();
+        let program = parser.load_string(&source)
+            .and_then(|_| parser.parse())
+            .map_err(|errors| format!("Parsing error(s):\n{}", errors.join("\n")))?;
+        TypeChecker::new().check(&program)
+            .map_err(|errors| format!("Type error(s):\n{}", errors.join("\n")))?;
+
+        self.stop_on_entry = arguments.get("stopOnEntry").and_then(Json::as_bool).unwrap_or(false);
+        self.stepper.mode = if self.stop_on_entry { StepMode::Into } else { StepMode::Continue };
+        self.stepper.breakpoints = self.breakpoints.get(path).cloned().unwrap_or_default();
+        self.launch = Some(Launch{ path: String::from(path), lines: source.lines().count(), program: Some(program) });
+        Ok(())
+    }
+
+    // The program starts once it's launched and configured, in either order.
+    fn ready(&self) -> Flow {
+        match &self.launch {
+            Some(Launch{ program: Some(_), .. }) if self.configured => Flow::Start,
+            _ => Flow::Wait,
+        }
+    }
+
+    // Replaces the breakpoints in one source. Lines past its end can never be hit, so they're
+    // reported as unverified.
+    fn set_breakpoints(&mut self, arguments: &Json) -> Result<Json, String> {
+        let path = arguments.get("source").and_then(|s| s.get("path")).and_then(Json::as_str)
+            .ok_or_else(|| String::from("Expected a source with a path."))?;
+        let lines: Vec<usize> = arguments.get("breakpoints").and_then(Json::as_array).unwrap_or(&[]).iter()
+            .filter_map(|b| b.get("line").and_then(Json::as_usize))
+            .map(|line| line + 1 - self.line_base)
+            .collect();
+        let length = match &self.launch {
+            Some(launch) if launch.path == path => Some(launch.lines),
+            _ => fs::read_to_string(path).ok().map(|source| source.lines().count()),
+        };
+
+        let mut breakpoints = Vec::new();
+        for line in lines.iter() {
+            let mut fields = vec![("line", Json::from(self.client_line(*line)))];
+            if length.is_some_and(|length| *line >= 1 && *line <= length) {
+                fields.push(("verified", Json::from(true)));
+            } else {
+                fields.push(("verified", Json::from(false)));
+                fields.push(("message", Json::from(format!("There is no line {}.", line))));
+            }
+            breakpoints.push(Json::object(fields));
+        }
+
+        let lines: BTreeSet<usize> = lines.into_iter().collect();
+        if self.launch.as_ref().is_some_and(|launch| launch.path == path) {
+            self.stepper.breakpoints = lines.clone();
+        }
+        self.breakpoints.insert(String::from(path), lines);
+        Ok(Json::object(vec![("breakpoints", Json::from(breakpoints))]))
+    }
+
+    // Innermost call first. The frames are numbered the same way.
+    fn stack_trace(&self, interpreter: &LoxInterpreter) -> Json {
+        let path = self.launch.as_ref().map_or("", |l| l.path.as_str());
+        let name = Path::new(path).file_name().map_or(path, |n| n.to_str().unwrap_or(path));
+        let source = Json::object(vec![("name", Json::from(name)), ("path", Json::from(path))]);
+        let frames: Vec<Json> = interpreter.frames().iter()
+            .rev()
+            .enumerate()
+            .map(|(id, frame)| Json::object(vec![
+                ("id", Json::from(id)),
+                ("name", Json::from(frame.function.as_str())),
+                ("source", source.clone()),
+                ("line", Json::from(self.client_line(frame.line))),
+                ("column", Json::from(self.column_base)),
+            ]))
+            .collect();
+        let total = frames.len();
+        Json::object(vec![("stackFrames", Json::from(frames)), ("totalFrames", Json::from(total))])
+    }
+
+    // Only the innermost frame's locals can be seen; the scopes of its callers aren't kept.
+    fn scopes(arguments: &Json) -> Json {
+        let scope = |name: &str, reference: usize| Json::object(vec![
+            ("name", Json::from(name)),
+            ("variablesReference", Json::from(reference)),
+            ("expensive", Json::from(false)),
+        ]);
+        let mut scopes = vec![scope("Globals", GLOBALS_REFERENCE)];
+        if arguments.get("frameId").and_then(Json::as_usize) == Some(0) {
+            scopes.insert(0, scope("Locals", LOCALS_REFERENCE));
+        }
+        Json::object(vec![("scopes", Json::from(scopes))])
+    }
+
+    fn variables(&self, arguments: &Json, interpreter: &mut LoxInterpreter) -> Result<Json, String> {
+        let bindings = match arguments.get("variablesReference").and_then(Json::as_usize) {
+            Some(LOCALS_REFERENCE) => {
+                let mut scopes = interpreter.scopes();
+                scopes.pop();
+                unshadowed(scopes.into_iter().flatten().collect())
+            },
+            Some(GLOBALS_REFERENCE) => user_bindings(interpreter.globals()),
+            _ => return Err(String::from("Unknown variables reference.")),
+        };
+        let mut variables = Vec::new();
+        for (name, value) in bindings {
+            variables.push(Json::object(vec![
+                ("name", Json::from(name)),
+                ("value", Json::from(interpreter.display(value)?)),
+                ("variablesReference", Json::from(0)),
+            ]));
+        }
+        Ok(Json::object(vec![("variables", Json::from(variables))]))
+    }
+
+    // Only variables can be evaluated, which covers hovering over them.
+    fn evaluate(arguments: &Json, interpreter: &mut LoxInterpreter) -> Result<Json, String> {
+        let name = arguments.get("expression").and_then(Json::as_str).unwrap_or("").trim();
+        let value = interpreter.lookup(name)?;
+        Ok(Json::object(vec![
+            ("result", Json::from(interpreter.display(value)?)),
+            ("variablesReference", Json::from(0)),
+        ]))
+    }
+
+    // Called before each statement, to pause there if the stepper says so. Returns false if the
+    // program should be stopped.
+    fn pause(&mut self, interpreter: &mut LoxInterpreter, line: usize) -> io::Result<bool> {
+        if !self.stepper.should_stop(line, interpreter.frames().len()) {
+            return Ok(true);
+        }
+        let reason = if std::mem::take(&mut self.stop_on_entry) {
+            "entry"
+        } else if self.stepper.breakpoints.contains(&line) {
+            "breakpoint"
+        } else {
+            "step"
+        };
+        self.flush(interpreter)?;
+        self.event("stopped", Json::object(vec![
+            ("reason", Json::from(reason)),
+            ("threadId", Json::from(THREAD_ID)),
+            ("allThreadsStopped", Json::from(true)),
+        ]))?;
+
+        while let Some(request) = read_message(&mut self.input)? {
+            match self.handle(&request, Some(interpreter))? {
+                Flow::Resume => return Ok(true),
+                Flow::Stop => return Ok(false),
+                _ => {},
+            }
+        }
+        self.over = true;
+        Ok(false)
+    }
+
+    // Reports how the program ended, unless the client has already gone.
+    fn finish(&mut self, interpreter: &mut LoxInterpreter, result: Result<String, String>) -> io::Result<()> {
+        if self.over {
+            return Ok(());
+        }
+        // The interpreter hands back the last of the output without its newline.
+        if result.is_ok() {
+            let mut printed = interpreter.take_output();
+            if !printed.is_empty() {
+                printed.push('\n');
+                self.output_event("stdout", printed)?;
+            }
+        } else {
+            self.flush(interpreter)?;
+        }
+
+        match result {
+            Ok(_) => self.event("exited", Json::object(vec![("exitCode", Json::from(0))]))?,
+            Err(e) if e == STOPPED => {},
+            Err(e) => {
+                self.output_event("stderr", format!("Runtime error: {}\n", e))?;
+                self.event("exited", Json::object(vec![("exitCode", Json::from(RUNTIME_ERROR_EXIT_CODE))]))?;
+            },
+        }
+        self.event("terminated", Json::Null)
+    }
+
+    // Sends on whatever the program has printed since the last time.
+    fn flush(&mut self, interpreter: &mut LoxInterpreter) -> io::Result<()> {
+        let printed = interpreter.take_output();
+        if printed.is_empty() {
+            return Ok(());
+        }
+        self.output_event("stdout", printed)
+    }
+
+    fn output_event(&mut self, category: &str, text: String) -> io::Result<()> {
+        self.event("output", Json::object(vec![("category", Json::from(category)), ("output", Json::from(text))]))
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        let mut fields = vec![("event", Json::from(event))];
+        if body != Json::Null {
+            fields.push(("body", body));
+        }
+        self.send("event", fields)
+    }
+
+    fn respond(&mut self, request: &Json, result: Result<Json, String>) -> io::Result<()> {
+        let mut fields = vec![
+            ("request_seq", request.get("seq").cloned().unwrap_or(Json::Null)),
+            ("success", Json::from(result.is_ok())),
+            ("command", request.get("command").cloned().unwrap_or(Json::Null)),
+        ];
+        match result {
+            Ok(Json::Null) => {},
+            Ok(body) => fields.push(("body", body)),
+            Err(message) => fields.push(("message", Json::from(message))),
+        }
+        self.send("response", fields)
+    }
+
+    fn send(&mut self, kind: &str, fields: Vec<(&str, Json)>) -> io::Result<()> {
+        self.seq += 1;
+        let mut message = vec![("seq", Json::from(self.seq)), ("type", Json::from(kind))];
+        message.extend(fields);
+        write_message(&mut self.output, &Json::object(message))
+    }
+
+    fn client_line(&self, line: usize) -> usize {
+        (line + self.line_base).saturating_sub(1)
+    }
+}
+
+// Pauses the program on the session's behalf.
+struct PauseHook<R: BufRead, W: Write>(Rc<RefCell<Session<R, W>>>);
+
+impl<R: BufRead, W: Write> ExecutionHook for PauseHook<R, W> {
+    fn before_statement(&mut self, interpreter: &mut LoxInterpreter, line: usize) -> Result<(), String> {
+        match self.0.borrow_mut().pause(interpreter, line) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(String::from(STOPPED)),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn next_iteration(&mut self, _interpreter: &mut LoxInterpreter, _line: usize) {
+        self.0.borrow_mut().stepper.next_iteration();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/debugging.lox");
+
+    // Output the test can still read after the adapter that owns it is done.
+    #[derive(Clone)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Requests are numbered from 1 in the order given.
+    fn session(requests: &[(&str, Json)]) -> Vec<Json> {
+        let mut input = Vec::new();
+        for (seq, (command, arguments)) in requests.iter().enumerate() {
+            let request = Json::object(vec![
+                ("seq", Json::from(seq + 1)),
+                ("type", Json::from("request")),
+                ("command", Json::from(*command)),
+                ("arguments", arguments.clone()),
+            ]);
+            write_message(&mut input, &request).expect("Writing to memory");
+        }
+        let buffer = SharedBuffer(Rc::new(RefCell::new(Vec::new())));
+        LoxDebugAdapter::new(io::Cursor::new(input), buffer.clone()).serve().expect("Serving from memory");
+
+        let mut output = io::Cursor::new(buffer.0.borrow().clone());
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut output).expect("Valid messages") {
+            messages.push(message);
+        }
+        messages
+    }
+
+    // The requests that start the fixture, stopping at the given lines.
+    fn start(stop_on_entry: bool, breakpoints: &[usize]) -> Vec<(&'static str, Json)> {
+        let lines: Vec<Json> = breakpoints.iter().map(|l| Json::object(vec![("line", Json::from(*l))])).collect();
+        vec![
+            ("initialize", Json::object(vec![("linesStartAt1", Json::from(true))])),
+            ("launch", Json::object(vec![("program", Json::from(FIXTURE)), ("stopOnEntry", Json::from(stop_on_entry))])),
+            ("setBreakpoints", Json::object(vec![
+                ("source", Json::object(vec![("path", Json::from(FIXTURE))])),
+                ("breakpoints", Json::from(lines)),
+            ])),
+            ("configurationDone", Json::Null),
+        ]
+    }
+
+    fn response(messages: &[Json], seq: usize) -> &Json {
+        messages.iter()
+            .find(|m| m.get("type").and_then(Json::as_str) == Some("response") && m.get("request_seq").and_then(Json::as_usize) == Some(seq))
+            .unwrap_or_else(|| panic!("No response to request {} in {:?}", seq, messages))
+    }
+
+    fn body(messages: &[Json], seq: usize) -> &Json {
+        let response = response(messages, seq);
+        assert_eq!(Some(true), response.get("success").and_then(Json::as_bool), "{:?}", response);
+        response.get("body").unwrap_or(&Json::Null)
+    }
+
+    fn events<'a>(messages: &'a [Json], event: &str) -> Vec<&'a Json> {
+        messages.iter()
+            .filter(|m| m.get("event").and_then(Json::as_str) == Some(event))
+            .collect()
+    }
+
+    fn output(messages: &[Json], category: &str) -> String {
+        events(messages, "output").iter()
+            .filter_map(|e| e.get("body"))
+            .filter(|b| b.get("category").and_then(Json::as_str) == Some(category))
+            .filter_map(|b| b.get("output").and_then(Json::as_str))
+            .collect()
+    }
+
+    // The innermost frame's line from each stack trace response.
+    fn stopped_lines(messages: &[Json]) -> Vec<usize> {
+        messages.iter()
+            .filter(|m| m.get("command").and_then(Json::as_str) == Some("stackTrace"))
+            .filter_map(|m| m.get("body")?.get("stackFrames")?.as_array()?.first()?.get("line")?.as_usize())
+            .collect()
+    }
+
+    // (name, value) of each variable in a variables response.
+    fn variables(body: &Json) -> Vec<(&str, &str)> {
+        body.get("variables").and_then(Json::as_array).expect("A list of variables").iter()
+            .map(|v| (v.get("name").and_then(Json::as_str).expect("A name"), v.get("value").and_then(Json::as_str).expect("A value")))
+            .collect()
+    }
+
+    mod lifecycle {
+        use super::*;
+
+        #[test]
+        fn test_initialize() {
+            let messages = session(&[("initialize", Json::Null)]);
+            let capabilities = body(&messages, 1);
+            assert_eq!(Some(true), capabilities.get("supportsConfigurationDoneRequest").and_then(Json::as_bool));
+            // The client may only configure once the response has told it what's supported.
+            assert_eq!(Some("initialized"), messages[1].get("event").and_then(Json::as_str));
+        }
+
+        #[test]
+        fn test_run_to_end() {
+            let messages = session(&start(false, &[]));
+            assert!(events(&messages, "stopped").is_empty());
+            assert_eq!("2\n8\n20\ndone\n", output(&messages, "stdout"));
+            assert_eq!(Some(0), events(&messages, "exited")[0].get("body").and_then(|b| b.get("exitCode")).and_then(Json::as_usize));
+            assert_eq!(Some("terminated"), messages.last().and_then(|m| m.get("event")).and_then(Json::as_str));
+        }
+
+        #[test]
+        fn test_launch_after_configuration() {
+            let mut requests = start(false, &[]);
+            requests.swap(1, 3);
+            let messages = session(&requests);
+            assert_eq!("2\n8\n20\ndone\n", output(&messages, "stdout"));
+        }
+
+        #[test]
+        fn test_launch_errors() {
+            let messages = session(&[
+                ("launch", Json::Null),
+                ("launch", Json::object(vec![("program", Json::from("/no/such/file.lox"))])),
+                ("stackTrace", Json::Null),
+                ("attach", Json::Null),
+            ]);
+            let message = |seq| response(&messages, seq).get("message").and_then(Json::as_str).unwrap_or("");
+            assert_eq!("Expected a program to launch.", message(1));
+            assert!(message(2).starts_with("/no/such/file.lox: "), "{}", message(2));
+            assert_eq!("The program isn't paused.", message(3));
+            assert_eq!("Unknown command 'attach'.", message(4));
+        }
+
+        #[test]
+        fn test_runtime_error() {
+            let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/runtime_err.lox");
+            let messages = session(&[
+                ("launch", Json::object(vec![("program", Json::from(path))])),
+                ("configurationDone", Json::Null),
+            ]);
+            assert_eq!("Runtime error: Undefined variable 'i'.\n", output(&messages, "stderr"));
+            assert_eq!(Some(RUNTIME_ERROR_EXIT_CODE), events(&messages, "exited")[0].get("body").and_then(|b| b.get("exitCode")).and_then(Json::as_usize));
+        }
+
+        #[test]
+        fn test_disconnect_while_paused() {
+            let mut requests = start(true, &[]);
+            requests.push(("disconnect", Json::Null));
+            requests.push(("threads", Json::Null));
+            let messages = session(&requests);
+            assert_eq!(1, events(&messages, "stopped").len());
+            assert!(events(&messages, "exited").is_empty());
+            // Nothing is read after the disconnect.
+            assert_eq!(Some("disconnect"), messages.last().and_then(|m| m.get("command")).and_then(Json::as_str));
+        }
+
+        #[test]
+        fn test_terminate_while_paused() {
+            let mut requests = start(true, &[]);
+            requests.push(("terminate", Json::Null));
+            let messages = session(&requests);
+            assert!(events(&messages, "exited").is_empty());
+            assert_eq!(1, events(&messages, "terminated").len());
+        }
+    }
+
+    mod stepping {
+        use super::*;
+
+        #[test]
+        fn test_breakpoints() {
+            let mut requests = start(false, &[3, 99]);
+            requests.extend([
+                ("stackTrace", Json::Null),
+                ("continue", Json::Null),
+                ("stackTrace", Json::Null),
+                ("continue", Json::Null),
+                ("stackTrace", Json::Null),
+                ("continue", Json::Null),
+            ]);
+            let messages = session(&requests);
+
+            let verified: Vec<Option<bool>> = body(&messages, 3).get("breakpoints").and_then(Json::as_array).expect("Breakpoints").iter()
+                .map(|b| b.get("verified").and_then(Json::as_bool))
+                .collect();
+            assert_eq!(vec![Some(true), Some(false)], verified);
+            assert_eq!(vec![3, 3, 3], stopped_lines(&messages));
+            let reasons: Vec<&str> = events(&messages, "stopped").iter()
+                .filter_map(|e| e.get("body")?.get("reason")?.as_str())
+                .collect();
+            assert_eq!(vec!["breakpoint"; 3], reasons);
+            // Output is sent before each stop, so it arrives in order.
+            assert_eq!("2\n8\n20\ndone\n", output(&messages, "stdout"));
+        }
+
+        #[test]
+        fn test_breakpoints_change_while_paused() {
+            let mut requests = start(false, &[3]);
+            requests.extend([
+                ("setBreakpoints", Json::object(vec![
+                    ("source", Json::object(vec![("path", Json::from(FIXTURE))])),
+                    ("breakpoints", Json::from(vec![Json::object(vec![("line", Json::from(11))])])),
+                ])),
+                ("continue", Json::Null),
+                ("stackTrace", Json::Null),
+                ("continue", Json::Null),
+            ]);
+            let messages = session(&requests);
+            assert_eq!(vec![11], stopped_lines(&messages));
+            assert_eq!(2, events(&messages, "stopped").len());
+        }
+
+        #[test]
+        fn test_step_commands() {
+            let mut requests = start(true, &[]);
+            for command in ["next", "next", "stepIn", "stepIn", "stepOut", "next"] {
+                requests.push(("stackTrace", Json::Null));
+                requests.push((command, Json::Null));
+            }
+            requests.push(("stackTrace", Json::Null));
+            requests.push(("disconnect", Json::Null));
+            let messages = session(&requests);
+            // The rest of line 8 runs after stepping out, so the next stop is line 9.
+            assert_eq!(vec![6, 7, 8, 2, 3, 9, 7], stopped_lines(&messages));
+            assert_eq!(Some("entry"), events(&messages, "stopped")[0].get("body").and_then(|b| b.get("reason")).and_then(Json::as_str));
+        }
+
+        #[test]
+        fn test_zero_based_lines() {
+            let mut requests = start(false, &[2]);
+            requests[0] = ("initialize", Json::object(vec![("linesStartAt1", Json::from(false))]));
+            requests.extend([("stackTrace", Json::Null), ("disconnect", Json::Null)]);
+            let messages = session(&requests);
+            assert_eq!(vec![2], stopped_lines(&messages));
+            let frames = body(&messages, 5).get("stackFrames").and_then(Json::as_array).expect("Frames");
+            assert_eq!(Some(7), frames[1].get("line").and_then(Json::as_usize));
+        }
+    }
+
+    mod inspection {
+        use super::*;
+
+        #[test]
+        fn test_stack_trace() {
+            let mut requests = start(false, &[3]);
+            requests.push(("stackTrace", Json::Null));
+            let messages = session(&requests);
+            let frames = body(&messages, 5).get("stackFrames").and_then(Json::as_array).expect("Frames");
+            let names: Vec<&str> = frames.iter().filter_map(|f| f.get("name").and_then(Json::as_str)).collect();
+            assert_eq!(vec!["area", "script"], names);
+            assert_eq!(Some("debugging.lox"), frames[0].get("source").and_then(|s| s.get("name")).and_then(Json::as_str));
+        }
+
+        #[test]
+        fn test_scopes_and_variables() {
+            let mut requests = start(false, &[3]);
+            requests.extend([
+                ("scopes", Json::object(vec![("frameId", Json::from(0))])),
+                ("scopes", Json::object(vec![("frameId", Json::from(1))])),
+                ("variables", Json::object(vec![("variablesReference", Json::from(LOCALS_REFERENCE))])),
+                ("variables", Json::object(vec![("variablesReference", Json::from(GLOBALS_REFERENCE))])),
+                ("variables", Json::object(vec![("variablesReference", Json::from(9))])),
+            ]);
+            let messages = session(&requests);
+            let scope_names = |seq| body(&messages, seq).get("scopes").and_then(Json::as_array).expect("Scopes").iter()
+                .filter_map(|s| s.get("name").and_then(Json::as_str))
+                .collect::<Vec<&str>>();
+            assert_eq!(vec!["Locals", "Globals"], scope_names(5));
+            assert_eq!(vec!["Globals"], scope_names(6));
+            assert_eq!(vec![("result", "2"), ("height", "2"), ("width", "1")], variables(body(&messages, 7)));
+            assert_eq!(vec![("area", "<fn area>"), ("total", "0")], variables(body(&messages, 8)));
+            assert_eq!(Some(false), response(&messages, 9).get("success").and_then(Json::as_bool));
+        }
+
+        #[test]
+        fn test_evaluate() {
+            let mut requests = start(false, &[9]);
+            requests.extend([
+                ("evaluate", Json::object(vec![("expression", Json::from("total"))])),
+                ("evaluate", Json::object(vec![("expression", Json::from("side"))])),
+                ("evaluate", Json::object(vec![("expression", Json::from("missing"))])),
+            ]);
+            let messages = session(&requests);
+            let result = |seq| body(&messages, seq).get("result").and_then(Json::as_str);
+            assert_eq!(Some("2"), result(5));
+            assert_eq!(Some("1"), result(6));
+            assert_eq!(Some("Undefined variable 'missing'."), response(&messages, 7).get("message").and_then(Json::as_str));
+        }
+    }
+}
@@ -1,8 +1,20 @@
 pub mod program;
 pub mod prompt;
+pub mod lsp;
+pub mod dap;
 mod components{
     pub mod parser;
     pub mod interpreter;
+    pub mod checker;
+    pub mod formatter;
+    pub mod linter;
+    pub mod debugger;
+    pub mod profiler;
+    pub mod coverage;
+    pub mod tester;
+    pub mod tracer;
+    pub mod json;
+    pub mod symbols;
     pub mod instructions;
 
     mod error;
@@ -11,6 +23,15 @@ mod components{
 
 use crate::program::*;
 use crate::prompt::*;
+use crate::lsp::*;
+use crate::dap::*;
+use crate::components::coverage::Coverage;
+use crate::components::tracer::TraceFormat;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
 use std::io;
 use std::io::Write;
 
@@ -22,6 +43,212 @@ pub fn run_file(file_name: &str) {
     println!("{}", file_runner.run());
 }
 
+// Runs the file under the step debugger, taking commands from stdin.
+pub fn debug_file(file_name: &str) {
+    let file_runner = LoxProgram::build(file_name).unwrap_or_else(|_err| {
+        panic!("Unhandled error opening file.")
+    });
+    println!("{}", file_runner.debug(io::stdin().lock(), io::stdout()));
+}
+
+// Runs the file, tracing what it does to stderr while it runs, then prints its output. With json
+// set, each event is written as a line of JSON instead.
+pub fn trace_file(file_name: &str, json: bool) {
+    let file_runner = LoxProgram::build(file_name).unwrap_or_else(|_err| {
+        panic!("Unhandled error opening file.")
+    });
+    let format = if json { TraceFormat::JsonLines } else { TraceFormat::Text };
+    println!("{}", file_runner.trace(io::stderr(), format));
+}
+
+// Runs the file and prints a report of where it spent its time after its output. With a folded
+// path, the call stacks are also written there in folded form, for flamegraph tools.
+pub fn profile_file(file_name: &str, folded_path: Option<&str>) {
+    let file_runner = LoxProgram::build(file_name).unwrap_or_else(|_err| {
+        panic!("Unhandled error opening file.")
+    });
+    let (output, profile) = match file_runner.profile() {
+        Ok(result) => result,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        },
+    };
+    println!("{}", output);
+    println!("\n{}", profile.report());
+
+    if let Some(path) = folded_path {
+        match fs::write(path, profile.folded()) {
+            Ok(()) => println!("Folded stacks written to {}.", path),
+            Err(e) => eprintln!("{}: {}", path, e),
+        }
+    }
+}
+
+// Runs each file in turn, then prints the source of every file with how often each line and
+// branch ran. With an lcov path, the counts are also written there in lcov format, added to the
+// counts already in it if merge is set. Returns false if a file couldn't be loaded or the
+// coverage couldn't be written.
+pub fn cover_files(file_names: &[String], lcov_path: Option<&str>, merge: bool) -> bool {
+    let mut coverage = Coverage::new();
+    if let (Some(lcov_path), true) = (lcov_path, merge) {
+        let merged = fs::read_to_string(lcov_path)
+            .map_err(|e| e.to_string())
+            .and_then(|report| coverage.merge_lcov(&report));
+        if let Err(e) = merged {
+            eprintln!("{}: {}", lcov_path, e);
+            return false;
+        }
+    }
+
+    let mut loaded = true;
+    for file_name in file_names {
+        match LoxProgram::build(file_name) {
+            Ok(program) => println!("{}", program.cover(file_name, &mut coverage)),
+            Err(e) => {
+                eprintln!("{}: {}", file_name, e);
+                loaded = false;
+            },
+        }
+    }
+    for path in coverage.paths() {
+        match fs::read_to_string(path) {
+            Ok(source) => print!("\n{}", coverage.annotate(path, &source)),
+            Err(e) => eprintln!("{}: {}", path, e),
+        }
+    }
+
+    if let Some(lcov_path) = lcov_path {
+        if let Err(e) = fs::write(lcov_path, coverage.lcov()) {
+            eprintln!("{}: {}", lcov_path, e);
+            return false;
+        }
+        println!("\nCoverage written to {}.", lcov_path);
+    }
+    loaded
+}
+
+// Runs the tests in the given files, and in the .lox files found anywhere under the given
+// directories, printing each result and then a summary. A file that can't be loaded counts as one
+// failed test. Returns false if anything failed or no tests were found.
+pub fn test_paths(paths: &[String]) -> bool {
+    let started = Instant::now();
+    let mut files = Vec::new();
+    for path in paths {
+        if let Err(e) = find_lox_files(Path::new(path), &mut files) {
+            eprintln!("{}: {}", path, e);
+            return false;
+        }
+    }
+
+    let (mut passed, mut failed) = (0, 0);
+    for file in files.iter() {
+        let file_name = file.display().to_string();
+        let results = LoxProgram::build(&file_name)
+            .map_err(|e| e.to_string())
+            .and_then(|program| program.test());
+        match results {
+            Ok(results) => {
+                for result in results {
+                    let status = if result.passed() { "ok  " } else { "FAIL" };
+                    println!("{} {}: {} ({})", status, file_name, result.name, millis(result.duration));
+                    if let Some(failure) = result.failure {
+                        println!("     {}", failure);
+                        failed += 1;
+                    } else {
+                        passed += 1;
+                    }
+                }
+            },
+            Err(e) => {
+                println!("FAIL {}\n     {}", file_name, e.replace('\n', "\n     "));
+                failed += 1;
+            },
+        }
+    }
+
+    let noun = if files.len() == 1 { "file" } else { "files" };
+    println!("\n{} passed, {} failed in {} {} ({})", passed, failed, files.len(), noun, millis(started.elapsed()));
+    failed == 0 && passed > 0
+}
+
+fn find_lox_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries = fs::read_dir(path)?.map(|e| e.map(|e| e.path())).collect::<io::Result<Vec<PathBuf>>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            find_lox_files(&entry, files)?;
+        } else if entry.extension().is_some_and(|e| e == "lox") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2} ms", duration.as_secs_f64() * 1000.0)
+}
+
+// Rewrites the file in canonical form, or with check set, only reports whether it already is.
+// Returns false if the file needed formatting in check mode or couldn't be formatted at all.
+pub fn format_file(file_name: &str, check: bool, indent_width: usize, max_width: usize) -> bool {
+    let program = match LoxProgram::build(file_name) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}: {}", file_name, e);
+            return false;
+        },
+    };
+    let formatted = match program.format(indent_width, max_width) {
+        Ok(formatted) => formatted,
+        Err(e) => {
+            eprintln!("{}: {}", file_name, e);
+            return false;
+        },
+    };
+
+    if program.is_formatted(&formatted) {
+        return true;
+    }
+    if check {
+        println!("{} is not formatted.", file_name);
+        return false;
+    }
+    if let Err(e) = fs::write(file_name, formatted) {
+        eprintln!("{}: {}", file_name, e);
+        return false;
+    }
+    println!("Formatted {}.", file_name);
+    true
+}
+
+// Prints the file's lint warnings. Returns false if there were any, or if it couldn't be linted.
+pub fn lint_file(file_name: &str) -> bool {
+    let program = match LoxProgram::build(file_name) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}: {}", file_name, e);
+            return false;
+        },
+    };
+    match program.lint() {
+        Ok(warnings) => {
+            for w in warnings.iter() {
+                println!("{}: {}", file_name, w);
+            }
+            warnings.is_empty()
+        },
+        Err(e) => {
+            eprintln!("{}: {}", file_name, e);
+            false
+        },
+    }
+}
+
 pub fn run_prompt() {
     println!("Planning to initialize command prompt.");
     let mut prompt = LoxPrompt::new();
@@ -33,4 +260,20 @@ pub fn run_prompt() {
         io::stdin().read_line(&mut input).expect("Failed to read line");
         println!("{}", prompt.command(&input));
     }
-}
\ No newline at end of file
+}
+// Serves the language server protocol over stdin and stdout until the editor says to exit.
+pub fn run_language_server() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    if let Err(e) = LoxLanguageServer::new().serve(stdin.lock(), stdout.lock()) {
+        eprintln!("Language server stopped: {}", e);
+    }
+}
+
+// Serves the debug adapter protocol over stdin and stdout until the editor disconnects.
+pub fn run_debug_adapter() {
+    let adapter = LoxDebugAdapter::new(io::stdin().lock(), io::stdout());
+    if let Err(e) = adapter.serve() {
+        eprintln!("Debug adapter stopped: {}", e);
+    }
+}
@@ -0,0 +1,650 @@
+use crate::components as lox;
+use lox::parser::*;
+use lox::checker::*;
+use lox::linter::*;
+use lox::json::*;
+use lox::symbols::*;
+use std::collections::HashMap;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+const ERROR_SEVERITY: usize = 1;
+const WARNING_SEVERITY: usize = 2;
+
+// A language server for editors, speaking JSON-RPC with Content-Length framing. Documents are
+// synced whole on every change, and each change is answered with fresh diagnostics from the
+// parser, the type checker and the linter. Positions are zero-based lines and UTF-16 columns,
+// as in the protocol, and are converted to and from the character columns the index uses.
+#[derive(Default)]
+pub struct LoxLanguageServer {
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+}
+
+struct Document {
+    index: SymbolIndex,
+    lines: Vec<String>,
+}
+
+impl Document {
+    fn new(text: &str) -> Document {
+        Document{ index: SymbolIndex::build(text), lines: text.lines().map(String::from).collect() }
+    }
+
+    // A protocol position, counting UTF-16 code units, as a line from 1 and a character column.
+    // Columns past the end of the line are kept past it by the same amount.
+    fn position_at(&self, line: usize, units: usize) -> Position {
+        let Some(text) = self.lines.get(line) else { return Position{ line: line + 1, column: units }; };
+        let mut column = 0;
+        let mut counted = 0;
+        for c in text.chars() {
+            if counted >= units { break; }
+            counted += c.len_utf16();
+            column += 1;
+        }
+        Position{ line: line + 1, column: column + units.saturating_sub(counted) }
+    }
+
+    fn to_protocol(&self, p: Position) -> Json {
+        let Some(text) = self.lines.get(p.line - 1) else { return position(p.line, p.column); };
+        let units: usize = text.chars().take(p.column).map(char::len_utf16).sum();
+        position(p.line, units + p.column.saturating_sub(text.chars().count()))
+    }
+
+    fn range(&self, start: Position, end: Position) -> Json {
+        Json::object(vec![("start", self.to_protocol(start)), ("end", self.to_protocol(end))])
+    }
+
+    // Where a name starting at 'start' is, as a range.
+    fn name_range(&self, start: Position, length: usize) -> Json {
+        self.range(start, Position{ line: start.line, column: start.column + length })
+    }
+}
+
+impl LoxLanguageServer {
+    pub fn new() -> LoxLanguageServer {
+        LoxLanguageServer::default()
+    }
+
+    // Handles messages until an exit notification or the end of the input.
+    pub fn serve<R: BufRead, W: Write>(&mut self, mut input: R, mut output: W) -> io::Result<()> {
+        while let Some(message) = read_message(&mut input)? {
+            if message.get("method").and_then(Json::as_str) == Some("exit") {
+                break;
+            }
+            for reply in self.handle(&message) {
+                write_message(&mut output, &reply)?;
+            }
+        }
+        Ok(())
+    }
+
+    // The responses and notifications to send back for one message.
+    fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let Some(id) = message.get("id").cloned() else {
+            return self.notification(method, params);
+        };
+
+        let result = if self.shut_down && method != "shutdown" {
+            Err((INVALID_REQUEST, String::from("The server has shut down.")))
+        } else {
+            match method {
+                "initialize" => Ok(LoxLanguageServer::capabilities()),
+                "shutdown" => {
+                    self.shut_down = true;
+                    Ok(Json::Null)
+                },
+                "textDocument/definition" => self.definition(params),
+                "textDocument/references" => self.references(params),
+                "textDocument/hover" => self.hover(params),
+                "textDocument/documentSymbol" => self.document_symbols(params),
+                "textDocument/completion" => self.completion(params),
+                _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'.", method))),
+            }
+        };
+        let reply = match result {
+            Ok(result) => ("result", result),
+            Err((code, message)) => ("error", Json::object(vec![("code", Json::Number(code as f64)), ("message", Json::from(message))])),
+        };
+        vec![Json::object(vec![("jsonrpc", Json::from("2.0")), ("id", id), reply])]
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let document = params.get("textDocument");
+        let Some(uri) = document.and_then(|d| d.get("uri")).and_then(Json::as_str) else {
+            return Vec::new(); // 'initialized', and anything not about a document, needs no reply
+        };
+        let text = match method {
+            "textDocument/didOpen" => document.and_then(|d| d.get("text")).and_then(Json::as_str),
+            // With full sync, the last change holds the whole document.
+            "textDocument/didChange" => params.get("contentChanges")
+                .and_then(Json::as_array)
+                .and_then(|changes| changes.last())
+                .and_then(|change| change.get("text"))
+                .and_then(Json::as_str),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return vec![LoxLanguageServer::publish(uri, Vec::new())];
+            },
+            _ => None,
+        };
+        let Some(text) = text else { return Vec::new(); };
+
+        self.documents.insert(String::from(uri), Document::new(text));
+        vec![LoxLanguageServer::publish(uri, diagnostics(text))]
+    }
+
+    fn capabilities() -> Json {
+        Json::object(vec![
+            ("capabilities", Json::object(vec![
+                ("textDocumentSync", Json::from(1)), // Full
+                ("definitionProvider", Json::from(true)),
+                ("referencesProvider", Json::from(true)),
+                ("hoverProvider", Json::from(true)),
+                ("documentSymbolProvider", Json::from(true)),
+                ("completionProvider", Json::object(vec![("triggerCharacters", Json::from(vec![Json::from(".")]))])),
+            ])),
+            ("serverInfo", Json::object(vec![
+                ("name", Json::from("crablox-lsp")),
+                ("version", Json::from(env!("CARGO_PKG_VERSION"))),
+            ])),
+        ])
+    }
+
+    fn publish(uri: &str, diagnostics: Vec<Json>) -> Json {
+        Json::object(vec![
+            ("jsonrpc", Json::from("2.0")),
+            ("method", Json::from("textDocument/publishDiagnostics")),
+            ("params", Json::object(vec![("uri", Json::from(uri)), ("diagnostics", Json::from(diagnostics))])),
+        ])
+    }
+
+    // The document named in the request, and the position asked about.
+    fn document_position(&self, params: &Json) -> Result<(&str, &Document, Position), (i32, String)> {
+        let (uri, document) = self.document(params)?;
+        let position = params.get("position");
+        let line = position.and_then(|p| p.get("line")).and_then(Json::as_usize);
+        let units = position.and_then(|p| p.get("character")).and_then(Json::as_usize);
+        match (line, units) {
+            (Some(line), Some(units)) => Ok((uri, document, document.position_at(line, units))),
+            _ => Err((INVALID_PARAMS, String::from("Expected a position."))),
+        }
+    }
+
+    fn document(&self, params: &Json) -> Result<(&str, &Document), (i32, String)> {
+        let uri = params.get("textDocument").and_then(|d| d.get("uri")).and_then(Json::as_str)
+            .ok_or((INVALID_PARAMS, String::from("Expected a text document.")))?;
+        let (uri, document) = self.documents.get_key_value(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Document '{}' isn't open.", uri)))?;
+        Ok((uri, document))
+    }
+
+    fn definition(&self, params: &Json) -> Result<Json, (i32, String)> {
+        let (uri, document, position) = self.document_position(params)?;
+        let locations = document.index.definitions(position).iter()
+            .map(|s| location(uri, document.name_range(s.position, s.name.chars().count())))
+            .collect::<Vec<Json>>();
+        Ok(Json::from(locations))
+    }
+
+    fn references(&self, params: &Json) -> Result<Json, (i32, String)> {
+        let (uri, document, position) = self.document_position(params)?;
+        let include_declaration = params.get("context")
+            .and_then(|c| c.get("includeDeclaration"))
+            .and_then(Json::as_bool)
+            .unwrap_or(true);
+        let locations = document.index.references(position, include_declaration).into_iter()
+            .map(|(start, length)| location(uri, document.name_range(start, length)))
+            .collect::<Vec<Json>>();
+        Ok(Json::from(locations))
+    }
+
+    fn hover(&self, params: &Json) -> Result<Json, (i32, String)> {
+        let (_, document, position) = self.document_position(params)?;
+        Ok(match document.index.hover(position) {
+            Some(text) => Json::object(vec![
+                ("contents", Json::object(vec![("kind", Json::from("plaintext")), ("value", Json::from(text))])),
+            ]),
+            None => Json::Null,
+        })
+    }
+
+    // Top-level declarations, with the members of classes, traits and enums as their children.
+    fn document_symbols(&self, params: &Json) -> Result<Json, (i32, String)> {
+        let (_, document) = self.document(params)?;
+        let index = &document.index;
+        let symbols = index.symbols();
+        let to_json = |i: usize, children: Vec<Json>| {
+            let symbol = &symbols[i];
+            let mut fields = vec![
+                ("name", Json::from(symbol.name.as_str())),
+                ("detail", Json::from(symbol.detail.as_str())),
+                ("kind", Json::from(symbol_kind(symbol.kind))),
+                ("range", document.range(symbol.position, symbol.end)),
+                ("selectionRange", document.name_range(symbol.position, symbol.name.chars().count())),
+            ];
+            if symbol.kind.is_member() || !children.is_empty() || symbol.container.is_some() {
+                fields.push(("children", Json::from(children)));
+            }
+            Json::object(fields)
+        };
+        let tree = index.globals().into_iter()
+            .map(|i| to_json(i, index.members(i).into_iter().map(|m| to_json(m, Vec::new())).collect()))
+            .collect::<Vec<Json>>();
+        Ok(Json::from(tree))
+    }
+
+    fn completion(&self, params: &Json) -> Result<Json, (i32, String)> {
+        let (_, document, position) = self.document_position(params)?;
+        let items = document.index.completions(position).into_iter()
+            .map(|c| Json::object(vec![
+                ("label", Json::from(c.label)),
+                ("kind", Json::from(completion_kind(c.kind))),
+                ("detail", Json::from(c.detail)),
+            ]))
+            .collect::<Vec<Json>>();
+        Ok(Json::from(items))
+    }
+}
+
+// Errors stop at the first stage that fails, like running the program would; lint warnings
+// are only looked for in programs that parse.
+fn diagnostics(source: &str) -> Vec<Json> {
+    let lines: Vec<&str> = source.lines().collect();
+    let diagnostic = |text: &str, severity: usize, code: Option<&str>| {
+        let (line, message) = split_line(text);
+        let length = lines.get(line - 1).map_or(0, |l| l.encode_utf16().count());
+        let mut fields = vec![
+            ("range", Json::object(vec![("start", position(line, 0)), ("end", position(line, length))])),
+            ("severity", Json::from(severity)),
+            ("source", Json::from("crablox")),
+            ("message", Json::from(message)),
+        ];
+        if let Some(code) = code {
+            fields.push(("code", Json::from(code)));
+        }
+        Json::object(fields)
+    };
+
+    let mut parser = LoxParser::new();
+    let program = parser.load_string(source).and_then(|_| parser.parse());
+    let program = match program {
+        Ok(program) => program,
+        Err(errors) => return errors.iter().map(|e| diagnostic(e, ERROR_SEVERITY, None)).collect(),
+    };
+    if let Err(errors) = TypeChecker::new().check(&program) {
+        return errors.iter().map(|e| diagnostic(e, ERROR_SEVERITY, None)).collect();
+    }
+    match LoxLinter::new().lint_source(source) {
+        Ok(warnings) => warnings.iter()
+            .map(|w| diagnostic(&w.to_string(), WARNING_SEVERITY, Some(w.rule.id())))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+// Takes the line out of a message starting "[Line N] Error: " or "[Line N] Warning (rule): ".
+// Messages without one are put on the first line.
+fn split_line(text: &str) -> (usize, &str) {
+    let Some(rest) = text.strip_prefix("[Line ") else { return (1, text); };
+    let Some((number, rest)) = rest.split_once("] ") else { return (1, text); };
+    let message = rest.split_once(": ").map_or(rest, |(_, message)| message);
+    (number.parse().unwrap_or(1).max(1), message)
+}
+
+// A line counting from 1 and a column already in UTF-16 code units.
+fn position(line: usize, units: usize) -> Json {
+    Json::object(vec![("line", Json::from(line - 1)), ("character", Json::from(units))])
+}
+
+fn location(uri: &str, range: Json) -> Json {
+    Json::object(vec![("uri", Json::from(uri)), ("range", range)])
+}
+
+fn symbol_kind(kind: SymbolKind) -> usize {
+    match kind {
+        SymbolKind::Class => 5,
+        SymbolKind::Method => 6,
+        SymbolKind::Field => 8,
+        SymbolKind::Enum => 10,
+        SymbolKind::Trait => 11, // Interface
+        SymbolKind::Function => 12,
+        SymbolKind::Variable | SymbolKind::Parameter => 13,
+        SymbolKind::Constant => 14,
+        SymbolKind::Variant => 22,
+    }
+}
+
+fn completion_kind(kind: SymbolKind) -> usize {
+    match kind {
+        SymbolKind::Method => 2,
+        SymbolKind::Function => 3,
+        SymbolKind::Field => 5,
+        SymbolKind::Variable | SymbolKind::Parameter => 6,
+        SymbolKind::Class => 7,
+        SymbolKind::Trait => 8, // Interface
+        SymbolKind::Enum => 13,
+        SymbolKind::Variant => 20,
+        SymbolKind::Constant => 21,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "file:///test.lox";
+
+    fn request(id: usize, method: &str, params: Json) -> Json {
+        Json::object(vec![("jsonrpc", Json::from("2.0")), ("id", Json::from(id)), ("method", Json::from(method)), ("params", params)])
+    }
+
+    fn notification(method: &str, params: Json) -> Json {
+        Json::object(vec![("jsonrpc", Json::from("2.0")), ("method", Json::from(method)), ("params", params)])
+    }
+
+    fn open(text: &str) -> Json {
+        notification("textDocument/didOpen", Json::object(vec![
+            ("textDocument", Json::object(vec![("uri", Json::from(URI)), ("languageId", Json::from("lox")), ("version", Json::from(1)), ("text", Json::from(text))])),
+        ]))
+    }
+
+    fn at(id: usize, method: &str, line: usize, character: usize) -> Json {
+        request(id, method, Json::object(vec![
+            ("textDocument", Json::object(vec![("uri", Json::from(URI))])),
+            ("position", Json::object(vec![("line", Json::from(line)), ("character", Json::from(character))])),
+        ]))
+    }
+
+    // Sends the messages through in-memory pipes and returns everything the server wrote.
+    fn session(messages: &[Json]) -> Vec<Json> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, message).expect("Writing to memory");
+        }
+        let mut output = Vec::new();
+        LoxLanguageServer::new().serve(io::Cursor::new(input), &mut output).expect("Serving from memory");
+
+        let mut output = io::Cursor::new(output);
+        let mut replies = Vec::new();
+        while let Some(reply) = read_message(&mut output).expect("Valid messages") {
+            replies.push(reply);
+        }
+        replies
+    }
+
+    fn result_for(replies: &[Json], id: usize) -> &Json {
+        replies.iter()
+            .find(|r| r.get("id").and_then(Json::as_usize) == Some(id))
+            .and_then(|r| r.get("result"))
+            .unwrap_or_else(|| panic!("No result for request {} in {:?}", id, replies))
+    }
+
+    // (line, start character, end character) of each location, or of each range in a list.
+    fn spans(locations: &Json) -> Vec<(usize, usize, usize)> {
+        locations.as_array().expect("A list of locations").iter()
+            .map(|l| l.get("range").unwrap_or(l))
+            .map(|r| (
+                r.get("start").and_then(|p| p.get("line")).and_then(Json::as_usize).expect("A line"),
+                r.get("start").and_then(|p| p.get("character")).and_then(Json::as_usize).expect("A character"),
+                r.get("end").and_then(|p| p.get("character")).and_then(Json::as_usize).expect("A character"),
+            ))
+            .collect()
+    }
+
+    fn labels(items: &Json) -> Vec<&str> {
+        items.as_array().expect("A list of items").iter()
+            .map(|i| i.get("label").or(i.get("name")).and_then(Json::as_str).expect("A label"))
+            .collect()
+    }
+
+    fn diagnostics_in(replies: &[Json]) -> Vec<&[Json]> {
+        replies.iter()
+            .filter(|r| r.get("method").and_then(Json::as_str) == Some("textDocument/publishDiagnostics"))
+            .map(|r| r.get("params").and_then(|p| p.get("diagnostics")).and_then(Json::as_array).expect("A list of diagnostics"))
+            .collect()
+    }
+
+    const SOURCE: &str = "\
+class Counter {
+    init(start) {
+        this.count = start;
+    }
+    increment(by) {
+        this.count = this.count + by;
+        return this;
+    }
+}
+fun make(start) {
+    var counter = Counter(start);
+    return counter.increment(1);
+}
+var made = make(2);
+print made.count;
+";
+
+    mod lifecycle {
+        use super::*;
+
+        #[test]
+        fn test_initialize() {
+            let replies = session(&[
+                request(1, "initialize", Json::object(vec![("capabilities", Json::object(vec![]))])),
+                notification("initialized", Json::object(vec![])),
+                request(2, "shutdown", Json::Null),
+                notification("exit", Json::Null),
+                request(3, "shutdown", Json::Null), // Never read
+            ]);
+            assert_eq!(2, replies.len());
+            let capabilities = result_for(&replies, 1).get("capabilities").expect("Capabilities");
+            assert_eq!(Some(1), capabilities.get("textDocumentSync").and_then(Json::as_usize));
+            for provider in ["definitionProvider", "referencesProvider", "hoverProvider", "documentSymbolProvider"] {
+                assert_eq!(Some(true), capabilities.get(provider).and_then(Json::as_bool), "{}", provider);
+            }
+            assert!(capabilities.get("completionProvider").is_some());
+            assert_eq!(&Json::Null, result_for(&replies, 2));
+        }
+
+        #[test]
+        fn test_request_after_shutdown() {
+            let replies = session(&[
+                request(1, "shutdown", Json::Null),
+                at(2, "textDocument/hover", 0, 0),
+            ]);
+            let code = replies[1].get("error").and_then(|e| e.get("code")).expect("An error");
+            assert_eq!(&Json::Number(-32600.0), code);
+        }
+
+        #[test]
+        fn test_unknown_method() {
+            let replies = session(&[request(1, "workspace/symbol", Json::object(vec![]))]);
+            let code = replies[0].get("error").and_then(|e| e.get("code")).expect("An error");
+            assert_eq!(&Json::Number(-32601.0), code);
+        }
+
+        #[test]
+        fn test_unopened_document() {
+            let replies = session(&[at(1, "textDocument/hover", 0, 0)]);
+            let code = replies[0].get("error").and_then(|e| e.get("code")).expect("An error");
+            assert_eq!(&Json::Number(-32602.0), code);
+        }
+    }
+
+    mod diagnostics {
+        use super::*;
+
+        #[test]
+        fn test_diagnostics_on_change() {
+            let change = |text: &str| notification("textDocument/didChange", Json::object(vec![
+                ("textDocument", Json::object(vec![("uri", Json::from(URI)), ("version", Json::from(2))])),
+                ("contentChanges", Json::from(vec![Json::object(vec![("text", Json::from(text))])])),
+            ]));
+            let replies = session(&[
+                open("var a = 1;\nprint a +;\n"),
+                change("var a = 1;\nvar b = 2;\nprint a;\n"),
+                change("print 1;\n"),
+                notification("textDocument/didClose", Json::object(vec![("textDocument", Json::object(vec![("uri", Json::from(URI))]))])),
+            ]);
+            let published = diagnostics_in(&replies);
+            assert_eq!(4, published.len());
+
+            assert_eq!(1, published[0].len());
+            assert_eq!(vec![(1, 0, 10)], spans(&Json::from(published[0].to_vec())));
+            assert_eq!(Some(1), published[0][0].get("severity").and_then(Json::as_usize));
+
+            assert_eq!(1, published[1].len());
+            assert_eq!(vec![(1, 0, 10)], spans(&Json::from(published[1].to_vec())));
+            assert_eq!(Some(2), published[1][0].get("severity").and_then(Json::as_usize));
+            assert_eq!(Some("unused-variable"), published[1][0].get("code").and_then(Json::as_str));
+
+            assert!(published[2].is_empty());
+            assert!(published[3].is_empty());
+        }
+
+        #[test]
+        fn test_type_error_diagnostic() {
+            let replies = session(&[open("fun f(a: Number) {\n    return a;\n}\nf(\"one\");\n")]);
+            let published = diagnostics_in(&replies);
+            assert_eq!(1, published[0].len());
+            assert_eq!(vec![(3, 0, 9)], spans(&Json::from(published[0].to_vec())));
+            let message = published[0][0].get("message").and_then(Json::as_str).expect("A message");
+            assert!(message.starts_with("Type error"), "{}", message);
+        }
+    }
+
+    mod navigation {
+        use super::*;
+
+        #[test]
+        fn test_definition() {
+            let replies = session(&[
+                open(SOURCE),
+                at(1, "textDocument/definition", 10, 22), // Counter(start)
+                at(2, "textDocument/definition", 10, 30), // The parameter 'start'
+                at(3, "textDocument/definition", 13, 12), // make(2)
+                at(4, "textDocument/definition", 11, 21), // counter.increment
+                at(5, "textDocument/definition", 14, 6), // made
+            ]);
+            assert_eq!(vec![(0, 6, 13)], spans(result_for(&replies, 1)));
+            assert_eq!(vec![(9, 9, 14)], spans(result_for(&replies, 2)));
+            assert_eq!(vec![(9, 4, 8)], spans(result_for(&replies, 3)));
+            assert_eq!(vec![(4, 4, 13)], spans(result_for(&replies, 4)));
+            assert_eq!(vec![(13, 4, 8)], spans(result_for(&replies, 5)));
+        }
+
+        #[test]
+        fn test_references() {
+            let without_declaration = request(2, "textDocument/references", Json::object(vec![
+                ("textDocument", Json::object(vec![("uri", Json::from(URI))])),
+                ("position", Json::object(vec![("line", Json::from(9)), ("character", Json::from(10))])),
+                ("context", Json::object(vec![("includeDeclaration", Json::from(false))])),
+            ]));
+            let replies = session(&[
+                open(SOURCE),
+                at(1, "textDocument/references", 0, 8),
+                without_declaration,
+                at(3, "textDocument/references", 2, 14), // this.count
+                at(4, "textDocument/references", 1, 10), // The other 'start' isn't the same variable
+            ]);
+            assert_eq!(vec![(0, 6, 13), (10, 18, 25)], spans(result_for(&replies, 1)));
+            assert_eq!(vec![(10, 26, 31)], spans(result_for(&replies, 2)));
+            assert_eq!(vec![(2, 13, 18), (5, 13, 18), (5, 26, 31), (14, 11, 16)], spans(result_for(&replies, 3)));
+            assert_eq!(vec![(1, 9, 14), (2, 21, 26)], spans(result_for(&replies, 4)));
+        }
+
+        #[test]
+        fn test_hover() {
+            let replies = session(&[
+                open(SOURCE),
+                at(1, "textDocument/hover", 11, 24),
+                at(2, "textDocument/hover", 13, 12),
+                at(3, "textDocument/hover", 10, 9),
+                at(4, "textDocument/hover", 8, 0),
+            ]);
+            let text = |id| result_for(&replies, id).get("contents").and_then(|c| c.get("value")).and_then(Json::as_str);
+            assert_eq!(Some("increment(by)"), text(1));
+            assert_eq!(Some("fun make(start)"), text(2));
+            assert_eq!(Some("var counter"), text(3));
+            assert_eq!(&Json::Null, result_for(&replies, 4));
+        }
+
+        #[test]
+        fn test_utf16_positions() {
+            // The emoji takes two UTF-16 code units but is one character.
+            let replies = session(&[
+                open("var s = \"é😀\"; var t = s;\nprint \"😀\" +;\n"),
+                at(1, "textDocument/definition", 0, 23),
+                at(2, "textDocument/references", 0, 4),
+            ]);
+            assert_eq!(vec![(0, 4, 5)], spans(result_for(&replies, 1)));
+            assert_eq!(vec![(0, 4, 5), (0, 23, 24)], spans(result_for(&replies, 2)));
+            assert_eq!(vec![(1, 0, 13)], spans(&Json::from(diagnostics_in(&replies)[0].to_vec())));
+        }
+
+        #[test]
+        fn test_document_symbols() {
+            let replies = session(&[
+                open(SOURCE),
+                request(1, "textDocument/documentSymbol", Json::object(vec![("textDocument", Json::object(vec![("uri", Json::from(URI))]))])),
+            ]);
+            let symbols = result_for(&replies, 1);
+            assert_eq!(vec!["Counter", "make", "made"], labels(symbols));
+            // Bodies run to their closing brace, on a later line.
+            assert_eq!(vec![(0, 6, 1), (9, 4, 1), (13, 4, 8)], spans(symbols));
+            let counter = &symbols.as_array().expect("Symbols")[0];
+            assert_eq!(Some(8), counter.get("range").and_then(|r| r.get("end")).and_then(|p| p.get("line")).and_then(Json::as_usize));
+            assert_eq!(vec!["init", "increment"], labels(counter.get("children").expect("Members")));
+        }
+    }
+
+    mod completion {
+        use super::*;
+
+        #[test]
+        fn test_identifier_completion() {
+            let replies = session(&[
+                open("var outer = 1;\nfun f(param) {\n    var inner = 2;\n    \n}\nvar later = 3;\n"),
+                at(1, "textDocument/completion", 3, 4),
+                at(2, "textDocument/completion", 2, 4),
+            ]);
+            let inside = labels(result_for(&replies, 1));
+            for name in ["inner", "param", "outer", "f", "later", "clock"] {
+                assert!(inside.contains(&name), "{} in {:?}", name, inside);
+            }
+            let before_inner = labels(result_for(&replies, 2));
+            assert!(!before_inner.contains(&"inner"), "{:?}", before_inner);
+        }
+
+        #[test]
+        fn test_method_completion() {
+            let source = "\
+class A {
+    one() {}
+    class make() { return A(); }
+    two() { this. }
+}
+class B { three() {} }
+var a = A();
+a.t
+A.
+";
+            let replies = session(&[
+                open(source),
+                at(1, "textDocument/completion", 3, 17),
+                at(2, "textDocument/completion", 7, 3),
+                at(3, "textDocument/completion", 8, 2),
+            ]);
+            assert_eq!(vec!["one", "make", "two"], labels(result_for(&replies, 1)));
+            assert_eq!(vec!["one", "make", "two", "three"], labels(result_for(&replies, 2)));
+            assert_eq!(vec!["one", "make", "two"], labels(result_for(&replies, 3)));
+        }
+    }
+}
@@ -1,19 +1,152 @@
 use std::env;
 use std::process;
 
+const FMT_USAGE: &str = "Usage: crablox fmt [--check] [--indent width] [--width columns] [files]";
+const LINT_USAGE: &str = "Usage: crablox lint [files]";
+const COVERAGE_USAGE: &str = "Usage: crablox coverage [--lcov file [--merge]] [files]";
+const TEST_USAGE: &str = "Usage: crablox test [files or directories]";
+
 fn main() {
 
     // Take in file path
     let args: Vec<String> = env::args().collect();
-    if args.len() > 2 {
+    if args.len() > 1 && args[1] == "fmt" {
+        fmt(&args[2..]);
+    }
+    else if args.len() > 1 && args[1] == "lint" {
+        lint(&args[2..]);
+    }
+    else if args.len() > 1 && args[1] == "coverage" {
+        coverage(&args[2..]);
+    }
+    else if args.len() > 1 && args[1] == "test" {
+        test(&args[2..]);
+    }
+    else if args.len() == 3 && args[1] == "--debug" {
+        crablox::debug_file(&args[2]);
+    }
+    else if args.len() == 3 && args[1] == "--trace" {
+        crablox::trace_file(&args[2], false);
+    }
+    else if args.len() == 4 && args[1] == "--trace" && args[2] == "--json" {
+        crablox::trace_file(&args[3], true);
+    }
+    else if args.len() == 3 && args[1] == "--profile" {
+        crablox::profile_file(&args[2], None);
+    }
+    else if args.len() == 5 && args[1] == "--profile" && args[2] == "--folded" {
+        crablox::profile_file(&args[4], Some(&args[3]));
+    }
+    else if args.len() > 2 {
         eprintln!("Usage: crablox -- [script]");
+        eprintln!("       crablox --debug script");
+        eprintln!("       crablox --trace [--json] script");
+        eprintln!("       crablox --profile [--folded path] script");
+        eprintln!("       {}", FMT_USAGE);
+        eprintln!("       {}", LINT_USAGE);
+        eprintln!("       {}", COVERAGE_USAGE);
+        eprintln!("       {}", TEST_USAGE);
         process::exit(64);
     }
-    else if args.len() == 2 { 
+    else if args.len() == 2 {
         crablox::run_file(&args[1]);
     }
     else {
         crablox::run_prompt();
     }
 
-}
\ No newline at end of file
+}
+
+fn fmt(args: &[String]) {
+    let mut check = false;
+    let mut indent_width = 4;
+    let mut max_width = 100;
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--indent" => indent_width = number_option(args.next()),
+            "--width" => max_width = number_option(args.next()),
+            file => files.push(file),
+        }
+    }
+    if files.is_empty() {
+        eprintln!("{}", FMT_USAGE);
+        process::exit(64);
+    }
+
+    // Every file is visited, even after one fails, so a check lists everything left to format.
+    let mut all_ok = true;
+    for file in files {
+        all_ok &= crablox::format_file(file, check, indent_width, max_width);
+    }
+    if !all_ok {
+        process::exit(1);
+    }
+}
+
+fn lint(files: &[String]) {
+    if files.is_empty() {
+        eprintln!("{}", LINT_USAGE);
+        process::exit(64);
+    }
+
+    let mut all_clean = true;
+    for file in files {
+        all_clean &= crablox::lint_file(file);
+    }
+    if !all_clean {
+        process::exit(1);
+    }
+}
+
+fn coverage(args: &[String]) {
+    let mut lcov_path = None;
+    let mut merge = false;
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lcov" => match args.next() {
+                Some(path) => lcov_path = Some(path.as_str()),
+                None => coverage_usage(),
+            },
+            "--merge" => merge = true,
+            file => files.push(String::from(file)),
+        }
+    }
+    if files.is_empty() || (merge && lcov_path.is_none()) {
+        coverage_usage();
+    }
+    if !crablox::cover_files(&files, lcov_path, merge) {
+        process::exit(1);
+    }
+}
+
+fn test(paths: &[String]) {
+    if paths.is_empty() {
+        eprintln!("{}", TEST_USAGE);
+        process::exit(64);
+    }
+    if !crablox::test_paths(paths) {
+        process::exit(1);
+    }
+}
+
+fn coverage_usage() {
+    eprintln!("{}", COVERAGE_USAGE);
+    process::exit(64);
+}
+
+fn number_option(value: Option<&String>) -> usize {
+    match value.and_then(|v| v.parse().ok()) {
+        Some(n) => n,
+        None => {
+            eprintln!("{}", FMT_USAGE);
+            process::exit(64);
+        },
+    }
+}
@@ -1,7 +1,20 @@
 use crate::components as lox;
 use lox::interpreter::*;
 use lox::parser::*;
+use lox::checker::*;
+use lox::formatter::*;
+use lox::linter::*;
+use lox::debugger::*;
+use lox::profiler::*;
+use lox::coverage::*;
+use lox::tester::*;
+use lox::tracer::*;
+use lox::instructions::statement::Statement;
 use std::fs;
+use std::io::{BufRead, Write};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Instant;
 use std::error::Error;
 
 pub struct LoxProgram {
@@ -15,31 +28,124 @@ impl LoxProgram {
     }
 
     pub fn run(&self) -> String {
+        self.run_with(LoxInterpreter::new())
+    }
+
+    // Runs the program, keeping what it printed apart from the error that stopped it, if any.
+    pub fn execute(&self) -> (String, Option<String>) {
+        let program = match self.load() {
+            Ok(program) => program,
+            Err(e) => return (String::new(), Some(e)),
+        };
+        let mut interpreter = LoxInterpreter::new();
+        match interpreter.interpret(program) {
+            Ok(output) => (output, None),
+            Err(e) => (interpreter.take_output(), Some(LoxProgram::output(Err(e)))),
+        }
+    }
+
+    // Runs the program under the step debugger, which reads its commands from the given input.
+    pub fn debug<R: BufRead + 'static, W: Write + 'static>(&self, commands: R, output: W) -> String {
+        let mut interpreter = LoxInterpreter::new();
+        interpreter.set_hook(Box::new(LoxDebugger::new(&self.instructions, commands, output)));
+        self.run_with(interpreter)
+    }
+
+    // Runs the program, writing a trace of what it does to the given output as it goes.
+    pub fn trace<W: Write + 'static>(&self, output: W, format: TraceFormat) -> String {
+        let mut interpreter = LoxInterpreter::new();
+        interpreter.set_hook(Box::new(LoxTracer::new(&self.instructions, output, format)));
+        self.run_with(interpreter)
+    }
+
+    // Runs the program and returns its output along with where it spent its time. Fails only if
+    // the program doesn't get as far as running.
+    pub fn profile(&self) -> Result<(String, Profile), String> {
+        let program = self.load()?;
+        let profile = Rc::new(RefCell::new(Profile::new(Instant::now())));
+        let mut interpreter = LoxInterpreter::new();
+        interpreter.set_hook(Box::new(ProfileHook(Rc::clone(&profile))));
+        let output = LoxProgram::output(interpreter.interpret(program));
+        profile.borrow_mut().finish(Instant::now());
+
+        drop(interpreter);
+        let profile = Rc::try_unwrap(profile).ok().expect("The interpreter is done with the profile");
+        Ok((output, profile.into_inner()))
+    }
+
+    // Runs the program, adding what ran to the coverage under the given path.
+    pub fn cover(&self, path: &str, coverage: &mut Coverage) -> String {
+        let program = match self.load() {
+            Ok(program) => program,
+            Err(e) => return e,
+        };
+        coverage.track(path, &program);
+        let shared = Rc::new(RefCell::new(std::mem::take(coverage)));
+        let mut interpreter = LoxInterpreter::new();
+        interpreter.set_hook(Box::new(CoverageHook::new(path, Rc::clone(&shared))));
+        let output = LoxProgram::output(interpreter.interpret(program));
+
+        drop(interpreter);
+        *coverage = Rc::try_unwrap(shared).ok().expect("The interpreter is done with the coverage").into_inner();
+        output
+    }
+
+    // Runs each of the program's tests on its own. Fails only if the program couldn't be loaded.
+    pub fn test(&self) -> Result<Vec<TestResult>, String> {
+        Ok(run_tests(self.load()?))
+    }
+
+    fn run_with(&self, mut interpreter: LoxInterpreter) -> String {
+        match self.load() {
+            Ok(program) => LoxProgram::output(interpreter.interpret(program)),
+            Err(e) => e,
+        }
+    }
+
+    // The parsed and checked program, or the errors that kept it from getting that far.
+    fn load(&self) -> Result<Vec<Statement>, String> {
         let mut parser = LoxParser::new();
         if let Err(v) = parser.load_string(&self.instructions) {
-            return format!("Scanning error(s):\n{}", LoxProgram::format_vec_output(v));
+            return Err(format!("Scanning error(s):\n{}", LoxProgram::format_vec_output(v)));
+        }
+        let program = parser.parse()
+            .map_err(|v| format!("Parsing error(s):\n{}", LoxProgram::format_vec_output(v)))?;
+        if let Err(v) = TypeChecker::new().check(&program) {
+            return Err(format!("Type error(s):\n{}", LoxProgram::format_vec_output(v)));
         }
-        let program = parser.parse();
-
-        match program {
-            Ok(p) => {
-                let mut interpreter = LoxInterpreter::new();
-                match interpreter.interpret(p) {
-                    Ok(result) => result,
-                    Err(e) => format!("Runtime error: {}", e),
-                }
-            },
-            Err(v) => {
-                format!("Parsing error(s):\n{}", LoxProgram::format_vec_output(v))
-            },
+        Ok(program)
+    }
+
+    fn output(result: Result<String, String>) -> String {
+        match result {
+            Ok(result) => result,
+            Err(e) => format!("Runtime error: {}", e),
         }
     }
 
+    // The canonical form of the program, or the errors that kept it from being formatted.
+    pub fn format(&self, indent_width: usize, max_width: usize) -> Result<String, String> {
+        LoxFormatter::new(indent_width, max_width)
+            .format_source(&self.instructions)
+            .map_err(|v| format!("Formatting error(s):\n{}", LoxProgram::format_vec_output(v)))
+    }
+
+    pub fn lint(&self) -> Result<Vec<String>, String> {
+        match LoxLinter::new().lint_source(&self.instructions) {
+            Ok(warnings) => Ok(warnings.iter().map(|w| w.to_string()).collect()),
+            Err(v) => Err(format!("Parsing error(s):\n{}", LoxProgram::format_vec_output(v))),
+        }
+    }
+
+    pub fn is_formatted(&self, formatted: &str) -> bool {
+        self.instructions == formatted
+    }
+
     fn format_vec_output(v: Vec<String>) -> String {
         let mut output = String::new();
         for s in v {
             output.push_str(&s);
-            output.push_str("\n");
+            output.push('\n');
         }
         output.pop();
         output
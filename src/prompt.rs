@@ -1,9 +1,11 @@
 use crate::components as lox;
 use lox::interpreter::*;
 use lox::parser::*;
+use lox::checker::*;
 
 pub struct LoxPrompt {
     parser : LoxParser,
+    checker : TypeChecker,
     interpreter : LoxInterpreter,
 }
 
@@ -11,6 +13,7 @@ impl LoxPrompt {
     pub fn new() -> LoxPrompt {
         LoxPrompt {
             parser : LoxParser::new(),
+            checker : TypeChecker::new(),
             interpreter : LoxInterpreter::new(),
         }
     }
@@ -23,6 +26,9 @@ impl LoxPrompt {
         
         match program {
             Ok(p) => {
+                if let Err(v) = self.checker.check(&p) {
+                    return format!("Type error(s):\n{}", LoxPrompt::format_vec_output(v));
+                }
                 match self.interpreter.interpret(p) {
                     Ok(result) => result,
                     Err(e) => format!("Runtime error: {}", e),
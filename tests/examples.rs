@@ -0,0 +1,139 @@
+// Runs every script under examples/ and checks what it prints against the expectations written in
+// its comments, in the format of the Crafting Interpreters test suite:
+//
+//     print 1 + 2; // expect: 3
+//     i = i + 1;   // expect runtime error: Undefined variable 'i'.
+//
+// The printed lines must match the expect comments in order. A script that expects a runtime
+// error must stop with it once everything expected before it has been printed.
+//
+// Scripts written for upstream Lox that crablox knowingly runs differently are listed in
+// KNOWN_DIFFERENCES. Those must still differ, so that the list stays accurate.
+
+use crablox::program::LoxProgram;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+const EXPECT: &str = "// expect: ";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error: ";
+const RUNTIME_ERROR: &str = "Runtime error: ";
+
+// Paths under examples/, and how crablox differs there.
+const KNOWN_DIFFERENCES: &[(&str, &str)] = &[
+    ("nil.lox", "nil prints as Nil"),
+];
+
+// What a script says it will do, with the line each expectation is written on.
+struct Expectations {
+    output: Vec<(usize, String)>,
+    error: Option<(usize, String)>,
+}
+
+fn expectations(source: &str) -> Expectations {
+    let mut expected = Expectations{ output: Vec::new(), error: None };
+    for (i, line) in source.lines().enumerate() {
+        if let Some((_, text)) = line.split_once(EXPECT) {
+            expected.output.push((i + 1, String::from(text)));
+        } else if let Some((_, message)) = line.split_once(EXPECT_RUNTIME_ERROR) {
+            expected.error = Some((i + 1, String::from(message)));
+        }
+    }
+    expected
+}
+
+// Runs the script and describes every way it differs from its expectations.
+fn differences(path: &Path) -> Vec<String> {
+    let source = fs::read_to_string(path).expect("Example should be readable");
+    let expected = expectations(&source);
+    let program = LoxProgram::build(&path.display().to_string()).expect("Example should be readable");
+    let (output, error) = program.execute();
+
+    let mut differences = Vec::new();
+    let printed: Vec<&str> = output.lines().collect();
+    for i in 0..expected.output.len().max(printed.len()) {
+        match (expected.output.get(i), printed.get(i)) {
+            (Some((line, text)), Some(actual)) if text != actual => {
+                differences.push(format!("line {}: expected {:?}, got {:?}", line, text, actual));
+            },
+            (Some((line, text)), None) => differences.push(format!("line {}: expected {:?}, got nothing", line, text)),
+            (None, Some(actual)) => differences.push(format!("unexpected output {:?}", actual)),
+            _ => {},
+        }
+    }
+
+    match (expected.error, error) {
+        (Some((line, message)), Some(actual)) => {
+            if actual.strip_prefix(RUNTIME_ERROR) != Some(message.as_str()) {
+                differences.push(format!("line {}: expected runtime error {:?}, got {:?}", line, message, actual));
+            }
+        },
+        (Some((line, message)), None) => differences.push(format!("line {}: expected runtime error {:?}, but the script finished", line, message)),
+        (None, Some(actual)) => differences.push(format!("unexpected error {:?}", actual)),
+        (None, None) => {},
+    }
+    differences
+}
+
+fn scripts(dir: &Path) -> Vec<PathBuf> {
+    let mut scripts = Vec::new();
+    for entry in fs::read_dir(dir).expect("Examples directory should be readable") {
+        let path = entry.expect("Examples directory should be readable").path();
+        if path.is_dir() {
+            scripts.append(&mut self::scripts(&path));
+        } else if path.extension().is_some_and(|e| e == "lox") {
+            scripts.push(path);
+        }
+    }
+    scripts.sort();
+    scripts
+}
+
+#[test]
+fn test_examples() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let scripts = scripts(&dir);
+    assert!(!scripts.is_empty(), "No examples found");
+
+    let mut report = String::new();
+    for script in scripts.iter() {
+        let differences = differences(script);
+        let relative = script.strip_prefix(&dir).expect("Scripts should be under examples/");
+        match KNOWN_DIFFERENCES.iter().find(|(path, _)| Path::new(path) == relative) {
+            Some((_, reason)) if differences.is_empty() => {
+                report.push_str(&format!("{}: listed as a known difference ({}), but matches its expectations\n", script.display(), reason));
+            },
+            Some(_) => {},
+            None => {
+                for difference in differences {
+                    report.push_str(&format!("{}: {}\n", script.display(), difference));
+                }
+            },
+        }
+    }
+    for (path, _) in KNOWN_DIFFERENCES {
+        if !dir.join(path).exists() {
+            report.push_str(&format!("{}: listed as a known difference, but missing\n", path));
+        }
+    }
+    assert!(report.is_empty(), "Examples differ from their expectations:\n{}", report);
+}
+
+#[test]
+fn test_differences_reported() {
+    let path = std::env::temp_dir().join(format!("crablox-expectations-{}.lox", std::process::id()));
+    fs::write(&path, concat!(
+        "print 1; // expect: 1\n",
+        "print 2; // expect: 3\n",
+        "// expect: 4\n",
+        "print missing; // expect runtime error: Undefined variable 'other'.\n",
+    )).expect("Temporary script should be writable");
+    let differences = differences(&path);
+    fs::remove_file(&path).expect("Temporary script should be removable");
+
+    assert_eq!(vec![
+        String::from("line 2: expected \"3\", got \"2\""),
+        String::from("line 3: expected \"4\", got nothing"),
+        String::from("line 4: expected runtime error \"Undefined variable 'other'.\", got \"Runtime error: Undefined variable 'missing'.\""),
+    ], differences);
+}